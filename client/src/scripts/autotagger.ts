@@ -77,12 +77,21 @@ class AutotaggerConfig {
     writeLrc: boolean = false;
     enhancedLrc: boolean = false;
     capitalizeGenres: boolean = false;
+    // undefined = disabled
+    genreMapping?: GenreMapping;
+    // undefined = default frames
+    tagMapping?: TagMapping;
+    // undefined = disabled
+    credits?: CreditsConfig;
     id3CommLang?: string;
     removeAllCovers: boolean = false;
     fetchAllResults: boolean = false;
     albumTagging: boolean = false;
     albumTaggingRatio: number = 0.5;
+    releaseMatching: boolean = false;
     coverFilename: string | undefined = undefined;
+    companionFiles: CompanionFiles = new CompanionFiles();
+    art: ArtConfig = new ArtConfig();
 
     spotify?: SpotifyConfig;
 
@@ -97,9 +106,75 @@ class AutotaggerConfig {
         Object.assign(this, data);
         this.stylesCustomTag = Object.assign(FrameName.same('STYLE'), data.stylesCustomTag);
         this.separators = Object.assign(new Separators(), data.separators);
+        this.companionFiles = Object.assign(new CompanionFiles(), data.companionFiles);
+        this.art = Object.assign(new ArtConfig(), data.art);
     }
 }
 
+/// Genre normalization rules, shareable as JSON file
+class GenreMapping {
+    rewrites: { regex: string, replacement: string }[] = [];
+    // alias: genre
+    aliases: Record<string, string> = {};
+    // sub-genre: parent
+    parents: Record<string, string> = {};
+    // keep, addParents, topLevel
+    hierarchy: string = 'keep';
+    whitelist: string[] = [];
+    blacklist: string[] = [];
+    // 0 = unlimited
+    maxCount: number = 0;
+    styles: boolean = false;
+}
+
+/// Featured artists, remixer and version extraction from title
+class CreditsConfig {
+    // title, field, both
+    featured: string = 'field';
+    version: string = 'both';
+    remixer: boolean = true;
+    featFormat: string = 'feat.';
+    splitArtists: boolean = false;
+    separators: string[] = ['vs.', 'vs', 'x', '&', ',', ';'];
+    // Artists which shouldn't be split
+    exceptions: string[] = [];
+}
+
+/// Custom frames per tag
+class TagMapping {
+    name: string = 'Custom';
+    // no frames and keepDefault = false disables the tag
+    rules: { tag: SupportedTag, frames: FrameName[], keepDefault: boolean }[] = [];
+}
+
+/// Files which are moved together with the audio file
+class CompanionFiles {
+    enabled: boolean = false;
+    extensions: string[] = ['lrc', 'cue', 'nfo', 'asd'];
+    // leave, move, copy
+    folderArt: string = 'move';
+    folderArtNames: string[] = ['cover.jpg', 'cover.png', 'folder.jpg', 'folder.png', 'front.jpg', 'albumart.jpg'];
+    removeEmptyDirs: boolean = false;
+}
+
+/// Album art sources, selection and processing
+class ArtConfig {
+    embed: boolean = true;
+    coverArtArchive: boolean = false;
+    itunes: boolean = false;
+    minResolution: number = 0;
+    // 0 = unlimited
+    maxResolution: number = 0;
+    // Longer / shorter side, 0 = any
+    maxAspectRatio: number = 0;
+    // 0 = keep
+    targetSize: number = 0;
+    // original, jpeg, png
+    format: string = 'original';
+    jpegQuality: number = 90;
+    dedupe: boolean = true;
+}
+
 interface SpotifyConfig {
     clientId: string;
     clientSecret: string;
@@ -261,4 +336,4 @@ interface RepoPlatform {
 }
 
 export type { AutotaggerPlatform, PlatformInfo, AutotaggerProfile, TaggingStatusWrap, Track, ConfigCallbackResponse, RepoPlatform };
-export { AutotaggerConfig, TaggerStatus, SupportedTag, SUPPORTED_TAGS };
\ No newline at end of file
+export { AutotaggerConfig, TaggerStatus, SupportedTag, SUPPORTED_TAGS, CompanionFiles, ArtConfig, GenreMapping, TagMapping, CreditsConfig };
\ No newline at end of file
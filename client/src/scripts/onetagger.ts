@@ -1,6 +1,6 @@
 import { Dialog, DialogChainObject, Notify, setCssVar } from 'quasar';
 import { ref, Ref } from 'vue';
-import { AutotaggerConfig, AutotaggerPlatform, ConfigCallbackResponse, TaggerStatus } from './autotagger';
+import { AutotaggerConfig, AutotaggerPlatform, ConfigCallbackResponse, GenreMapping, TaggerStatus, TagMapping } from './autotagger';
 import { Player } from './player';
 import { QTTrack, QuickTag, QuickTagFile } from './quicktag';
 import { Settings } from './settings';
@@ -27,6 +27,7 @@ class OneTagger {
     taggerStatus: Ref<TaggerStatus> = ref(new TaggerStatus());
     autoTaggerPlaylist: Ref<Playlist> = ref({});
     manualTag: Ref<ManualTag> = ref(new ManualTag());
+    tagMappingPresets: Ref<TagMapping[]> = ref([]);
 
     // Websocket
     private ws!: WebSocket;
@@ -262,6 +263,38 @@ class OneTagger {
             case 'quickTagFolder':
                 this.onQuickTagBrowserEvent(json);
                 break;
+            // Mapped genres of selected track
+            case 'quickTagMapGenres':
+                let mapped = this.quickTag.value.track.getTrack(json.path);
+                if (mapped)
+                    mapped.genres = json.genres;
+                break;
+            // Separator migration report, confirm before writing
+            case 'separatorsMigrate':
+                const report = json.report;
+                if (json.write) {
+                    Notify.create({ message: `Converted separators in ${report.written} files` + (report.failed ? `, ${report.failed} failed` : '') });
+                    break;
+                }
+                const ambiguous = report.ambiguous.slice(0, 10).map((a: any) => a.value).join(', ');
+                Dialog.create({
+                    title: 'Convert separators',
+                    message: `${report.changes.length} fields in ${report.files} files will be converted.` 
+                        + (ambiguous ? ` Ambiguous values, check them or add exceptions: ${ambiguous}${report.ambiguous.length > 10 ? '...' : ''}` : ''),
+                    cancel: true,
+                    ok: { color: 'primary', label: 'Convert' }
+                }).onOk(() => {
+                    this.send('separatorsMigrate', { path: json.path, config: { target: this.settings.value.quickTag.separators, write: true } });
+                });
+                break;
+            // Imported genre mapping
+            case 'genreMappingLoad':
+                this.config.value.genreMapping = Object.assign(new GenreMapping(), json.mapping);
+                break;
+            // Built-in tag mapping profiles
+            case 'tagMappingPresets':
+                this.tagMappingPresets.value = json.presets;
+                break;
             // Spotify
             case 'spotifyAuthorized':
                 this.spotify.value.authorized = json.value;
@@ -14,6 +14,8 @@ class QuickTag {
 
     // Track to load in tag editor
     toTagEditor?: string;
+    // Tracks to open in bulk edit (find & replace)
+    toBulkEdit?: string[];
 
     constructor() {}
 
@@ -73,7 +75,7 @@ interface QuickTagCustomValue {
 
 interface QuickTagFile {
     path: string;
-    format: 'flac' | 'aiff' | 'mp3' | 'mp4' | 'wav' | 'ogg';
+    format: 'flac' | 'aiff' | 'mp3' | 'mp4' | 'wav' | 'ogg' | 'ape' | 'dsf';
     title: string;
     artists: string[];
     genres: string[];
@@ -272,7 +274,7 @@ class QTMultiTrack {
 class QTTrack implements QuickTagFile {
     // QuickTagFile
     path!: string;
-    format!: "flac" | "aiff" | "mp3" | "mp4" | "wav" | "ogg";
+    format!: "flac" | "aiff" | "mp3" | "mp4" | "wav" | "ogg" | "ape" | "dsf";
     title!: string;
     artists!: string[];
     genres!: string[];
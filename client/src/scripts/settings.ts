@@ -58,6 +58,10 @@ class RenamerSettings {
     copy: boolean = false;
     subfolders: boolean = true;
     overwrite: boolean = false;
+    conflict: string = 'skip';
+    companion?: any;
+    filesystem?: string;
+    transliterate: boolean = false;
 }
 
 class AudioFeaturesSettings {
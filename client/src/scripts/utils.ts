@@ -48,9 +48,11 @@ class FrameName {
             case 'aif':
             case 'id3':
             case 'wav':
+            case 'dsf':
                 return this.id3;
             case 'flac':
             case 'ogg':
+            case 'ape':
             case 'vorbis':
                 return this.vorbis;
             case 'mp4':
@@ -0,0 +1,355 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use anyhow::Error;
+use image::{DynamicImage, GenericImageView, ImageFormat, ImageReader};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use onetagger_platforms::itunes::{ITunes, SearchResult};
+use onetagger_platforms::musicbrainz::MusicBrainz;
+use onetagger_tagger::{ArtConfig, ArtFormat, MatchingUtils, TaggerConfig, Track};
+
+use crate::TrackImpl;
+
+/// Default resolution requested from iTunes
+const ITUNES_RESOLUTION: u32 = 3000;
+
+lazy_static::lazy_static! {
+    /// Art of albums processed in this run
+    static ref ALBUM_ART: Mutex<HashMap<String, Arc<Mutex<AlbumArt>>>> = Mutex::new(HashMap::new());
+    /// Cover files written in this run
+    static ref COVER_FILES: Mutex<HashSet<PathBuf>> = Mutex::new(HashSet::new());
+}
+
+/// Where the art candidate came from
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArtSource {
+    Platform(String),
+    CoverArtArchive,
+    ITunes,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArtCandidate {
+    pub url: String,
+    pub source: ArtSource,
+}
+
+/// Downloaded candidate
+#[derive(Debug, Clone)]
+struct ArtImage {
+    data: Vec<u8>,
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+}
+
+/// Art ready to be written
+#[derive(Debug, Clone)]
+pub struct ProcessedArt {
+    pub data: Vec<u8>,
+    pub mime: String,
+}
+
+impl ProcessedArt {
+    /// Extension of the cover file
+    pub fn extension(&self) -> &'static str {
+        match self.mime.as_str() {
+            "image/png" => "png",
+            _ => "jpg"
+        }
+    }
+}
+
+/// Candidates and selected art of one album
+#[derive(Debug, Default)]
+struct AlbumArt {
+    candidates: Vec<ArtCandidate>,
+    /// None = failed downloading or decoding
+    downloaded: HashMap<String, Option<ArtImage>>,
+    extra_sources: bool,
+    selected: Option<String>,
+    art: Option<ProcessedArt>,
+}
+
+/// Forget art and cover files of previous runs
+pub fn reset() {
+    ALBUM_ART.lock().unwrap().clear();
+    COVER_FILES.lock().unwrap().clear();
+}
+
+/// Select and process art for track in folder, None if no candidate fits
+pub fn album_art(track: &Track, folder: impl AsRef<Path>, config: &TaggerConfig) -> Option<ProcessedArt> {
+    album_art_tracks(&[track], folder, config)
+}
+
+/// Select art from candidates of all the tracks, used when the whole album is known before writing
+pub fn album_art_tracks(tracks: &[&Track], folder: impl AsRef<Path>, config: &TaggerConfig) -> Option<ProcessedArt> {
+    let track = *tracks.first()?;
+    // Tracks of the same album share candidates from all platforms they were matched on
+    let album = match album_key(track, folder, &config.art) {
+        Some(key) => ALBUM_ART.lock().unwrap().entry(key).or_default().clone(),
+        None => Default::default()
+    };
+    let mut album = album.lock().unwrap();
+    let album = &mut *album;
+    // Art is chosen once, so all tracks and the cover file get the same one
+    if album.selected.is_some() {
+        return album.art.clone();
+    }
+
+    let mut changed = false;
+    for track in tracks {
+        if let Some(url) = track.art.as_ref() {
+            if !album.candidates.iter().any(|c| &c.url == url) {
+                album.candidates.push(ArtCandidate { url: url.to_string(), source: ArtSource::Platform(track.platform.to_string()) });
+                changed = true;
+            }
+        }
+    }
+    if !album.extra_sources {
+        album.extra_sources = true;
+        album.candidates.extend(extra_candidates(track, config));
+        changed = true;
+    }
+    if !changed {
+        return album.art.clone();
+    }
+
+    // Download new candidates
+    for candidate in &album.candidates {
+        if album.downloaded.contains_key(&candidate.url) {
+            continue;
+        }
+        info!("Downloading art from {:?}: {}", candidate.source, candidate.url);
+        let image = match track.download_art(&candidate.url) {
+            Ok(Some(data)) => match decode(data) {
+                Ok(image) => Some(image),
+                Err(e) => {
+                    warn!("Invalid album art {}: {e}", candidate.url);
+                    None
+                }
+            },
+            Ok(None) => None,
+            Err(e) => {
+                warn!("Error downloading album art {}: {e}", candidate.url);
+                None
+            }
+        };
+        album.downloaded.insert(candidate.url.to_string(), image);
+    }
+
+    // Select
+    let images = album.candidates.iter()
+        .filter_map(|c| album.downloaded.get(&c.url).and_then(|i| i.as_ref()).map(|i| (c.url.as_str(), i)))
+        .collect::<Vec<_>>();
+    let (url, image) = match select(&images, &config.art) {
+        Some(selected) => selected,
+        None => {
+            warn!("No album art candidate fits the size requirements");
+            return album.art.clone();
+        }
+    };
+    debug!("Selected art: {url} ({}x{})", image.width, image.height);
+    match process(image, &config.art) {
+        Ok(art) => {
+            album.selected = Some(url.to_string());
+            album.art = Some(art);
+        },
+        Err(e) => warn!("Failed processing album art: {e}"),
+    }
+    album.art.clone()
+}
+
+/// Should the cover file be written, with dedupe only the first time in a run
+pub fn claim_cover_file(path: impl AsRef<Path>, config: &TaggerConfig) -> bool {
+    let new = COVER_FILES.lock().unwrap().insert(path.as_ref().to_owned());
+    new || !config.art.dedupe
+}
+
+/// Key of the album in folder, None if the art shouldn't be shared
+fn album_key(track: &Track, folder: impl AsRef<Path>, config: &ArtConfig) -> Option<String> {
+    if !config.dedupe {
+        return None;
+    }
+    let album = track.album.as_ref().map(|a| a.trim().to_lowercase()).filter(|a| !a.is_empty())?;
+    Some(format!("{}|{album}", folder.as_ref().display()))
+}
+
+/// Candidates from Cover Art Archive and iTunes
+fn extra_candidates(track: &Track, config: &TaggerConfig) -> Vec<ArtCandidate> {
+    let mut candidates = vec![];
+    if config.art.cover_art_archive {
+        match cover_art_archive(track, config) {
+            Ok(Some(url)) => candidates.push(ArtCandidate { url, source: ArtSource::CoverArtArchive }),
+            Ok(None) => {},
+            Err(e) => warn!("Failed searching Cover Art Archive: {e}"),
+        }
+    }
+    if config.art.itunes {
+        match itunes(track, &config.art) {
+            Ok(Some(url)) => candidates.push(ArtCandidate { url, source: ArtSource::ITunes }),
+            Ok(None) => {},
+            Err(e) => warn!("Failed searching iTunes art: {e}"),
+        }
+    }
+    candidates
+}
+
+/// Get Cover Art Archive URL, searches the release on MusicBrainz if not matched there
+fn cover_art_archive(track: &Track, config: &TaggerConfig) -> Result<Option<String>, Error> {
+    let musicbrainz = MusicBrainz::new(config.get_custom("musicbrainz").unwrap_or_default());
+    let release_id = match (track.platform.as_str(), track.release_id.as_ref()) {
+        ("musicbrainz", Some(id)) => id.to_string(),
+        _ => {
+            let (album, artist) = match (track.album.as_ref(), album_artist(track)) {
+                (Some(album), Some(artist)) => (album, artist),
+                _ => return Ok(None)
+            };
+            let query = format!("release:\"{}\" AND artist:\"{}\"", album.replace('"', ""), artist.replace('"', ""));
+            let album = MatchingUtils::clean_title_matching(album);
+            match musicbrainz.search_releases(&query)?.releases.into_iter().find(|r| MatchingUtils::clean_title_matching(&r.title) == album) {
+                Some(release) => release.id,
+                None => return Ok(None)
+            }
+        }
+    };
+    musicbrainz.release_art(&release_id)
+}
+
+/// Get iTunes art URL of album with the same name
+fn itunes(track: &Track, config: &ArtConfig) -> Result<Option<String>, Error> {
+    let (album, artist) = match (track.album.as_ref(), album_artist(track)) {
+        (Some(album), Some(artist)) => (album, artist),
+        _ => return Ok(None)
+    };
+    let resolution = match (config.target_size, config.max_resolution) {
+        (0, 0) => ITUNES_RESOLUTION,
+        (0, max) => max,
+        (target, _) => target,
+    }.max(config.min_resolution);
+
+    let results = ITunes::new().album_search(&format!("{artist} {album}"))?;
+    let album = MatchingUtils::clean_title_matching(album);
+    Ok(results.results.iter().find(|r| match r {
+        SearchResult::Collection { collection_name: Some(name), .. } => MatchingUtils::clean_title_matching(name) == album,
+        _ => false
+    }).and_then(|r| r.art_url(resolution)))
+}
+
+/// Album artist or first artist
+fn album_artist(track: &Track) -> Option<&String> {
+    track.album_artists.first().or(track.artists.first())
+}
+
+/// Sniff format from data (not the URL or served MIME) and check the image decodes
+fn decode(data: Vec<u8>) -> Result<ArtImage, Error> {
+    let reader = ImageReader::new(Cursor::new(&data)).with_guessed_format()?;
+    let format = reader.format().ok_or(anyhow!("Unknown image format"))?;
+    let (width, height) = reader.decode()?.dimensions();
+    Ok(ArtImage { data, format, width, height })
+}
+
+/// Pick the largest image within limits, or the smallest one above max resolution (gets downscaled)
+fn select<'a>(images: &[(&'a str, &'a ArtImage)], config: &ArtConfig) -> Option<(&'a str, &'a ArtImage)> {
+    let fits = images.iter().enumerate().filter(|(_, (_, i))| {
+        let (short, long) = (i.width.min(i.height), i.width.max(i.height));
+        short >= config.min_resolution && short > 0
+            && (config.max_aspect_ratio <= 0.0 || long as f32 / short as f32 <= config.max_aspect_ratio)
+    }).collect::<Vec<_>>();
+    let pixels = |i: &ArtImage| i.width as u64 * i.height as u64;
+
+    // On equal size prefer earlier candidates (matched platform)
+    fits.iter()
+        .filter(|(_, (_, i))| config.max_resolution == 0 || i.width.max(i.height) <= config.max_resolution)
+        .max_by_key(|(index, (_, i))| (pixels(i), std::cmp::Reverse(*index)))
+        .or(fits.iter().min_by_key(|(_, (_, i))| pixels(i)))
+        .map(|(_, (url, i))| (*url, *i))
+}
+
+/// Resize and recompress if needed
+fn process(image: &ArtImage, config: &ArtConfig) -> Result<ProcessedArt, Error> {
+    // Images above max resolution are downscaled even without target size
+    let size = match config.target_size {
+        0 => config.max_resolution,
+        size => size
+    };
+    let resize = size > 0 && image.width.max(image.height) > size;
+    // Only JPEG and PNG are supported by all formats and players
+    let format = match (config.format, image.format) {
+        (ArtFormat::Jpeg, _) => ImageFormat::Jpeg,
+        (ArtFormat::Png, _) => ImageFormat::Png,
+        (ArtFormat::Original, ImageFormat::Png) => ImageFormat::Png,
+        (ArtFormat::Original, _) => ImageFormat::Jpeg,
+    };
+    if !resize && format == image.format {
+        return Ok(ProcessedArt { data: image.data.clone(), mime: format.to_mime_type().to_string() });
+    }
+
+    let mut img = image::load_from_memory_with_format(&image.data, image.format)?;
+    if resize {
+        img = img.resize(size, size, FilterType::Lanczos3);
+    }
+    Ok(ProcessedArt { data: encode(&img, format, config.jpeg_quality)?, mime: format.to_mime_type().to_string() })
+}
+
+/// Encode as JPEG or PNG
+pub fn encode(img: &DynamicImage, format: ImageFormat, quality: u8) -> Result<Vec<u8>, Error> {
+    let mut data = vec![];
+    match format {
+        ImageFormat::Png => img.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)?,
+        _ => JpegEncoder::new_with_quality(&mut data, quality.clamp(1, 100)).encode_image(&img.to_rgb8())?,
+    }
+    Ok(data)
+}
+
+/// Image with given size and format
+#[cfg(test)]
+fn test_image(width: u32, height: u32, format: ImageFormat) -> ArtImage {
+    let mut data = vec![];
+    DynamicImage::new_rgb8(width, height).write_to(&mut Cursor::new(&mut data), format).unwrap();
+    ArtImage { data, format, width, height }
+}
+
+#[test]
+fn test_select() {
+    let small = test_image(300, 300, ImageFormat::Png);
+    let medium = test_image(1000, 1000, ImageFormat::Png);
+    let large = test_image(3000, 3000, ImageFormat::Png);
+    let wide = test_image(1600, 600, ImageFormat::Png);
+    let images = [("small", &small), ("medium", &medium), ("large", &large), ("wide", &wide)];
+    let mut config = ArtConfig { min_resolution: 500, max_resolution: 2000, max_aspect_ratio: 1.5, ..Default::default() };
+    assert_eq!(select(&images, &config).unwrap().0, "medium");
+
+    // Only larger than max, smallest one gets downscaled
+    assert_eq!(select(&images[2..], &config).unwrap().0, "large");
+    // Nothing fits
+    assert!(select(&images[..1], &config).is_none());
+    config.max_aspect_ratio = 0.0;
+    config.max_resolution = 0;
+    assert_eq!(select(&images, &config).unwrap().0, "large");
+    // Same size, first candidate wins
+    assert_eq!(select(&[("a", &medium), ("b", &medium)], &config).unwrap().0, "a");
+}
+
+#[test]
+fn test_process() {
+    let png = test_image(800, 400, ImageFormat::Png);
+    let config = ArtConfig { format: ArtFormat::Original, ..Default::default() };
+    let art = process(&png, &config).unwrap();
+    assert_eq!((art.mime.as_str(), &art.data), ("image/png", &png.data));
+
+    // Downscaled and converted
+    let config = ArtConfig { format: ArtFormat::Jpeg, target_size: 200, ..Default::default() };
+    let art = process(&png, &config).unwrap();
+    assert_eq!(art.mime, "image/jpeg");
+    let image = image::load_from_memory_with_format(&art.data, ImageFormat::Jpeg).unwrap();
+    assert_eq!(image.dimensions(), (200, 100));
+
+    // Larger than max resolution without target size
+    let config = ArtConfig { format: ArtFormat::Original, max_resolution: 400, target_size: 0, ..Default::default() };
+    let art = process(&png, &config).unwrap();
+    let image = image::load_from_memory_with_format(&art.data, ImageFormat::Png).unwrap();
+    assert_eq!(image.dimensions(), (400, 200));
+}
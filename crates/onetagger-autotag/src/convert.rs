@@ -0,0 +1,346 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use anyhow::Error;
+use serde::{Serialize, Deserialize};
+use onetagger_tag::{AudioFileFormat, Field, FrameName, Tag, TagSeparators};
+use onetagger_tag::id3::ID3Tag;
+
+use crate::separators::SeparatorMigration;
+
+/// Fields copied through the `Field` abstraction, track number is handled separately
+const FIELDS: [Field; 15] = [
+    Field::Title, Field::Artist, Field::Album, Field::AlbumArtist, Field::Key, Field::BPM, Field::Genre, Field::Style,
+    Field::Label, Field::ISRC, Field::CatalogNumber, Field::Version, Field::Remixer, Field::Mood, Field::DiscNumber
+];
+
+/// Tags copied by date, rating and art, or which are derived from the audio
+const SPECIAL_TAGS: [&str; 17] = [
+    "TDRC", "TYER", "TDAT", "TIME", "DATE", "YEAR", "©day", "POPM", "RATING", "RATING WMP", "rate",
+    "TOTALTRACKS", "TLEN", "LENGTH", "METADATA_BLOCK_PICTURE", "COVERART", "COVERARTMIME"
+];
+
+/// Rewrite tags of files: ID3 version, cleanup and copying between formats
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TagConversionConfig {
+    /// Convert ID3 to v2.4 (true) or v2.3 (false), None = keep the version
+    pub id3v24: Option<bool>,
+    /// Remove ID3v1 and APE tags from MP3 files
+    pub strip_mp3: bool,
+    /// Remove frames / tags without any content
+    pub remove_empty: bool,
+    /// Remove repeated ID3 frames
+    pub remove_duplicates: bool,
+    /// Copy all tags from a file with the same name and this extension, eg. "flac"
+    pub copy_from: Option<String>,
+    /// Folder with the files to copy from, default = same folder
+    pub source_path: Option<PathBuf>,
+    /// Separators of written multi-value tags
+    pub separators: TagSeparators,
+    /// Save the changes, otherwise only report
+    pub write: bool,
+}
+
+impl Default for TagConversionConfig {
+    fn default() -> Self {
+        Self {
+            id3v24: None,
+            strip_mp3: true,
+            remove_empty: true,
+            remove_duplicates: true,
+            copy_from: None,
+            source_path: None,
+            separators: TagSeparators::default(),
+            write: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversionNote {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversionReport {
+    pub files: usize,
+    /// Files which couldn't be loaded or saved
+    pub failed: usize,
+    /// Files written
+    pub written: usize,
+    pub changes: Vec<ConversionNote>,
+    /// Tags which couldn't be carried over
+    pub unsupported: Vec<ConversionNote>,
+}
+
+pub struct TagConverter {
+    config: TagConversionConfig
+}
+
+impl TagConverter {
+    pub fn new(config: TagConversionConfig) -> TagConverter {
+        TagConverter { config }
+    }
+
+    /// Convert all files (or only report if write is disabled)
+    pub fn convert(&self, files: &[PathBuf]) -> ConversionReport {
+        let mut report = ConversionReport::default();
+        for path in files {
+            report.files += 1;
+            match self.convert_file(path, &mut report) {
+                Ok(true) => report.written += 1,
+                Ok(false) => {},
+                Err(e) => {
+                    error!("Tag conversion of {path:?} failed: {e}");
+                    report.failed += 1;
+                }
+            }
+        }
+        info!("Tag conversion: {} files, {} changes, {} unsupported", report.files, report.changes.len(), report.unsupported.len());
+        report
+    }
+
+    /// Returns whether the file was written
+    fn convert_file(&self, path: &Path, report: &mut ConversionReport) -> Result<bool, Error> {
+        let mut changes = vec![];
+        let mut unsupported = vec![];
+        let source = self.source_file(path);
+        let mut tag_wrap = Tag::load_file(path, source.is_some())?;
+        tag_wrap.set_separators(&self.config.separators);
+
+        // Copy from other file
+        if let Some(source) = source {
+            match source.exists() {
+                true => {
+                    let mut source_tag = Tag::load_file(&source, false)?;
+                    unsupported.extend(copy_tags(&mut source_tag, &mut tag_wrap));
+                    changes.push(format!("Copied tags from {:?}", source.file_name().unwrap_or_default()));
+                },
+                false => unsupported.push(format!("Source file {source:?} not found")),
+            }
+        }
+
+        match &mut tag_wrap {
+            Tag::ID3(id3) => {
+                if let Some(id3v24) = self.config.id3v24.filter(|v| *v != id3.id3v24) {
+                    let version = if id3v24 { "ID3v2.4" } else { "ID3v2.3" };
+                    unsupported.extend(id3.convert_version(id3v24));
+                    changes.push(format!("Converted to {version}"));
+                }
+                if self.config.remove_empty {
+                    changes.extend(id3.remove_empty_frames().into_iter().map(|f| format!("Removed empty {f}")));
+                }
+                if self.config.remove_duplicates {
+                    changes.extend(id3.remove_duplicate_frames());
+                }
+            },
+            other if self.config.remove_empty => {
+                let tag = other.tag_mut();
+                let mut empty = tag.all_tags().into_iter().filter(|(_, v)| v.iter().all(|v| v.trim().is_empty())).map(|(k, _)| k).collect::<Vec<_>>();
+                empty.sort();
+                for name in empty {
+                    tag.remove_raw(&name);
+                    changes.push(format!("Removed empty {name}"));
+                }
+            },
+            _ => {}
+        }
+        let modified = !changes.is_empty();
+
+        // Junk at the end of MP3
+        let strip = self.config.strip_mp3 && tag_wrap.format() == AudioFileFormat::MP3;
+        if strip {
+            changes.extend(ID3Tag::trailing_tags(&mut File::open(path)?)?.into_iter().map(|(t, _)| format!("Removed {t} tag")));
+        }
+
+        report.changes.extend(changes.iter().map(|message| ConversionNote { path: path.to_owned(), message: message.to_string() }));
+        report.unsupported.extend(unsupported.into_iter().map(|message| ConversionNote { path: path.to_owned(), message }));
+        if changes.is_empty() || !self.config.write {
+            return Ok(false);
+        }
+        if modified {
+            tag_wrap.tag_mut().save_file(path)?;
+        }
+        if strip {
+            ID3Tag::strip_trailing_tags(path)?;
+        }
+        Ok(true)
+    }
+
+    /// File with the same name to copy the tags from
+    fn source_file(&self, path: &Path) -> Option<PathBuf> {
+        let extension = self.config.copy_from.as_ref()?.trim_start_matches('.');
+        let folder = self.config.source_path.clone().or(path.parent().map(|p| p.to_owned()))?;
+        let source = folder.join(format!("{}.{extension}", path.file_stem()?.to_string_lossy()));
+        match source == path {
+            true => None,
+            false => Some(source)
+        }
+    }
+}
+
+/// Copy all tags between two files of any format, returns messages about tags which couldn't be copied
+pub fn copy_tags(source: &mut Tag, target: &mut Tag) -> Vec<String> {
+    // Lossless between ID3 files
+    if let (Tag::ID3(source), Tag::ID3(target)) = (&*source, &mut *target) {
+        target.copy_frames(source);
+        target.set_id3v24(source.id3v24);
+        return vec![];
+    }
+
+    let mut unsupported = match &*source {
+        Tag::ID3(id3) => id3.binary_frames(),
+        _ => vec![]
+    };
+    // Keep the multiple values as they are stored
+    source.set_separators(&SeparatorMigration::native_separators());
+    let (source_format, target_format) = (source.format(), target.format());
+    let src = source.tag();
+    let dst = target.tag_mut();
+
+    let mut copied = SPECIAL_TAGS.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+    for field in FIELDS.iter().cloned() {
+        copied.push(field.by_format(&source_format).to_string());
+        if let Some(value) = src.get_field(field.clone()).filter(|v| !v.is_empty()) {
+            dst.set_field(field, value, true);
+        }
+    }
+
+    // Track number, can be in 1/10 format
+    copied.extend([Field::TrackNumber, Field::TrackTotal, Field::Duration].iter().map(|f| f.by_format(&source_format).to_string()));
+    if let Some(track) = src.get_field(Field::TrackNumber).and_then(|v| v.first().cloned()) {
+        let (number, total) = match track.split_once('/') {
+            Some((n, t)) => (n.to_string(), Some(t.to_string())),
+            None => (track, None)
+        };
+        let total = total
+            .or(src.get_field(Field::TrackTotal).and_then(|v| v.first().cloned()))
+            .or(src.get_raw("TOTALTRACKS").and_then(|v| v.first().cloned()))
+            .and_then(|t| t.trim().parse().ok());
+        dst.set_track_number(number.trim(), total, true);
+    }
+
+    if let Some(date) = src.get_date() {
+        dst.set_date(&date, true);
+    }
+    if let Some(rating) = src.get_rating() {
+        dst.set_rating(rating, true);
+    }
+    for picture in src.get_art() {
+        let description = Some(picture.description.as_str()).filter(|d| !d.is_empty());
+        if let Err(e) = dst.set_art(picture.kind.clone(), &picture.mime, description, picture.data) {
+            debug!("Picture not copied: {e}");
+            unsupported.push(format!("{:?} picture ({})", picture.kind, picture.mime));
+        }
+    }
+
+    // Everything else by raw name
+    let mapping = raw_mapping();
+    let mut tags = src.all_tags().into_iter().collect::<Vec<_>>();
+    tags.sort();
+    for (name, value) in tags {
+        if copied.iter().any(|c| c.eq_ignore_ascii_case(&name)) || value.iter().all(|v| v.trim().is_empty()) {
+            continue;
+        }
+        let mapped = match mapping.iter().find(|m| m.by_format(&source_format).eq_ignore_ascii_case(&name)) {
+            Some(m) => Some(m.by_format(&target_format)),
+            None => custom_name(&name, &source_format, &target_format)
+        };
+        match mapped {
+            Some(mapped) => dst.set_raw(&mapped, value, true),
+            None => unsupported.push(name)
+        }
+    }
+    let mut unsupported = unsupported.into_iter().map(|t| format!("Can't copy {t}")).collect::<Vec<_>>();
+
+    // Mapped frames are ID3v2.4 (TDOR, TDRL...)
+    if let Tag::ID3(id3) = target {
+        if !id3.id3v24 {
+            unsupported.extend(id3.convert_version(false));
+        }
+    }
+    unsupported
+}
+
+/// Name of custom (TXXX / freeform) tag in target format, None if it can't be stored
+fn custom_name(name: &str, source: &AudioFileFormat, target: &AudioFileFormat) -> Option<String> {
+    let id3 = |format: &AudioFileFormat| matches!(format, AudioFileFormat::MP3 | AudioFileFormat::AIFF | AudioFileFormat::WAV | AudioFileFormat::DSF);
+    let name = match source {
+        _ if id3(source) => match name.strip_prefix("TXXX:") {
+            Some(name) => name,
+            // Frame IDs which aren't mapped
+            None if name.len() == 4 => return None,
+            None => name
+        },
+        // Atoms, freeform is mean:name
+        AudioFileFormat::MP4 => match name.rsplit_once(':') {
+            Some((_, name)) => name,
+            None => return None
+        },
+        _ => name
+    };
+    match target {
+        // Would be read as frame ID without the prefix
+        _ if id3(target) && name.len() == 4 => Some(format!("TXXX:{name}")),
+        _ if id3(target) => Some(name.to_string()),
+        AudioFileFormat::MP4 => Some(format!("com.apple.iTunes:{name}")),
+        _ => Some(name.to_uppercase())
+    }
+}
+
+/// Common tags which have a different name in every format and aren't a `Field`
+fn raw_mapping() -> Vec<FrameName> {
+    vec![
+        FrameName::new("TCOM", "COMPOSER", "©wrt"),
+        FrameName::new("TEXT", "LYRICIST", "com.apple.iTunes:LYRICIST"),
+        FrameName::new("TPE3", "CONDUCTOR", "com.apple.iTunes:CONDUCTOR"),
+        FrameName::new("TCOP", "COPYRIGHT", "cprt"),
+        FrameName::new("TENC", "ENCODEDBY", "com.apple.iTunes:ENCODEDBY"),
+        FrameName::new("TSSE", "ENCODER", "©too"),
+        FrameName::new("TLAN", "LANGUAGE", "com.apple.iTunes:LANGUAGE"),
+        FrameName::new("TIT1", "GROUPING", "©grp"),
+        FrameName::new("TMED", "MEDIA", "com.apple.iTunes:MEDIA"),
+        FrameName::new("TCMP", "COMPILATION", "com.apple.iTunes:COMPILATION"),
+        FrameName::new("TOPE", "ORIGINALARTIST", "com.apple.iTunes:ORIGINALARTIST"),
+        FrameName::new("TOAL", "ORIGINALALBUM", "com.apple.iTunes:ORIGINALALBUM"),
+        FrameName::new("TDOR", "ORIGINALDATE", "com.apple.iTunes:ORIGINALDATE"),
+        FrameName::new("TORY", "ORIGINALDATE", "com.apple.iTunes:ORIGINALDATE"),
+        FrameName::new("TDRL", "RELEASEDATE", "com.apple.iTunes:RELEASEDATE"),
+        FrameName::new("TSOA", "ALBUMSORT", "soal"),
+        FrameName::new("TSOP", "ARTISTSORT", "soar"),
+        FrameName::new("TSOT", "TITLESORT", "sonm"),
+        FrameName::new("TSO2", "ALBUMARTISTSORT", "soaa"),
+        FrameName::new("TSOC", "COMPOSERSORT", "soco"),
+        FrameName::new("COMM", "COMMENT", "©cmt"),
+        FrameName::new("USLT", "LYRICS", "©lyr"),
+    ]
+}
+
+#[test]
+fn test_copy_tags() {
+    // Minimal FLAC with empty STREAMINFO
+    let dir = tempfile::tempdir().unwrap();
+    let flac = dir.path().join("test.flac");
+    std::fs::write(&flac, [b"fLaC".as_slice(), &[0x80, 0, 0, 34], &[0; 34]].concat()).unwrap();
+    let mut source = Tag::load_file(&flac, false).unwrap();
+    let tag = source.tag_mut();
+    tag.set_field(Field::Title, vec!["Title".to_string()], true);
+    tag.set_raw("ORIGINALDATE", vec!["1999-01-02".to_string()], true);
+    tag.set_raw("RELEASEDATE", vec!["2020-06-01".to_string()], true);
+    tag.set_raw("WORK", vec!["Work".to_string()], true);
+
+    let mp3 = crate::lint::test_mp3(dir.path(), &[("TIT2", "Old")]);
+    let mut target = Tag::load_file(&mp3, false).unwrap();
+    assert_eq!(copy_tags(&mut source, &mut target), vec![
+        "Moved TDRL to TXXX:RELEASEDATE, no ID3v2.3 equivalent"
+    ]);
+    let tag = target.tag();
+    assert_eq!(tag.get_field(Field::Title), Some(vec!["Title".to_string()]));
+    assert_eq!(tag.get_raw("TORY"), Some(vec!["1999".to_string()]));
+    assert_eq!(tag.get_raw("RELEASEDATE"), Some(vec!["2020-06-01".to_string()]));
+    assert_eq!(tag.get_raw("TXXX:WORK"), Some(vec!["Work".to_string()]));
+    assert!(tag.get_raw("TDOR").is_none() && tag.get_raw("TDRL").is_none());
+}
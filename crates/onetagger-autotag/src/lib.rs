@@ -2,7 +2,7 @@
 #[macro_use] extern crate anyhow;
 #[macro_use] extern crate onetagger_shared;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use anyhow::Error;
 use onetagger_renamer::{Renamer, RenamerConfig, TemplateParser};
 use rand::seq::SliceRandom;
@@ -15,14 +15,16 @@ use std::default::Default;
 use std::io::prelude::*;
 use chrono::Local;
 use execute::Execute;
-use onetagger_tagger::{FileTaggedStatus, LyricsExt, MatchReason, MatchingUtils, SupportedTag, TrackMatch};
+use onetagger_tagger::{Album, FileTaggedStatus, LyricsExt, MatchReason, MatchingUtils, SupportedTag, TrackMatch};
+use onetagger_tagger::release::ReleaseMatch;
+use onetagger_tagger::credits::{Credits, CreditsConfig};
 use regex::Regex;
 use reqwest::StatusCode;
 use walkdir::WalkDir;
 use chrono::Datelike;
 use serde::{Serialize, Deserialize};
 use crossbeam_channel::{unbounded, Sender, Receiver};
-use onetagger_tag::{AudioFileFormat, Tag, Field, TagDate, CoverType, TagImpl, EXTENSIONS};
+use onetagger_tag::{AudioFileFormat, Tag, Field, TagDate, CoverType, TagImpl, TagSeparators, EXTENSIONS};
 use onetagger_shared::Settings;
 use onetagger_player::AudioSources;
 use onetagger_tagger::{Track, AudioFileInfo, TaggerConfig, StylesOptions, AutotaggerSource, AutotaggerSourceBuilder};
@@ -30,13 +32,23 @@ use onetagger_tagger::{Track, AudioFileInfo, TaggerConfig, StylesOptions, Autota
 use crate::shazam::Shazam;
 mod shazam;
 
+pub mod art;
 pub mod repo;
 pub mod platforms;
 pub mod audiofeatures;
+pub mod lint;
+pub mod separators;
+pub mod convert;
 
 // Re-exports
 pub use platforms::{AUTOTAGGER_PLATFORMS, AutotaggerPlatforms};
 
+/// How many files of folder are matched track by track to find candidate releases
+const RELEASE_SAMPLE_FILES: usize = 3;
+/// Max releases fetched for release matching
+const MAX_RELEASE_CANDIDATES: usize = 6;
+/// Files aligned with lower similarity are left for track by track matching
+const MIN_RELEASE_TRACK_SIMILARITY: f64 = 0.4;
 
 lazy_static::lazy_static! {
     /// Stop tagging global variable
@@ -66,11 +78,70 @@ impl TaggerConfigExt for TaggerConfig {
     }
 } 
 
+pub trait CreditsConfigExt {
+    /// Fields which would be changed by extracting credits from title and artist of tag
+    fn changes(&self, tag: &dyn TagImpl) -> Vec<(Field, Vec<String>)>;
+    /// Extract credits from title and artist of tag, returns whether anything changed
+    fn apply_tag(&self, tag: &mut dyn TagImpl) -> bool;
+    /// Extract credits in file, returns the parsed credits if anything changed
+    fn apply_file(&self, path: impl AsRef<Path>, separators: &TagSeparators, save: bool) -> Result<Option<Credits>, Error>;
+}
+impl CreditsConfigExt for CreditsConfig {
+    fn changes(&self, tag: &dyn TagImpl) -> Vec<(Field, Vec<String>)> {
+        let title = tag.get_field(Field::Title).and_then(|t| t.first().cloned()).unwrap_or_default();
+        let artists = tag.get_field(Field::Artist).unwrap_or_default();
+        if title.trim().is_empty() && artists.is_empty() {
+            return vec![];
+        }
+        let credits = self.parse(&title, &artists);
+
+        let mut changes = vec![
+            (Field::Title, vec![self.format_title(&credits)]),
+            (Field::Artist, self.format_artists(&credits)),
+        ];
+        if let (true, Some(version)) = (self.version.field(), credits.version.as_ref()) {
+            changes.push((Field::Version, vec![version.to_string()]));
+        }
+        if self.remixer && !credits.remixers.is_empty() {
+            let mut remixers = tag.get_field(Field::Remixer).unwrap_or_default();
+            CreditsConfig::extend_unique(&mut remixers, credits.remixers);
+            changes.push((Field::Remixer, remixers));
+        }
+        changes.into_iter().filter(|(field, value)| !value.is_empty() && tag.get_field(field.clone()).as_ref() != Some(value)).collect()
+    }
+
+    fn apply_tag(&self, tag: &mut dyn TagImpl) -> bool {
+        let changes = self.changes(tag);
+        let changed = !changes.is_empty();
+        for (field, value) in changes {
+            tag.set_field(field, value, true);
+        }
+        changed
+    }
+
+    fn apply_file(&self, path: impl AsRef<Path>, separators: &TagSeparators, save: bool) -> Result<Option<Credits>, Error> {
+        let mut tag_wrap = Tag::load_file(&path, false)?;
+        tag_wrap.set_separators(separators);
+        let tag = tag_wrap.tag_mut();
+        let title = tag.get_field(Field::Title).and_then(|t| t.first().cloned()).unwrap_or_default();
+        let credits = self.parse(&title, &tag.get_field(Field::Artist).unwrap_or_default());
+        if !self.apply_tag(&mut **tag) {
+            return Ok(None);
+        }
+        if save {
+            tag.save_file(path.as_ref())?;
+        }
+        Ok(Some(credits))
+    }
+}
+
 
 pub trait TrackImpl {
     fn write_to_file(&self, path: impl AsRef<Path>, config: &TaggerConfig) -> Result<(), Error>;
     fn download_art(&self, url: &str) -> Result<Option<Vec<u8>>, Error>;
     fn merge_styles(self, option: &StylesOptions) -> Self;
+    fn map_genres(self, config: &TaggerConfig) -> Self;
+    fn extract_credits(self, config: &TaggerConfig) -> Self;
 }
 
 impl TrackImpl for Track {
@@ -90,10 +161,19 @@ impl TrackImpl for Track {
                 }
             }
         }
+
+        // Album art
+        let embed_art = config.tag_enabled(SupportedTag::AlbumArt) && config.art.embed
+            && (config.overwrite_tag(SupportedTag::AlbumArt) || tag_wrap.tag().get_art().is_empty());
+        let cover_file = config.tag_enabled(SupportedTag::AlbumArt) && config.album_art_file;
+        let art = match embed_art || cover_file {
+            true => art::album_art(self, path.as_ref().parent().unwrap_or(Path::new(".")), config),
+            false => None
+        };
+
         // MP4 Album art override
         if let Tag::MP4(mp4) = &mut tag_wrap {
-            // Has art
-            if (config.overwrite_tag(SupportedTag::AlbumArt) || mp4.get_art().is_empty()) && self.art.is_some() && config.tag_enabled(SupportedTag::AlbumArt) {
+            if embed_art && art.is_some() {
                 mp4.remove_all_artworks();
             }
         }
@@ -101,23 +181,24 @@ impl TrackImpl for Track {
         let tag = tag_wrap.tag_mut();
         // Set tags
         if config.tag_enabled(SupportedTag::Title) {
-            match config.short_title {
-                true => tag.set_field(Field::Title, vec![self.title.to_string()], config.overwrite_tag(SupportedTag::Title)),
-                false => tag.set_field(Field::Title, vec![self.full_title()], config.overwrite_tag(SupportedTag::Title))
-            }
+            let title = match config.short_title {
+                true => self.title.to_string(),
+                false => self.full_title()
+            };
+            write_tag(&mut **tag, config, &format, SupportedTag::Title, vec![title], |t, v, o| t.set_field(Field::Title, v, o));
         }
         // Version
         if config.tag_enabled(SupportedTag::Version) && self.version.is_some() {
-            tag.set_field(Field::Version, vec![self.version.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::Version));
+            write_tag(&mut **tag, config, &format, SupportedTag::Version, vec![self.version.as_ref().unwrap().to_string()], |t, v, o| t.set_field(Field::Version, v, o));
         }
         if config.tag_enabled(SupportedTag::Artist) {
-            tag.set_field(Field::Artist, self.artists.clone(), config.overwrite_tag(SupportedTag::Artist));
+            write_tag(&mut **tag, config, &format, SupportedTag::Artist, self.artists.clone(), |t, v, o| t.set_field(Field::Artist, v, o));
         }
         if config.tag_enabled(SupportedTag::AlbumArtist) && !self.album_artists.is_empty() {
-            tag.set_field(Field::AlbumArtist, self.album_artists.clone(), config.overwrite_tag(SupportedTag::AlbumArtist));
+            write_tag(&mut **tag, config, &format, SupportedTag::AlbumArtist, self.album_artists.clone(), |t, v, o| t.set_field(Field::AlbumArtist, v, o));
         }
         if self.album.is_some() && config.tag_enabled(SupportedTag::Album)  {
-            tag.set_field(Field::Album, vec![self.album.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::Album));
+            write_tag(&mut **tag, config, &format, SupportedTag::Album, vec![self.album.as_ref().unwrap().to_string()], |t, v, o| t.set_field(Field::Album, v, o));
         }
         if config.tag_enabled(SupportedTag::Key) && self.key.is_some() {
             let mut value = self.key.as_ref().unwrap().to_string();
@@ -125,13 +206,13 @@ impl TrackImpl for Track {
             if config.camelot {
                 value = onetagger_tagger::to_camelot(&value).to_owned();
             }
-            tag.set_field(Field::Key, vec![value], config.overwrite_tag(SupportedTag::Key));
+            write_tag(&mut **tag, config, &format, SupportedTag::Key, vec![value], |t, v, o| t.set_field(Field::Key, v, o));
         }
         if config.tag_enabled(SupportedTag::BPM) && self.bpm.is_some() {
-            tag.set_field(Field::BPM, vec![self.bpm.unwrap().to_string()], config.overwrite_tag(SupportedTag::BPM));
+            write_tag(&mut **tag, config, &format, SupportedTag::BPM, vec![self.bpm.unwrap().to_string()], |t, v, o| t.set_field(Field::BPM, v, o));
         }
         if config.tag_enabled(SupportedTag::Label) && self.label.is_some() {
-            tag.set_field(Field::Label, vec![self.label.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::Label));
+            write_tag(&mut **tag, config, &format, SupportedTag::Label, vec![self.label.as_ref().unwrap().to_string()], |t, v, o| t.set_field(Field::Label, v, o));
         }
         if config.tag_enabled(SupportedTag::Genre) && !self.genres.is_empty() {
             let mut genres = if config.merge_genres {
@@ -149,7 +230,7 @@ impl TrackImpl for Track {
                 genres = genres.into_iter().map(|g| onetagger_shared::capitalize(&g)).collect();
             }
 
-            tag.set_field(Field::Genre, genres, config.overwrite_tag(SupportedTag::Genre));
+            write_tag(&mut **tag, config, &format, SupportedTag::Genre, genres, |t, v, o| t.set_field(Field::Genre, v, o));
         }
         if config.tag_enabled(SupportedTag::Style) && !self.styles.is_empty() {
             if config.styles_options == StylesOptions::CustomTag && config.styles_custom_tag.is_some() {
@@ -162,17 +243,17 @@ impl TrackImpl for Track {
                 let mut current: Vec<String> = tag.get_field(Field::Style).unwrap_or(vec![]).into_iter().filter(|i| !i.trim().is_empty()).collect::<Vec<_>>();
                 let mut styles = self.styles.clone().into_iter().filter(|s| !current.iter().any(|i| i.to_lowercase() == s.to_lowercase())).collect();
                 current.append(&mut styles);
-                tag.set_field(Field::Style, current, config.overwrite_tag(SupportedTag::Style)); 
+                write_tag(&mut **tag, config, &format, SupportedTag::Style, current, |t, v, o| t.set_field(Field::Style, v, o));
 
             } else {
                 // Default write to style
-                tag.set_field(Field::Style, self.styles.clone(), config.overwrite_tag(SupportedTag::Style));
+                write_tag(&mut **tag, config, &format, SupportedTag::Style, self.styles.clone(), |t, v, o| t.set_field(Field::Style, v, o));
             }
         }
         // Release dates
         if config.tag_enabled(SupportedTag::ReleaseDate) {
-            if let Some(date) = self.release_date {
-                tag.set_date(&TagDate {
+            let date = match (self.release_date, self.release_year) {
+                (Some(date), _) => Some(TagDate {
                     year: date.year() as i32,
                     month: match config.only_year {
                         true => None,
@@ -182,19 +263,18 @@ impl TrackImpl for Track {
                         true => None,
                         false => Some(date.day() as u8)
                     }
-                }, config.overwrite_tag(SupportedTag::ReleaseDate));
-            } else if let Some(year) = self.release_year {
-                tag.set_date(&TagDate {
-                    year: year as i32,
-                    month: None,
-                    day: None
-                }, config.overwrite_tag(SupportedTag::ReleaseDate));
+                }),
+                (None, Some(year)) => Some(TagDate { year: year as i32, month: None, day: None }),
+                (None, None) => None
+            };
+            if let Some(date) = date {
+                write_tag(&mut **tag, config, &format, SupportedTag::ReleaseDate, vec![date.to_string()], |t, _, o| t.set_date(&date, o));
             }
         }
         // Publish date
         if config.tag_enabled(SupportedTag::PublishDate) {
-            if let Some(date) = self.publish_date {
-                tag.set_publish_date(&TagDate {
+            let date = match (self.publish_date, self.publish_year) {
+                (Some(date), _) => Some(TagDate {
                     year: date.year() as i32,
                     month: match config.only_year {
                         true => None,
@@ -204,18 +284,17 @@ impl TrackImpl for Track {
                         true => None,
                         false => Some(date.day() as u8)
                     }
-                }, config.overwrite_tag(SupportedTag::PublishDate));
-            } else if let Some(year) = self.publish_year {
-                tag.set_publish_date(&TagDate {
-                    year: year as i32,
-                    month: None,
-                    day: None
-                }, config.overwrite_tag(SupportedTag::PublishDate));
+                }),
+                (None, Some(year)) => Some(TagDate { year: year as i32, month: None, day: None }),
+                (None, None) => None
+            };
+            if let Some(date) = date {
+                write_tag(&mut **tag, config, &format, SupportedTag::PublishDate, vec![date.to_string()], |t, _, o| t.set_publish_date(&date, o));
             }
         }
         // URL
         if config.tag_enabled(SupportedTag::URL) {
-            tag.set_raw("WWWAUDIOFILE", vec![self.url.to_string()], config.overwrite_tag(SupportedTag::URL));
+            write_tag(&mut **tag, config, &format, SupportedTag::URL, vec![self.url.to_string()], |t, v, o| t.set_raw("WWWAUDIOFILE", v, o));
         }
         // Other tags
         if config.tag_enabled(SupportedTag::OtherTags) {
@@ -225,43 +304,49 @@ impl TrackImpl for Track {
         }
         // IDs
         if config.tag_enabled(SupportedTag::TrackId) && self.track_id.is_some() {
-            let t = format!("{}_TRACK_ID", serde_json::to_value(self.platform.clone()).unwrap().as_str().unwrap().to_uppercase());
-            tag.set_raw(&t, vec![self.track_id.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::TrackId));
+            let name = format!("{}_TRACK_ID", serde_json::to_value(self.platform.clone()).unwrap().as_str().unwrap().to_uppercase());
+            write_tag(&mut **tag, config, &format, SupportedTag::TrackId, vec![self.track_id.as_ref().unwrap().to_string()], |t, v, o| t.set_raw(&name, v, o));
         }
         if config.tag_enabled(SupportedTag::ReleaseId) && self.release_id.is_some() {
-            let t = format!("{}_RELEASE_ID", serde_json::to_value(self.platform.clone()).unwrap().as_str().unwrap().to_uppercase());
-            tag.set_raw(&t, vec![self.release_id.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::ReleaseId));
+            let name = format!("{}_RELEASE_ID", serde_json::to_value(self.platform.clone()).unwrap().as_str().unwrap().to_uppercase());
+            write_tag(&mut **tag, config, &format, SupportedTag::ReleaseId, vec![self.release_id.as_ref().unwrap().to_string()], |t, v, o| t.set_raw(&name, v, o));
         }
         // Catalog number
         if config.tag_enabled(SupportedTag::CatalogNumber) && self.catalog_number.is_some() {
-            tag.set_field(Field::CatalogNumber, vec![self.catalog_number.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::CatalogNumber));
+            write_tag(&mut **tag, config, &format, SupportedTag::CatalogNumber, vec![self.catalog_number.as_ref().unwrap().to_string()], |t, v, o| t.set_field(Field::CatalogNumber, v, o));
         }
         // Duration
         if config.tag_enabled(SupportedTag::Duration) && self.duration.as_secs() > 0 {
-            tag.set_field(Field::Duration, vec![self.duration.as_secs().to_string()], config.overwrite_tag(SupportedTag::Duration));
+            write_tag(&mut **tag, config, &format, SupportedTag::Duration, vec![self.duration.as_secs().to_string()], |t, v, o| t.set_field(Field::Duration, v, o));
         }
         // Remixers
         if config.tag_enabled(SupportedTag::Remixer) && !self.remixers.is_empty() {
-            tag.set_field(Field::Remixer, self.remixers.clone(), config.overwrite_tag(SupportedTag::Remixer));
+            write_tag(&mut **tag, config, &format, SupportedTag::Remixer, self.remixers.clone(), |t, v, o| t.set_field(Field::Remixer, v, o));
         }
         // ISRC
         if config.tag_enabled(SupportedTag::ISRC) && self.isrc.is_some() {
-            tag.set_field(Field::ISRC, vec![self.isrc.clone().unwrap()], config.overwrite_tag(SupportedTag::ISRC));
+            write_tag(&mut **tag, config, &format, SupportedTag::ISRC, vec![self.isrc.clone().unwrap()], |t, v, o| t.set_field(Field::ISRC, v, o));
         }
         // Mood
         if config.tag_enabled(SupportedTag::Mood) && self.mood.is_some() {
-            tag.set_field(Field::Mood, vec![self.mood.clone().unwrap()], config.overwrite_tag(SupportedTag::Mood));
+            write_tag(&mut **tag, config, &format, SupportedTag::Mood, vec![self.mood.clone().unwrap()], |t, v, o| t.set_field(Field::Mood, v, o));
         }
         // Disc number
         if config.tag_enabled(SupportedTag::DiscNumber) && self.disc_number.is_some() {
-            tag.set_field(Field::DiscNumber, vec![self.disc_number.clone().unwrap().to_string()], config.overwrite_tag(SupportedTag::DiscNumber));
+            write_tag(&mut **tag, config, &format, SupportedTag::DiscNumber, vec![self.disc_number.clone().unwrap().to_string()], |t, v, o| t.set_field(Field::DiscNumber, v, o));
         }
         // Track number
         if config.tag_enabled(SupportedTag::TrackNumber) && self.track_number.is_some() {
-            match config.tag_enabled(SupportedTag::TrackTotal) {
-                true => tag.set_track_number(&self.track_number.as_ref().unwrap().to_string_with_zeroes(config.track_number_leading_zeroes), self.track_total.clone(), config.overwrite_tag(SupportedTag::TrackNumber)),
-                false => tag.set_track_number(&self.track_number.as_ref().unwrap().to_string_with_zeroes(config.track_number_leading_zeroes), None, config.overwrite_tag(SupportedTag::TrackNumber)),
-            }
+            let track_number = self.track_number.as_ref().unwrap().to_string_with_zeroes(config.track_number_leading_zeroes);
+            let track_total = match config.tag_enabled(SupportedTag::TrackTotal) {
+                true => self.track_total.clone(),
+                false => None
+            };
+            let value = match track_total {
+                Some(total) => format!("{track_number}/{total}"),
+                None => track_number.to_string()
+            };
+            write_tag(&mut **tag, config, &format, SupportedTag::TrackNumber, vec![value], |t, _, o| t.set_track_number(&track_number, track_total, o));
         }
         // Lyrics
         if config.tag_enabled(SupportedTag::SyncedLyrics) && self.lyrics.is_some() {
@@ -276,34 +361,23 @@ impl TrackImpl for Track {
         }
 
         // Album art
-        let mut cover_data = None;
-        if (config.overwrite_tag(SupportedTag::AlbumArt) || tag.get_art().is_empty()) && self.art.is_some() && config.tag_enabled(SupportedTag::AlbumArt) {
-            info!("Downloading art: {:?}", self.art);
-            match self.download_art(self.art.as_ref().unwrap()) {
-                Ok(data) => {
-                    match data {
-                        Some(data) => {
-                            // Remove covers
-                            if config.remove_all_covers {
-                                for t in CoverType::types() {
-                                    tag.remove_art(t);
-                                }
-                            }
-
-                            tag.set_art(CoverType::CoverFront, "image/jpeg", Some("Cover"), data.clone());
-                            cover_data = Some(data);
-                        },
-                        None => warn!("Invalid album art!")
-                    } 
-                },
-                Err(e) => warn!("Error downloading album art! {}", e)
+        if let (true, Some(art)) = (embed_art, art.as_ref()) {
+            // Remove covers
+            if config.remove_all_covers {
+                for t in CoverType::types() {
+                    tag.remove_art(t);
+                }
+            }
+            if let Err(e) = tag.set_art(CoverType::CoverFront, &art.mime, Some("Cover"), art.data.clone()) {
+                warn!("Failed embedding album art: {e}");
             }
         }
 
         // Meta tags (date / success)
         if config.tag_enabled(SupportedTag::MetaTags) {
             let time = Local::now();
-            tag.set_raw("1T_TAGGEDDATE", vec![format!("{}_AT", time.format("%Y-%m-%d %H:%M:%S"))], true);
+            let value = vec![format!("{}_AT", time.format("%Y-%m-%d %H:%M:%S"))];
+            tag.set_raw("1T_TAGGEDDATE", value, true);
         }
 
         // LRC
@@ -324,13 +398,15 @@ impl TrackImpl for Track {
         tag.save_file(&path.as_ref())?;
 
         // Cover file
-        if let Some(cover_data) = cover_data {
+        if let (true, Some(art)) = (cover_file, art) {
             match AudioFileInfo::load_file(&path, None, None) {
                 Ok(info) => {
-                    let cover_path = get_cover_path(&info, path.as_ref().parent().unwrap(), config);
-                    match std::fs::write(&cover_path, cover_data) {
-                        Ok(_) => debug!("Cover written to: {}", cover_path.display()),
-                        Err(e) => error!("Failed to write cover file: {e}"),
+                    let cover_path = get_cover_path(&info, path.as_ref().parent().unwrap(), art.extension(), config);
+                    if art::claim_cover_file(&cover_path, config) {
+                        match std::fs::write(&cover_path, art.data) {
+                            Ok(_) => debug!("Cover written to: {}", cover_path.display()),
+                            Err(e) => error!("Failed to write cover file: {e}"),
+                        }
                     }
                 },
                 Err(e) => {
@@ -388,19 +464,75 @@ impl TrackImpl for Track {
         self
     }
 
+    /// Normalize genres (and styles) by the user's mapping rules
+    fn map_genres(mut self, config: &TaggerConfig) -> Self {
+        if let Some(mapping) = config.genre_mapping.as_ref() {
+            self.genres = mapping.apply(&self.genres);
+            if mapping.styles {
+                self.styles = mapping.apply(&self.styles);
+            }
+        }
+        self
+    }
+
+    /// Move featured artists, remixers and version out of title by the user's rules
+    fn extract_credits(mut self, config: &TaggerConfig) -> Self {
+        let rules = match config.credits.as_ref() {
+            Some(rules) => rules,
+            None => return self
+        };
+        let credits = rules.parse(&self.title, &self.artists);
+        self.title = rules.format_title(&Credits { version: None, ..credits.clone() });
+        self.artists = rules.format_artists(&credits);
+        // Version is written together with title (unless short title)
+        if let Some(version) = credits.version.as_ref() {
+            match self.version.is_none() && rules.version.field() {
+                true => self.version = Some(version.to_string()),
+                false => self.title = format!("{} ({version})", self.title),
+            }
+        }
+        if rules.remixer {
+            let remixers = self.version.as_ref().or(credits.version.as_ref()).map(|v| rules.remixers(v)).unwrap_or_default();
+            CreditsConfig::extend_unique(&mut self.remixers, remixers);
+        }
+        self
+    }
+
+}
+
+/// Write value into the default frame of the tag and/or the frames from tag mapping
+fn write_tag(tag: &mut dyn TagImpl, config: &TaggerConfig, format: &AudioFileFormat, supported: SupportedTag, value: Vec<String>, default: impl FnOnce(&mut dyn TagImpl, Vec<String>, bool)) {
+    let overwrite = config.overwrite_tag(supported);
+    let rule = match config.tag_mapping.as_ref().and_then(|m| m.rule(supported)) {
+        Some(rule) => rule,
+        None => return default(tag, value, overwrite)
+    };
+    for frame in &rule.frames {
+        let name = frame.by_format(format);
+        if !name.trim().is_empty() {
+            tag.set_raw(&name, value.clone(), overwrite);
+        }
+    }
+    if rule.keep_default {
+        default(tag, value, overwrite);
+    }
 }
 
-/// Get path to cover file
-fn get_cover_path(info: &AudioFileInfo, folder: impl AsRef<Path>, config: &TaggerConfig) -> PathBuf {
-    let mut path = folder.as_ref().join("cover.jpg");
+/// Get path to cover file, extension has to match the image format
+fn get_cover_path(info: &AudioFileInfo, folder: impl AsRef<Path>, extension: &str, config: &TaggerConfig) -> PathBuf {
+    let mut path = folder.as_ref().join(format!("cover.{extension}"));
 
     if let Some(template) = config.cover_filename.as_ref() {
         if !template.trim().is_empty() {
             // Generate new filename
             let renamer_config = RenamerConfig::default_with_paths(&folder, template);
-            let mut renamer = Renamer::new(TemplateParser::parse(template));
-            let new_path = renamer.generate_name(folder.as_ref(), info, &renamer_config);
-            path = new_path.with_extension("jpg");
+            match TemplateParser::parse(template) {
+                Ok(parser) => {
+                    let new_path = Renamer::new(parser).generate_name(folder.as_ref(), info, &renamer_config);
+                    path = new_path.with_extension(extension);
+                },
+                Err(e) => warn!("Invalid cover filename template, using cover.{extension}: {e}"),
+            }
         }
     }
 
@@ -631,6 +763,7 @@ impl Tagger {
     // Returtns progress receiver, and file count
     pub fn tag_files(cfg: &TaggerConfig, mut files: Vec<PathBuf>, finished: Arc<Mutex<Option<TaggerFinishedData>>>) -> Receiver<TaggingStatusWrap> {
         STOP_TAGGING.store(false, Ordering::SeqCst);
+        art::reset();
 
         // Shuffle so album tag is more "efficient"
         if cfg.album_tagging {
@@ -718,11 +851,15 @@ impl Tagger {
             }
 
             // Move files
+            let mut moved = vec![];
             let mut successful_paths = vec![];
             for file in &succesful_files {
                 if config.move_success && config.move_success_path.is_some() {
-                    match Self::move_file(file, &config.move_success_path.as_ref().unwrap()) {
-                        Ok(p) => successful_paths.push(p),
+                    match Self::move_file(file, &config.move_success_path.as_ref().unwrap(), &config) {
+                        Ok(p) => {
+                            moved.push((PathBuf::from(file), p.clone()));
+                            successful_paths.push(p);
+                        },
                         Err(e) => warn!("Failed moving file: {file:?} {e}"),
                     }
                 } else {
@@ -732,14 +869,25 @@ impl Tagger {
             let mut failed_paths = vec![];
             for file in &failed_files {
                 if config.move_failed && config.move_failed_path.is_some() {
-                    match Self::move_file(file, &config.move_failed_path.as_ref().unwrap()) {
-                        Ok(p) => failed_paths.push(p),
+                    match Self::move_file(file, &config.move_failed_path.as_ref().unwrap(), &config) {
+                        Ok(p) => {
+                            moved.push((PathBuf::from(file), p.clone()));
+                            failed_paths.push(p);
+                        },
                         Err(e) => warn!("Failed moving file: {file:?} {e}"),
                     }
                 } else {
                     failed_paths.push(PathBuf::from(file))
                 }
             }
+            // Folder art and empty folders
+            if config.companion_files.enabled && !moved.is_empty() {
+                onetagger_renamer::transfer_folder_art(&moved, &config.companion_files);
+                if let (true, Some(root)) = (config.companion_files.remove_empty_dirs, &config.path) {
+                    let dirs = moved.iter().filter_map(|(from, _)| from.parent().map(|p| p.to_owned()));
+                    onetagger_renamer::remove_empty_dirs(dirs, root);
+                }
+            }
             std::mem::drop(succesful_files);
             std::mem::drop(failed_files);
             std::mem::drop(files);
@@ -932,7 +1080,7 @@ impl Tagger {
         // Save
         out.release_id = track.track.release_id.clone();
         out.reason = Some(track.reason);
-        match track.track.merge_styles(&config.styles_options).write_to_file(&info.path, &config) {
+        match track.track.merge_styles(&config.styles_options).map_genres(config).extract_credits(config).write_to_file(&info.path, &config) {
             Ok(_) => {
                 out.accuracy = Some(track.accuracy);
                 out.status = TaggingState::Ok;
@@ -1000,6 +1148,8 @@ impl Tagger {
         // Spawn album tag thread
         if config.album_tagging {
             let config = config.clone();
+            let tx = tx.clone();
+            let album_tagging = album_tagging.clone();
             match tagger.get_source(&config) {
                 Ok(mut source) => {
                     std::thread::spawn(move || {
@@ -1035,6 +1185,47 @@ impl Tagger {
             error!("All AT sources failed to create!");
             return None;
         }
+
+        // Match whole folders first, the rest is tagged track by track
+        if config.album_tagging && config.release_matching {
+            let mut source = match tagger.get_source(&config) {
+                Ok(source) => source,
+                Err(e) => {
+                    error!("Failed to get source for release matching! {e}");
+                    return None;
+                }
+            };
+            let files = files.to_owned();
+            let config = config.clone();
+            std::thread::spawn(move || {
+                let mut folders: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+                for f in files {
+                    folders.entry(f.parent().map(|p| p.to_owned()).unwrap_or_default()).or_default().push(f);
+                }
+                for (folder, folder_files) in folders {
+                    if STOP_TAGGING.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let mut tagged = HashSet::new();
+                    match Self::tag_release(&folder_files, &mut source, &config) {
+                        Ok(statuses) => {
+                            album_tagging.lock().unwrap().set_release(&folder);
+                            for status in statuses {
+                                tagged.insert(status.path.to_owned());
+                                tx.send(status).ok();
+                            }
+                        },
+                        Err(e) => warn!("Release matching failed: {e}, path: {}", folder.display()),
+                    }
+                    // Leftovers are tagged track by track while the next folder is matched
+                    for f in folder_files.into_iter().filter(|f| !tagged.contains(f)) {
+                        file_tx.send(f).ok();
+                    }
+                }
+            });
+            return Some(rx);
+        }
+
         // Send files
         for f in files {
             file_tx.send(f.to_owned()).ok();
@@ -1042,6 +1233,99 @@ impl Tagger {
         Some(rx)
     }
 
+    /// Match files of one folder against whole releases and tag them all from the best one
+    pub fn tag_release(files: &[PathBuf], source: &mut Box<dyn AutotaggerSource>, config: &TaggerConfig) -> Result<Vec<TaggingStatus>, Error> {
+        // Load files
+        let mut loaded = vec![];
+        for file in files {
+            let (info, status) = Self::load_track(file, config);
+            if let Some(mut info) = info {
+                if info.duration.is_none() {
+                    info.load_duration();
+                }
+                loaded.push((info, status));
+            }
+        }
+        if loaded.is_empty() {
+            return Err(anyhow!("No files could be loaded"));
+        }
+        let infos = loaded.iter().map(|(i, _)| i.clone()).collect::<Vec<_>>();
+
+        // Candidates from album search and per track matches
+        let mut candidates = vec![];
+        if let Some((album, artist)) = ReleaseMatch::album_query(&infos) {
+            match source.search_albums(&album, &artist, infos.len(), config) {
+                Ok(ids) => candidates.extend(ids),
+                Err(e) => warn!("Album search failed: {e}"),
+            }
+        }
+        for info in infos.iter().take(RELEASE_SAMPLE_FILES) {
+            if let Ok(mut tracks) = source.match_track(info, config) {
+                MatchingUtils::sort_tracks(&mut tracks, config);
+                candidates.extend(tracks.into_iter().filter_map(|t| t.track.release_id).take(2));
+            }
+        }
+        let mut seen = HashSet::new();
+        candidates.retain(|id| seen.insert(id.to_owned()));
+        candidates.truncate(MAX_RELEASE_CANDIDATES);
+
+        // Score the candidates
+        let mut best: Option<(Album, ReleaseMatch)> = None;
+        for id in candidates {
+            let album = match source.get_album(&id, config) {
+                Ok(Some(album)) => album,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("Failed fetching release {id}: {e}");
+                    continue;
+                }
+            };
+            let release_match = ReleaseMatch::new(&infos, &album, config);
+            debug!("Release {id} ({}) score: {:.3}", album.name, release_match.score);
+            if best.as_ref().map(|(_, m)| release_match.score > m.score).unwrap_or(true) {
+                best = Some((album, release_match));
+            }
+        }
+        let (album, release_match) = best.ok_or(anyhow!("No candidate releases found"))?;
+        if release_match.score < config.strictness {
+            return Err(anyhow!("Best release {} ({}) score {:.2} is too low", album.name, album.id, release_match.score));
+        }
+        info!("Release matched: {} ({}), score: {:.2}", album.name, album.id, release_match.score);
+
+        // Art is chosen from all the tracks before any of them is written
+        if config.tag_enabled(SupportedTag::AlbumArt) && (config.art.embed || config.album_art_file) {
+            let tracks = release_match.pairs.iter()
+                .filter(|(_, _, similarity)| *similarity >= MIN_RELEASE_TRACK_SIMILARITY)
+                .map(|(_, track, _)| &album.tracks[*track])
+                .collect::<Vec<_>>();
+            art::album_art_tracks(&tracks, files[0].parent().unwrap_or(Path::new(".")), config);
+        }
+
+        // Tag files
+        let mut statuses = vec![];
+        for (file, track, similarity) in release_match.pairs {
+            if similarity < MIN_RELEASE_TRACK_SIMILARITY {
+                continue;
+            }
+            let (info, mut status) = (&loaded[file].0, loaded[file].1.clone());
+            let mut track = album.tracks[track].clone();
+            track.track_total = track.track_total.or(Some(album.tracks.len() as u16));
+            track.release_id = Some(album.id.to_string());
+            match track.merge_styles(&config.styles_options).map_genres(config).extract_credits(config).write_to_file(&info.path, config) {
+                Ok(_) => status.status = TaggingState::Ok,
+                Err(e) => {
+                    status.status = TaggingState::Error;
+                    error!("Release matching writing tags failed: {e} ({})", info.path.display());
+                }
+            }
+            status.accuracy = Some(similarity);
+            status.release_id = Some(album.id.to_string());
+            status.reason = Some(MatchReason::Album);
+            statuses.push(status);
+        }
+        Ok(statuses)
+    }
+
     /// Tag an album by ID
     pub fn tag_album(path: impl AsRef<Path>, release_id: &str, source: &mut Box<dyn AutotaggerSource>, config: &TaggerConfig) -> Result<Vec<TaggingStatus>, Error> {
         info!("Album tagging release: {release_id} in {}", path.as_ref().display());
@@ -1062,6 +1346,11 @@ impl Tagger {
 
         let mut statuses = vec![];
 
+        // Art is chosen from all the tracks before any of them is written
+        if config.tag_enabled(SupportedTag::AlbumArt) && (config.art.embed || config.album_art_file) {
+            art::album_art_tracks(&album.tracks.iter().collect::<Vec<_>>(), path.as_ref(), &config);
+        }
+
         // Load files
         let files = std::fs::read_dir(&path)?.filter_map(|e| e.ok()).map(|f| f.path()).collect::<Vec<_>>();
         for file in files {
@@ -1080,7 +1369,7 @@ impl Tagger {
             let track = tracks.remove(0);
             
             // TODO: Extend track if needed (?)
-            if let Err(e) = track.track.merge_styles(&config.styles_options).write_to_file(&info.path, &config) {
+            if let Err(e) = track.track.merge_styles(&config.styles_options).map_genres(config).extract_credits(config).write_to_file(&info.path, &config) {
                 status.status = TaggingState::Error;
                 error!("Album tag writing tags failed: {e} ({})", file.display());
             } else {
@@ -1096,8 +1385,8 @@ impl Tagger {
         Ok(statuses)
     }
 
-    /// Move file to target dir if enabled
-    fn move_file(source: impl AsRef<Path>, target: impl AsRef<Path>) -> Result<PathBuf, Error> {
+    /// Move file (and companion files) to target dir if enabled
+    fn move_file(source: impl AsRef<Path>, target: impl AsRef<Path>, config: &TaggerConfig) -> Result<PathBuf, Error> {
         // Generate path
         let target_dir = Path::new(target.as_ref());
         let filename = Path::new(source.as_ref()).file_name().unwrap();
@@ -1107,12 +1396,13 @@ impl Tagger {
             return Ok(target);
         }
         // Try to rename, if fails copy
-        match std::fs::rename(source.as_ref(), &target) {
-            Ok(_) => return Ok(target),
-            Err(_) => {}
+        if std::fs::rename(source.as_ref(), &target).is_err() {
+            std::fs::copy(source.as_ref(), &target)?;
+            std::fs::remove_file(source.as_ref())?;
+        }
+        if config.companion_files.enabled {
+            onetagger_renamer::move_companions(source.as_ref(), &target, &config.companion_files);
         }
-        std::fs::copy(source.as_ref(), &target)?;
-        std::fs::remove_file(source.as_ref())?;
 
         Ok(target)
     }
@@ -1155,7 +1445,7 @@ impl AlbumTagContext {
         // Get folder path
         let path = status.path.parent()?;
         let stats = self.folders.get_mut(path)?;
-        if stats.marked {
+        if stats.marked || stats.release {
             return None;
         }
 
@@ -1178,6 +1468,13 @@ impl AlbumTagContext {
         None
     }
 
+    /// Folder was already matched as whole release, don't vote
+    pub fn set_release(&mut self, path: impl AsRef<Path>) {
+        if let Some(stats) = self.folders.get_mut(path.as_ref()) {
+            stats.release = true;
+        }
+    }
+
     /// Check if path is marked
     pub fn is_marked(&self, path: impl AsRef<Path>) -> bool {
         if let Some(parent) = path.as_ref().parent() {
@@ -1198,6 +1495,8 @@ struct AlbumTagFolderStats {
     pub albums: HashMap<String, usize>,
     /// Is already marked as album
     pub marked: bool,
+    /// Was matched as whole release
+    pub release: bool,
 }
 
 impl AlbumTagFolderStats {
@@ -1288,6 +1587,7 @@ pub fn manual_tagger_apply(mut matches: Vec<TrackMatch>, path: impl AsRef<Path>,
     if matches.is_empty() {
         return Ok(())
     }
+    art::reset();
     
     // Extend each match
     for m in matches.iter_mut() {
@@ -1322,6 +1622,6 @@ pub fn manual_tagger_apply(mut matches: Vec<TrackMatch>, path: impl AsRef<Path>,
     }
 
     // Save
-    track.merge_styles(&config.styles_options).write_to_file(&path, &config)?;
+    track.merge_styles(&config.styles_options).map_genres(config).extract_credits(config).write_to_file(&path, &config)?;
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,467 @@
+use std::collections::{HashMap, HashSet, BTreeMap};
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use onetagger_tag::{Tag, TagImpl, Field, TagSeparators};
+use onetagger_tagger::{SupportedTag, CAMELOT_NOTES};
+
+/// Tags which can contain the same value multiple times
+const FREEFORM_TAGS: [&str; 7] = ["COMM", "USLT", "COMMENT", "LYRICS", "UNSYNCEDLYRICS", "©cmt", "©lyr"];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum LintRule {
+    /// Tag couldn't be loaded at all
+    UnreadableTag,
+    MissingField,
+    ArtistInTitle,
+    InconsistentAlbumArtist,
+    MixedId3Versions,
+    DuplicateFrames,
+    MultipleCommLanguages,
+    UnreadableArt,
+    TrackNumberGaps,
+    BpmOutOfRange,
+    NonCamelotKey,
+    NullSeparator,
+}
+
+impl LintRule {
+    pub fn severity(&self) -> LintSeverity {
+        match self {
+            LintRule::UnreadableTag | LintRule::MissingField | LintRule::UnreadableArt => LintSeverity::Error,
+            _ => LintSeverity::Warning
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum LintSeverity {
+    Warning, Error
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LintConfig {
+    /// Rules to check, empty = all
+    pub rules: Vec<LintRule>,
+    /// Tags which have to be present
+    pub required: Vec<SupportedTag>,
+    pub min_bpm: f32,
+    pub max_bpm: f32,
+    /// Keys should be in Camelot notation
+    pub camelot: bool,
+    /// Apply the unambiguous fixes and save
+    pub fix: bool,
+    pub separators: TagSeparators,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            rules: vec![],
+            required: vec![SupportedTag::Title, SupportedTag::Artist],
+            min_bpm: 40.0,
+            max_bpm: 250.0,
+            camelot: false,
+            fix: false,
+            separators: Default::default(),
+        }
+    }
+}
+
+impl LintConfig {
+    fn enabled(&self, rule: LintRule) -> bool {
+        self.rules.is_empty() || self.rules.contains(&rule)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintIssue {
+    pub rule: LintRule,
+    pub severity: LintSeverity,
+    /// File or folder (for folder wide rules)
+    pub path: PathBuf,
+    pub message: String,
+    pub fixable: bool,
+    pub fixed: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintReport {
+    pub files: usize,
+    pub issues: Vec<LintIssue>,
+}
+
+impl LintReport {
+    /// Count of unfixed issues with severity
+    pub fn count(&self, severity: LintSeverity) -> usize {
+        self.issues.iter().filter(|i| i.severity == severity && !i.fixed).count()
+    }
+}
+
+/// Values used by the folder wide rules
+struct LintFileInfo {
+    album: Option<String>,
+    album_artist: Option<String>,
+    disc: Option<String>,
+    track: Option<u16>,
+    /// ID3 files only
+    id3v24: Option<bool>,
+}
+
+pub struct Linter {
+    config: LintConfig,
+    issues: Vec<LintIssue>,
+}
+
+impl Linter {
+    /// Lint all the files and fix them if enabled
+    pub fn lint(files: &[PathBuf], config: &LintConfig) -> LintReport {
+        let mut linter = Linter { config: config.clone(), issues: vec![] };
+        let mut folders: BTreeMap<PathBuf, Vec<LintFileInfo>> = BTreeMap::new();
+        for path in files {
+            if let Some(info) = linter.lint_file(path) {
+                folders.entry(path.parent().unwrap_or(Path::new(".")).to_owned()).or_default().push(info);
+            }
+        }
+        for (folder, files) in folders {
+            linter.lint_folder(&folder, &files);
+        }
+        LintReport { files: files.len(), issues: linter.issues }
+    }
+
+    /// Add issue, returns whether it should be fixed
+    fn issue(&mut self, rule: LintRule, path: impl AsRef<Path>, message: impl Into<String>, fixable: bool) -> bool {
+        let fixed = fixable && self.config.fix;
+        self.issues.push(LintIssue {
+            rule,
+            severity: rule.severity(),
+            path: path.as_ref().to_owned(),
+            message: message.into(),
+            fixable,
+            fixed
+        });
+        fixed
+    }
+
+    /// Check single file
+    fn lint_file(&mut self, path: &Path) -> Option<LintFileInfo> {
+        let mut tag_wrap = match Tag::load_file(path, false) {
+            Ok(tag) => tag,
+            Err(e) => {
+                if self.config.enabled(LintRule::UnreadableTag) {
+                    self.issue(LintRule::UnreadableTag, path, format!("Failed loading tag: {e}"), false);
+                }
+                return None;
+            }
+        };
+        tag_wrap.set_separators(&self.config.separators);
+        let issues_start = self.issues.len();
+        let mut modified = false;
+
+        // ID3 specific
+        let mut id3v24 = None;
+        let mut handled = HashSet::new();
+        if let Tag::ID3(id3) = &tag_wrap {
+            id3v24 = Some(id3.id3v24);
+            if self.config.enabled(LintRule::MultipleCommLanguages) {
+                let mut langs = id3.get_comments().into_iter().map(|c| c.lang).collect::<Vec<_>>();
+                langs.sort();
+                langs.dedup();
+                if langs.len() > 1 {
+                    self.issue(LintRule::MultipleCommLanguages, path, format!("COMM frames in multiple languages: {}", langs.join(", ")), false);
+                }
+            }
+            let duplicates = id3.duplicate_frames();
+            let nulls = id3.null_separated_frames();
+            let tag = tag_wrap.tag_mut();
+            if self.config.enabled(LintRule::DuplicateFrames) {
+                for (id, texts) in duplicates {
+                    // Merge the values of all the frames
+                    let mut values: Vec<String> = vec![];
+                    for value in texts.iter().flat_map(|t| t.split('\0')).filter(|v| !v.trim().is_empty()) {
+                        if !values.iter().any(|v| v == value) {
+                            values.push(value.to_string());
+                        }
+                    }
+                    let message = match values.len() {
+                        1 => format!("Duplicate {id} frames"),
+                        _ => format!("Duplicate {id} frames with different values: {}", values.join(", "))
+                    };
+                    if self.issue(LintRule::DuplicateFrames, path, message, true) {
+                        tag.remove_raw(&id);
+                        tag.set_raw(&id, values, true);
+                        modified = true;
+                    }
+                    handled.insert(id);
+                }
+            }
+            if self.config.enabled(LintRule::NullSeparator) {
+                for (id, value) in nulls {
+                    if handled.contains(&id) {
+                        continue;
+                    }
+                    if self.issue(LintRule::NullSeparator, path, format!("Null separator in {id} which is invalid in ID3v2.3"), true) {
+                        tag.set_raw(&id, value.split('\0').filter(|v| !v.trim().is_empty()).map(String::from).collect(), true);
+                        modified = true;
+                    }
+                    handled.insert(id);
+                }
+            }
+        }
+
+        let tag = tag_wrap.tag_mut();
+
+        // Required fields
+        if self.config.enabled(LintRule::MissingField) {
+            for required in self.config.required.clone() {
+                let present = match field(required) {
+                    Some(f) => first(&**tag, f).is_some(),
+                    None => match required {
+                        SupportedTag::AlbumArt => tag.has_art(),
+                        SupportedTag::ReleaseDate => tag.get_date().is_some(),
+                        _ => {
+                            warn!("Lint: {required:?} can't be checked");
+                            true
+                        }
+                    }
+                };
+                if !present {
+                    self.issue(LintRule::MissingField, path, format!("Missing {required:?}"), false);
+                }
+            }
+        }
+
+        // Artist in title
+        if let (true, Some(title)) = (self.config.enabled(LintRule::ArtistInTitle), first(&**tag, Field::Title)) {
+            let artists = tag.get_field(Field::Artist).unwrap_or_default();
+            for artist in artists.iter().filter(|a| !a.trim().is_empty()) {
+                let prefix = format!("{} - ", artist.trim());
+                let len = prefix.chars().count();
+                if title.chars().take(len).collect::<String>().to_lowercase() != prefix.to_lowercase() {
+                    continue;
+                }
+                let new_title = title.chars().skip(len).collect::<String>().trim().to_string();
+                if self.issue(LintRule::ArtistInTitle, path, format!("Title contains artist: {title}"), !new_title.is_empty()) {
+                    tag.set_field(Field::Title, vec![new_title], true);
+                    modified = true;
+                }
+                break;
+            }
+        }
+
+        // Unreadable art
+        if self.config.enabled(LintRule::UnreadableArt) {
+            for picture in tag.get_art() {
+                if let Err(e) = image::load_from_memory(&picture.data) {
+                    self.issue(LintRule::UnreadableArt, path, format!("Unreadable {:?} art: {e}", picture.kind), false);
+                }
+            }
+        }
+
+        // BPM
+        if let (true, Some(bpm)) = (self.config.enabled(LintRule::BpmOutOfRange), first(&**tag, Field::BPM)) {
+            match bpm.trim().parse::<f32>() {
+                Ok(v) if v >= self.config.min_bpm && v <= self.config.max_bpm => {},
+                _ => { self.issue(LintRule::BpmOutOfRange, path, format!("Invalid BPM: {bpm}"), false); }
+            }
+        }
+
+        // Camelot
+        if let (true, true, Some(key)) = (self.config.camelot, self.config.enabled(LintRule::NonCamelotKey), first(&**tag, Field::Key)) {
+            if !CAMELOT_NOTES.iter().any(|(_, c)| *c == key.trim()) {
+                let camelot = onetagger_tagger::to_camelot(&key).to_string();
+                let fixable = camelot != key;
+                if self.issue(LintRule::NonCamelotKey, path, format!("Key not in Camelot notation: {key}"), fixable) {
+                    tag.set_field(Field::Key, vec![camelot], true);
+                    modified = true;
+                }
+            }
+        }
+
+        // Duplicate and null separated values
+        let mut tags = tag.all_tags().into_iter().collect::<Vec<_>>();
+        tags.sort();
+        for (name, values) in tags {
+            // Already checked as ID3 frames
+            if handled.contains(&name) {
+                continue;
+            }
+            // Null is the standard separator in ID3v2.4
+            if self.config.enabled(LintRule::NullSeparator) && id3v24 != Some(true) && values.iter().any(|v| v.contains('\0')) {
+                if self.issue(LintRule::NullSeparator, path, format!("Stray null separator in {name}"), true) {
+                    let values = values.iter().flat_map(|v| v.split('\0')).filter(|v| !v.trim().is_empty()).map(String::from).collect();
+                    tag.set_raw(&name, values, true);
+                    modified = true;
+                }
+                continue;
+            }
+            if !self.config.enabled(LintRule::DuplicateFrames) || FREEFORM_TAGS.contains(&name.as_str()) {
+                continue;
+            }
+            let mut unique: Vec<String> = vec![];
+            for value in &values {
+                if !unique.contains(value) {
+                    unique.push(value.to_string());
+                }
+            }
+            if unique.len() != values.len() && self.issue(LintRule::DuplicateFrames, path, format!("Duplicate values in {name}"), true) {
+                tag.set_raw(&name, unique, true);
+                modified = true;
+            }
+        }
+
+        // Save fixes
+        if modified {
+            if let Err(e) = tag.save_file(path) {
+                error!("Failed saving lint fixes to {path:?}: {e}");
+                self.issues[issues_start..].iter_mut().for_each(|i| i.fixed = false);
+            }
+        }
+
+        Some(LintFileInfo {
+            album: first(&**tag, Field::Album),
+            album_artist: first(&**tag, Field::AlbumArtist),
+            disc: first(&**tag, Field::DiscNumber),
+            track: first(&**tag, Field::TrackNumber).and_then(|t| t.trim().parse().ok()),
+            id3v24,
+        })
+    }
+
+    /// Check folder wide rules
+    fn lint_folder(&mut self, folder: &Path, files: &[LintFileInfo]) {
+        // Album artist should be the same for the whole album
+        if self.config.enabled(LintRule::InconsistentAlbumArtist) {
+            let mut albums: BTreeMap<&str, Vec<&LintFileInfo>> = BTreeMap::new();
+            for file in files {
+                albums.entry(file.album.as_deref().unwrap_or_default()).or_default().push(file);
+            }
+            for (album, files) in albums {
+                let mut counts: HashMap<&str, usize> = HashMap::new();
+                for file in &files {
+                    *counts.entry(file.album_artist.as_deref().unwrap_or_default()).or_default() += 1;
+                }
+                if counts.len() < 2 {
+                    continue;
+                }
+                let mut artists = counts.keys().map(|a| format!("\"{a}\"")).collect::<Vec<_>>();
+                artists.sort();
+                self.issue(LintRule::InconsistentAlbumArtist, folder, format!("Album \"{album}\" has multiple album artists: {}", artists.join(", ")), false);
+            }
+        }
+
+        // Mixed ID3 versions
+        if self.config.enabled(LintRule::MixedId3Versions) {
+            let v24 = files.iter().filter(|f| f.id3v24 == Some(true)).count();
+            let v23 = files.iter().filter(|f| f.id3v24 == Some(false)).count();
+            if v24 > 0 && v23 > 0 {
+                self.issue(LintRule::MixedId3Versions, folder, format!("{v23} files with ID3v2.3 and {v24} with ID3v2.4"), false);
+            }
+        }
+
+        // Track numbers per album and disc
+        if self.config.enabled(LintRule::TrackNumberGaps) {
+            let mut discs: BTreeMap<(&str, &str), Vec<u16>> = BTreeMap::new();
+            for file in files {
+                if let Some(track) = file.track {
+                    discs.entry((file.album.as_deref().unwrap_or_default(), file.disc.as_deref().unwrap_or_default())).or_default().push(track);
+                }
+            }
+            for ((album, disc), mut tracks) in discs {
+                if tracks.len() < 2 {
+                    continue;
+                }
+                tracks.sort();
+                let mut duplicates = tracks.windows(2).filter(|w| w[0] == w[1]).map(|w| w[0]).collect::<Vec<_>>();
+                duplicates.dedup();
+                let missing = (1..*tracks.last().unwrap()).filter(|t| !tracks.contains(t)).collect::<Vec<_>>();
+                if missing.is_empty() && duplicates.is_empty() {
+                    continue;
+                }
+                let disc = match disc {
+                    "" => String::new(),
+                    d => format!(" disc {d}")
+                };
+                self.issue(LintRule::TrackNumberGaps, folder, format!("Album \"{album}\"{disc}: missing tracks {missing:?}, duplicate tracks {duplicates:?}"), false);
+            }
+        }
+    }
+}
+
+/// First non empty value of field
+fn first(tag: &dyn TagImpl, field: Field) -> Option<String> {
+    tag.get_field(field)?.into_iter().find(|v| !v.trim().is_empty())
+}
+
+/// Get the field which can be checked for SupportedTag
+pub(crate) fn field(tag: SupportedTag) -> Option<Field> {
+    Some(match tag {
+        SupportedTag::Title => Field::Title,
+        SupportedTag::Artist => Field::Artist,
+        SupportedTag::Album => Field::Album,
+        SupportedTag::AlbumArtist => Field::AlbumArtist,
+        SupportedTag::Key => Field::Key,
+        SupportedTag::BPM => Field::BPM,
+        SupportedTag::Genre => Field::Genre,
+        SupportedTag::Style => Field::Style,
+        SupportedTag::Label => Field::Label,
+        SupportedTag::ISRC => Field::ISRC,
+        SupportedTag::CatalogNumber => Field::CatalogNumber,
+        SupportedTag::Version => Field::Version,
+        SupportedTag::TrackNumber => Field::TrackNumber,
+        SupportedTag::TrackTotal => Field::TrackTotal,
+        SupportedTag::DiscNumber => Field::DiscNumber,
+        SupportedTag::Duration => Field::Duration,
+        SupportedTag::Remixer => Field::Remixer,
+        SupportedTag::Mood => Field::Mood,
+        _ => return None
+    })
+}
+
+/// Empty MP3 with ID3v2.3 tag
+#[cfg(test)]
+pub(crate) fn test_mp3(dir: &Path, tags: &[(&str, &str)]) -> PathBuf {
+    let path = dir.join("test.mp3");
+    std::fs::write(&path, b"").unwrap();
+    let mut tag = Tag::load_file(&path, true).unwrap();
+    if let Tag::ID3(id3) = &mut tag {
+        id3.id3v24 = false;
+    }
+    for (name, value) in tags {
+        tag.tag_mut().set_raw(name, vec![value.to_string()], true);
+    }
+    tag.tag_mut().save_file(&path).unwrap();
+    path
+}
+
+#[test]
+fn test_lint() {
+    let dir = tempfile::tempdir().unwrap();
+    let files = vec![test_mp3(dir.path(), &[("TPE1", "A\0B"), ("TIT2", "Title"), ("CUSTOM", "C\0D"), ("DUPES", "E, E")])];
+    let config = LintConfig { rules: vec![LintRule::NullSeparator, LintRule::DuplicateFrames], ..Default::default() };
+    let report = Linter::lint(&files, &config);
+    let issues = |rule| report.issues.iter().filter(|i| i.rule == rule).map(|i| i.message.as_str()).collect::<Vec<_>>();
+    assert_eq!(issues(LintRule::NullSeparator), vec!["Null separator in TPE1 which is invalid in ID3v2.3", "Stray null separator in CUSTOM"]);
+    assert_eq!(issues(LintRule::DuplicateFrames), vec!["Duplicate values in DUPES"]);
+    assert!(report.issues.iter().all(|i| i.fixable && !i.fixed));
+}
+
+#[test]
+fn test_lint_fix() {
+    let dir = tempfile::tempdir().unwrap();
+    let files = vec![test_mp3(dir.path(), &[("TPE1", "A\0B"), ("CUSTOM", "C\0D"), ("DUPES", "E, E")])];
+    let config = LintConfig { rules: vec![LintRule::NullSeparator, LintRule::DuplicateFrames], fix: true, ..Default::default() };
+    let report = Linter::lint(&files, &config);
+    assert_eq!(report.issues.len(), 3);
+    assert!(report.issues.iter().all(|i| i.fixed));
+
+    let tag = Tag::load_file(&files[0], false).unwrap();
+    let tag = tag.tag();
+    assert_eq!(tag.get_raw("TPE1"), Some(vec!["A".to_string(), "B".to_string()]));
+    assert_eq!(tag.get_raw("CUSTOM"), Some(vec!["C".to_string(), "D".to_string()]));
+    assert_eq!(tag.get_raw("DUPES"), Some(vec!["E".to_string()]));
+    assert!(Linter::lint(&files, &config).issues.is_empty());
+}
@@ -0,0 +1,338 @@
+use std::path::{Path, PathBuf};
+use anyhow::Error;
+use regex::{Regex, RegexBuilder};
+use serde::{Serialize, Deserialize};
+use onetagger_tag::{Tag, TagSeparators};
+use onetagger_tagger::SupportedTag;
+
+use crate::lint::field;
+
+/// Placeholder of protected (exception) values while splitting
+const PLACEHOLDER: char = '\u{E000}';
+
+/// Convert multi-value tags to native multi-value frames or to other separator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SeparatorMigrationConfig {
+    /// Tags to migrate
+    pub tags: Vec<SupportedTag>,
+    /// Separators to detect in single values, in order of priority. Spaces around are ignored
+    pub detect: Vec<String>,
+    /// Target separators, empty (or \0 for ID3) = native multi-value frames. Native ID3 saves the file as ID3v2.4
+    pub target: TagSeparators,
+    /// Values which should never be split, eg. "AC/DC"
+    pub exceptions: Vec<String>,
+    /// Values containing these are left as they are and reported, eg. "Simon & Garfunkel".
+    /// Values split by a detected separator from this list ("/", ",") are reported and not written
+    pub ambiguous: Vec<String>,
+    /// Save the changes, otherwise only report
+    pub write: bool,
+}
+
+impl Default for SeparatorMigrationConfig {
+    fn default() -> Self {
+        Self {
+            tags: vec![SupportedTag::Artist, SupportedTag::AlbumArtist, SupportedTag::Remixer, SupportedTag::Genre, SupportedTag::Style, SupportedTag::Mood],
+            detect: vec![";".to_string(), "/".to_string(), ",".to_string()],
+            target: TagSeparators { id3: "\\0".to_string(), vorbis: None, mp4: String::new() },
+            exceptions: vec!["AC/DC", "Earth, Wind & Fire", "Crosby, Stills, Nash & Young", "Emerson, Lake & Palmer", "Tyler, The Creator"]
+                .into_iter().map(String::from).collect(),
+            ambiguous: vec!["&", " and ", " x ", " vs", " + ", "/", ","].into_iter().map(String::from).collect(),
+            write: false,
+        }
+    }
+}
+
+/// How are multiple values currently stored
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", tag = "type", content = "separator")]
+pub enum SeparatorStyle {
+    Single,
+    /// ID3v2.4 null separated, repeated Vorbis comments / MP4 atoms
+    Native,
+    Separator(String),
+    /// Multiple separators or separators inside native values
+    Mixed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeparatorChange {
+    pub path: PathBuf,
+    pub tag: SupportedTag,
+    pub style: SeparatorStyle,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+/// Value which might contain multiple values, but wasn't split
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeparatorAmbiguity {
+    pub path: PathBuf,
+    pub tag: SupportedTag,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeparatorReport {
+    pub files: usize,
+    /// Files which couldn't be loaded or saved
+    pub failed: usize,
+    /// Files written
+    pub written: usize,
+    pub changes: Vec<SeparatorChange>,
+    pub ambiguous: Vec<SeparatorAmbiguity>,
+}
+
+pub struct SeparatorMigration {
+    config: SeparatorMigrationConfig,
+    detect: Option<Regex>,
+}
+
+impl SeparatorMigration {
+    /// Create new instance with compiled separators
+    pub fn new(config: SeparatorMigrationConfig) -> SeparatorMigration {
+        let pattern = config.detect.iter().map(|s| s.trim()).filter(|s| !s.is_empty())
+            .map(|s| format!(r"\s*{}\s*", regex::escape(s))).collect::<Vec<_>>().join("|");
+        let detect = match pattern.is_empty() {
+            true => None,
+            false => RegexBuilder::new(&pattern).build().map_err(|e| warn!("Invalid separators: {e}")).ok()
+        };
+        SeparatorMigration { config, detect }
+    }
+
+    /// Detect separators of all files and migrate them (if enabled)
+    pub fn migrate(&self, files: &[PathBuf]) -> SeparatorReport {
+        let mut report = SeparatorReport::default();
+        for path in files {
+            report.files += 1;
+            match self.migrate_file(path, &mut report) {
+                Ok(true) => report.written += 1,
+                Ok(false) => {},
+                Err(e) => {
+                    error!("Separator migration of {path:?} failed: {e}");
+                    report.failed += 1;
+                }
+            }
+        }
+        info!("Separator migration: {} files, {} changes, {} ambiguous", report.files, report.changes.len(), report.ambiguous.len());
+        report
+    }
+
+    /// Returns whether the file was written
+    fn migrate_file(&self, path: &Path, report: &mut SeparatorReport) -> Result<bool, Error> {
+        let mut tag_wrap = Tag::load_file(path, false)?;
+        let format = tag_wrap.format();
+        // Read with native separators, so values stay as they are stored
+        tag_wrap.set_separators(&Self::native_separators());
+        let (null_native, null_frames) = match &tag_wrap {
+            Tag::ID3(t) => (t.id3v24, t.null_separated_frames()),
+            Tag::FLAC(_) | Tag::Vorbis(_) | Tag::MP4(_) | Tag::APE(_) => (true, vec![])
+        };
+        let target = self.target_separator(&tag_wrap);
+
+        let mut changes = vec![];
+        for tag in &self.config.tags {
+            let field = match field(*tag) {
+                Some(f) => f,
+                None => continue
+            };
+            // ID3v2.3 null separated values are read as "/"
+            let name = field.by_format(&format);
+            let values = match null_frames.iter().find(|(id, _)| id == name) {
+                Some((_, content)) => vec![content.to_string()],
+                None => tag_wrap.tag().get_field(field.clone()).unwrap_or_default()
+            };
+            if values.is_empty() {
+                continue;
+            }
+
+            let (style, new, ambiguous) = self.detect(&values, null_native);
+            // Values split by an ambiguous separator are only reported, the user has to check them
+            let split_ambiguous = ambiguous.iter().any(|v| !new.contains(v));
+            report.ambiguous.extend(ambiguous.into_iter().map(|value| SeparatorAmbiguity { path: path.to_owned(), tag: *tag, value }));
+            if split_ambiguous {
+                continue;
+            }
+            let expected = match (new.len() > 1, &target) {
+                (false, _) => SeparatorStyle::Single,
+                (true, None) => SeparatorStyle::Native,
+                (true, Some(s)) => SeparatorStyle::Separator(s.trim().to_string()),
+            };
+            if style == expected {
+                continue;
+            }
+            changes.push((field, new.clone()));
+            report.changes.push(SeparatorChange { path: path.to_owned(), tag: *tag, style, old: values, new });
+        }
+
+        if changes.is_empty() || !self.config.write {
+            return Ok(false);
+        }
+        match target {
+            Some(_) => tag_wrap.set_separators(&self.config.target),
+            None => tag_wrap.set_separators(&Self::native_separators()),
+        }
+        // Native multi-value requires ID3v2.4
+        if let (Tag::ID3(t), None) = (&mut tag_wrap, &target) {
+            t.set_id3v24(true);
+        }
+        let tag = tag_wrap.tag_mut();
+        for (field, value) in changes {
+            tag.set_field(field, value, true);
+        }
+        tag.save_file(path)?;
+        Ok(true)
+    }
+
+    /// ID3v2.4 null separated, repeated Vorbis comments and MP4 atoms
+    pub(crate) fn native_separators() -> TagSeparators {
+        TagSeparators { id3: "\0".to_string(), vorbis: None, mp4: String::new() }
+    }
+
+    /// Separator the values are going to be written with, None = native
+    fn target_separator(&self, tag: &Tag) -> Option<String> {
+        let target = &self.config.target;
+        let separator = match tag {
+            Tag::ID3(_) => target.id3.to_string(),
+            Tag::MP4(_) => target.mp4.to_string(),
+            Tag::FLAC(_) | Tag::Vorbis(_) | Tag::APE(_) => target.vorbis.clone().unwrap_or_default(),
+        }.replace("\\0", "\0");
+        match separator.as_str() {
+            "" => None,
+            "\0" if matches!(tag, Tag::ID3(_) | Tag::APE(_)) => None,
+            _ => Some(separator)
+        }
+    }
+
+    /// Get current style, split values and ambiguous values
+    fn detect(&self, values: &[String], null_native: bool) -> (SeparatorStyle, Vec<String>, Vec<String>) {
+        let mut native = values.len() > 1;
+        let mut found: Vec<String> = vec![];
+        let mut parts = vec![];
+        for value in values {
+            let protected = self.protect(value);
+            if protected.contains('\0') {
+                match null_native {
+                    true => native = true,
+                    false => found.push("\0".to_string()),
+                }
+            }
+            parts.extend(protected.split('\0').map(String::from));
+        }
+
+        let mut output: Vec<String> = vec![];
+        let mut ambiguous = vec![];
+        for part in parts {
+            let split = match &self.detect {
+                Some(re) => {
+                    let separators = re.find_iter(&part).map(|m| m.as_str().trim().to_string()).collect::<Vec<_>>();
+                    // Might be a name, eg. "Earth, Wind & Fire"
+                    if separators.iter().any(|s| self.config.ambiguous.iter().any(|a| a.trim() == s)) {
+                        ambiguous.push(self.restore(part.trim()));
+                    }
+                    found.extend(separators);
+                    re.split(&part).map(String::from).collect()
+                },
+                None => vec![part]
+            };
+
+            for value in split {
+                let value = self.restore(value.trim());
+                if value.is_empty() {
+                    continue;
+                }
+                let lower = value.to_lowercase();
+                if !self.config.exceptions.iter().any(|e| e.eq_ignore_ascii_case(&value))
+                    && self.config.ambiguous.iter().any(|a| !a.is_empty() && lower.contains(&a.to_lowercase()))
+                    && !ambiguous.contains(&value) {
+                    ambiguous.push(value.to_string());
+                }
+                output.push(value);
+            }
+        }
+
+        found.sort();
+        found.dedup();
+        let style = match (native, found.len()) {
+            (false, 0) => SeparatorStyle::Single,
+            (true, 0) => SeparatorStyle::Native,
+            (false, 1) => SeparatorStyle::Separator(found.remove(0)),
+            _ => SeparatorStyle::Mixed
+        };
+        (style, output, ambiguous)
+    }
+
+    /// Replace exceptions with placeholders, so they don't get split.
+    /// ID3v2.3 "/" is read as null, so "AC/DC" might be "AC\0DC"
+    fn protect(&self, value: &str) -> String {
+        let mut value = value.to_string();
+        for (i, exception) in self.config.exceptions.iter().enumerate().filter(|(_, e)| !e.is_empty()) {
+            let placeholder = format!("{PLACEHOLDER}{i}{PLACEHOLDER}");
+            value = value.replace(exception.as_str(), &placeholder).replace(&exception.replace('/', "\0"), &placeholder);
+        }
+        value
+    }
+
+    /// Put exceptions back
+    fn restore(&self, value: &str) -> String {
+        let mut value = value.to_string();
+        for (i, exception) in self.config.exceptions.iter().enumerate() {
+            value = value.replace(&format!("{PLACEHOLDER}{i}{PLACEHOLDER}"), exception);
+        }
+        value
+    }
+}
+
+#[test]
+fn test_detect() {
+    let migration = SeparatorMigration::new(SeparatorMigrationConfig { exceptions: vec!["AC/DC".to_string()], ..Default::default() });
+    let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+    let detect = |values: &[&str], null_native| migration.detect(&strings(values), null_native);
+    let separator = |s: &str| SeparatorStyle::Separator(s.to_string());
+
+    // Spaces around separators are ignored
+    assert_eq!(detect(&["A; B ;C"], true), (separator(";"), strings(&["A", "B", "C"]), vec![]));
+    assert_eq!(detect(&["A", "B"], true), (SeparatorStyle::Native, strings(&["A", "B"]), vec![]));
+    assert_eq!(detect(&["A; B/C"], true).0, SeparatorStyle::Mixed);
+
+    // Exceptions, also when ID3v2.3 "/" is read as null
+    assert_eq!(detect(&["AC/DC; B"], true), (separator(";"), strings(&["AC/DC", "B"]), vec![]));
+    assert_eq!(detect(&["AC\0DC"], false), (SeparatorStyle::Single, strings(&["AC/DC"]), vec![]));
+    assert_eq!(detect(&["A\0B"], false), (separator("\0"), strings(&["A", "B"]), vec![]));
+
+    // Ambiguous values are kept, ambiguous separators are split
+    assert_eq!(detect(&["Simon & Garfunkel; B"], true), (separator(";"), strings(&["Simon & Garfunkel", "B"]), strings(&["Simon & Garfunkel"])));
+    assert_eq!(detect(&["Drum/Bass"], true), (separator("/"), strings(&["Drum", "Bass"]), strings(&["Drum/Bass"])));
+    let migration = SeparatorMigration::new(SeparatorMigrationConfig::default());
+    assert_eq!(migration.detect(&strings(&["Earth, Wind & Fire"]), true), (SeparatorStyle::Single, strings(&["Earth, Wind & Fire"]), vec![]));
+}
+
+#[test]
+fn test_migrate_file() {
+    use onetagger_tag::Field;
+
+    // ID3v2.3 can't store null separators, so TPE1 is written as "A/B"
+    let dir = tempfile::tempdir().unwrap();
+    let path = crate::lint::test_mp3(dir.path(), &[("TPE1", "A\0B"), ("TPE2", "AC/DC"), ("TCON", "House; Techno"), ("TMOO", "Dark, Deep")]);
+    let report = SeparatorMigration::new(SeparatorMigrationConfig { write: true, ..Default::default() }).migrate(&[path.clone()]);
+    assert_eq!(report.written, 1);
+    assert_eq!(report.changes.iter().map(|c| (c.tag, c.style.clone())).collect::<Vec<_>>(), vec![
+        (SupportedTag::Artist, SeparatorStyle::Separator("\0".to_string())),
+        (SupportedTag::Genre, SeparatorStyle::Separator(";".to_string())),
+    ]);
+    assert_eq!(report.ambiguous.iter().map(|a| (a.tag, a.value.as_str())).collect::<Vec<_>>(), vec![(SupportedTag::Mood, "Dark, Deep")]);
+
+    let mut tag_wrap = Tag::load_file(&path, false).unwrap();
+    tag_wrap.set_separators(&SeparatorMigration::native_separators());
+    if let Tag::ID3(id3) = &tag_wrap {
+        assert!(id3.id3v24);
+    }
+    let tag = tag_wrap.tag();
+    assert_eq!(tag.get_field(Field::Artist), Some(vec!["A".to_string(), "B".to_string()]));
+    assert_eq!(tag.get_field(Field::AlbumArtist), Some(vec!["AC/DC".to_string()]));
+    assert_eq!(tag.get_field(Field::Genre), Some(vec!["House".to_string(), "Techno".to_string()]));
+    assert_eq!(tag.get_field(Field::Mood), Some(vec!["Dark, Deep".to_string()]));
+}
@@ -9,11 +9,17 @@ use std::sync::{Arc, Mutex};
 use clap::{Parser, Subcommand};
 use convert_case::{Casing, Case};
 use onetagger_platforms::spotify::Spotify;
-use onetagger_renamer::{RenamerConfig, Renamer, TemplateParser};
+use onetagger_renamer::{RenamerConfig, Renamer, TemplateParser, ConflictStrategy, FilesystemProfile, PathTemplate, PathTagsConfig};
 use onetagger_shared::VERSION;
 use onetagger_autotag::audiofeatures::{AudioFeaturesConfig, AudioFeatures};
-use onetagger_autotag::{Tagger, TaggerConfigExt, AudioFileInfoImpl};
-use onetagger_tagger::{TaggerConfig, AudioFileInfo, SupportedTag};
+use onetagger_autotag::{Tagger, TaggerConfigExt, AudioFileInfoImpl, CreditsConfigExt};
+use onetagger_autotag::lint::{Linter, LintConfig, LintRule, LintSeverity};
+use onetagger_autotag::separators::{SeparatorMigration, SeparatorMigrationConfig};
+use onetagger_autotag::convert::{TagConverter, TagConversionConfig};
+use onetagger_tagger::{TaggerConfig, AudioFileInfo, SupportedTag, CompanionFiles, TagSeparators};
+use onetagger_tagger::credits::CreditsConfig;
+use onetagger_tagger::genres::GenreMapping;
+use onetagger_tagger::tag_mapping::TagMapping;
 use env_logger;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -89,7 +95,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             info!("Tagging finished, took: {} seconds.", (timestamp!() - start) / 1000);
         },
-        Actions::QueryUrl { url, directory, confidence, output_format } => {
+        Actions::QueryUrl { url, directory, confidence, output_format, full } => {
             // Check if directory exists if provided
             if let Some(dir) = &directory {
                 let dir_path = std::path::Path::new(dir);
@@ -101,7 +107,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             // Create SongDownloader instance
             let mut downloader = onetagger_songdownloader::SongDownloader::new()
                 .with_url(url)
-                .with_confidence(*confidence);
+                .with_confidence(*confidence)
+                .with_incremental(!*full);
             
             if let Some(dir) = &directory {
                 downloader = downloader.with_directory(std::path::Path::new(dir));
@@ -123,7 +130,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
         },
-        Actions::DownloadSongs { csv_file, directory } => {
+        Actions::DownloadSongs { csv_file, directory, max_attempts } => {
             // Check if directory exists
             let dir_path = std::path::Path::new(&directory);
             if !dir_path.exists() {
@@ -138,7 +145,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             
             // Create SongDownloader instance
             let downloader = onetagger_songdownloader::SongDownloader::new()
-                .with_directory(dir_path);
+                .with_directory(dir_path)
+                .with_max_attempts(*max_attempts);
             
             // Download songs
             match downloader.download_songs(csv_path) {
@@ -150,6 +158,46 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
         },
+        Actions::SyncSongs { url, directory, confidence, max_attempts } => {
+            if !directory.exists() {
+                return Err(anyhow::anyhow!("Directory does not exist: {:?}", directory).into());
+            }
+            
+            let downloader = onetagger_songdownloader::SongDownloader::new()
+                .with_directory(directory)
+                .with_confidence(*confidence)
+                .with_max_attempts(*max_attempts);
+            
+            let manifest = downloader.sync(url).map_err(|e| anyhow::anyhow!("Failed to sync: {}", e))?;
+            // Non-zero exit code when something is still failing, useful for cron
+            let failed = manifest.entries.iter().filter(|e| e.status == onetagger_songdownloader::DownloadStatus::Failed).count();
+            if failed > 0 {
+                println!("\n{} songs failed to download, they will be retried on next sync.", failed);
+                std::process::exit(2);
+            }
+            println!("\nSync finished successfully!");
+        },
+        Actions::ParseTracklist { input, json } => {
+            // Read from stdin if no file given
+            let text = match input {
+                Some(path) => std::fs::read_to_string(path)?,
+                None => std::io::read_to_string(std::io::stdin())?
+            };
+            let tracklist = onetagger_songdownloader::Tracklist::parse(&text);
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&tracklist)?);
+                return Ok(());
+            }
+            for (i, entry) in tracklist.entries.iter().enumerate() {
+                let time = match (entry.start_timestamp(), entry.end_timestamp()) {
+                    (Some(start), Some(end)) => format!("[{start} - {end}] "),
+                    (Some(start), None) => format!("[{start}] "),
+                    _ => String::new()
+                };
+                let flag = if entry.unidentified { " (unidentified)" } else { "" };
+                println!("{:02}. {time}{entry}{flag}", entry.position.unwrap_or(i as u32 + 1));
+            }
+        },
         // Spotify OAuth flow
         Actions::AuthorizeSpotify { client_id, client_secret, prompt, expose } => {
             let (auth_url, client) = Spotify::generate_auth_url(&client_id, &client_secret).expect("Failed generating auth URL!");
@@ -180,7 +228,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             std::process::exit(0);
         },
         // Renamer
-        Actions::Renamer { path, output, template, copy, no_subfolders, preview, overwrite, separator, keep_subfolders } => {
+        Actions::Renamer { path, output, template, copy, no_subfolders, preview, overwrite, separator, keep_subfolders, conflict, companions, filesystem, transliterate } => {
             let config = RenamerConfig {
                 path: path.to_owned(),
                 out_dir: output.to_owned(),
@@ -190,20 +238,194 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 overwrite: *overwrite,
                 separator: separator.to_string(),
                 keep_subfolders: *keep_subfolders,
+                conflict: *conflict,
+                companion: CompanionFiles { enabled: *companions, ..Default::default() },
+                filesystem: filesystem.unwrap_or_default(),
+                transliterate: *transliterate,
             };
-            let mut renamer = Renamer::new(TemplateParser::parse(&template));
+            // Don't touch any files with invalid template
+            let parser = match TemplateParser::parse(&template) {
+                Ok(parser) => parser,
+                Err(e) => {
+                    for diagnostic in &e.diagnostics {
+                        eprintln!("{}\n", diagnostic.report(template));
+                    }
+                    std::process::exit(1);
+                }
+            };
+            let mut renamer = Renamer::new(parser);
             let files = AudioFileInfo::load_files_iter(&config.path, config.subfolders, None, None);
             let names = renamer.generate(files, &config).expect("Failed generating filenames!");
 
+            let plan = renamer.plan(&names, &config);
+
+            // Only preview
+            if *preview {
+                for (i, op) in plan.operations.iter().enumerate() {
+                    println!("{}. {:?} -> {:?}", i + 1, op.from, op.to);
+                    if !op.issues.is_empty() {
+                        println!("   {:?}: {:?}", op.action, op.issues);
+                    }
+                }
+                return Ok(());
+            }
+
+            plan.execute().expect("Failed renaming, changes were reverted!");
+        },
+        // Parse tags from paths
+        Actions::PathTags { path, template, no_subfolders, preview, overwrite, id3v24 } => {
+            let config = PathTagsConfig {
+                path: path.to_owned(),
+                template: template.to_string(),
+                subfolders: !*no_subfolders,
+                overwrite: *overwrite,
+                separators: TagSeparators::default(),
+                id3v24: *id3v24,
+            };
+            let path_template = match PathTemplate::parse(&config.template) {
+                Ok(t) => t,
+                Err(e) => {
+                    eprintln!("Invalid template: {e}");
+                    std::process::exit(1);
+                }
+            };
+            let files = path_template.generate(AudioFileInfo::get_file_list(&config.path, config.subfolders));
+
             // Only preview
             if *preview {
-                for (i, (from, to)) in names.iter().enumerate() {
-                    println!("{}. {:?} -> {:?}", i + 1, from, to);
+                for (i, file) in files.iter().enumerate() {
+                    println!("{}. {:?}", i + 1, file.path);
+                    match file.values.is_empty() {
+                        true => println!("   Doesn't match the template"),
+                        false => println!("   {}", file.values.iter().map(|(k, v)| format!("{k}: {v}")).collect::<Vec<_>>().join(", ")),
+                    }
                 }
                 return Ok(());
             }
 
-            renamer.rename(&names, &config).expect("Failed renaming!");
+            let count = PathTemplate::apply(&files, &config);
+            info!("Written tags to {count} files");
+        },
+        // Tag lint
+        Actions::Lint { path, config, required, rules, camelot, fix, json, no_subfolders } => {
+            let mut lint_config: LintConfig = match config {
+                Some(config) => serde_json::from_reader(&File::open(config)?)?,
+                None => LintConfig::default()
+            };
+            if let Some(required) = required {
+                lint_config.required = required.split(",").map(|t| serde_json::from_str(&format!("\"{}\"", t.trim().to_case(Case::Camel))))
+                    .collect::<Result<_, _>>().map_err(|e| anyhow::anyhow!("Invalid required tag: {e}"))?;
+            }
+            if let Some(rules) = rules {
+                lint_config.rules = rules.split(",").map(|r| serde_json::from_str::<LintRule>(&format!("\"{}\"", r.trim().to_case(Case::Camel))))
+                    .collect::<Result<_, _>>().map_err(|e| anyhow::anyhow!("Invalid lint rule: {e}"))?;
+            }
+            lint_config.camelot |= *camelot;
+            lint_config.fix |= *fix;
+
+            let files = AudioFileInfo::get_file_list(path, !*no_subfolders);
+            let report = Linter::lint(&files, &lint_config);
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                for issue in &report.issues {
+                    let fixed = if issue.fixed { " (fixed)" } else { "" };
+                    println!("{:?} [{:?}] {:?}: {}{fixed}", issue.severity, issue.rule, issue.path, issue.message);
+                }
+                println!("\n{} files checked, {} errors, {} warnings", report.files, report.count(LintSeverity::Error), report.count(LintSeverity::Warning));
+            }
+
+            // Exit code for CI
+            if report.count(LintSeverity::Error) > 0 {
+                std::process::exit(2);
+            }
+            if report.count(LintSeverity::Warning) > 0 {
+                std::process::exit(3);
+            }
+        },
+        // Featured artists, remixers and version extraction
+        Actions::Credits { path, config, separator, dry_run, no_subfolders } => {
+            let rules: CreditsConfig = match config {
+                Some(config) => serde_json::from_reader(&File::open(config)?)?,
+                None => CreditsConfig::default()
+            };
+            let separators = match separator {
+                Some(separator) => TagSeparators { id3: separator.to_string(), vorbis: Some(separator.to_string()), mp4: separator.to_string() },
+                None => TagSeparators::default()
+            };
+            let mut count = 0;
+            for file in AudioFileInfo::get_file_list(path, !*no_subfolders) {
+                match rules.apply_file(&file, &separators, !*dry_run) {
+                    Ok(Some(credits)) => {
+                        println!("{file:?}: {} - {}", rules.format_artists(&credits).join(", "), rules.format_title(&credits));
+                        count += 1;
+                    },
+                    Ok(None) => {},
+                    Err(e) => error!("Failed extracting credits of {file:?}: {e}"),
+                }
+            }
+            println!("\n{count} files {}", if *dry_run { "would be changed" } else { "changed" });
+        },
+        // Separator migration
+        Actions::Separators { path, config, target, exceptions, write, json, no_subfolders } => {
+            let mut migration_config: SeparatorMigrationConfig = match config {
+                Some(config) => serde_json::from_reader(&File::open(config)?)?,
+                None => SeparatorMigrationConfig::default()
+            };
+            if let Some(target) = target {
+                migration_config.target = TagSeparators { id3: target.to_string(), vorbis: Some(target.to_string()), mp4: target.to_string() };
+            }
+            if let Some(exceptions) = exceptions {
+                migration_config.exceptions = exceptions.split(",").map(|e| e.trim().to_string()).filter(|e| !e.is_empty()).collect();
+            }
+            migration_config.write |= *write;
+
+            let files = AudioFileInfo::get_file_list(path, !*no_subfolders);
+            let report = SeparatorMigration::new(migration_config).migrate(&files);
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                for change in &report.changes {
+                    println!("{:?} [{:?}] {:?}: {:?} -> {:?}", change.path, change.tag, change.style, change.old, change.new);
+                }
+                for ambiguous in &report.ambiguous {
+                    println!("Ambiguous {:?} [{:?}]: {}", ambiguous.path, ambiguous.tag, ambiguous.value);
+                }
+                println!("\n{} files checked, {} fields to convert, {} ambiguous values, {} files written, {} failed",
+                    report.files, report.changes.len(), report.ambiguous.len(), report.written, report.failed);
+            }
+        },
+        // Tag conversion
+        Actions::Convert { path, config, id3v24, id3v23, copy_from, source_path, write, json, no_subfolders } => {
+            let mut conversion_config: TagConversionConfig = match config {
+                Some(config) => serde_json::from_reader(&File::open(config)?)?,
+                None => TagConversionConfig::default()
+            };
+            if *id3v24 || *id3v23 {
+                conversion_config.id3v24 = Some(*id3v24);
+            }
+            if let Some(copy_from) = copy_from {
+                conversion_config.copy_from = Some(copy_from.to_string());
+            }
+            if let Some(source_path) = source_path {
+                conversion_config.source_path = Some(source_path.to_owned());
+            }
+            conversion_config.write |= *write;
+
+            let files = AudioFileInfo::get_file_list(path, !*no_subfolders);
+            let report = TagConverter::new(conversion_config).convert(&files);
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                for change in &report.changes {
+                    println!("{:?}: {}", change.path, change.message);
+                }
+                for unsupported in &report.unsupported {
+                    println!("Unsupported {:?}: {}", unsupported.path, unsupported.message);
+                }
+                println!("\n{} files checked, {} changes, {} unsupported, {} files written, {} failed",
+                    report.files, report.changes.len(), report.unsupported.len(), report.written, report.failed);
+            }
         },
         // Server mode
         Actions::Server { expose, path, browser } => {
@@ -272,7 +494,7 @@ enum Actions {
         #[clap(long)]
         strictness: Option<u8>,
 
-        /// Writes a cover.jpg into the folder
+        /// Writes a cover.jpg (or cover.png) into the folder
         #[clap(long)]
         album_art_file: bool,
 
@@ -331,6 +553,18 @@ enum Actions {
         /// Tag on multiple platforms instead of the default fallback mode
         #[clap(long)]
         multiplatform: bool,
+
+        /// Path to genre mapping rules (JSON) to normalize genres with
+        #[clap(long)]
+        genre_mapping: Option<PathBuf>,
+
+        /// Tag mapping preset: Rekordbox, Serato, Traktor, iTunes, Picard (custom mapping goes into config)
+        #[clap(long)]
+        tag_mapping: Option<String>,
+
+        /// Move featured artists, remixers and versions from titles into their fields (default rules unless set in config)
+        #[clap(long)]
+        credits: bool,
     },
     /// Start Audio Features in CLI mode
     Audiofeatures {
@@ -371,6 +605,10 @@ enum Actions {
         /// Output format (csv or json)
         #[clap(long)]
         output_format: Option<String>,
+        
+        /// Process all videos, even the ones already synced into the directory's manifest
+        #[clap(long)]
+        full: bool,
     },
     /// Download songs from a CSV/JSON file generated by query-url
     DownloadSongs {
@@ -381,6 +619,38 @@ enum Actions {
         /// Directory where songs will be downloaded
         #[clap(short, long)]
         directory: PathBuf,
+        
+        /// How many times to try downloading a song before giving up
+        #[clap(long, default_value = "3")]
+        max_attempts: u32,
+    },
+    /// Query new videos from channels / playlists and download them (meant to be run periodically)
+    SyncSongs {
+        /// URLs to sync, can be specified multiple times
+        #[clap(short, long, required = true)]
+        url: Vec<String>,
+        
+        /// Directory where songs will be downloaded, contains the manifest
+        #[clap(short, long)]
+        directory: PathBuf,
+        
+        /// Shazam confidence threshold (0.0-1.0)
+        #[clap(long, default_value = "0.75")]
+        confidence: f32,
+        
+        /// How many times to try downloading a song before giving up
+        #[clap(long, default_value = "3")]
+        max_attempts: u32,
+    },
+    /// Parse a tracklist pasted from anywhere into structured entries
+    ParseTracklist {
+        /// Text file with the tracklist, reads stdin if not specified
+        #[clap(short, long)]
+        input: Option<PathBuf>,
+        
+        /// Output as JSON
+        #[clap(long)]
+        json: bool,
     },
     /// Authorize Spotify and cache the token
     AuthorizeSpotify {
@@ -436,6 +706,172 @@ enum Actions {
         /// Keep original subfolders
         #[clap(long)]
         keep_subfolders: bool,
+
+        /// What to do when target file exists: skip, suffix, keep-larger, keep-better
+        #[clap(long, default_value = "skip")]
+        conflict: ConflictStrategy,
+
+        /// Move .lrc, .cue and folder art together with the audio files
+        #[clap(long)]
+        companions: bool,
+
+        /// Naming rules of the target filesystem: ntfs, fat32, posix, smb, ascii (default: ntfs on Windows, posix elsewhere)
+        #[clap(long)]
+        filesystem: Option<FilesystemProfile>,
+
+        /// Convert accented and non-latin characters to ASCII
+        #[clap(long)]
+        transliterate: bool,
+    },
+    /// Write tags parsed from file paths
+    PathTags {
+        /// Path to input files
+        #[clap(long, short)]
+        path: PathBuf,
+
+        /// Path template, for example `%albumartist%/%year% - %album%/%track%. %title%`
+        #[clap(long, short)]
+        template: String,
+
+        /// Exclude subfolders
+        #[clap(long)]
+        no_subfolders: bool,
+
+        /// Don't write anything, only print the parsed values
+        #[clap(long)]
+        preview: bool,
+
+        /// Overwrite existing tag values
+        #[clap(long)]
+        overwrite: bool,
+
+        /// Write ID3v2.4 instead of ID3v2.3
+        #[clap(long)]
+        id3v24: bool,
+    },
+    /// Check the tags for issues. Exit code is 2 if there are errors, 3 if only warnings
+    Lint {
+        /// Path to music files
+        #[clap(short, long)]
+        path: PathBuf,
+
+        /// Path to lint config file (JSON)
+        #[clap(short, long)]
+        config: Option<PathBuf>,
+
+        /// Comma separated list of tags which have to be present (default: title,artist)
+        #[clap(long)]
+        required: Option<String>,
+
+        /// Comma separated list of rules to check, for example `missing-field,bpm-out-of-range` (default: all)
+        #[clap(long)]
+        rules: Option<String>,
+
+        /// Keys should be in Camelot notation
+        #[clap(long)]
+        camelot: bool,
+
+        /// Fix the unambiguous issues
+        #[clap(long)]
+        fix: bool,
+
+        /// Output the report as JSON
+        #[clap(long)]
+        json: bool,
+
+        /// Don't include subfolders
+        #[clap(long)]
+        no_subfolders: bool,
+    },
+    /// Move featured artists, remixers and versions from titles into Artist, Remixer and Version
+    Credits {
+        /// Path to music files
+        #[clap(short, long)]
+        path: PathBuf,
+
+        /// Path to extraction rules (JSON)
+        #[clap(short, long)]
+        config: Option<PathBuf>,
+
+        /// Separator of multiple artists in tags of all formats (default: ", " in ID3 and MP4, native in Vorbis)
+        #[clap(long)]
+        separator: Option<String>,
+
+        /// Only print the changes
+        #[clap(long)]
+        dry_run: bool,
+
+        /// Don't include subfolders
+        #[clap(long)]
+        no_subfolders: bool,
+    },
+    /// Detect separators of multi-value tags and convert them to native multi-value frames or other separator
+    Separators {
+        /// Path to music files
+        #[clap(short, long)]
+        path: PathBuf,
+
+        /// Path to migration config file (JSON)
+        #[clap(short, long)]
+        config: Option<PathBuf>,
+
+        /// Target separator for all formats (default: native multi-value frames)
+        #[clap(long)]
+        target: Option<String>,
+
+        /// Comma separated list of values which should never be split
+        #[clap(long)]
+        exceptions: Option<String>,
+
+        /// Write the changes, otherwise only report
+        #[clap(long)]
+        write: bool,
+
+        /// Output the report as JSON
+        #[clap(long)]
+        json: bool,
+
+        /// Don't include subfolders
+        #[clap(long)]
+        no_subfolders: bool,
+    },
+    /// Convert ID3 versions, remove ID3v1 / APE from MP3, empty and duplicate frames, copy tags between formats
+    Convert {
+        /// Path to music files
+        #[clap(short, long)]
+        path: PathBuf,
+
+        /// Path to conversion config file (JSON)
+        #[clap(short, long)]
+        config: Option<PathBuf>,
+
+        /// Convert ID3 tags to ID3v2.4
+        #[clap(long, conflicts_with = "id3v23")]
+        id3v24: bool,
+
+        /// Convert ID3 tags to ID3v2.3
+        #[clap(long)]
+        id3v23: bool,
+
+        /// Copy all tags from file with the same name and this extension (eg. flac)
+        #[clap(long)]
+        copy_from: Option<String>,
+
+        /// Folder with the files to copy tags from (default: same folder)
+        #[clap(long)]
+        source_path: Option<PathBuf>,
+
+        /// Write the changes, otherwise only report
+        #[clap(long)]
+        write: bool,
+
+        /// Output the report as JSON
+        #[clap(long)]
+        json: bool,
+
+        /// Don't include subfolders
+        #[clap(long)]
+        no_subfolders: bool,
     },
     /// Start OneTagger server mode
     Server {
@@ -470,7 +906,7 @@ impl Actions {
             Actions::Autotagger { path, config, platforms, tags, id3v24, 
                 overwrite, threads, strictness, album_art_file, merge_genres, camelot, 
                 short_title, match_duration, max_duration_difference, match_by_id, enable_shazam, force_shazam, 
-                skip_tagged, parse_filename, filename_template, no_subfolders, only_year, multiplatform } => {
+                skip_tagged, parse_filename, filename_template, no_subfolders, only_year, multiplatform, genre_mapping, tag_mapping, credits } => {
 
                 // Load config
                 let mut config = if let Some(config_path) = config {
@@ -524,6 +960,16 @@ impl Actions {
                 if *no_subfolders {
                     config.include_subfolders = false;
                 }
+                if let Some(genre_mapping) = genre_mapping {
+                    config.genre_mapping = Some(GenreMapping::load(genre_mapping)?);
+                }
+                if let Some(name) = tag_mapping {
+                    let preset = TagMapping::presets().into_iter().find(|m| m.name.to_lowercase().starts_with(&name.to_lowercase()));
+                    config.tag_mapping = Some(preset.ok_or(anyhow::anyhow!("Unknown tag mapping preset: {name}"))?);
+                }
+                if *credits && config.credits.is_none() {
+                    config.credits = Some(CreditsConfig::default());
+                }
                 return Ok(config);
             },
             _ => unreachable!()
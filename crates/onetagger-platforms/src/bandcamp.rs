@@ -1,11 +1,11 @@
 use anyhow::Error;
 use std::time::Duration;
 use chrono::{NaiveDate, Datelike};
-use onetagger_tagger::{supported_tags, AudioFileInfo, AutotaggerSource, AutotaggerSourceBuilder, MatchingUtils, PlatformCustomOptionValue, PlatformCustomOptions, PlatformInfo, TaggerConfig, Track, TrackMatch};
+use onetagger_tagger::{supported_tags, AudioFileInfo, AutotaggerSource, AutotaggerSourceBuilder, MatchingUtils, PlatformCustomOptionValue, PlatformCustomOptions, PlatformInfo, TaggerConfig, Track, TrackMatch, TrackNumber, Album};
 use reqwest::blocking::Client;
 use scraper::{Html, Selector};
 use serde_json::{json, Value};
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
 
 pub struct Bandcamp {
     client: Client
@@ -47,19 +47,29 @@ impl Bandcamp {
 
     /// Get data from track page
     fn track_page(&self, url: &str) -> Result<BandcampTrack, Error> {
+        self.page_data(url)
+    }
+
+    /// Get data from album page
+    fn album_page(&self, url: &str) -> Result<BandcampAlbum, Error> {
+        self.page_data(url)
+    }
+
+    /// Get JSON data embedded in page
+    fn page_data<D: DeserializeOwned>(&self, url: &str) -> Result<D, Error> {
         // Fetch with rate limit
         let response = self.client.get(url).send()?;
         if response.status().is_client_error() {
-            warn!("Bandcamp track page rate limit... Waiting for few seconds");
+            warn!("Bandcamp page rate limit... Waiting for few seconds");
             std::thread::sleep(Duration::from_secs(3));
-            return self.track_page(url);
+            return self.page_data(url);
         }
         let response = response.text()?;
         // Get <script> tag
         let document = Html::parse_document(&response);
         let selector = Selector::parse("script[type=\"application/ld+json\"]").unwrap();
         let elem = document.select(&selector).next().ok_or(anyhow!("Missing <script> tag with data on: {url}"))?;
-        let data: BandcampTrack = serde_json::from_str(&elem.text().collect::<Vec<_>>().join(""))?;
+        let data: D = serde_json::from_str(&elem.text().collect::<Vec<_>>().join(""))?;
         Ok(data)
     }
 
@@ -82,7 +92,11 @@ impl AutotaggerSource for Bandcamp {
         Ok(())
     }
 
-    
+    fn get_album(&mut self, id: &str, _config: &TaggerConfig) -> Result<Option<Album>, Error> {
+        // Release ID is the album url
+        let album = self.album_page(id.trim())?;
+        Ok(Some(album.into()))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -114,7 +128,7 @@ impl AutotaggerSourceBuilder for BandcampBuilder {
             custom_options: PlatformCustomOptions::new()
                 .add("match_artist", "Match Artist", PlatformCustomOptionValue::Boolean { value: true }),
             requires_auth: false,
-            supported_tags: supported_tags!(Title, Artist, ReleaseDate, Album, Artist, Label, AlbumArt, Style, Genre, TrackId, URL, ReleaseId, TrackTotal)
+            supported_tags: supported_tags!(Title, Artist, ReleaseDate, Album, Artist, Label, AlbumArt, Style, Genre, TrackId, URL, ReleaseId, TrackNumber, TrackTotal)
         }
     }
 }
@@ -181,13 +195,7 @@ impl Into<Track> for BandcampTrack {
             // Prioritize album artist, because it is more likely the artist
             artists: vec![self.in_album.by_artist.map(|a| a.name.to_owned()).unwrap_or(self.by_artist.name)],
             label: Some(self.publisher.name),
-            styles: self.keywords.unwrap_or(vec![]).into_iter()
-                .filter(|k| 
-                    Some(k.to_lowercase()) != genre.as_ref().map(|g| g.to_lowercase()) && 
-                    crate::bandcamp_genres::GENRES.contains(&k.to_lowercase().trim())
-                )
-                .map(|s| onetagger_shared::capitalize(&s.replace(" and ", " & ")))
-                .collect::<Vec<_>>(),
+            styles: styles(self.keywords.unwrap_or(vec![]), genre.as_ref()),
             genres: genre.map(|g| vec![g]).unwrap_or(vec![]),
             track_id: Some(self.id.clone()),
             url: self.id,
@@ -200,6 +208,83 @@ impl Into<Track> for BandcampTrack {
     }
 }
 
+/// Get styles from keywords
+fn styles(keywords: Vec<String>, genre: Option<&String>) -> Vec<String> {
+    keywords.into_iter()
+        .filter(|k| 
+            Some(k.to_lowercase()) != genre.map(|g| g.to_lowercase()) && 
+            crate::bandcamp_genres::GENRES.contains(&k.to_lowercase().trim())
+        )
+        .map(|s| onetagger_shared::capitalize(&s.replace(" and ", " & ")))
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BandcampAlbum {
+    pub name: String,
+    pub date_published: String,
+    pub by_artist: BandcampArtistSmall,
+    pub publisher: BandcampPublisherSmall,
+    pub keywords: Option<Vec<String>>,
+    pub image: String,
+    pub num_tracks: Option<u16>,
+    pub track: BandcampTrackList,
+    #[serde(rename = "@id")]
+    pub id: String
+}
+
+impl Into<Album> for BandcampAlbum {
+    fn into(self) -> Album {
+        let genre = self.publisher.genre();
+        let release_date = NaiveDate::parse_from_str(&self.date_published.chars().take(11).collect::<String>(), "%d %b %Y").ok();
+        let styles = styles(self.keywords.unwrap_or(vec![]), genre.as_ref());
+        let track_total = self.num_tracks.unwrap_or(self.track.item_list_element.len() as u16);
+        let tracks = self.track.item_list_element.into_iter().map(|t| Track {
+            platform: "bandcamp".to_string(),
+            release_date,
+            release_year: release_date.map(|d| d.year() as i16),
+            title: t.item.name,
+            album: Some(self.name.to_string()),
+            artists: vec![t.item.by_artist.map(|a| a.name).unwrap_or(self.by_artist.name.to_string())],
+            label: Some(self.publisher.name.to_string()),
+            styles: styles.clone(),
+            genres: genre.clone().map(|g| vec![g]).unwrap_or(vec![]),
+            track_id: Some(t.item.id.to_string()),
+            url: t.item.id,
+            release_id: Some(self.id.to_string()),
+            track_number: Some(TrackNumber::Number(t.position)),
+            track_total: Some(track_total),
+            thumbnail: Some(self.image.replace("_10.", "_23.")),
+            art: Some(self.image.to_string()),
+            ..Default::default()
+        }).collect();
+        Album { id: self.id, name: self.name, tracks }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BandcampTrackList {
+    pub item_list_element: Vec<BandcampTrackListItem>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BandcampTrackListItem {
+    pub position: i32,
+    pub item: BandcampAlbumTrack
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BandcampAlbumTrack {
+    pub name: String,
+    pub by_artist: Option<BandcampArtistSmall>,
+    #[serde(rename = "@id")]
+    pub id: String
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct BandcampAlbumSmall {
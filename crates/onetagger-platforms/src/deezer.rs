@@ -2,7 +2,7 @@ use anyhow::Error;
 use reqwest::header::{HeaderMap, HeaderValue};
 use std::time::Duration;
 use chrono::NaiveDate;
-use onetagger_tagger::{Track, AutotaggerSourceBuilder, PlatformInfo, TaggerConfig, AutotaggerSource, PlatformCustomOptions, PlatformCustomOptionValue, AudioFileInfo, MatchingUtils, TrackNumber, supported_tags, TrackMatch};
+use onetagger_tagger::{Track, AutotaggerSourceBuilder, PlatformInfo, TaggerConfig, AutotaggerSource, PlatformCustomOptions, PlatformCustomOptionValue, AudioFileInfo, MatchingUtils, TrackNumber, supported_tags, TrackMatch, Album};
 use reqwest::blocking::Client;
 use serde::de::DeserializeOwned;
 use serde::{Serialize, Deserialize};
@@ -59,6 +59,16 @@ impl Deezer {
         Ok(self.get(&format!("/album/{id}"), &[])?)
     }
 
+    /// Search albums on Deezer api
+    pub fn album_search(&self, query: &str) -> Result<SearchResults<DeezerAlbumSearchResult>, Error> {
+        Ok(self.get("/search/album", &[("q", query)])?)
+    }
+
+    /// Get all tracks of album
+    pub fn album_tracks(&self, id: i64) -> Result<SearchResults<DeezerAlbumTrack>, Error> {
+        Ok(self.get(&format!("/album/{id}/tracks"), &[("limit", "1000")])?)
+    }
+
     /// Generate Deezer image url
     pub fn image_url(image_type: &str, md5: &str, resolution: u16) -> String {
         format!("https://e-cdns-images.dzcdn.net/images/{image_type}/{md5}/{resolution}x{resolution}-000000-80-0-0.jpg")
@@ -112,6 +122,48 @@ impl AutotaggerSource for Deezer {
         Ok(())
     }
 
+    fn search_albums(&mut self, album: &str, artist: &str, track_count: usize, _config: &TaggerConfig) -> Result<Vec<String>, Error> {
+        let query = format!("artist:\"{}\" album:\"{}\"", artist.replace('"', ""), album.replace('"', ""));
+        let mut albums = self.album_search(&query)?.data;
+        // Prefer albums with same track count
+        albums.sort_by_key(|a| a.nb_tracks.map(|c| (c as usize).abs_diff(track_count)).unwrap_or(usize::MAX));
+        Ok(albums.into_iter().map(|a| a.id.to_string()).collect())
+    }
+
+    fn get_album(&mut self, id: &str, _config: &TaggerConfig) -> Result<Option<Album>, Error> {
+        let id: i64 = id.trim().parse()?;
+        let album = self.album(id)?;
+        let tracks = self.album_tracks(id)?.data;
+        let release_date = NaiveDate::parse_from_str(&album.release_date, "%Y-%m-%d").ok();
+        let art = Self::image_url("cover", &album.md5_image, self.config.art_resolution);
+
+        let tracks = tracks.into_iter().map(|t| Track {
+            platform: "deezer".to_string(),
+            title: t.title_short,
+            version: t.title_version.filter(|v| !v.is_empty()),
+            artists: vec![t.artist.name],
+            album_artists: album.contributors.iter().map(|a| a.name.to_string()).collect(),
+            album: Some(album.title.to_string()),
+            url: t.link,
+            catalog_number: Some(t.id.to_string()),
+            track_id: Some(t.id.to_string()),
+            release_id: Some(album.id.to_string()),
+            duration: Duration::from_secs(t.duration as u64).into(),
+            explicit: t.explicit_lyrics,
+            isrc: t.isrc,
+            track_number: t.track_position.map(|p| TrackNumber::Number(p)),
+            disc_number: t.disk_number,
+            track_total: Some(album.nb_tracks),
+            genres: album.genres.data.iter().map(|g| g.name.to_string()).collect(),
+            label: Some(album.label.to_string()),
+            release_date,
+            thumbnail: Some(Deezer::image_url("cover", &album.md5_image, 150)),
+            art: Some(art.to_string()),
+            ..Default::default()
+        }).collect();
+
+        Ok(Some(Album { id: album.id.to_string(), name: album.title, tracks }))
+    }
 }
 
 
@@ -236,6 +288,35 @@ pub struct DeezerTrackFull {
     pub album: DeezerAlbum,
 }
 
+/// Album from search results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeezerAlbumSearchResult {
+    pub id: i64,
+    pub title: String,
+    pub nb_tracks: Option<u16>,
+}
+
+/// Track from album tracklist
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeezerAlbumTrack {
+    pub id: i64,
+    pub title_short: String,
+    pub title_version: Option<String>,
+    pub isrc: Option<String>,
+    pub link: String,
+    pub duration: i64,
+    pub track_position: Option<i32>,
+    pub disk_number: Option<u16>,
+    pub explicit_lyrics: Option<bool>,
+    pub artist: DeezerArtistSmall,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeezerArtistSmall {
+    pub id: i64,
+    pub name: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeezerAlbumFull {
     pub id: i64,
@@ -10,7 +10,7 @@ use serde_json::Value;
 use serde::{Serialize, Deserialize};
 use onetagger_tag::FrameName;
 use onetagger_tagger::{Track, AutotaggerSource, TaggerConfig, AudioFileInfo, MatchingUtils, TrackNumber, 
-    AutotaggerSourceBuilder, PlatformInfo, PlatformCustomOptions, PlatformCustomOptionValue, supported_tags, SupportedTag, TrackMatch};
+    AutotaggerSourceBuilder, PlatformInfo, PlatformCustomOptions, PlatformCustomOptionValue, supported_tags, SupportedTag, TrackMatch, Album};
 
 pub struct Discogs {
     client: Client,
@@ -18,8 +18,8 @@ pub struct Discogs {
     // Requests per minute
     pub rate_limit: i16,
     last_request: u128,
-    // Cache of ID:Value
-    release_cache: HashMap<i64, ReleaseMaster>
+    // Cache of (Type, ID):Value, masters and releases have separate IDs
+    release_cache: HashMap<(ReleaseType, i64), ReleaseMaster>
 }
 
 impl Discogs {
@@ -132,8 +132,8 @@ impl Discogs {
     // Get full release info
     pub fn full_release(&mut self, release_type: ReleaseType, id: i64) -> Result<ReleaseMaster, Error> {
         // Check if cached
-        if self.release_cache.contains_key(&id) {
-            return Ok(self.release_cache.get(&id).unwrap().to_owned());
+        if let Some(release) = self.release_cache.get(&(release_type.clone(), id)) {
+            return Ok(release.to_owned());
         }
         // Get 
         let rtype = match release_type {
@@ -141,9 +141,10 @@ impl Discogs {
             ReleaseType::Release => "releases"
         };
         let response = self.get(&format!("https://api.discogs.com/{}/{}", rtype, id), vec![])?.json()?;
-        let release: ReleaseMaster = serde_json::from_value(response)?;
+        let mut release: ReleaseMaster = serde_json::from_value(response)?;
+        release.rtype = release_type.clone();
         // Cache
-        self.release_cache.insert(id, release.clone());
+        self.release_cache.insert((release_type, id), release.clone());
         Ok(release)
     }
 }
@@ -153,22 +154,18 @@ impl AutotaggerSource for Discogs {
         let discogs_config: DiscogsConfig = config.get_custom("discogs")?;
         // Exact ID match
         if config.match_by_id {
-            if let Some(id) = info.tags.get("DISCOGS_RELEASE_ID").map(|t| t.first().map(|id| id.trim().replace("\0", "").parse().ok()).flatten()).flatten() {
-                let release = self.full_release(ReleaseType::Release, id)?;
-                // Exact track number match
+            if let Some((rtype, id)) = info.tags.get("DISCOGS_RELEASE_ID").map(|t| t.first().map(|id| ReleaseType::parse_id(&id.replace("\0", ""))).flatten()).flatten() {
+                let release = self.full_release(rtype, id)?;
+                // Exact track number match, headings don't count
+                let tracks = release.get_tracks(&discogs_config);
                 if let Some(track_number) = info.track_number {
-                    if track_number as usize <= release.tracks.len() {
-                        let track = release.get_track(track_number as usize - 1, &discogs_config);
-                        return Ok(vec![TrackMatch::new_id(track)]);
+                    if track_number > 0 && track_number as usize <= tracks.len() {
+                        return Ok(vec![TrackMatch::new_id(tracks[track_number as usize - 1].clone())]);
                     } else {
                         warn!("Track number out of bounds, searching normally...");
                     }
                 }
                 // Match inside release
-                let mut tracks = vec![];
-                for i in 0..release.tracks.len() {
-                    tracks.push(release.get_track(i, &discogs_config));
-                }
                 let tracks = MatchingUtils::match_track(&info, &tracks, &config, false);
                 if !tracks.is_empty() {
                     return Ok(tracks);
@@ -204,10 +201,7 @@ impl AutotaggerSource for Discogs {
             }
             let release = r.unwrap();
             
-            let mut tracks = vec![];
-            for i in 0..release.tracks.len() {
-                tracks.push(release.get_track(i, &discogs_config));
-            }
+            let tracks = release.get_tracks(&discogs_config);
             let tracks = MatchingUtils::match_track(&info, &tracks, &config, false);
             return Ok(tracks);
         }
@@ -248,18 +242,55 @@ impl AutotaggerSource for Discogs {
         Ok(())
     }
 
-    
+    fn search_albums(&mut self, album: &str, artist: &str, track_count: usize, config: &TaggerConfig) -> Result<Vec<String>, Error> {
+        let discogs_config: DiscogsConfig = config.get_custom("discogs")?;
+        let query = format!("{} {}", MatchingUtils::clean_artist_searching(artist), album);
+        let results = self.search(Some("release,master"), Some(&query), None, None)?;
+        // Search results don't have track count, full releases are cached for get_album
+        let mut releases = vec![];
+        for result in results.into_iter().take(discogs_config.max_albums.max(1) as usize) {
+            match self.full_release(result.rtype, result.id) {
+                Ok(release) => releases.push(release),
+                Err(e) => warn!("Failed getting Discogs release {}: {e}", result.id)
+            }
+        }
+        // Prefer releases with same track count
+        releases.sort_by_key(|r| r.track_count().abs_diff(track_count));
+        Ok(releases.into_iter().map(|r| r.album_id()).collect())
+    }
 
+    fn get_album(&mut self, id: &str, config: &TaggerConfig) -> Result<Option<Album>, Error> {
+        let discogs_config: DiscogsConfig = config.get_custom("discogs")?;
+        let (rtype, id) = ReleaseType::parse_id(id).ok_or(anyhow!("Invalid Discogs release ID: {id}"))?;
+        let release = self.full_release(rtype, id)?;
+        Ok(Some(Album {
+            id: release.album_id(),
+            name: release.title.to_string(),
+            tracks: release.get_tracks(&discogs_config)
+        }))
+    }
 }
 
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum ReleaseType {
+    #[default]
     Release,
     Master
 }
 
+impl ReleaseType {
+    /// Parse ID from `ReleaseMaster::album_id`, masters are prefixed with `m`
+    pub fn parse_id(id: &str) -> Option<(ReleaseType, i64)> {
+        let id = id.trim();
+        match id.strip_prefix('m') {
+            Some(id) => Some((ReleaseType::Master, id.parse().ok()?)),
+            None => Some((ReleaseType::Release, id.parse().ok()?))
+        }
+    }
+}
+
 // Used in search results
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReleaseMasterSearchResult {
@@ -341,16 +372,61 @@ pub struct ReleaseMaster {
     pub tracks: Vec<DiscogsTrack>,
     pub released: Option<String>,
     pub main_release: Option<i64>,
-    pub formats: Option<Vec<ReleaseFormat>>
+    pub formats: Option<Vec<ReleaseFormat>>,
+    #[serde(skip)]
+    pub rtype: ReleaseType,
 }
 
 impl ReleaseMaster {
+    /// ID used for album tagging, masters are prefixed with `m`
+    pub fn album_id(&self) -> String {
+        match self.rtype {
+            ReleaseType::Master => format!("m{}", self.id),
+            ReleaseType::Release => self.id.to_string()
+        }
+    }
+
     // Remove (n) at end of artist
     pub fn clean_artist(input: &str) -> String {
         let re = Regex::new(r" \(\d{1,2}\)$").unwrap();
         re.replace(input, "").to_string()
     }
 
+    /// Number of vinyl records in release
+    fn vinyl_count(&self) -> u16 {
+        self.formats.as_ref().map(|formats| formats.iter()
+            .filter(|f| f.name == "Vinyl")
+            .map(|f| f.qty.parse::<u16>().unwrap_or(1))
+            .sum()
+        ).unwrap_or(0)
+    }
+
+    /// Disc number from position, `1-03` / `1.03` or vinyl side (`C2` on 2xLP = disc 2)
+    fn disc_number(&self, position: &str) -> Option<u16> {
+        let re = Regex::new(r"^(\d+)[.-]\d+").unwrap();
+        if let Some(captures) = re.captures(position) {
+            return captures.get(1).unwrap().as_str().parse().ok();
+        }
+        if self.vinyl_count() < 2 {
+            return None;
+        }
+        let side = position.chars().next().filter(|c| c.is_ascii_uppercase())?;
+        Some((side as u16 - 'A' as u16) / 2 + 1)
+    }
+
+    /// Number of tracks without headings
+    pub fn track_count(&self) -> usize {
+        self.tracks.iter().filter(|t| !t.position.is_empty()).count()
+    }
+
+    /// All tracks (without headings)
+    pub fn get_tracks(&self, discogs_config: &DiscogsConfig) -> Vec<Track> {
+        (0..self.tracks.len())
+            .filter(|i| !self.tracks[*i].position.is_empty())
+            .map(|i| self.get_track(i, discogs_config))
+            .collect()
+    }
+
     pub fn get_track(&self, track_index: usize, discogs_config: &DiscogsConfig) -> Track {
         // Parse release date
         let release_date = match &self.released {
@@ -370,15 +446,15 @@ impl ReleaseMaster {
             }
         }
 
-        // Parse track number
-        let mut track_number = TrackNumber::Number((track_index + 1) as i32);
-        let mut disc_number = None;
+        // Parse track number, headings don't count
+        let index = self.tracks[..=track_index].iter().filter(|t| !t.position.is_empty()).count().max(1);
+        let mut track_number = TrackNumber::Number(index as i32);
         let position = self.tracks[track_index].position.to_string();
+        let disc_number = self.disc_number(&position);
 
         if discogs_config.track_number_int {
             let re = Regex::new("(\\d+)(\\.|-)(\\d+)").unwrap();
             if let Some(captures) = re.captures(&position) {
-                disc_number = Some(captures.get(1).unwrap().as_str().parse::<u16>().ok()).flatten();
                 track_number = TrackNumber::Number(captures.get(3).unwrap().as_str().parse().unwrap());
             } 
         } else {
@@ -431,11 +507,11 @@ impl ReleaseMaster {
             release_date,
             catalog_number,
             track_id: None,
-            release_id: Some(self.id.to_string()),
+            release_id: Some(self.album_id()),
             duration: MatchingUtils::parse_duration(&self.tracks[track_index].duration).unwrap_or(Duration::ZERO).into(),
             track_number: Some(track_number),
             disc_number,
-            track_total: Some(self.tracks.len() as u16),
+            track_total: Some(self.track_count() as u16),
             other,
             thumbnail: self.images.as_ref().unwrap_or(&Vec::new()).iter().min_by(|a, b| a.width.cmp(&b.width)).map(|i| i.url.to_string()),
             ..Default::default()
@@ -497,3 +573,20 @@ pub struct DiscogsConfig {
     pub track_number_int: bool,
     pub rate_limit: Option<i32>,
 }
+
+#[test]
+fn test_track_numbers() {
+    let track = |position: &str, title: &str| serde_json::json!({ "position": position, "title": title, "duration": "" });
+    let release: ReleaseMaster = serde_json::from_value(serde_json::json!({
+        "id": 1, "genres": [], "artists": [], "uri": "", "title": "Album",
+        "tracklist": [track("", "Side A"), track("A1", "First"), track("A2", "Second"), track("", "Side B"), track("B1", "Third")]
+    })).unwrap();
+    let config = DiscogsConfig { token: String::new(), max_albums: 1, track_number_int: true, rate_limit: None };
+
+    // Headings don't count
+    let tracks = release.get_tracks(&config);
+    assert_eq!(release.track_count(), 3);
+    assert_eq!(tracks.iter().map(|t| t.title.as_str()).collect::<Vec<_>>(), vec!["First", "Second", "Third"]);
+    assert_eq!(tracks.iter().map(|t| t.track_number.clone()).collect::<Vec<_>>(), (1..=3).map(|n| Some(TrackNumber::Number(n))).collect::<Vec<_>>());
+    assert!(tracks.iter().all(|t| t.track_total == Some(3)));
+}
@@ -52,6 +52,11 @@ impl ITunes {
     pub fn search(&mut self, query: &str) -> Result<SearchResults, Error> {
         Ok(self.get("/search", &[("term", query)])?.json()?)
     }
+
+    /// Search albums on the iTunes API
+    pub fn album_search(&mut self, query: &str) -> Result<SearchResults, Error> {
+        Ok(self.get("/search", &[("term", query), ("entity", "album")])?.json()?)
+    }
 }
 
 impl AutotaggerSource for ITunes {
@@ -104,13 +109,21 @@ pub enum SearchResult {
         primary_genre_name: String,
         release_date: Option<String>,
         artwork_url100: Option<String>
+    },
+    #[serde(rename_all = "camelCase")]
+    Collection {
+        collection_id: i64,
+        artist_name: Option<String>,
+        collection_name: Option<String>,
+        track_count: Option<u16>,
+        artwork_url100: Option<String>
     }
 }
 
 impl SearchResult {
     pub fn into_track(&self, art_resolution: u32) -> Option<Track> {
         match self {
-            SearchResult::Track { collection_id, track_id, artist_name, collection_name, track_name, track_view_url, track_time_millis, primary_genre_name, release_date, track_number, artwork_url100, track_count, .. } => {
+            SearchResult::Track { collection_id, track_id, artist_name, collection_name, track_name, track_view_url, track_time_millis, primary_genre_name, release_date, track_number, track_count, .. } => {
                 Some(Track {
                     platform: "itunes".to_string(),
                     title: track_name.clone(),
@@ -124,12 +137,22 @@ impl SearchResult {
                     release_date: release_date.as_ref().map(|release_date| NaiveDate::parse_from_str(&release_date[0..10], "%Y-%m-%d").ok()).flatten(),
                     track_number: track_number.map(|t| t.into()),
                     track_total: *track_count,
-                    art: artwork_url100.clone().map(|a| a.replace("100x100bb.jpg", &format!("{art_resolution}x{art_resolution}bb.jpg"))),
+                    art: self.art_url(art_resolution),
                     ..Default::default()
                 })
             },
+            SearchResult::Collection { .. } => None
         }
     }
+
+    /// Get URL of artwork in given resolution
+    pub fn art_url(&self, art_resolution: u32) -> Option<String> {
+        let artwork_url100 = match self {
+            SearchResult::Track { artwork_url100, .. } => artwork_url100,
+            SearchResult::Collection { artwork_url100, .. } => artwork_url100,
+        };
+        artwork_url100.as_ref().map(|a| a.replace("100x100bb.jpg", &format!("{art_resolution}x{art_resolution}bb.jpg")))
+    }
 }
 
 
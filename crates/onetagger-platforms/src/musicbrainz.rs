@@ -1,23 +1,35 @@
 use anyhow::Error;
-use std::time::Duration;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use chrono::NaiveDate;
 use rand::Rng;
 use reqwest::StatusCode;
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
-use onetagger_tagger::{Track, AutotaggerSource, AudioFileInfo, TaggerConfig, MatchingUtils, TrackNumber, AutotaggerSourceBuilder, PlatformInfo, supported_tags, TrackMatch};
+use onetagger_tag::FrameName;
+use onetagger_tagger::{Track, AutotaggerSource, AudioFileInfo, TaggerConfig, MatchingUtils, TrackNumber, AutotaggerSourceBuilder, PlatformInfo, 
+    supported_tags, TrackMatch, Album, PlatformCustomOptions, PlatformCustomOptionValue, SupportedTag};
+
+/// Public MusicBrainz server
+const PUBLIC_URL: &str = "https://musicbrainz.org";
+/// Public server allows 1 request per second
+const PUBLIC_RATE_LIMIT: Duration = Duration::from_millis(1000);
+/// Time of last request to public server, shared between threads
+static LAST_REQUEST: Mutex<Option<Instant>> = Mutex::new(None);
 
 pub struct MusicBrainz {
-    client: Client
+    client: Client,
+    config: MusicBrainzConfig
 }
 
 impl MusicBrainz {
-    pub fn new() -> MusicBrainz {
+    pub fn new(config: MusicBrainzConfig) -> MusicBrainz {
         MusicBrainz {
             client: Client::builder()
                 .user_agent("OneTagger/1.0")
                 .build()
-                .unwrap()
+                .unwrap(),
+            config
         }
     }
 
@@ -27,7 +39,21 @@ impl MusicBrainz {
         new_query.push(("fmt", "json"));
         debug!("MusicBrainz GET: {} {:?}", path, new_query);
 
-        let response = self.client.get(&format!("https://musicbrainz.org/ws/2{}", path))
+        // Mirrors have no rate limit, slot is reserved so other threads don't wait on the lock
+        if self.config.is_public() {
+            let wait = {
+                let mut last = LAST_REQUEST.lock().unwrap();
+                let now = Instant::now();
+                let next = last.map(|l| l + PUBLIC_RATE_LIMIT).filter(|n| *n > now).unwrap_or(now);
+                *last = Some(next);
+                next - now
+            };
+            if !wait.is_zero() {
+                std::thread::sleep(wait);
+            }
+        }
+
+        let response = self.client.get(&format!("{}/ws/2{}", self.config.base_url(), path))
             .query(&new_query)
             .send()?;
         if response.status() == StatusCode::SERVICE_UNAVAILABLE {
@@ -52,42 +78,137 @@ impl MusicBrainz {
     pub fn full_release(&self, recording_id: &str) -> Result<BrowseReleases, Error> {
         let results: BrowseReleases = self.get("/release", &[
             ("recording", recording_id),
-            ("inc", "labels isrcs recordings genres tags")
+            ("inc", "labels isrcs recordings genres tags release-groups"),
+            ("limit", "100")
+        ])?;
+        Ok(results)
+    }
+
+    /// Search releases
+    pub fn search_releases(&self, query: &str) -> Result<ReleaseSearchResults, Error> {
+        let results: ReleaseSearchResults = self.get("/release", &[
+            ("query", query),
+            ("limit", "25")
         ])?;
         Ok(results)
     }
 
+    /// Get release with all the tracks
+    pub fn release(&self, id: &str) -> Result<Release, Error> {
+        let release: Release = self.get(&format!("/release/{id}"), &[
+            ("inc", "recordings artist-credits labels isrcs genres release-groups artist-rels work-rels release-group-rels release-group-level-rels recording-level-rels work-level-rels")
+        ])?;
+        Ok(release)
+    }
+
+    /// Cover art of release, only the fields needed for it are fetched
+    pub fn release_art(&self, id: &str) -> Result<Option<String>, Error> {
+        let release: ReleaseArt = self.get(&format!("/release/{id}"), &[("inc", "release-groups")])?;
+        Ok(self.art_url(&release.id, &release.cover_art_archive, release.release_group.as_ref()))
+    }
+
+    /// Release art, release group art only if the release has none
+    fn art_url(&self, id: &str, cover_art_archive: &CoverArtArchive, release_group: Option<&ReleaseGroup>) -> Option<String> {
+        match (cover_art_archive.front, release_group) {
+            (true, _) => Some(self.config.art_url("release", id)),
+            (false, Some(release_group)) => Some(self.config.art_url("release-group", &release_group.id)),
+            (false, None) => None
+        }
+    }
+
+    /// Pick release by preferences (official, original, country), compilations last
+    pub fn select_release<'a>(&self, releases: &'a [Release]) -> Option<&'a Release> {
+        let countries = self.config.countries();
+        releases.iter().min_by_key(|r| {
+            let unofficial = self.config.prefer_official && r.status.as_deref() != Some("Official");
+            let compilation = r.release_group.as_ref().map(|g| g.is_compilation()).unwrap_or(false);
+            let country = r.country.as_ref()
+                .and_then(|c| countries.iter().position(|p| p.eq_ignore_ascii_case(c)))
+                .unwrap_or(countries.len());
+            let date = match self.config.prefer_original {
+                true => r.date.clone().filter(|d| !d.is_empty()).unwrap_or("9999".to_string()),
+                false => String::new()
+            };
+            (unofficial, compilation, country, date)
+        })
+    }
+
     /// Add info from release to track
-    pub fn extend_track(track: &mut Track, releases: BrowseReleases) {
-        if let Some(release) = releases.releases.first() {
-            // Add cover
-            if release.cover_art_archive.back || release.cover_art_archive.front {
-                track.art = Some(format!("https://coverartarchive.org/release/{}/{}", release.id, match release.cover_art_archive.front {
-                    true => "front",
-                    false => "back"
-                }));
-            }
-            track.album = Some(release.title.to_string());
-            track.release_id = Some(release.id.to_string());
-            // Label
-            if let Some(label_info) = match &release.label_info {
-                LabelInfoResult::Array(labels) => labels.first(),
-                LabelInfoResult::Single(label) => Some(label),
-            } {
-                if let Some(label) = &label_info.label {
-                    track.label = Some(label.name.to_string());
-                }
-                track.catalog_number = label_info.catalog_number.clone();
-            }
-            // Get track for track number
-            if let Some(mb_track) = release.media.iter().filter_map(
-                |m| m.tracks.iter().find(|t| &t.recording.id == track.track_id.as_ref().unwrap())
-            ).next() {
-                track.track_number = Some(TrackNumber::Number(mb_track.position as i32));
+    pub fn extend_track(&self, track: &mut Track, releases: BrowseReleases, config: &TaggerConfig) -> Result<(), Error> {
+        let release = match self.select_release(&releases.releases) {
+            Some(release) => release,
+            None => return Ok(())
+        };
+        // Relationships are only available with lookup
+        if config.any_tag_enabled(&[SupportedTag::OtherTags, SupportedTag::Remixer]) {
+            let release = self.release(&release.id)?;
+            self.extend_track_release(track, &release);
+        } else {
+            self.extend_track_release(track, release);
+        }
+        Ok(())
+    }
+
+    /// Add info from single release to track
+    fn extend_track_release(&self, track: &mut Track, release: &Release) {
+        // Add cover, fallback to release group
+        if let Some(art) = self.art_url(&release.id, &release.cover_art_archive, release.release_group.as_ref()) {
+            track.art = Some(art);
+        }
+        track.album = Some(release.title.to_string());
+        track.release_id = Some(release.id.to_string());
+        if track.album_artists.is_empty() {
+            track.album_artists = release.artist_credit.iter().flatten().map(|a| a.name.to_string()).collect();
+        }
+        // Date
+        if let Some(date) = release.date.as_ref().filter(|d| d.len() >= 4) {
+            track.release_date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok();
+            track.release_year = date[0..4].parse().ok();
+        }
+        // Label
+        if let Some(label_info) = match &release.label_info {
+            LabelInfoResult::Array(labels) => labels.first(),
+            LabelInfoResult::Single(label) => Some(label),
+        } {
+            if let Some(label) = &label_info.label {
+                track.label = Some(label.name.to_string());
             }
+            track.catalog_number = label_info.catalog_number.clone();
+        }
+        // Get track for track number
+        let mut relations = release.relations.clone();
+        relations.extend(release.release_group.iter().flat_map(|g| g.relations.iter().cloned()));
+        if let Some((media, mb_track)) = release.media.iter().filter_map(
+            |m| m.tracks.iter().find(|t| &t.recording.id == track.track_id.as_ref().unwrap()).map(|t| (m, t))
+        ).next() {
+            track.track_number = Some(TrackNumber::Number(mb_track.position as i32));
+            track.disc_number = media.position.map(|p| p as u16);
+            track.track_total = Some(media.tracks.len() as u16);
+            relations.extend(mb_track.recording.relations.iter().cloned());
+        }
+
+        // Genres
+        track.genres = release.genres.iter().map(|g| g.name.to_string()).collect();
 
-            // Genres
-            track.genres = release.genres.iter().map(|g| g.name.to_string()).collect();
+        // Relationships
+        let remixers = Relation::artists(&relations, &["remixer"]);
+        if !remixers.is_empty() {
+            track.remixers = remixers;
+        }
+        let works = relations.iter().filter_map(|r| r.work.as_ref()).flat_map(|w| w.relations.iter().cloned()).collect::<Vec<_>>();
+        let other = [
+            (FrameName::new("TCOM", "COMPOSER", "©wrt"), Relation::artists(&works, &["composer", "writer"])),
+            (FrameName::same("PRODUCER"), Relation::artists(&relations, &["producer"])),
+            (FrameName::same("BARCODE"), release.barcode.iter().filter(|b| !b.is_empty()).cloned().collect()),
+            (FrameName::same("RELEASECOUNTRY"), release.country.iter().cloned().collect()),
+            (FrameName::same("RELEASETYPE"), release.release_group.as_ref().map(|g| g.release_types()).unwrap_or_default()),
+        ];
+        for (frame, value) in other {
+            if value.is_empty() {
+                continue;
+            }
+            track.other.retain(|(f, _)| f != &frame);
+            track.other.push((frame, value));
         }
     }
 }
@@ -108,13 +229,43 @@ impl AutotaggerSource for MusicBrainz {
 
     }
 
-    fn extend_track(&mut self, track: &mut Track, _config: &TaggerConfig) -> Result<(), Error> {
+    fn extend_track(&mut self, track: &mut Track, config: &TaggerConfig) -> Result<(), Error> {
         let releases = self.full_release(track.track_id.as_ref().unwrap())?;
-        MusicBrainz::extend_track(track, releases);
-        Ok(())
+        MusicBrainz::extend_track(self, track, releases, config)
     }
 
-    
+    fn search_albums(&mut self, album: &str, artist: &str, track_count: usize, _config: &TaggerConfig) -> Result<Vec<String>, Error> {
+        let query = format!("release:\"{}\" AND artist:\"{}\"", album.replace('"', ""), artist.replace('"', ""));
+        let mut releases = self.search_releases(&query)?.releases;
+        // Prefer releases with same track count
+        releases.sort_by_key(|r| r.track_count.map(|c| c.abs_diff(track_count)).unwrap_or(usize::MAX));
+        Ok(releases.into_iter().map(|r| r.id).collect())
+    }
+
+    fn get_album(&mut self, id: &str, _config: &TaggerConfig) -> Result<Option<Album>, Error> {
+        let release = self.release(id.trim())?;
+        let album_artists: Vec<String> = release.artist_credit.iter().flatten().map(|a| a.name.to_string()).collect();
+
+        let mut tracks = vec![];
+        for media in &release.media {
+            for mb_track in &media.tracks {
+                let mut track: Track = mb_track.recording.clone().into();
+                self.extend_track_release(&mut track, &release);
+                // Same recording can be on multiple discs
+                track.track_number = Some(TrackNumber::Number(mb_track.position as i32));
+                track.disc_number = media.position.map(|p| p as u16);
+                track.track_total = Some(media.tracks.len() as u16);
+                track.album_artists = album_artists.clone();
+                tracks.push(track);
+            }
+        }
+
+        Ok(Some(Album {
+            id: release.id.to_string(),
+            name: release.title.to_string(),
+            tracks
+        }))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -133,7 +284,9 @@ pub struct Recording {
     pub artist_credit: Option<Vec<ArtistCredit>>,
     pub first_release_date: Option<String>,
     pub releases: Option<Vec<ReleaseSmall>>,
-    pub isrcs: Option<Vec<String>>
+    pub isrcs: Option<Vec<String>>,
+    #[serde(default)]
+    pub relations: Vec<Relation>
 }
 
 impl Into<Track> for Recording {
@@ -181,6 +334,16 @@ pub struct ReleaseSmall {
     pub artist_credit: Option<Vec<ArtistCredit>>,
     pub release_group: ReleaseGroup,
     pub date: Option<String>,
+    #[serde(default)]
+    pub track_count: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReleaseSearchResults {
+    pub count: usize,
+    pub offset: usize,
+    pub releases: Vec<ReleaseSmall>
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -191,10 +354,25 @@ pub struct Release {
     pub artist_credit: Option<Vec<ArtistCredit>>,
     pub date: Option<String>,
     pub barcode: Option<String>,
+    pub status: Option<String>,
+    pub country: Option<String>,
+    #[serde(default)]
     pub genres: Vec<Genre>,
     pub label_info: LabelInfoResult,
     pub media: Vec<ReleaseMedia>,
-    pub cover_art_archive: CoverArtArchive
+    pub cover_art_archive: CoverArtArchive,
+    pub release_group: Option<ReleaseGroup>,
+    #[serde(default)]
+    pub relations: Vec<Relation>
+}
+
+/// Release lookup without includes, for cover art
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReleaseArt {
+    pub id: String,
+    pub cover_art_archive: CoverArtArchive,
+    pub release_group: Option<ReleaseGroup>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -216,6 +394,8 @@ pub enum LabelInfoResult {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct ReleaseMedia {
+    #[serde(default)]
+    pub position: Option<usize>,
     pub tracks: Vec<MusicBrainzTrack>
 }
 
@@ -253,7 +433,55 @@ pub struct Artist {
 pub struct ReleaseGroup {
     pub id: String,
     pub title: String,
+    pub primary_type: Option<String>,
     pub secondary_types: Option<Vec<String>>,
+    #[serde(default)]
+    pub relations: Vec<Relation>
+}
+
+impl ReleaseGroup {
+    /// Is compilation
+    pub fn is_compilation(&self) -> bool {
+        self.secondary_types.iter().flatten().any(|t| t.eq_ignore_ascii_case("compilation"))
+    }
+
+    /// Primary and secondary types (lowercase, like Picard)
+    pub fn release_types(&self) -> Vec<String> {
+        self.primary_type.iter().chain(self.secondary_types.iter().flatten()).map(|t| t.to_lowercase()).collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Relation {
+    #[serde(rename = "type")]
+    pub relation_type: String,
+    pub artist: Option<Artist>,
+    pub work: Option<Work>,
+}
+
+impl Relation {
+    /// Names of artists with relation of any of the types
+    pub fn artists(relations: &[Relation], types: &[&str]) -> Vec<String> {
+        let mut output: Vec<String> = vec![];
+        for relation in relations.iter().filter(|r| types.contains(&r.relation_type.as_str())) {
+            if let Some(artist) = &relation.artist {
+                if !output.contains(&artist.name) {
+                    output.push(artist.name.to_string());
+                }
+            }
+        }
+        output
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Work {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub relations: Vec<Relation>
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -286,8 +514,10 @@ impl AutotaggerSourceBuilder for MusicBrainzBuilder {
         MusicBrainzBuilder
     }
 
-    fn get_source(&mut self, _config: &TaggerConfig) -> Result<Box<dyn AutotaggerSource>, Error> {
-        Ok(Box::new(MusicBrainz::new()))
+    fn get_source(&mut self, config: &TaggerConfig) -> Result<Box<dyn AutotaggerSource>, Error> {
+        // Config is missing when upgrading from older version
+        let musicbrainz_config: MusicBrainzConfig = config.get_custom("musicbrainz").unwrap_or_default();
+        Ok(Box::new(MusicBrainz::new(musicbrainz_config)))
     }
 
     fn info(&self) -> PlatformInfo {
@@ -296,11 +526,72 @@ impl AutotaggerSourceBuilder for MusicBrainzBuilder {
             name: "MusicBrainz".to_string(),
             description: "Published & unpublished, western & non-western".to_string(),
             icon: include_bytes!("../assets/musicbrainz.png"),
-            max_threads: 4,
-            version: "1.0.0".to_string(),
-            custom_options: Default::default(),
+            // Public server is rate limited per request
+            max_threads: 0,
+            version: "1.1.0".to_string(),
+            custom_options: PlatformCustomOptions::new()
+                .add_tooltip("base_url", "Server URL", "Use your own MusicBrainz mirror to avoid the rate limit", PlatformCustomOptionValue::String { value: PUBLIC_URL.to_string(), hidden: Some(false) })
+                .add_tooltip("countries", "Preferred Countries", "Comma separated release countries in order of preference, for example: US, GB, XW", PlatformCustomOptionValue::String { value: String::new(), hidden: Some(false) })
+                .add("prefer_official", "Prefer Official Releases", PlatformCustomOptionValue::Boolean { value: true })
+                .add("prefer_original", "Prefer Original Releases", PlatformCustomOptionValue::Boolean { value: true })
+                .add("art_resolution", "Album Art Resolution", PlatformCustomOptionValue::Option { 
+                    values: vec!["250".to_string(), "500".to_string(), "1200".to_string(), "original".to_string()], 
+                    value: "1200".to_string() 
+                }),
             requires_auth: false,
-            supported_tags: supported_tags!(Title, Artist, AlbumArtist, Album, URL, ReleaseId, TrackId, Duration, ISRC, Label, CatalogNumber, TrackNumber, Genre),
+            supported_tags: supported_tags!(Title, Artist, AlbumArtist, Album, URL, ReleaseId, TrackId, Duration, ISRC, Label, CatalogNumber, TrackNumber, DiscNumber, 
+                TrackTotal, Genre, ReleaseDate, AlbumArt, Remixer, OtherTags),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MusicBrainzConfig {
+    pub base_url: String,
+    /// Comma separated
+    pub countries: String,
+    pub prefer_official: bool,
+    pub prefer_original: bool,
+    /// `250`, `500`, `1200` or `original`
+    pub art_resolution: String,
+}
+
+impl MusicBrainzConfig {
+    /// Server URL without trailing slash
+    pub fn base_url(&self) -> &str {
+        match self.base_url.trim().trim_end_matches('/') {
+            "" => PUBLIC_URL,
+            url => url
+        }
+    }
+
+    /// Is the rate limited public server used
+    pub fn is_public(&self) -> bool {
+        self.base_url().ends_with("://musicbrainz.org")
+    }
+
+    /// Preferred countries
+    pub fn countries(&self) -> Vec<String> {
+        self.countries.split(',').map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect()
+    }
+
+    /// Cover Art Archive front cover URL (entity = `release` or `release-group`)
+    pub fn art_url(&self, entity: &str, id: &str) -> String {
+        match self.art_resolution.as_str() {
+            "original" | "" => format!("https://coverartarchive.org/{entity}/{id}/front"),
+            resolution => format!("https://coverartarchive.org/{entity}/{id}/front-{resolution}")
+        }
+    }
+}
+
+impl Default for MusicBrainzConfig {
+    fn default() -> Self {
+        Self {
+            base_url: PUBLIC_URL.to_string(),
+            countries: String::new(),
+            prefer_official: true,
+            prefer_original: true,
+            art_resolution: "1200".to_string(),
         }
     }
 }
@@ -310,7 +601,7 @@ impl AutotaggerSourceBuilder for MusicBrainzBuilder {
 mod tests {
     #[test]
     pub fn test_search_serialization() {
-        let m = super::MusicBrainz::new();
+        let m = super::MusicBrainz::new(Default::default());
         m.search("marting garrix animals").expect("Search 1 failed");
         m.search("illenium needed you").expect("Search 2 failed");
         m.search("michael jackson").expect("Search 3 failed");
@@ -319,11 +610,51 @@ mod tests {
 
     #[test]
     pub fn text_full_release_serialization() {
-        let m = super::MusicBrainz::new();
+        let m = super::MusicBrainz::new(Default::default());
         let results = m.search("illenium needed you").expect("Search failed!");
         for r in results.recordings {
             println!("ID: {}", r.id);
             m.full_release(&r.id).expect("Failed getting full release info");
         }
     }
+
+    #[test]
+    pub fn test_album() {
+        use onetagger_tagger::{AutotaggerSource, TaggerConfig, Track};
+        let mut m = super::MusicBrainz::new(Default::default());
+        let results = m.search("illenium needed you").expect("Search failed!");
+        let track: Track = results.recordings[0].clone().into();
+        let album = m.get_album(&track.release_id.unwrap(), &TaggerConfig::default()).unwrap().unwrap();
+        assert!(album.tracks.iter().all(|t| t.track_number.is_some() && t.disc_number.is_some()));
+    }
+
+    #[test]
+    pub fn test_art_url() {
+        let m = super::MusicBrainz::new(Default::default());
+        let release_group = serde_json::from_value::<super::ReleaseGroup>(serde_json::json!({ "id": "rg", "title": "Album" })).unwrap();
+        let art = |front| super::CoverArtArchive { back: false, front, artwork: front, count: front as usize };
+        assert_eq!(m.art_url("r", &art(true), Some(&release_group)).unwrap(), "https://coverartarchive.org/release/r/front-1200");
+        assert_eq!(m.art_url("r", &art(false), Some(&release_group)).unwrap(), "https://coverartarchive.org/release-group/rg/front-1200");
+        assert!(m.art_url("r", &art(false), None).is_none());
+    }
+
+    #[test]
+    pub fn test_select_release() {
+        let release = |id: &str, status: &str, country: &str, date: &str| serde_json::from_value::<super::Release>(serde_json::json!({
+            "id": id, "title": "Album", "status": status, "country": country, "date": date,
+            "label-info": [], "media": [], "release-group": { "id": "rg", "title": "Album", "primary-type": "Album" },
+            "cover-art-archive": { "back": false, "front": false, "artwork": false, "count": 0 }
+        })).unwrap();
+        let releases = vec![
+            release("bootleg", "Bootleg", "GB", "1990"),
+            release("reissue", "Official", "GB", "2010-01-01"),
+            release("original", "Official", "US", "1995-05-01"),
+        ];
+        let mut m = super::MusicBrainz::new(Default::default());
+        assert_eq!(m.select_release(&releases).unwrap().id, "original");
+        m.config.countries = "GB, XW".to_string();
+        assert_eq!(m.select_release(&releases).unwrap().id, "reissue");
+        m.config.prefer_official = false;
+        assert_eq!(m.select_release(&releases).unwrap().id, "bootleg");
+    }
 }
\ No newline at end of file
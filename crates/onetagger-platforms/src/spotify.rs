@@ -10,7 +10,7 @@ use rspotify::model::search::SearchResult;
 use rspotify::model::track::FullTrack;
 use rspotify::model::audio::AudioFeatures;
 use onetagger_shared::{Settings, WEBSERVER_CALLBACKS, PORT};
-use onetagger_tagger::{AutotaggerSource, Track, TaggerConfig, AudioFileInfo, MatchingUtils, TrackNumber, AutotaggerSourceBuilder, PlatformInfo, supported_tags, SupportedTag, TrackMatch};
+use onetagger_tagger::{AutotaggerSource, Track, TaggerConfig, AudioFileInfo, MatchingUtils, TrackNumber, AutotaggerSourceBuilder, PlatformInfo, supported_tags, SupportedTag, TrackMatch, Album};
 
 /// Reexport, beacause the rspotify dependency is git
 pub use rspotify;
@@ -142,6 +142,25 @@ impl Spotify {
         self.rate_limit_wrap(|s| s.spotify.album(id.to_owned(), None))
     }
 
+    /// Fetch all tracks of album with full info (ISRC)
+    pub fn album_tracks(&self, album: &FullAlbum) -> Result<Vec<FullTrack>, Error> {
+        // Album contains only first page of tracks
+        let mut ids: Vec<TrackId<'static>> = album.tracks.items.iter().filter_map(|t| t.id.clone()).collect();
+        let mut next = album.tracks.next.is_some();
+        while next {
+            let offset = ids.len() as u32;
+            let page = self.rate_limit_wrap(|s| s.spotify.album_track_manual(album.id.clone(), None, Some(50), Some(offset)))?;
+            next = page.next.is_some() && !page.items.is_empty();
+            ids.extend(page.items.into_iter().filter_map(|t| t.id));
+        }
+        // Max 50 tracks per request
+        let mut tracks = vec![];
+        for chunk in ids.chunks(50) {
+            tracks.extend(self.rate_limit_wrap(|s| s.spotify.tracks(chunk.to_vec(), None))?);
+        }
+        Ok(tracks)
+    }
+
     /// Fetch full artist
     pub fn artist(&self, id: &ArtistId) -> Result<FullArtist, Error> {
         self.rate_limit_wrap(|s| s.spotify.artist(id.to_owned()))
@@ -226,8 +245,24 @@ impl AutotaggerSource for Spotify {
         Ok(())
     }
 
-    
-   
+    fn get_album(&mut self, id: &str, _config: &TaggerConfig) -> Result<Option<Album>, Error> {
+        let album = self.album(&AlbumId::from_id(id.trim())?)?;
+        let tracks = self.album_tracks(&album)?;
+        let track_total = tracks.len() as u16;
+        let tracks = tracks.into_iter().map(|t| {
+            let mut track = full_track_to_track(t);
+            track.track_total = Some(track_total);
+            track.label = album.label.clone();
+            track.genres = album.genres.clone();
+            track
+        }).collect();
+
+        Ok(Some(Album {
+            id: album.id.id().to_string(),
+            name: album.name,
+            tracks
+        }))
+    }
 }
 
 /// Convert rspotify FullTrack into 1T Track
@@ -246,6 +281,7 @@ fn full_track_to_track(track: FullTrack) -> Track {
         release_id: track.album.id.map(|i| i.id().to_string()),
         duration: track.duration.to_std().unwrap().into(),
         track_number: Some(TrackNumber::Number(track.track_number as i32)),
+        disc_number: Some(track.disc_number as u16),
         isrc: track.external_ids.into_iter().find(|(k, _)| k == "isrc").map(|(_, v)| v.to_string()),
         release_year: track.album.release_date.as_ref().map(|d| if d.len() > 4 { d[0..4].to_string().parse().ok() } else { None }).flatten(),
         release_date: track.album.release_date.as_ref().map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()).flatten(),
@@ -280,7 +316,7 @@ impl AutotaggerSourceBuilder for SpotifyBuilder {
             version: "1.0.0".to_string(),
             custom_options: Default::default(),
             requires_auth: true,
-            supported_tags: supported_tags!(Title, Artist, AlbumArtist, Album, AlbumArt, URL, TrackId, ReleaseId, Duration, TrackNumber, ISRC, ReleaseDate, Genre, Key, Label, Explicit)
+            supported_tags: supported_tags!(Title, Artist, AlbumArtist, Album, AlbumArt, URL, TrackId, ReleaseId, Duration, TrackNumber, DiscNumber, TrackTotal, ISRC, ReleaseDate, Genre, Key, Label, Explicit)
         }
     }
 }
\ No newline at end of file
@@ -2,7 +2,7 @@ use anyhow::Error;
 use reqwest::blocking::Client;
 use chrono::NaiveDate;
 use scraper::{Html, Selector};
-use onetagger_tagger::{Track, AudioFileInfo, TaggerConfig, AutotaggerSource, MatchingUtils, TrackNumber, AutotaggerSourceBuilder, PlatformInfo, supported_tags, TrackMatch, SupportedTag};
+use onetagger_tagger::{Track, AudioFileInfo, TaggerConfig, AutotaggerSource, MatchingUtils, TrackNumber, AutotaggerSourceBuilder, PlatformInfo, supported_tags, TrackMatch, SupportedTag, Album};
 
 pub struct Traxsource {
     client: Client
@@ -197,6 +197,93 @@ impl Traxsource {
 
         Ok(())
     }
+
+    /// Get release with all tracks from title page
+    pub fn album(&self, id: &str) -> Result<Album, Error> {
+        let data = self.client.get(format!("https://www.traxsource.com/title/{id}"))
+            .send()?
+            .text()?;
+        // Minify and parse
+        let data = String::from_utf8(minify_html::minify(data.as_bytes(), &minify_html::Cfg::spec_compliant()))?;
+        let document = Html::parse_document(&data);
+
+        // Release info
+        let text = |selector: &str| document.select(&Selector::parse(selector).unwrap()).next()
+            .map(|e| e.text().collect::<Vec<_>>().join(" ").trim().to_string());
+        let name = text("h1.title").ok_or(anyhow!("Missing release title"))?;
+        let album_artists: Vec<String> = text("h1.artists")
+            .map(|a| a.split(",").map(|i| i.trim().to_string()).collect())
+            .unwrap_or_default();
+        let (catalog_number, release_date) = match text("div.cat-rdate") {
+            Some(v) => {
+                let split = v.split(" | ").map(|i| i.trim()).collect::<Vec<_>>();
+                match split.len() >= 2 {
+                    true => (Some(split[0].to_string()), NaiveDate::parse_from_str(split[1], "%Y-%m-%d").ok()),
+                    false => (None, NaiveDate::parse_from_str(&v, "%Y-%m-%d").ok())
+                }
+            },
+            None => (None, None)
+        };
+        let label = text("a.com-label");
+        let art = document.select(&Selector::parse("div.t-image img").unwrap()).next()
+            .and_then(|e| e.value().attr("src"))
+            .map(String::from);
+
+        // Tracks
+        let selector = Selector::parse("div.trk-row.play-trk").unwrap();
+        let rows = document.select(&selector).collect::<Vec<_>>();
+        let track_total = rows.len() as u16;
+        let mut tracks = vec![];
+        for (i, row) in rows.into_iter().enumerate() {
+            let get = |selector: &str| row.select(&Selector::parse(selector).unwrap()).next();
+            // Title with version and duration
+            let title_elem = match get("div.title") {
+                Some(e) => e,
+                None => continue
+            };
+            let title_vec = title_elem.text().collect::<Vec<_>>();
+            let title = title_vec.first().map(|t| t.trim().to_string()).unwrap_or_default();
+            let version = (title_vec.len() == 3).then(|| title_vec[1].trim().to_string());
+            let duration = title_vec.last().and_then(|d| MatchingUtils::parse_duration(d).ok()).unwrap_or_default();
+            let href = title_elem.select(&Selector::parse("a").unwrap()).next()
+                .and_then(|a| a.value().attr("href"))
+                .unwrap_or_default();
+            let track_id = href.replace("/track/", "").split('/').next().map(String::from).filter(|i| !i.is_empty());
+
+            // Key, BPM
+            let key_bpm = get("div.key-bpm").map(|e| e.text().collect::<Vec<_>>()).unwrap_or_default();
+            let (key, bpm) = match key_bpm.len() == 2 {
+                true => (Some(key_bpm[0].replace("maj", "").replace("min", "m")), key_bpm[1].parse().ok()),
+                false => (None, None)
+            };
+            let track_number = get("div.tnum")
+                .and_then(|e| e.text().collect::<String>().trim().parse().ok())
+                .unwrap_or(i as i32 + 1);
+
+            tracks.push(Track {
+                platform: "traxsource".to_string(),
+                title, version, key, bpm,
+                artists: row.select(&Selector::parse("div.artists a").unwrap()).map(|e| e.text().collect::<String>()).collect(),
+                album_artists: album_artists.clone(),
+                album: Some(name.to_string()),
+                url: format!("https://www.traxsource.com{href}"),
+                label: get("div.label").map(|e| e.text().collect::<String>()).or(label.clone()),
+                catalog_number: catalog_number.clone(),
+                release_date,
+                genres: get("div.genre").map(|e| vec![e.text().collect::<String>()]).unwrap_or_default(),
+                track_id,
+                release_id: Some(id.to_string()),
+                duration: duration.into(),
+                track_number: Some(TrackNumber::Number(track_number)),
+                track_total: Some(track_total),
+                art: art.clone(),
+                thumbnail: art.clone(),
+                ..Default::default()
+            });
+        }
+
+        Ok(Album { id: id.to_string(), name, tracks })
+    }
 }
 
 impl AutotaggerSource for Traxsource {
@@ -217,7 +304,9 @@ impl AutotaggerSource for Traxsource {
         Ok(())
     }
 
-    
+    fn get_album(&mut self, id: &str, _config: &TaggerConfig) -> Result<Option<Album>, Error> {
+        Ok(Some(self.album(id.trim())?))
+    }
 }
 
 #[derive(Debug, Clone)]
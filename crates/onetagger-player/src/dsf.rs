@@ -0,0 +1,153 @@
+use std::path::{Path, PathBuf};
+use anyhow::Error;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::time::Duration;
+use rodio::Source;
+
+use crate::AudioSource;
+
+/// Minimal output sample rate of the DSD -> PCM conversion
+const PCM_SAMPLE_RATE: u32 = 44100;
+
+pub struct DSFSource {
+    path: PathBuf,
+    duration: Duration
+}
+
+impl DSFSource {
+    pub fn new(path: impl AsRef<Path>) -> Result<DSFSource, Error> {
+        let decoder = DSFDecoder::open(&path)?;
+        Ok(DSFSource {
+            path: path.as_ref().to_owned(),
+            duration: decoder.total_duration().ok_or(anyhow!("Missing duration"))?
+        })
+    }
+}
+
+impl AudioSource for DSFSource {
+    // Get duration
+    fn duration(&self) -> u128 {
+        self.duration.as_millis()
+    }
+
+    // Get rodio source
+    fn get_source(&self) -> Result<Box<dyn Source<Item = i16> + Send>, Error> {
+        Ok(Box::new(DSFDecoder::open(&self.path)?))
+    }
+}
+
+/// Streaming DSD to PCM decoder, averages the 1-bit samples (good enough for preview)
+struct DSFDecoder {
+    reader: BufReader<File>,
+    channels: u32,
+    /// DSD samples per channel
+    samples: u64,
+    block_size: usize,
+    /// DSD bytes per PCM sample
+    decimation: usize,
+    /// Output sample rate
+    pcm_rate: u32,
+    /// Interleaved PCM samples of the current block
+    buffer: Vec<i16>,
+    index: usize,
+    /// PCM samples per channel left
+    remaining: u64,
+}
+
+impl DSFDecoder {
+    /// Parse header and seek to audio data
+    pub fn open(path: impl AsRef<Path>) -> Result<DSFDecoder, Error> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0u8; 28 + 52 + 12];
+        reader.read_exact(&mut header)?;
+        if &header[0..4] != b"DSD " || &header[28..32] != b"fmt " || &header[80..84] != b"data" {
+            return Err(anyhow!("Not a DSF file"));
+        }
+        let u32_at = |i: usize| u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]);
+        let channels = u32_at(52);
+        let sample_rate = u32_at(56);
+        let bits_per_sample = u32_at(60);
+        let samples = u64::from_le_bytes(header[64..72].try_into()?);
+        let block_size = u32_at(72) as usize;
+        if bits_per_sample != 1 || channels == 0 || block_size == 0 || sample_rate < PCM_SAMPLE_RATE * 8 {
+            return Err(anyhow!("Unsupported DSF format"));
+        }
+
+        // Power of two, so both 44.1k and 48k based DSD rates decimate exactly
+        let decimation = (sample_rate / PCM_SAMPLE_RATE / 8) as usize;
+        let decimation = 1 << decimation.ilog2();
+        let pcm_rate = sample_rate / (decimation as u32 * 8);
+        reader.seek(SeekFrom::Start(header.len() as u64))?;
+        Ok(DSFDecoder {
+            reader,
+            channels,
+            samples,
+            block_size,
+            decimation,
+            pcm_rate,
+            buffer: vec![],
+            index: 0,
+            remaining: samples / (decimation as u64 * 8),
+        })
+    }
+
+    /// Decode next block of every channel
+    fn next_block(&mut self) -> Option<()> {
+        let mut data = vec![0u8; self.block_size * self.channels as usize];
+        self.reader.read_exact(&mut data).ok()?;
+
+        let count = (self.block_size / self.decimation).min(self.remaining as usize);
+        self.buffer.clear();
+        for i in 0..count {
+            for channel in 0..self.channels as usize {
+                let start = channel * self.block_size + i * self.decimation;
+                let ones: u32 = data[start..start + self.decimation].iter().map(|b| b.count_ones()).sum();
+                let bits = self.decimation as f32 * 8.0;
+                // Full scale DSD is only 50% modulation
+                let sample = ((ones as f32 * 2.0 - bits) / bits * 2.0).clamp(-1.0, 1.0);
+                self.buffer.push((sample * i16::MAX as f32) as i16);
+            }
+        }
+        self.remaining -= count as u64;
+        self.index = 0;
+        Some(())
+    }
+}
+
+impl Source for DSFDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.pcm_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f64(self.samples as f64 / (self.pcm_rate as f64 * self.decimation as f64 * 8.0)))
+    }
+}
+
+impl Iterator for DSFDecoder {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.buffer.len() {
+            if self.remaining == 0 {
+                return None;
+            }
+            self.next_block()?;
+            if self.buffer.is_empty() {
+                return None;
+            }
+        }
+        let sample = self.buffer[self.index];
+        self.index += 1;
+        Some(sample)
+    }
+}
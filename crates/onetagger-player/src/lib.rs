@@ -16,6 +16,7 @@ pub mod ogg;
 pub mod alac;
 pub mod flac;
 pub mod aiff;
+pub mod dsf;
 
 /// Re-Export to prevent dependency issues
 pub use rodio;
@@ -170,6 +171,10 @@ impl AudioSources {
         if p == "ogg" || p == "opus" || p == "oga" || p == "spx" {
             return Ok(Box::new(ogg::OGGSource::new(path)?));
         }
+        // DSF
+        if p == "dsf" {
+            return Ok(Box::new(dsf::DSFSource::new(path)?));
+        }
 
         Err(anyhow!("Unsupported format!").into())
     }
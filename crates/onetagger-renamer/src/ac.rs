@@ -3,7 +3,7 @@ use std::cmp::Ordering;
 use pulldown_cmark::{Parser, Options};
 use serde::{Serialize, Deserialize};
 
-use crate::docs::{VARIABLES, FUNCTIONS, PROPERTIES};
+use crate::docs::{VARIABLES, FUNCTIONS, PROPERTIES, EXPRESSIONS};
 
 /// Separate parser for autocomplete
 pub struct Autocomplete {
@@ -22,14 +22,27 @@ impl Autocomplete {
         let mut string = false;
         let mut dot_count = 0;
         let mut suggest = false;
+        // Open parentheses, (is expression, dot count before)
+        let mut parens: Vec<(bool, usize)> = vec![];
 
+        let mut previous = None;
         for c in input.chars() {
+            // Inside function parameters only constants are allowed
+            let expression = parens.last().map(|p| p.0).unwrap_or(true);
+            let command_start = command && previous == Some('%');
+            previous = Some(c);
             match c {
+                // `%[` starts optional section, not a command
+                '[' if command_start => {
+                    command = false;
+                    suggest = false;
+                },
                 // Command start/stop
                 '%' if !string => {
                     command = !command;
                     suggest = command;
                     dot_count = 0;
+                    parens.clear();
                     output.clear();
                 },
                 // String escapes
@@ -37,20 +50,34 @@ impl Autocomplete {
                 // String
                 '"' if command && !escape => {
                     string = !string;
+                    suggest = false;
                     dot_count = 0;
                     output.clear();
                 },
+                _ if string => escape = false,
                 // Params & functions
-                '.' if command && !string => {
+                '.' if command => {
                     output.clear();
                     dot_count += 1;
-                    suggest = true;
+                    suggest = expression;
                 }
-                // Parameters
-                '(' if command && !string => {
-                    suggest = false;
+                // if() and ( ) contain expressions, functions parameters
+                '(' if command => {
+                    let is_expression = expression && dot_count == 0 && (output.is_empty() || output.to_lowercase() == "if");
+                    parens.push((is_expression, dot_count));
+                    suggest = is_expression;
+                    dot_count = 0;
+                    output.clear();
                 },
-                ')' if command && !string => {
+                ')' if command => {
+                    dot_count = parens.pop().map(|p| p.1).unwrap_or(0);
+                    suggest = false;
+                    output.clear();
+                }
+                // Operators and separators start a new value
+                ' ' | ',' | '?' | '=' | '!' | '<' | '>' | '&' | '|' | '~' if command => {
+                    suggest = expression;
+                    dot_count = 0;
                     output.clear();
                 }
                 _ => {
@@ -84,7 +111,10 @@ impl Autocomplete {
 
         let text = self.buffer.to_lowercase();
         let mut matches = match self.state {
-            AutocompleteState::Variable => VARIABLES.iter().filter(|d| d.name.starts_with(&text)).collect::<Vec<&SymbolDoc>>(),
+            AutocompleteState::Variable => VARIABLES.iter()
+                .chain(EXPRESSIONS.iter().filter(|d| d.kind == DocSymbolType::Function))
+                .filter(|d| d.name.starts_with(&text))
+                .collect::<Vec<&SymbolDoc>>(),
             AutocompleteState::Property => PROPERTIES.iter()
                 .filter(|d| d.name.starts_with(&text))
                 .chain(FUNCTIONS.iter().filter(|d| d.name.starts_with(&text)))
@@ -171,6 +201,16 @@ impl SymbolDoc {
         }
     }
 
+    /// Short for creating new operator doc
+    pub(crate) fn op(name: &str, doc: &str) -> SymbolDoc {
+        SymbolDoc {
+            name: name.to_string(),
+            doc: doc.to_string(),
+            kind: DocSymbolType::Operator,
+            parameters: vec![],
+        }
+    }
+
     // Short for creating new fn doc
     pub(crate) fn f(name: &str, doc: &str, parameters: Vec<DocParameter>) -> SymbolDoc {
         SymbolDoc {
@@ -187,14 +227,16 @@ impl SymbolDoc {
 pub enum DocSymbolType {
     Variable,
     Property,
-    Function
+    Function,
+    Operator
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum DocParameter {
     Number { name: String, required: bool },
-    String { name: String, required: bool }
+    String { name: String, required: bool },
+    Expression { name: String, required: bool }
 }
 
 impl DocParameter {
@@ -207,5 +249,24 @@ impl DocParameter {
     pub(crate) fn s(name: &str, required: bool) -> DocParameter {
         DocParameter::String { name: name.to_string(), required }
     }
+
+    /// Name of the parameter
+    pub fn name(&self) -> &str {
+        match self {
+            DocParameter::Number { name, .. } | DocParameter::String { name, .. } | DocParameter::Expression { name, .. } => name,
+        }
+    }
+
+    /// Is the parameter required
+    pub fn required(&self) -> bool {
+        match self {
+            DocParameter::Number { required, .. } | DocParameter::String { required, .. } | DocParameter::Expression { required, .. } => *required,
+        }
+    }
+
+    /// Quick expression param creation
+    pub(crate) fn e(name: &str, required: bool) -> DocParameter {
+        DocParameter::Expression { name: name.to_string(), required }
+    }
 }
 
@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use anyhow::Error;
+use regex::{Regex, RegexBuilder};
+use onetagger_tag::{FrameName, Tag, TagChange, TagChanges, TagSeparators};
+use serde::{Serialize, Deserialize};
+
+use crate::TagTemplateChange;
+
+/// Find & replace and other transforms over tags of many files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkEditConfig {
+    pub path: PathBuf,
+    /// Selected files, overrides path
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
+    pub subfolders: bool,
+    /// Tags to modify
+    pub tags: Vec<FrameName>,
+    /// Applied in order
+    pub operations: Vec<BulkEditOperation>,
+    pub separators: TagSeparators,
+    pub id3v24: bool,
+    #[serde(default)]
+    pub id3_comm_lang: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum BulkEditOperation {
+    /// Regex, replacement can contain groups ($1)
+    #[serde(rename_all = "camelCase")]
+    Replace { find: String, replace: String, #[serde(default)] case_sensitive: bool },
+    Case { case: TextCase },
+    /// Trim and collapse whitespace
+    Trim,
+    /// Split every value into multiple
+    Split { separator: String },
+    /// Join all values into one
+    Join { separator: String },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum TextCase {
+    Lower, Upper, Title, Sentence
+}
+
+/// Operation with compiled regex
+enum Operation {
+    Replace(Regex, String),
+    Case(TextCase),
+    Trim,
+    Split(String),
+    Join(String),
+}
+
+impl Operation {
+    /// Apply to all values of tag
+    fn apply(&self, values: Vec<String>) -> Vec<String> {
+        match self {
+            Operation::Replace(regex, replace) => values.into_iter().map(|v| regex.replace_all(&v, replace.as_str()).to_string()).collect(),
+            Operation::Case(case) => values.into_iter().map(|v| match case {
+                TextCase::Lower => v.to_lowercase(),
+                TextCase::Upper => v.to_uppercase(),
+                TextCase::Title => titlecase::titlecase(&v),
+                TextCase::Sentence => {
+                    let mut chars = v.chars();
+                    match chars.next() {
+                        Some(c) => c.to_uppercase().chain(chars.flat_map(|c| c.to_lowercase())).collect(),
+                        None => v
+                    }
+                }
+            }).collect(),
+            Operation::Trim => values.into_iter().map(|v| v.split_whitespace().collect::<Vec<_>>().join(" ")).collect(),
+            Operation::Split(separator) if separator.is_empty() => values,
+            Operation::Split(separator) => values.iter().flat_map(|v| v.split(separator.as_str())).map(|v| v.trim().to_string()).collect(),
+            Operation::Join(separator) => vec![values.join(separator)],
+        }
+    }
+}
+
+pub struct BulkEdit {
+    operations: Vec<Operation>
+}
+
+impl BulkEdit {
+    /// Compile the operations
+    pub fn new(operations: &[BulkEditOperation]) -> Result<BulkEdit, Error> {
+        let operations = operations.iter().map(|op| Ok(match op {
+            BulkEditOperation::Replace { find, replace, case_sensitive } => Operation::Replace(
+                RegexBuilder::new(find).case_insensitive(!case_sensitive).build()?,
+                replace.to_string()
+            ),
+            BulkEditOperation::Case { case } => Operation::Case(*case),
+            BulkEditOperation::Trim => Operation::Trim,
+            BulkEditOperation::Split { separator } => Operation::Split(separator.to_string()),
+            BulkEditOperation::Join { separator } => Operation::Join(separator.to_string()),
+        })).collect::<Result<Vec<_>, Error>>()?;
+        Ok(BulkEdit { operations })
+    }
+
+    /// Apply operations to tag values, empty values are removed
+    pub fn apply(&self, values: &[String]) -> Vec<String> {
+        let mut values = values.to_vec();
+        for op in &self.operations {
+            values = op.apply(values);
+        }
+        values.retain(|v| !v.is_empty());
+        values
+    }
+
+    /// Generate changes of every selected tag, only modified are returned
+    pub fn generate<I, P>(&self, files: I, config: &BulkEditConfig) -> Vec<TagTemplateChange>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>
+    {
+        let mut output = vec![];
+        for path in files.into_iter() {
+            let path = path.as_ref();
+            // Values have to be split with the same separators they are written with
+            let mut tag_wrap = match Tag::load_file(path, false) {
+                Ok(t) => t,
+                Err(e) => {
+                    warn!("Failed loading file {path:?}: {e}");
+                    continue;
+                },
+            };
+            tag_wrap.set_separators(&config.separators);
+            let format = tag_wrap.format();
+            let tags = tag_wrap.tag().all_tags();
+            for tag in &config.tags {
+                let tag = tag.by_format(&format);
+                let old = match tags.iter().find(|(k, _)| k.eq_ignore_ascii_case(&tag)) {
+                    Some((_, v)) => v.clone(),
+                    None => continue
+                };
+                let change = TagTemplateChange { path: path.to_owned(), new: self.apply(&old), tag, old };
+                if change.changed() {
+                    output.push(change);
+                }
+            }
+        }
+        output
+    }
+
+    /// Write the changes (one commit per file), returns number of modified files
+    pub fn write(changes: &[TagTemplateChange], config: &BulkEditConfig) -> usize {
+        let mut files: BTreeMap<&Path, Vec<&TagTemplateChange>> = BTreeMap::new();
+        for change in changes.iter().filter(|c| c.changed()) {
+            files.entry(&change.path).or_default().push(change);
+        }
+        let mut count = 0;
+        for (path, changes) in files {
+            let mut tag_changes = TagChanges::new(path, config.separators.clone(), config.id3v24, config.id3_comm_lang.clone());
+            for change in changes {
+                tag_changes.add(match change.new.is_empty() {
+                    true => TagChange::Remove { tag: change.tag.to_string() },
+                    false => TagChange::Raw { tag: change.tag.to_string(), value: change.new.clone() }
+                });
+            }
+            match tag_changes.commit() {
+                Ok(_) => count += 1,
+                Err(e) => error!("Failed writing tags to {path:?}: {e}"),
+            }
+        }
+        info!("Bulk edit written to {count} files");
+        count
+    }
+}
+
+#[test]
+fn test_bulk_edit() {
+    let edit = |operations: Vec<BulkEditOperation>, values: &[&str]| {
+        BulkEdit::new(&operations).unwrap().apply(&values.iter().map(|v| v.to_string()).collect::<Vec<_>>())
+    };
+
+    let clean = vec![
+        BulkEditOperation::Replace { find: r"\s*\(original mix\)".to_string(), replace: String::new(), case_sensitive: false },
+        BulkEditOperation::Replace { find: r"\b(featuring|feat|ft)\.?\s".to_string(), replace: "feat. ".to_string(), case_sensitive: false },
+        BulkEditOperation::Trim,
+    ];
+    assert_eq!(edit(clean, &["Title  (Original Mix)", "Song FT  Someone"]), vec!["Title", "Song feat. Someone"]);
+    assert_eq!(edit(vec![BulkEditOperation::Case { case: TextCase::Sentence }], &["hELLO World"]), vec!["Hello world"]);
+    assert_eq!(edit(vec![BulkEditOperation::Split { separator: ";".to_string() }], &["House; Techno;"]), vec!["House", "Techno"]);
+    assert_eq!(edit(vec![BulkEditOperation::Join { separator: ", ".to_string() }], &["A", "B"]), vec!["A, B"]);
+    assert!(BulkEdit::new(&[BulkEditOperation::Replace { find: "(".to_string(), replace: String::new(), case_sensitive: false }]).is_err());
+}
@@ -0,0 +1,161 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use onetagger_tag::EXTENSIONS;
+use onetagger_tagger::{CompanionFiles, FolderArtMode};
+
+/// Files with the same name as the audio file and one of the configured extensions
+pub fn find_companions(audio: &Path, config: &CompanionFiles) -> Vec<PathBuf> {
+    let (dir, stem, name) = match (audio.parent(), audio.file_stem(), audio.file_name()) {
+        (Some(dir), Some(stem), Some(name)) if config.enabled => (dir, stem.to_string_lossy(), name.to_string_lossy()),
+        _ => return vec![]
+    };
+    let mut output = vec![];
+    for ext in &config.extensions {
+        // `track.lrc` and `track.mp3.asd`
+        for base in [&stem, &name] {
+            let path = dir.join(format!("{base}.{}", ext.trim_start_matches('.')));
+            if path.is_file() && !output.contains(&path) {
+                output.push(path);
+            }
+        }
+    }
+    output
+}
+
+/// New path of companion file after the audio file is moved
+pub fn companion_target(companion: &Path, audio_from: &Path, audio_to: &Path) -> PathBuf {
+    let name = companion.file_name().unwrap_or_default().to_string_lossy();
+    let from_name = audio_from.file_name().unwrap_or_default().to_string_lossy();
+    let from_stem = audio_from.file_stem().unwrap_or_default().to_string_lossy();
+    let to_name = audio_to.file_name().unwrap_or_default().to_string_lossy();
+    let to_stem = audio_to.file_stem().unwrap_or_default().to_string_lossy();
+    let new_name = match (name.strip_prefix(&format!("{from_name}.")), name.strip_prefix(&*from_stem)) {
+        (Some(rest), _) => format!("{to_name}.{rest}"),
+        (None, Some(rest)) => format!("{to_stem}{rest}"),
+        (None, None) => name.to_string()
+    };
+    audio_to.with_file_name(new_name)
+}
+
+/// Folder art files in the directory
+pub fn find_folder_art(dir: &Path, config: &CompanionFiles) -> Vec<PathBuf> {
+    if !config.enabled || config.folder_art == FolderArtMode::Leave {
+        return vec![];
+    }
+    let names = config.folder_art_names.iter().map(|n| n.to_lowercase()).collect::<Vec<_>>();
+    let mut output = fs::read_dir(dir).map(|entries| entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && names.contains(&p.file_name().unwrap_or_default().to_string_lossy().to_lowercase()))
+        .collect::<Vec<_>>()
+    ).unwrap_or_default();
+    output.sort();
+    output
+}
+
+/// Audio files directly in the directory
+fn audio_files(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir).map(|entries| entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && EXTENSIONS.iter().any(|e| p.extension().unwrap_or_default().to_ascii_lowercase() == *e))
+        .collect()
+    ).unwrap_or_default()
+}
+
+/// Folder art which should follow the moved audio files, output: [(from, to, copy)]
+/// Art is only moved if the whole folder moves into one place, otherwise it's copied to every target folder.
+pub fn folder_art_moves(files: &[(PathBuf, PathBuf)], config: &CompanionFiles, copy: bool) -> Vec<(PathBuf, PathBuf, bool)> {
+    let mut folders: HashMap<&Path, (HashSet<&Path>, Vec<&Path>)> = HashMap::new();
+    for (from, to) in files {
+        if let (Some(source), Some(target)) = (from.parent(), to.parent()) {
+            let (sources, targets) = folders.entry(source).or_default();
+            sources.insert(from);
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+    }
+
+    let mut output = vec![];
+    let mut folders = folders.into_iter().collect::<Vec<_>>();
+    folders.sort_by_key(|(dir, _)| dir.to_path_buf());
+    for (dir, (sources, targets)) in folders {
+        let art = find_folder_art(dir, config);
+        if art.is_empty() {
+            continue;
+        }
+        let whole = targets.len() == 1 && audio_files(dir).iter().all(|f| sources.contains(f.as_path()));
+        let copy = copy || config.folder_art == FolderArtMode::Copy || !whole;
+        for target in targets.into_iter().filter(|t| *t != dir) {
+            for file in &art {
+                output.push((file.to_owned(), target.join(file.file_name().unwrap()), copy));
+            }
+        }
+    }
+    output
+}
+
+/// Move companion files of an audio file which was already moved, returns new paths
+pub fn move_companions(audio_from: &Path, audio_to: &Path, config: &CompanionFiles) -> Vec<PathBuf> {
+    let mut output = vec![];
+    for file in find_companions(audio_from, config) {
+        let target = companion_target(&file, audio_from, audio_to);
+        if target.exists() {
+            warn!("Companion file already exists, skipping: {target:?}");
+            continue;
+        }
+        match move_or_copy(&file, &target, false) {
+            Ok(_) => output.push(target),
+            Err(e) => warn!("Failed moving companion file {file:?}: {e}"),
+        }
+    }
+    output
+}
+
+/// Move or copy the folder art for already moved files, files = [(from, to)]
+pub fn transfer_folder_art(files: &[(PathBuf, PathBuf)], config: &CompanionFiles) {
+    for (from, to, copy) in folder_art_moves(files, config, false) {
+        if to.exists() {
+            continue;
+        }
+        if let Err(e) = move_or_copy(&from, &to, copy) {
+            warn!("Failed transfering folder art {from:?} -> {to:?}: {e}");
+        }
+    }
+}
+
+fn move_or_copy(from: &Path, to: &Path, copy: bool) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if !copy && fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    if !copy {
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+/// Remove directories which became empty, going up until `root` (which is kept)
+pub fn remove_empty_dirs(dirs: impl IntoIterator<Item = PathBuf>, root: &Path) {
+    let mut dirs = dirs.into_iter().collect::<Vec<_>>();
+    // Deepest first
+    dirs.sort_by_key(|d| std::cmp::Reverse(d.components().count()));
+    dirs.dedup();
+    for dir in dirs {
+        for dir in dir.ancestors() {
+            if dir == root || !dir.starts_with(root) {
+                break;
+            }
+            // Fails if not empty
+            if fs::remove_dir(dir).is_err() {
+                break;
+            }
+            info!("Removed empty directory: {dir:?}");
+        }
+    }
+}
@@ -3,7 +3,7 @@ use serde::Serialize;
 use crate::ac::{SymbolDoc, DocParameter};
 
 lazy_static! {
-    pub static ref VARIABLES: [SymbolDoc; 30] = [
+    pub static ref VARIABLES: [SymbolDoc; 42] = [
         SymbolDoc::var("title", "Get the Title frame from tag.\n\n  Used tags:<br> **MP3**: `TIT2`<br> **FLAC**: `TITLE`<br> **MP4**: `©nam`"),
         SymbolDoc::var("artist", "Get the first Artist from tag.\n\n  Used tags:<br> **MP3**: `TPE1`<br> **FLAC**: `ARTIST`<br> **MP4**: `©ART`"),
         SymbolDoc::var("artists", "Get the Artists frame from tag.\n\n  Used tags:<br> **MP3**: `TPE1`<br> **FLAC**: `ARTIST`<br> **MP4**: `©ART`"),
@@ -34,6 +34,18 @@ lazy_static! {
         SymbolDoc::var("discnumber", "Get the Disc Number frame from tag.\n\n  Used tags:<br> **MP3**: `TPOS`<br> **FLAC**: `DISCNUMBER`<br> **MP4**: `disk`"),
         SymbolDoc::var("path", "Path to the file"),
         SymbolDoc::var("abspath", "Absolute path to the file"),
+        SymbolDoc::var("codec", "Audio codec: `MP3`, `FLAC`, `AAC`, `ALAC`, `Vorbis`, `Opus`, `Speex`, `PCM`, `WavPack`, `APE`, `Musepack` or `DSD`.\n\n  Read from the audio stream, not tag."),
+        SymbolDoc::var("container", "File container: `MP3`, `FLAC`, `MP4`, `OGG`, `WAV`, `AIFF`, `WV`, `APE`, `MPC` or `DSF`.\n\n  Read from the audio stream, not tag."),
+        SymbolDoc::var("format", "Same as `container`.\n\n  Example: `%format%/%bitrate%kbps/%artist% - %title%`"),
+        SymbolDoc::var("bitrate", "Bitrate in kbps, for example `320`.\n\n  Read from the audio stream, not tag."),
+        SymbolDoc::var("samplerate", "Sample rate in Hz, for example `44100`.\n\n  Read from the audio stream, not tag."),
+        SymbolDoc::var("bitdepth", "Bits per sample, for example `16` or `24`. Not available for lossy formats.\n\n  Example: `%[%samplerate%-%bitdepth%]`"),
+        SymbolDoc::var("channels", "Number of audio channels.\n\n  Read from the audio stream, not tag."),
+        SymbolDoc::var("length", "Exact length of the audio, for example `4m12s`.\n\n  Read from the audio stream, not tag."),
+        SymbolDoc::var("seconds", "Exact length of the audio in seconds.\n\n  Read from the audio stream, not tag."),
+        SymbolDoc::var("filesize", "Size of the file in bytes"),
+        SymbolDoc::var("filesizemb", "Size of the file in megabytes with one decimal place, for example `12.3`"),
+        SymbolDoc::var("compilation", "True if the file is part of a compilation (compilation flag is set or album artist is Various Artists).\n\n  Used tags:<br> **MP3**: `TCMP`<br> **FLAC**: `COMPILATION`<br> **MP4**: `cpil`"),
 
     ];
 
@@ -58,8 +70,17 @@ lazy_static! {
         SymbolDoc::f("join", "Join array into string with custom separator", vec![DocParameter::s("separator", true)]),
         SymbolDoc::f("parent", "Get parent folder of path", vec![]),
         SymbolDoc::f("filename", "Get file/folder name of path", vec![]),
-        SymbolDoc::f("camelot", "Convert key to camelot, or keep original", vec![DocParameter::s("value", true)]),
-        SymbolDoc::f("uncamelot", "Convert key from camelot, or keep original", vec![DocParameter::s("value", true)]),
+        SymbolDoc::f("camelot", "Convert key to camelot, or keep original", vec![DocParameter::s("value", false)]),
+        SymbolDoc::f("uncamelot", "Convert key from camelot, or keep original", vec![DocParameter::s("value", false)]),
+    ];
+
+    pub static ref EXPRESSIONS: [SymbolDoc; 6] = [
+        SymbolDoc::f("if", "Use `then` if `condition` is true, otherwise `else`. Empty values, `0` and `false` are false.\n\n  Example: `%if(compilation, \"Various Artists\", albumartist ?? artist)%`", vec![DocParameter::e("condition", true), DocParameter::e("then", true), DocParameter::e("else", false)]),
+        SymbolDoc::op("??", "Use the first value which is not empty.\n\n  Example: `%albumartist ?? artist ?? \"Unknown\"%`"),
+        SymbolDoc::op("==", "Compare values, numbers are compared as numbers, text is case insensitive. Also available: `!=`, `>`, `>=`, `<`, `<=` and `~` (contains).\n\n  Example: `%if(bpm >= 120, \"Fast\", \"Slow\")%`"),
+        SymbolDoc::op("&&", "Both conditions are true. Use `||` for either and `!` for negation.\n\n  Example: `%if(label && catalognumber, ...)%`"),
+        SymbolDoc::op("%[ ]", "Optional section, removed completely if any of the variables inside is empty. Use `\\]` for the character itself inside a section.\n\n  Example: `%artist% - %title%%[ (%version%)]`"),
+        SymbolDoc::op("\" \"", "Text inside a command is only added if the rest of the command has value.\n\n  Example: `%artist% - %title%%\" [\" label \"]\"%`"),
     ];
 }
 
@@ -68,13 +89,19 @@ lazy_static! {
 pub struct FullDocs {
     pub variables: Vec<SymbolDoc>,
     pub properties: Vec<SymbolDoc>,
-    pub functions: Vec<SymbolDoc>
+    pub functions: Vec<SymbolDoc>,
+    pub expressions: Vec<SymbolDoc>
 }
 
 impl FullDocs {
     /// Get all of the docs
     pub fn get() -> FullDocs {
-        FullDocs { variables: VARIABLES.to_vec(), properties: PROPERTIES.to_vec(), functions: FUNCTIONS.to_vec() }
+        FullDocs {
+            variables: VARIABLES.to_vec(),
+            properties: PROPERTIES.to_vec(),
+            functions: FUNCTIONS.to_vec(),
+            expressions: EXPRESSIONS.to_vec()
+        }
     }
 
     /// Convert to html
@@ -82,6 +109,17 @@ impl FullDocs {
         self.variables.iter_mut().for_each(|i| i.html());
         self.properties.iter_mut().for_each(|i| i.html());
         self.functions.iter_mut().for_each(|i| i.html());
+        self.expressions.iter_mut().for_each(|i| i.html());
         self
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_doc_examples() {
+    // Expression examples contain placeholders
+    for doc in VARIABLES.iter().chain(PROPERTIES.iter()).chain(FUNCTIONS.iter()) {
+        for example in doc.doc.split("Example: `").skip(1).filter_map(|e| e.split('`').next()) {
+            let diagnostics = crate::parser::TemplateParser::validate(example);
+            assert!(diagnostics.is_empty(), "{}: {example}: {diagnostics:?}", doc.name);
+        }
+    }
+}
@@ -4,15 +4,27 @@
 
 use std::path::{Path, PathBuf};
 use anyhow::Error;
-use onetagger_tagger::AudioFileInfo;
+use onetagger_tagger::{AudioFileInfo, CompanionFiles};
 use serde::{Serialize, Deserialize};
 
 pub mod ac;
+pub mod bulk;
+pub mod companion;
 pub mod docs;
 pub mod parser;
+pub mod plan;
+pub mod reverse;
+pub mod sanitize;
+pub mod tags;
 
 // Re-export
-pub use parser::{TemplateParser, SyntaxData, SyntaxType};
+pub use parser::{TemplateParser, SyntaxData, SyntaxType, TemplateError, TemplateDiagnostic};
+pub use plan::{RenamePlan, RenameOperation, RenameAction, RenameIssue, ConflictStrategy};
+pub use companion::{move_companions, transfer_folder_art, remove_empty_dirs};
+pub use sanitize::FilesystemProfile;
+pub use tags::{TagTemplate, TagTemplateConfig, TagTemplateChange};
+pub use reverse::{PathTemplate, PathTagsConfig, PathTags};
+pub use bulk::{BulkEdit, BulkEditConfig, BulkEditOperation, TextCase};
 
 
 /// Renamer itself
@@ -91,56 +103,32 @@ impl Renamer {
         Ok(output)
     }
 
-    /// Rename files, files = output from generate
-    pub fn rename(&mut self, files: &[(PathBuf, PathBuf)], config: &RenamerConfig) -> Result<(), Error> {
-        for (from, to) in files {
-            // Don't overwrite
-            if !config.overwrite && to.exists() {
-                info!("File exists, skipping: {to:?}");
-                continue;
-            }
-
-            // Create dir
-            if let Some(parent) = to.parent() {
-                if let Err(e) = std::fs::create_dir_all(parent) {
-                    warn!("Failed creating dir {parent:?}: {e}");
-                }
-            }
-            // Copy mode
-            if config.copy {
-                match std::fs::copy(&from, &to) {
-                    Ok(_) => info!("Copied: {to:?}"),
-                    Err(e) => error!("Failed copying {from:?} -> {to:?}: {e}"),
-                }
-            // Move
-            } else {
-                if std::fs::rename(&from, &to).is_err() {
-                    info!("Renaming failed, might be different FS, trying to copy.");
-                    match std::fs::copy(&from, &to) {
-                        Ok(_) => {
-                            info!("Copied: {to:?}");
-                            if let Err(e) = std::fs::remove_file(&from) {
-                                warn!("Failed deleting {from:?}: {e}");
-                            }
-                        },
-                        Err(e) => error!("Failed copying {from:?} -> {to:?}: {e}"),
-                    }
-                } else {
-                    info!("Renamed: {to:?}");
-                }
-            }
+    /// Detect conflicts and invalid paths, files = output from generate
+    pub fn plan(&self, files: &[(PathBuf, PathBuf)], config: &RenamerConfig) -> RenamePlan {
+        let plan = RenamePlan::new(files, config);
+        for op in plan.operations.iter().filter(|o| !o.issues.is_empty()) {
+            info!("{:?} -> {:?}: {:?} {:?}", op.from, op.to, op.action, op.issues);
         }
+        plan
+    }
 
-        Ok(())
+    /// Rename files, files = output from generate. Reverts all the changes on error.
+    pub fn rename(&mut self, files: &[(PathBuf, PathBuf)], config: &RenamerConfig) -> Result<(), Error> {
+        self.plan(files, config).execute()
     }
 
     /// Generate html from the syntax highlighting
     pub fn generate_html(&self, input: &str) -> String {
+        Self::syntax_html(&self.template.syntax, input)
+    }
+
+    /// Generate html from syntax highlighting data (for invalid templates)
+    pub fn syntax_html(syntax: &[SyntaxData], input: &str) -> String {
         // class prefix
         let prefix = "__renamer_";
         
         let mut output = String::new();
-        for syntax in &self.template.syntax {
+        for syntax in syntax {
             let text = input.chars().skip(syntax.start).take(syntax.length).collect::<String>().replace(" ", "&nbsp;");
             let class = match syntax.syntax {
                 SyntaxType::Text => "syntax_text",
@@ -150,6 +138,7 @@ impl Renamer {
                 SyntaxType::Operator => "syntax_operator",
                 SyntaxType::Property => "syntax_property",
                 SyntaxType::Variable => "syntax_variable",
+                SyntaxType::Error => "syntax_error",
             };
             output.push_str(&format!("<span class=\"{prefix}{class}\">{text}</span>"));
         }
@@ -168,6 +157,18 @@ pub struct RenamerConfig {
     pub overwrite: bool,
     pub separator: String,
     pub keep_subfolders: bool,
+    /// What to do when the target is taken
+    #[serde(default)]
+    pub conflict: ConflictStrategy,
+    /// Move .lrc, folder art and other files together with the audio files
+    #[serde(default)]
+    pub companion: CompanionFiles,
+    /// Naming rules of the target filesystem
+    #[serde(default)]
+    pub filesystem: FilesystemProfile,
+    /// Convert accented and non-latin characters in values to ASCII
+    #[serde(default)]
+    pub transliterate: bool,
 }
 
 impl RenamerConfig {
@@ -182,6 +183,10 @@ impl RenamerConfig {
             keep_subfolders: false,
             separator: ", ".to_owned(),
             subfolders: true,
+            conflict: ConflictStrategy::default(),
+            companion: CompanionFiles::default(),
+            filesystem: FilesystemProfile::default(),
+            transliterate: false,
         }
     }
 }
@@ -197,7 +202,7 @@ fn generate_html() {
         "%year% - %album%/%track% - %artist% - %title%"
     ];
     for i in items {
-        let renamer = Renamer::new(TemplateParser::parse(i));
+        let renamer = Renamer::new(TemplateParser::parse(i).unwrap());
         let output = renamer.generate_html(i);
         println!("{output}")
     }
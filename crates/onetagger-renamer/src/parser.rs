@@ -1,13 +1,26 @@
-use std::path::Path;
-use onetagger_tag::Tag;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use onetagger_tag::{Tag, AudioProperties};
 use onetagger_tagger::{AudioFileInfo, Field};
 use pad::{PadStr, Alignment};
 use regex::Regex;
+use serde::Serialize;
 
 use crate::RenamerConfig;
+use crate::ac::DocParameter;
+use crate::docs::{VARIABLES, PROPERTIES, FUNCTIONS};
 
-/// Illegal filename characters
-static ILLEGAL_FILENAME: &'static str = "<>:\"/\\|?*\0";
+/// Variables read from the audio stream instead of tag
+const PROPERTY_VARIABLES: [&str; 11] = ["codec", "container", "format", "bitrate", "samplerate", "bitdepth", "channels", "length", "seconds", "filesize", "filesizemb"];
+
+lazy_static! {
+    /// Properties of the last file, so every variable doesn't read the file again
+    static ref PROPERTIES_CACHE: Mutex<Option<(PathBuf, Option<AudioProperties>)>> = Mutex::new(None);
+}
+
+/// Album artist names which mark a compilation
+const VARIOUS_ARTISTS: [&str; 4] = ["various artists", "various", "va", "v.a."];
 
 #[derive(Debug, Clone)]
 pub struct TemplateParser {
@@ -18,79 +31,261 @@ pub struct TemplateParser {
 impl TemplateParser {
     /// Apply template
     pub fn evaluate(&mut self, info: &AudioFileInfo, config: &RenamerConfig) -> String {
-        let mut output = String::new();
-        for token in &self.tokens {
-            if let Some(data) = token.token().get_value(None, info, config) {
-                match token {
-                    // Do not sanitize constants
-                    TokenType::Constant(_) => output.push_str(&data.to_string(&config.separator)),
-                    _ => output.push_str(&data.sanitize().to_string(&config.separator))
-                }
-            }
+        evaluate_tokens(&self.tokens, info, config, false, true).unwrap_or_default()
+    }
+
+    /// Apply template without removing the illegal filename characters (for tag values)
+    pub fn evaluate_raw(&mut self, info: &AudioFileInfo, config: &RenamerConfig) -> String {
+        evaluate_tokens(&self.tokens, info, config, false, false).unwrap_or_default()
+    }
+
+
+    /// Parse the template, fails if there is any error
+    pub fn parse(input: &str) -> Result<TemplateParser, TemplateError> {
+        let (parser, diagnostics) = Self::parse_lenient(input);
+        if diagnostics.is_empty() {
+            return Ok(parser);
         }
-        output
+        Err(TemplateError { syntax: mark_errors(parser.syntax, &diagnostics), diagnostics })
     }
 
+    /// Check the template for errors
+    pub fn validate(input: &str) -> Vec<TemplateDiagnostic> {
+        Self::parse_lenient(input).1
+    }
 
-    /// Parse the template
-    pub fn parse(input: &str) -> TemplateParser {
+    /// Parse the template, invalid commands are left empty
+    fn parse_lenient(input: &str) -> (TemplateParser, Vec<TemplateDiagnostic>) {
         let mut buffer = String::new();
-        let mut command = false;
-        let mut tokens = vec![];
+        // Length of the text in source, because of escapes
+        let mut length = 0;
+        let mut command = None;
+        // Optional sections, last one is the current level
+        let mut sections: Vec<Vec<TokenType>> = vec![vec![]];
+        let mut section_starts = vec![];
         let mut syntax = SyntaxBuilder::new();
+        let mut diagnostics = vec![];
         let mut escape = false;
         let mut string = false;
 
-        for c in input.chars() {
-            match c {
-                '%' => {
+        // Add text outside command
+        let flush = |buffer: &mut String, length: &mut usize, sections: &mut Vec<Vec<TokenType>>, syntax: &mut SyntaxBuilder| {
+            if *length > 0 {
+                sections.last_mut().unwrap().push(TokenType::Constant(TokenConstant::new(buffer)));
+                syntax.add(*length, SyntaxType::Text);
+            }
+            buffer.clear();
+            *length = 0;
+        };
+
+        let chars: Vec<char> = input.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            i += 1;
+            // Inside command
+            if command.is_some() {
+                match c {
                     // End of command
-                    if command {
-                        if !string {
-                            tokens.push(TokenType::Command(TokenCommand::parse(&buffer, &mut syntax)));
-                            syntax.add(1, SyntaxType::Operator);
-                            buffer.clear();
-                            command = false;
-                            continue;
-                        }
-                    // Start command
-                    } else {
-                        if !buffer.is_empty() {
-                            tokens.push(TokenType::Constant(TokenConstant::new(&buffer)));
-                            syntax.add(buffer.len(), SyntaxType::Text);
-                            buffer.clear();
-                        }
+                    '%' if !string => {
+                        let token = TokenCommand::parse(&buffer, &mut syntax, &mut diagnostics);
+                        sections.last_mut().unwrap().push(TokenType::Command(token));
                         syntax.add(1, SyntaxType::Operator);
-                        command = true;
+                        buffer.clear();
+                        command = None;
                         continue;
-                    }
-                },
-                '\\' if command => {
-                    escape = true;
+                    },
+                    '\\' => escape = !escape,
+                    '"' if escape => escape = false,
+                    '"' => string = !string,
+                    _ => escape = false
+                }
+                buffer.push(c);
+                continue;
+            }
+
+            match c {
+                // Start of optional section
+                '%' if chars.get(i) == Some(&'[') => {
+                    flush(&mut buffer, &mut length, &mut sections, &mut syntax);
+                    syntax.add(2, SyntaxType::Operator);
+                    sections.push(vec![]);
+                    section_starts.push(i - 1);
+                    escape = false;
+                    i += 1;
+                    continue;
                 },
-                '"' if command => {
-                    if escape {
-                        escape = false;
-                    } else {
-                        string = !string;
-                    }
+                // Start command
+                '%' => {
+                    flush(&mut buffer, &mut length, &mut sections, &mut syntax);
+                    syntax.add(1, SyntaxType::Operator);
+                    command = Some(i - 1);
+                    escape = false;
+                    continue;
                 },
-                _ => {
+                // Escaped section end
+                ']' if escape && sections.len() > 1 => {
+                    buffer.pop();
+                    buffer.push(c);
+                    length += 1;
                     escape = false;
-                }
+                    continue;
+                },
+                // End of optional section
+                ']' if sections.len() > 1 => {
+                    flush(&mut buffer, &mut length, &mut sections, &mut syntax);
+                    syntax.add(1, SyntaxType::Operator);
+                    let tokens = sections.pop().unwrap();
+                    section_starts.pop();
+                    sections.last_mut().unwrap().push(TokenType::Section(TokenSection { tokens }));
+                    continue;
+                },
+                '\\' => escape = !escape,
+                _ => escape = false
             }
             buffer.push(c);
+            length += 1;
         }
 
         // Leftover
-        if !buffer.is_empty() {
-            tokens.push(TokenType::Constant(TokenConstant::new(&buffer)));
-            syntax.add(buffer.len(), SyntaxType::Text);
+        if let Some(start) = command {
+            diagnostics.push(TemplateDiagnostic::new(start, 1, "Unclosed command", Some("%")));
+            length = buffer.chars().count();
         }
-        TemplateParser { tokens, syntax: syntax.build() }
+        flush(&mut buffer, &mut length, &mut sections, &mut syntax);
+        // Unclosed sections
+        for start in section_starts {
+            diagnostics.push(TemplateDiagnostic::new(start, 2, "Unclosed optional section", Some("]")));
+            let tokens = sections.pop().unwrap();
+            sections.last_mut().unwrap().push(TokenType::Section(TokenSection { tokens }));
+        }
+        diagnostics.sort_by_key(|d| d.start);
+        (TemplateParser { tokens: sections.pop().unwrap(), syntax: syntax.build() }, diagnostics)
+    }
+}
+
+/// Problem in the template
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateDiagnostic {
+    /// Position in characters
+    pub start: usize,
+    pub length: usize,
+    pub message: String,
+    /// What should be there instead
+    pub suggestion: Option<String>,
+}
+
+impl TemplateDiagnostic {
+    fn new(start: usize, length: usize, message: &str, suggestion: Option<&str>) -> TemplateDiagnostic {
+        TemplateDiagnostic { start, length, message: message.to_string(), suggestion: suggestion.map(String::from) }
+    }
+
+    /// Show the error under the template
+    pub fn report(&self, template: &str) -> String {
+        let mut output = format!("{}\n  {template}\n  {}{}", self, " ".repeat(self.start), "^".repeat(self.length.max(1)));
+        if let Some(suggestion) = &self.suggestion {
+            output.push_str(&format!("\n  help: `{suggestion}`"));
+        }
+        output
+    }
+}
+
+impl std::fmt::Display for TemplateDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.start + 1)
+    }
+}
+
+/// Template failed to parse
+#[derive(Debug, Clone)]
+pub struct TemplateError {
+    pub diagnostics: Vec<TemplateDiagnostic>,
+    /// Highlighting with the errors marked
+    pub syntax: Vec<SyntaxData>,
+}
+
+impl std::fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let errors = self.diagnostics.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+        write!(f, "Invalid template: {}", errors.join(", "))
     }
 }
 
+impl std::error::Error for TemplateError {}
+
+/// Split the highlighting, so the diagnostics are marked as errors
+fn mark_errors(syntax: Vec<SyntaxData>, diagnostics: &[TemplateDiagnostic]) -> Vec<SyntaxData> {
+    let total = syntax.last().map(|s| s.start + s.length).unwrap_or(0);
+    // Empty spans mark the character after (or last)
+    let is_error = |i: usize| diagnostics.iter().any(|d| match d.length {
+        0 => i == d.start.min(total.saturating_sub(1)),
+        l => i >= d.start && i < d.start + l
+    });
+    let mut output: Vec<SyntaxData> = vec![];
+    for s in syntax {
+        for i in s.start..s.start + s.length {
+            let kind = match is_error(i) {
+                true => SyntaxType::Error,
+                false => s.syntax.clone()
+            };
+            match output.last_mut() {
+                Some(last) if last.start + last.length == i && last.syntax == kind && (kind == SyntaxType::Error || last.start >= s.start) => last.length += 1,
+                _ => output.push(SyntaxData::new(i, 1, kind)),
+            }
+        }
+    }
+    output
+}
+
+/// Tag field of the variable
+pub(crate) fn variable_field(var: &str) -> Option<Field> {
+    match &var.to_lowercase()[..] {
+        "title" => Some(Field::Title),
+        "artist" | "artists" => Some(Field::Artist),
+        "album" => Some(Field::Album),
+        "albumartist" | "albumartists" => Some(Field::AlbumArtist),
+        "key" => Some(Field::Key),
+        "bpm" => Some(Field::BPM),
+        "genre" => Some(Field::Genre),
+        "style" => Some(Field::Style),
+        "label" => Some(Field::Label),
+        "isrc" => Some(Field::ISRC),
+        "catalognumber" => Some(Field::CatalogNumber),
+        "version" => Some(Field::Version),
+        "track" | "tracknumber" => Some(Field::TrackNumber),
+        "duration" => Some(Field::Duration),
+        "remixer" => Some(Field::Remixer),
+        "total" | "tracktotal" => Some(Field::TrackTotal),
+        "disc" | "disk" | "discnumber" | "disknumber" => Some(Field::DiscNumber),
+        _ => None
+    }
+}
+
+/// Evaluate list of tokens, if `required` returns None when any of the commands is empty
+fn evaluate_tokens(tokens: &[TokenType], info: &AudioFileInfo, config: &RenamerConfig, required: bool, sanitize: bool) -> Option<String> {
+    let mut output = String::new();
+    for token in tokens {
+        let value = match token {
+            // Do not sanitize constants
+            TokenType::Constant(c) => Some(c.string.to_string()),
+            TokenType::Section(s) => evaluate_tokens(&s.tokens, info, config, true, sanitize),
+            _ => token.token().get_value(None, info, config)
+                .map(|data| match sanitize {
+                    true => data.sanitize(config),
+                    false => data
+                }.to_string(&config.separator))
+                .filter(|s| !s.is_empty()),
+        };
+        match value {
+            Some(value) => output.push_str(&value),
+            None if required => return None,
+            None => {}
+        }
+    }
+    Some(output)
+}
+
 /// For syntax highlighting
 #[derive(Debug, Clone)]
 pub struct SyntaxData {
@@ -106,7 +301,7 @@ impl SyntaxData {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SyntaxType {
     /// Outside command text
     Text,
@@ -121,7 +316,9 @@ pub enum SyntaxType {
     /// Property name
     Property,
     /// Variable name
-    Variable
+    Variable,
+    /// Invalid part of template
+    Error
 }
 
 struct SyntaxBuilder {
@@ -137,6 +334,9 @@ impl SyntaxBuilder {
 
     /// Add new item
     pub fn add(&mut self, length: usize, syntax: SyntaxType) {
+        if length == 0 {
+            return;
+        }
         self.data.push(SyntaxData::new(self.index, length, syntax));
         self.index += length;
     }
@@ -162,19 +362,34 @@ impl Data {
         }
     }
 
-    /// Sanitize illegal filename characters
-    pub fn sanitize(self) -> Self {
-        // Sanitize string
-        let sanitize = |mut s: String| {
-            for c in ILLEGAL_FILENAME.chars() {
-                s = s.replace(c, "");
-            }
-            s
-        };
-
+    /// Sanitize illegal filename characters for the target filesystem
+    pub fn sanitize(self, config: &RenamerConfig) -> Self {
+        let sanitize = |s: String| config.filesystem.sanitize(&s, config.transliterate);
         match self {
             Data::String(s) => Data::String(sanitize(s)),
-            Data::Array(a) => Data::Array(a.into_iter().map(|s| sanitize(s)).collect()),
+            Data::Array(a) => Data::Array(a.into_iter().map(sanitize).collect()),
+        }
+    }
+
+    /// Has no non-whitespace value
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Data::String(s) => s.trim().is_empty(),
+            Data::Array(a) => a.iter().all(|s| s.trim().is_empty()),
+        }
+    }
+
+    /// Used for conditions, empty, `0` and `false` are false
+    pub fn is_truthy(&self) -> bool {
+        let value = self.to_string(",").trim().to_lowercase();
+        !self.is_empty() && value != "0" && value != "false"
+    }
+
+    /// Boolean result of condition
+    fn boolean(value: bool) -> Option<Data> {
+        match value {
+            true => Some(Data::String("1".to_string())),
+            false => None
         }
     }
 }
@@ -193,7 +408,13 @@ enum TokenType {
     Constant(TokenConstant),
     Variable(TokenVariable),
     Property(TokenProperty),
-    Function(TokenFunction)
+    Function(TokenFunction),
+    Section(TokenSection),
+    Chain(TokenChain),
+    Condition(TokenCondition),
+    Coalesce(TokenCoalesce),
+    Compare(TokenCompare),
+    Logic(TokenLogic),
 }
 
 impl TokenType {
@@ -205,165 +426,578 @@ impl TokenType {
             TokenType::Variable(t) => t,
             TokenType::Property(t) => t,
             TokenType::Function(t) => t,
+            TokenType::Section(t) => t,
+            TokenType::Chain(t) => t,
+            TokenType::Condition(t) => t,
+            TokenType::Coalesce(t) => t,
+            TokenType::Compare(t) => t,
+            TokenType::Logic(t) => t,
         }
     }
 }
 
 
+/// Optional part of the template in %[ ], removed whole if any of the commands inside is empty
+#[derive(Debug, Clone)]
+struct TokenSection {
+    tokens: Vec<TokenType>
+}
+
+impl Token for TokenSection {
+    fn get_value(&self, _input: Option<&Data>, info: &AudioFileInfo, config: &RenamerConfig) -> Option<Data> {
+        evaluate_tokens(&self.tokens, info, config, true, true).map(Data::String)
+    }
+}
 
-/// Token with everything inside % %
+/// Token with everything inside % %, expressions next to each other are joined
 #[derive(Debug, Clone)]
 struct TokenCommand {
     tokens: Vec<TokenType>
 }
 
 impl TokenCommand {
-    fn parse(input: &str, syntax: &mut SyntaxBuilder) -> TokenCommand {
-        let mut buffer = String::new();
-        let mut was_variable = false;
+    /// Parse the contents of command, on error it is left empty
+    fn parse(input: &str, syntax: &mut SyntaxBuilder, diagnostics: &mut Vec<TemplateDiagnostic>) -> TokenCommand {
+        let mut parser = match CommandParser::new(input, syntax) {
+            Ok(p) => p,
+            Err(e) => {
+                diagnostics.push(e);
+                return TokenCommand { tokens: vec![] };
+            }
+        };
+        if parser.is_end() {
+            diagnostics.push(parser.error("Empty command", None));
+        }
         let mut tokens = vec![];
-        let mut escape = false;
-        let mut function = false;
-        let mut string = false;
+        while !parser.is_end() {
+            match parser.expression() {
+                Ok(t) => tokens.push(t),
+                Err(e) => {
+                    diagnostics.push(e);
+                    tokens.clear();
+                    break;
+                }
+            }
+        }
+        parser.finish();
+        TokenCommand { tokens }
+    }
+}
 
-        for c in input.chars() {
-            match c {
-                '.' if !string && !function => {
-                    if buffer.is_empty() {
-                        syntax.add(1, SyntaxType::Operator);
-                        continue;
-                    }
+impl Token for TokenCommand {
+    fn get_value(&self, _input: Option<&Data>, info: &AudioFileInfo, config: &RenamerConfig) -> Option<Data> {
+        if self.tokens.is_empty() {
+            return None;
+        }
+        // Keep arrays if single expression, so separator is applied later
+        if self.tokens.len() == 1 {
+            return self.tokens[0].token().get_value(None, info, config);
+        }
+        let mut output = String::new();
+        for t in &self.tokens {
+            output.push_str(&t.token().get_value(None, info, config)?.to_string(&config.separator));
+        }
+        Some(Data::String(output))
+    }
+}
 
-                    // Initial variable
-                    if !was_variable {
-                        was_variable = true;
-                        tokens.push(TokenType::Variable(TokenVariable::new(&buffer)));
-                        syntax.add(buffer.len(), SyntaxType::Variable);
-                        syntax.add(1, SyntaxType::Operator);
-                        buffer.clear();
-                        continue;
-                    // Property
-                    } else {
-                        tokens.push(TokenType::Property(TokenProperty::new(&buffer)));
-                        syntax.add(buffer.len(), SyntaxType::Property);
-                        syntax.add(1, SyntaxType::Operator);
-                        buffer.clear();
-                        continue;
-                    }
+/// Value followed by properties and functions
+#[derive(Debug, Clone)]
+struct TokenChain {
+    tokens: Vec<TokenType>
+}
 
-                },
-                '(' if !string => function = true,
-                // Function end
-                ')' if !string => {
-                    buffer.push(c);
-                    match TokenFunction::parse(&buffer, syntax) {
-                        Some(f) => tokens.push(TokenType::Function(f)),
-                        None => {
-                            error!("Failed to parse function, will be empty: {buffer}");
-                        },
-                    }
-                    buffer.clear();
-                    function = false;
-                    continue;
-                },
-                '\\' if !function => escape = true,
-                // Constant
-                '"' if !function => {
-                    // Escaped ""
-                    if escape {
-                        escape = false;
-                        buffer.push('"');
-                        continue;
-                    }
+impl Token for TokenChain {
+    fn get_value(&self, _input: Option<&Data>, info: &AudioFileInfo, config: &RenamerConfig) -> Option<Data> {
+        let mut data = None;
+        for t in &self.tokens {
+            data = Some(t.token().get_value(data.as_ref(), info, config)?);
+        }
+        data
+    }
+}
 
-                    // End of string
-                    if !buffer.is_empty() {
-                        if !string {
-                            // End of variable
-                            if !was_variable {
-                                tokens.push(TokenType::Variable(TokenVariable::new(&buffer)));
-                                syntax.add(buffer.len(), SyntaxType::Variable);
-                                buffer.clear();
-                                string = true;
-                                was_variable = true;
-                                syntax.add(1, SyntaxType::Operator);
-                                continue;
-                            }
-                            // End of property
-                            tokens.push(TokenType::Property(TokenProperty::new(&buffer)));
-                            syntax.add(buffer.len(), SyntaxType::Property);
-                            buffer.clear();
-                            string = true;
-                            syntax.add(1, SyntaxType::Operator);
-                            continue;
-                        }
+/// if(condition, then, else)
+#[derive(Debug, Clone)]
+struct TokenCondition {
+    condition: Box<TokenType>,
+    then: Box<TokenType>,
+    otherwise: Option<Box<TokenType>>
+}
 
-                        // End of string
-                        tokens.push(TokenType::Constant(TokenConstant::new(&buffer)));
-                        syntax.add(buffer.len(), SyntaxType::String);
-                        buffer.clear();
-                        string = false;
-                        syntax.add(1, SyntaxType::Operator);
-                        continue;
-                    }
-                    // Start of string
-                    syntax.add(1, SyntaxType::Operator);
-                    string = true;
-                    continue;
-                },
-                _ => {}
-            }
-            buffer.push(c);
+impl Token for TokenCondition {
+    fn get_value(&self, _input: Option<&Data>, info: &AudioFileInfo, config: &RenamerConfig) -> Option<Data> {
+        let condition = self.condition.token().get_value(None, info, config);
+        match condition.map(|c| c.is_truthy()).unwrap_or(false) {
+            true => self.then.token().get_value(None, info, config),
+            false => self.otherwise.as_ref()?.token().get_value(None, info, config)
         }
+    }
+}
 
-        // Trailing
-        if !buffer.is_empty() {
-            if was_variable {
-                tokens.push(TokenType::Property(TokenProperty::new(&buffer)));
-                syntax.add(buffer.len(), SyntaxType::Property);
-            } else {
-                tokens.push(TokenType::Variable(TokenVariable::new(&buffer)));
-                syntax.add(buffer.len(), SyntaxType::Variable);
+/// a ?? b, first non empty value
+#[derive(Debug, Clone)]
+struct TokenCoalesce {
+    options: Vec<TokenType>
+}
 
-            }
+impl Token for TokenCoalesce {
+    fn get_value(&self, _input: Option<&Data>, info: &AudioFileInfo, config: &RenamerConfig) -> Option<Data> {
+        self.options.iter()
+            .filter_map(|o| o.token().get_value(None, info, config))
+            .find(|d| !d.is_empty())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareOperator {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Contains
+}
+
+impl CompareOperator {
+    fn from_symbol(symbol: &str) -> Option<CompareOperator> {
+        match symbol {
+            "==" => Some(CompareOperator::Equal),
+            "!=" => Some(CompareOperator::NotEqual),
+            ">" => Some(CompareOperator::Greater),
+            ">=" => Some(CompareOperator::GreaterEqual),
+            "<" => Some(CompareOperator::Less),
+            "<=" => Some(CompareOperator::LessEqual),
+            "~" => Some(CompareOperator::Contains),
+            _ => None
         }
-        TokenCommand { tokens }
     }
 }
 
+/// Compare two values, numerically if both are numbers, otherwise case insensitive
+#[derive(Debug, Clone)]
+struct TokenCompare {
+    left: Box<TokenType>,
+    operator: CompareOperator,
+    right: Box<TokenType>
+}
 
-impl Token for TokenCommand {
+impl Token for TokenCompare {
     fn get_value(&self, _input: Option<&Data>, info: &AudioFileInfo, config: &RenamerConfig) -> Option<Data> {
-        let mut output = vec![];
-        let mut data = None;
-        for t in &self.tokens {
-            // Constants
-            if let TokenType::Constant(c) = t {
-                // Leftover data
-                if data.is_some() {
-                    output.push(data);
-                    data = None;
+        let value = |t: &TokenType| t.token().get_value(None, info, config)
+            .map(|d| d.to_string(&config.separator).trim().to_lowercase())
+            .unwrap_or_default();
+        let (left, right) = (value(&self.left), value(&self.right));
+        if self.operator == CompareOperator::Contains {
+            return Data::boolean(left.contains(&right));
+        }
+        let ordering = match (left.parse::<f64>(), right.parse::<f64>()) {
+            (Ok(l), Ok(r)) => l.partial_cmp(&r)?,
+            _ => left.cmp(&right)
+        };
+        Data::boolean(match self.operator {
+            CompareOperator::Equal => ordering == Ordering::Equal,
+            CompareOperator::NotEqual => ordering != Ordering::Equal,
+            CompareOperator::Greater => ordering == Ordering::Greater,
+            CompareOperator::GreaterEqual => ordering != Ordering::Less,
+            CompareOperator::Less => ordering == Ordering::Less,
+            CompareOperator::LessEqual => ordering != Ordering::Greater,
+            CompareOperator::Contains => unreachable!()
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogicOperator {
+    And,
+    Or,
+    Not
+}
+
+/// &&, || and !
+#[derive(Debug, Clone)]
+struct TokenLogic {
+    operator: LogicOperator,
+    operands: Vec<TokenType>
+}
+
+impl Token for TokenLogic {
+    fn get_value(&self, _input: Option<&Data>, info: &AudioFileInfo, config: &RenamerConfig) -> Option<Data> {
+        let mut values = self.operands.iter()
+            .map(|t| t.token().get_value(None, info, config).map(|d| d.is_truthy()).unwrap_or(false));
+        Data::boolean(match self.operator {
+            LogicOperator::And => values.all(|v| v),
+            LogicOperator::Or => values.any(|v| v),
+            LogicOperator::Not => !values.any(|v| v),
+        })
+    }
+}
+
+/// Symbols used inside command, longest first
+const SYMBOLS: [&str; 15] = ["??", "==", "!=", ">=", "<=", "&&", "||", ">", "<", "~", "!", ".", ",", "(", ")"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Lexeme {
+    /// Variable, property, function name or number
+    Word(String),
+    /// Text in quotes, unescaped
+    String(String),
+    Symbol(&'static str)
+}
+
+/// Lexeme with its position in the command
+#[derive(Debug, Clone)]
+struct LexemeSpan {
+    lexeme: Lexeme,
+    start: usize,
+    length: usize,
+    /// Whitespace before
+    space: usize
+}
+
+/// Split command into lexemes, returns lexemes and trailing whitespace length
+fn lex(input: &str, offset: usize) -> Result<(Vec<LexemeSpan>, usize), TemplateDiagnostic> {
+    let chars = input.chars().collect::<Vec<_>>();
+    let mut output = vec![];
+    let mut i = 0;
+    let mut space = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            space += 1;
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let lexeme = if c == '"' {
+            // String with escapes, unknown escapes are kept for regex
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(TemplateDiagnostic::new(offset + start, i - start, "Unclosed string", Some("\""))),
+                    Some('"') => break,
+                    Some('\\') if matches!(chars.get(i + 1), Some('"') | Some('\\')) => {
+                        value.push(chars[i + 1]);
+                        i += 1;
+                    },
+                    Some(c) => value.push(*c),
                 }
-                output.push(c.get_value(None, info, config));
-                continue;
+                i += 1;
+            }
+            i += 1;
+            Lexeme::String(value)
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| chars[i..].starts_with(&s.chars().collect::<Vec<_>>())) {
+            i += symbol.chars().count();
+            Lexeme::Symbol(symbol)
+        } else {
+            while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '"' && !SYMBOLS.iter().any(|s| s.starts_with(chars[i])) {
+                i += 1;
+            }
+            // Single ? = & |
+            if i == start {
+                let suggestion = match c {
+                    '?' => Some("??"),
+                    '=' => Some("=="),
+                    '&' => Some("&&"),
+                    '|' => Some("||"),
+                    _ => None
+                };
+                return Err(TemplateDiagnostic::new(offset + start, 1, &format!("Unexpected character `{c}`"), suggestion));
             }
-            // Normal
-            data = t.token().get_value(data.as_ref(), info, config);
-            if data.is_none() {
-                return None;
+            Lexeme::Word(chars[start..i].iter().collect())
+        };
+        output.push(LexemeSpan { lexeme, start, length: i - start, space });
+        space = 0;
+    }
+    Ok((output, space))
+}
+
+/// Recursive descent parser of expressions inside command
+struct CommandParser<'a> {
+    lexemes: Vec<LexemeSpan>,
+    position: usize,
+    syntax: &'a mut SyntaxBuilder,
+    /// Position of the command in template
+    offset: usize,
+    /// Length of the command
+    length: usize,
+    /// Whitespace at the end
+    trailing: usize,
+}
+
+impl<'a> CommandParser<'a> {
+    /// Create new parser
+    fn new(input: &str, syntax: &'a mut SyntaxBuilder) -> Result<CommandParser<'a>, TemplateDiagnostic> {
+        let offset = syntax.index;
+        let length = input.chars().count();
+        match lex(input, offset) {
+            Ok((lexemes, trailing)) => Ok(CommandParser { lexemes, position: 0, syntax, offset, length, trailing }),
+            Err(e) => {
+                syntax.add(length, SyntaxType::Text);
+                Err(e)
             }
         }
-        // leftover
-        if data.is_some() {
-            output.push(data);
+    }
+
+    /// No more lexemes
+    fn is_end(&self) -> bool {
+        self.position >= self.lexemes.len()
+    }
+
+    fn peek(&self) -> Option<&Lexeme> {
+        self.lexemes.get(self.position).map(|l| &l.lexeme)
+    }
+
+    /// Is next lexeme this symbol
+    fn peek_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Lexeme::Symbol(s)) if *s == symbol)
+    }
+
+    /// Consume next lexeme and highlight it
+    fn next(&mut self, syntax: SyntaxType) -> Option<Lexeme> {
+        let span = self.lexemes.get(self.position)?.clone();
+        self.position += 1;
+        self.syntax.add(span.space, SyntaxType::Operator);
+        match (&span.lexeme, syntax) {
+            // Quotes are operators
+            (Lexeme::String(_), SyntaxType::String) => {
+                self.syntax.add(1, SyntaxType::Operator);
+                self.syntax.add(span.length - 2, SyntaxType::String);
+                self.syntax.add(1, SyntaxType::Operator);
+            },
+            (_, syntax) => self.syntax.add(span.length, syntax)
+        }
+        Some(span.lexeme)
+    }
+
+    /// Create error at lexeme, or at the end of command
+    fn error_at(&self, position: usize, message: &str, suggestion: Option<&str>) -> TemplateDiagnostic {
+        match self.lexemes.get(position) {
+            Some(l) => TemplateDiagnostic::new(self.offset + l.start, l.length, message, suggestion),
+            None => TemplateDiagnostic::new(self.offset + self.length, 0, message, suggestion)
+        }
+    }
+
+    /// Error at the next lexeme
+    fn error(&self, message: &str, suggestion: Option<&str>) -> TemplateDiagnostic {
+        self.error_at(self.position, message, suggestion)
+    }
+
+    /// Consume symbol if it is next
+    fn symbol(&mut self, symbol: &str) -> bool {
+        if self.peek_symbol(symbol) {
+            self.next(SyntaxType::Operator);
+            return true;
+        }
+        false
+    }
+
+    /// Consume symbol or fail
+    fn expect(&mut self, symbol: &str) -> Result<(), TemplateDiagnostic> {
+        if !self.symbol(symbol) {
+            return Err(self.error(&format!("Expected `{symbol}`"), Some(symbol)));
+        }
+        Ok(())
+    }
+
+    /// Highlight the rest which wasn't parsed
+    fn finish(mut self) {
+        while self.position < self.lexemes.len() {
+            self.next(SyntaxType::Text);
+        }
+        self.syntax.add(self.trailing, SyntaxType::Operator);
+    }
+
+    /// expression = and ("||" and)*
+    fn expression(&mut self) -> Result<TokenType, TemplateDiagnostic> {
+        let mut operands = vec![self.and()?];
+        while self.symbol("||") {
+            operands.push(self.and()?);
+        }
+        if operands.len() == 1 {
+            return Ok(operands.pop().unwrap());
+        }
+        Ok(TokenType::Logic(TokenLogic { operator: LogicOperator::Or, operands }))
+    }
+
+    /// and = compare ("&&" compare)*
+    fn and(&mut self) -> Result<TokenType, TemplateDiagnostic> {
+        let mut operands = vec![self.compare()?];
+        while self.symbol("&&") {
+            operands.push(self.compare()?);
+        }
+        if operands.len() == 1 {
+            return Ok(operands.pop().unwrap());
+        }
+        Ok(TokenType::Logic(TokenLogic { operator: LogicOperator::And, operands }))
+    }
+
+    /// compare = coalesce (operator coalesce)?
+    fn compare(&mut self) -> Result<TokenType, TemplateDiagnostic> {
+        let left = self.coalesce()?;
+        let operator = match self.peek() {
+            Some(Lexeme::Symbol(s)) => CompareOperator::from_symbol(s),
+            _ => None
+        };
+        match operator {
+            Some(operator) => {
+                self.next(SyntaxType::Operator);
+                let right = self.coalesce()?;
+                Ok(TokenType::Compare(TokenCompare { left: Box::new(left), operator, right: Box::new(right) }))
+            },
+            None => Ok(left)
+        }
+    }
+
+    /// coalesce = unary ("??" unary)*
+    fn coalesce(&mut self) -> Result<TokenType, TemplateDiagnostic> {
+        let mut options = vec![self.unary()?];
+        while self.symbol("??") {
+            options.push(self.unary()?);
+        }
+        if options.len() == 1 {
+            return Ok(options.pop().unwrap());
         }
-        // Merge
-        let o = output
-            .iter()
-            .filter_map(|d| d.as_ref().map(|d| d.to_string(&config.separator)))
-            .collect::<String>();
-        Some(Data::String(o))
+        Ok(TokenType::Coalesce(TokenCoalesce { options }))
     }
+
+    /// unary = "!" unary | chain
+    fn unary(&mut self) -> Result<TokenType, TemplateDiagnostic> {
+        if self.symbol("!") {
+            let operand = self.unary()?;
+            return Ok(TokenType::Logic(TokenLogic { operator: LogicOperator::Not, operands: vec![operand] }));
+        }
+        self.chain()
+    }
+
+    /// chain = primary ("." property | "." function(params))*
+    fn chain(&mut self) -> Result<TokenType, TemplateDiagnostic> {
+        let mut tokens = vec![self.primary()?];
+        while self.peek_symbol(".") {
+            self.next(SyntaxType::Operator);
+            let is_function = matches!(self.lexemes.get(self.position + 1), Some(LexemeSpan { lexeme: Lexeme::Symbol("("), space: 0, .. }));
+            let name = match self.peek() {
+                Some(Lexeme::Word(w)) => w.to_string(),
+                _ => return Err(self.error("Expected property or function after `.`", None))
+            };
+            let position = self.position;
+            if is_function {
+                self.next(SyntaxType::Function);
+                if !FUNCTIONS.iter().any(|f| f.name == name.to_lowercase()) {
+                    let suggestion = closest(&name, FUNCTIONS.iter().map(|f| f.name.as_str()));
+                    return Err(self.error_at(position, &format!("Unknown function `{name}`"), suggestion));
+                }
+                let function = TokenFunction { name, params: self.params()? };
+                function.validate().map_err(|e| self.error_at(position, &e, None))?;
+                tokens.push(TokenType::Function(function));
+            } else {
+                self.next(SyntaxType::Property);
+                if name.parse::<usize>().is_err() && !PROPERTIES.iter().any(|p| p.name == name) {
+                    let suggestion = closest(&name, PROPERTIES.iter().chain(FUNCTIONS.iter()).map(|f| f.name.as_str()));
+                    return Err(self.error_at(position, &format!("Unknown property `{name}`"), suggestion));
+                }
+                tokens.push(TokenType::Property(TokenProperty::new(&name)));
+            }
+        }
+        if tokens.len() == 1 {
+            return Ok(tokens.pop().unwrap());
+        }
+        Ok(TokenType::Chain(TokenChain { tokens }))
+    }
+
+    /// Constant function parameters in ( )
+    fn params(&mut self) -> Result<Vec<FunctionParameter>, TemplateDiagnostic> {
+        self.expect("(")?;
+        let mut params = vec![];
+        while !self.symbol(")") {
+            if !params.is_empty() {
+                self.expect(",")?;
+            }
+            match self.peek().cloned() {
+                Some(Lexeme::String(s)) => {
+                    self.next(SyntaxType::String);
+                    params.push(FunctionParameter::String(s));
+                },
+                Some(Lexeme::Word(w)) => match w.parse() {
+                    Ok(n) => {
+                        self.next(SyntaxType::Number);
+                        params.push(FunctionParameter::Number(n));
+                    },
+                    Err(_) => return Err(self.error(&format!("Invalid number `{w}`"), None))
+                },
+                None => return Err(self.error("Unclosed function parameters", Some(")"))),
+                _ => return Err(self.error("Function parameters can only be text in quotes or numbers", None))
+            }
+        }
+        Ok(params)
+    }
+
+    /// primary = string | number | variable | if(...) | ( expression )
+    fn primary(&mut self) -> Result<TokenType, TemplateDiagnostic> {
+        let position = self.position;
+        let is_call = self.lexemes.get(position + 1).map(|l| l.lexeme == Lexeme::Symbol("(")).unwrap_or(false);
+        match self.peek().cloned() {
+            Some(Lexeme::String(s)) => {
+                self.next(SyntaxType::String);
+                Ok(TokenType::Constant(TokenConstant::new(&s)))
+            },
+            Some(Lexeme::Symbol("(")) => {
+                self.next(SyntaxType::Operator);
+                let expression = self.expression()?;
+                self.expect(")")?;
+                Ok(expression)
+            },
+            Some(Lexeme::Word(w)) if w.to_lowercase() == "if" && is_call => {
+                self.next(SyntaxType::Function);
+                self.expect("(")?;
+                let condition = self.expression()?;
+                self.expect(",")?;
+                let then = self.expression()?;
+                let otherwise = match self.symbol(",") {
+                    true => Some(Box::new(self.expression()?)),
+                    false => None
+                };
+                self.expect(")")?;
+                Ok(TokenType::Condition(TokenCondition { condition: Box::new(condition), then: Box::new(then), otherwise }))
+            },
+            // lower(artist) instead of artist.lower()
+            Some(Lexeme::Word(w)) if is_call => {
+                let suggestion = match FUNCTIONS.iter().any(|f| f.name == w.to_lowercase()) {
+                    true => Some(format!("value.{w}()")),
+                    false => closest(&w, ["if"].into_iter()).map(String::from)
+                };
+                Err(self.error(&format!("Unknown function `{w}`, functions are called on values"), suggestion.as_deref()))
+            },
+            Some(Lexeme::Word(w)) if w.starts_with(|c: char| c.is_ascii_digit() || c == '-') && w.parse::<f64>().is_ok() => {
+                self.next(SyntaxType::Number);
+                Ok(TokenType::Constant(TokenConstant::new(&w)))
+            },
+            Some(Lexeme::Word(w)) => {
+                // Lowercase names are likely typos of built-in variables, raw tag names are usually uppercase
+                if w.chars().any(|c| c.is_lowercase()) && !VARIABLES.iter().any(|v| v.name == w.to_lowercase()) {
+                    if let Some(suggestion) = closest(&w, VARIABLES.iter().map(|v| v.name.as_str())) {
+                        return Err(self.error(&format!("Unknown variable `{w}`"), Some(suggestion)));
+                    }
+                }
+                self.next(SyntaxType::Variable);
+                Ok(TokenType::Variable(TokenVariable::new(&w)))
+            },
+            Some(Lexeme::Symbol(s)) => Err(self.error(&format!("Expected value, found `{s}`"), None)),
+            None => Err(self.error("Expected value", None))
+        }
+    }
+}
+
+/// Closest name for suggestions
+fn closest<'a>(name: &str, options: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    options
+        .map(|o| (strsim::levenshtein(&name, o), o))
+        .filter(|(d, _)| *d <= 2 && *d < name.len())
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, o)| o)
 }
 
 
@@ -400,27 +1034,7 @@ impl TokenVariable {
     pub fn get_raw_value(&self, info: &AudioFileInfo) -> Option<Data> {
         // Parse field name
         let lower = self.var.to_lowercase();
-        let field = match &lower[..] {
-            "title" => Some(Field::Title),
-            "artist" | "artists" => Some(Field::Artist),
-            "album" => Some(Field::Album),
-            "albumartist" | "albumartists" => Some(Field::AlbumArtist),
-            "key" => Some(Field::Key),
-            "bpm" => Some(Field::BPM),
-            "genre" => Some(Field::Genre),
-            "style" => Some(Field::Style),
-            "label" => Some(Field::Label),
-            "isrc" => Some(Field::ISRC),
-            "catalognumber" => Some(Field::CatalogNumber),
-            "version" => Some(Field::Version),
-            "track" | "tracknumber" => Some(Field::TrackNumber),
-            "duration" => Some(Field::Duration),
-            "remixer" => Some(Field::Remixer),
-            "total" | "tracktotal" => Some(Field::TrackTotal),
-            "disc" | "disk" | "discnumber" | "disknumber" => Some(Field::DiscNumber),
-            _ => None
-        };
-        if let Some(field) = field {
+        if let Some(field) = variable_field(&lower) {
             let tag = field.by_format(&info.format);
             if let Some(v) = info.tags.get(tag) {
                 // Artist/Album artist override
@@ -448,6 +1062,41 @@ impl TokenVariable {
             }
         }
 
+        // Compilation flag or Various Artists as album artist
+        if lower == "compilation" {
+            let flag = ["COMPILATION", "TCMP", "cpil"].iter()
+                .filter_map(|t| info.tags.get(*t))
+                .flatten()
+                .any(|v| v.trim() == "1");
+            let various = info.tags.get(Field::AlbumArtist.by_format(&info.format))
+                .map(|v| v.iter().any(|a| VARIOUS_ARTISTS.contains(&a.trim().to_lowercase().as_str())))
+                .unwrap_or(false);
+            return Data::boolean(flag || various);
+        }
+
+        // Audio stream properties
+        if PROPERTY_VARIABLES.contains(&lower.as_str()) {
+            let properties = audio_properties(&info.path)?;
+            let seconds = properties.duration.as_secs();
+            let value = match &lower[..] {
+                "codec" => Some(properties.codec),
+                "container" | "format" => Some(properties.container),
+                "bitrate" => properties.bitrate.map(|b| b.to_string()),
+                "samplerate" => properties.sample_rate.map(|s| s.to_string()),
+                "bitdepth" => properties.bit_depth.map(|b| b.to_string()),
+                "channels" => properties.channels.map(|c| c.to_string()),
+                "length" => Some(match seconds / 3600 {
+                    0 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+                    h => format!("{h}h{:02}m{:02}s", seconds / 60 % 60, seconds % 60)
+                }),
+                "seconds" => Some(seconds.to_string()),
+                "filesize" => Some(properties.file_size.to_string()),
+                "filesizemb" => Some(format!("{:.1}", properties.file_size as f64 / 1024.0 / 1024.0)),
+                _ => None
+            }?;
+            return Some(Data::String(value));
+        }
+
         // Try to get tag directly
         if let Some(v) = info.tags.get(&self.var) {
             return Some(Data::Array(v.clone()));
@@ -469,6 +1118,21 @@ impl Token for TokenVariable {
 }
 
 
+/// Load audio properties only when used in template
+fn audio_properties(path: &Path) -> Option<AudioProperties> {
+    let mut cache = PROPERTIES_CACHE.lock().unwrap();
+    if let Some((cached, properties)) = &*cache {
+        if cached == path {
+            return properties.clone();
+        }
+    }
+    let properties = AudioProperties::load(path)
+        .map_err(|e| warn!("Failed reading audio properties of {path:?}: {e}"))
+        .ok();
+    *cache = Some((path.to_owned(), properties.clone()));
+    properties
+}
+
 #[derive(Debug, Clone)]
 struct TokenProperty {
     property: String
@@ -506,14 +1170,6 @@ enum FunctionParameter {
     String(String)
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-enum FunctionParseState {
-    Name,
-    String,
-    Number,
-    Params
-}
-
 #[derive(Debug, Clone)]
 struct TokenFunction {
     name: String,
@@ -521,96 +1177,27 @@ struct TokenFunction {
 }
 
 impl TokenFunction {
-    /// Parse the function from string
-    pub fn parse(input: &str, syntax: &mut SyntaxBuilder) -> Option<TokenFunction> {
-        let mut name = String::new();
-        let mut param = String::new();
-        let mut state = FunctionParseState::Name;
-        let mut params = vec![];
-        let mut escape = false;
-
-        for c in input.chars() {
-            // Parse name
-            if state == FunctionParseState::Name {
-                if c != '(' {
-                    name.push(c);
-                    continue;
-                }
-                state = FunctionParseState::Params;
-                syntax.add(name.len(), SyntaxType::Function);
-                syntax.add(1, SyntaxType::Operator);
-                continue;
-            }
-
-            // Parse args
-            match c {
-                '"' => {
-                    // Start string param
-                    if state == FunctionParseState::Params {
-                        state = FunctionParseState::String;
-                    // End string
-                    } else {
-                        // \" escape
-                        if escape {
-                            escape = false;
-                            param.push('"');
-                            continue;
-                        }
-                        state = FunctionParseState::Params;
-                        params.push(FunctionParameter::String(param.clone()));
-                        syntax.add(1, SyntaxType::Operator);
-                        // add escape char length coz it's double in shown, but only single in buffer
-                        syntax.add(param.len() + param.chars().filter(|c| c == &'\\').count(), SyntaxType::String);
-                        syntax.add(1, SyntaxType::Operator);
-                        param.clear();
-                    }
-                },
-                // End of number parameter
-                ' ' | ',' | ')' if !escape => {
-                    if state == FunctionParseState::Number {
-                        match param.parse() {
-                            Ok(n) => params.push(FunctionParameter::Number(n)),
-                            Err(e) => {
-                                error!("Failed parsing number function parameter ({param}): {e}");
-                                return None;
-                            },
-                        }
-                        syntax.add(param.len(), SyntaxType::Number);
-                        state = FunctionParseState::Params;
-                        param.clear();
-                    }
-                    syntax.add(1, SyntaxType::Operator);
-                },
-                '\\' => {
-                    if escape {
-                        param.push('\\');
-                        escape = false;
-                    } else {
-                        escape = true;
-                    }
-                },
-                // Number
-                c if c.is_digit(10) => {
-                    if state == FunctionParseState::Params {
-                        state = FunctionParseState::Number;
-                    }
-                    param.push(c);
-                }
-                _ => {
-                    match state {
-                        FunctionParseState::String => param.push(c),
-                        FunctionParseState::Number => {
-                            error!("Failed parsing number param: invalid end sequence: {c}");
-                            return None;
-                        }
-                        _ => {},
-                    }
-                    escape = false;
-                }
+    /// Check the parameters against docs
+    fn validate(&self) -> Result<(), String> {
+        let doc = FUNCTIONS.iter().find(|f| f.name == self.name.to_lowercase()).ok_or(format!("Unknown function `{}`", self.name))?;
+        let required = doc.parameters.iter().filter(|p| p.required()).count();
+        if self.params.len() < required || self.params.len() > doc.parameters.len() {
+            let names = doc.parameters.iter().map(|p| p.name()).collect::<Vec<_>>().join(", ");
+            return Err(format!("Invalid parameter count, usage: {}({names})", doc.name));
+        }
+        for (param, doc) in self.params.iter().zip(&doc.parameters) {
+            match (param, doc) {
+                (FunctionParameter::Number(_), DocParameter::Number { .. }) => {},
+                (FunctionParameter::String(_), DocParameter::String { .. }) => {},
+                (_, DocParameter::Number { name, .. }) => return Err(format!("Parameter `{name}` has to be a number")),
+                (_, doc) => return Err(format!("Parameter `{}` has to be a text in quotes", doc.name())),
             }
-        };
-
-        Some(TokenFunction { name, params })
+        }
+        // Regex has to be valid
+        if let ("replace", Some(FunctionParameter::String(from))) = (&self.name.to_lowercase()[..], self.params.first()) {
+            Regex::new(from).map_err(|e| format!("Invalid regex: {e}"))?;
+        }
+        Ok(())
     }
 
     /// Get int param or error
@@ -815,4 +1402,92 @@ impl Token for TokenFunction {
             }
         }
     }
-}
\ No newline at end of file
+}
+/// Conditions, fallbacks and optional sections
+#[test]
+fn test_expressions() {
+    use std::collections::HashMap;
+    use onetagger_tagger::{AudioFileFormat, FileTaggedStatus};
+
+    let tags = [("ARTIST", vec!["A", "B"]), ("TITLE", vec!["Title"]), ("BPM", vec!["128"]), ("ALBUMARTIST", vec!["Various Artists"])];
+    let info = AudioFileInfo {
+        title: None,
+        artists: vec![],
+        format: AudioFileFormat::FLAC,
+        path: "test.flac".into(),
+        isrc: None,
+        duration: None,
+        track_number: None,
+        tagged: FileTaggedStatus::Untagged,
+        tags: HashMap::from(tags.map(|(k, v)| (k.to_string(), v.into_iter().map(String::from).collect())))
+    };
+    let config = RenamerConfig::default_with_paths(".", "");
+    let evaluate = |template: &str| TemplateParser::parse(template).unwrap().evaluate(&info, &config);
+
+    assert_eq!(evaluate("%artist% - %title%%[ (%version%)]"), "A, B - Title");
+    assert_eq!(evaluate("%[%bpm% ]%title%%[ [%label%\\]]"), "128 Title");
+    assert_eq!(evaluate("%label ?? artists ?? \"Unknown\"%"), "A");
+    assert_eq!(evaluate("%if(bpm >= 120, \"Fast\", \"Slow\")% %if(key, key)%"), "Fast ");
+    assert_eq!(evaluate("%if(compilation, \"Various Artists\", albumartist ?? artist)%/%title.upper()%"), "Various Artists/TITLE");
+    assert_eq!(evaluate("%if(!label && title ~ \"tit\", artist.last)%%\" - \" version%"), "B");
+    assert_eq!(evaluate("%artist.replace(\"\\\\w\", \"x\")%"), "x, x");
+
+    // Highlighting covers whole template
+    let template = "%if(bpm > 1, \"a\\\"\")% %[x]  %label ?? artist.first  %";
+    let syntax = TemplateParser::parse(template).unwrap().syntax;
+    assert_eq!(syntax.iter().map(|s| s.length).sum::<usize>(), template.chars().count());
+}
+
+/// Templates written before expressions keep working
+#[test]
+fn test_old_templates() {
+    use std::collections::HashMap;
+    use onetagger_tagger::{AudioFileFormat, FileTaggedStatus};
+
+    let tags = [("ARTIST", vec!["A"]), ("TITLE", vec!["Title"]), ("BPM", vec!["128"]), ("INITIALKEY", vec!["Am"])];
+    let info = AudioFileInfo {
+        title: None,
+        artists: vec![],
+        format: AudioFileFormat::FLAC,
+        path: "test.flac".into(),
+        isrc: None,
+        duration: None,
+        track_number: None,
+        tagged: FileTaggedStatus::Untagged,
+        tags: HashMap::from(tags.map(|(k, v)| (k.to_string(), v.into_iter().map(String::from).collect())))
+    };
+    let config = RenamerConfig::default_with_paths(".", "");
+    let evaluate = |template: &str| TemplateParser::parse(template).unwrap().evaluate(&info, &config);
+
+    assert_eq!(evaluate("{%bpm%} %title% [Live]"), "{128} Title [Live]");
+    assert_eq!(evaluate("%artist% - %title%}{"), "A - Title}{");
+    assert_eq!(evaluate("%key.camelot()% %key.camelot(\"x\")%"), "8A 8A");
+    assert_eq!(evaluate("%if(bpm > 127.5, \"Fast\")% %if(bpm < -1.5, \"Slow\", \"-\")%"), "Fast -");
+    assert_eq!(evaluate("%title.pad(\"x\", 7)%"), "xxTitle");
+}
+
+/// Errors with positions and suggestions
+#[test]
+fn test_diagnostics() {
+    let error = |template: &str| {
+        let diagnostics = TemplateParser::validate(template);
+        assert_eq!(diagnostics.len(), 1, "{template}: {diagnostics:?}");
+        let d = diagnostics.into_iter().next().unwrap();
+        (d.start, d.length, d.suggestion)
+    };
+    assert_eq!(error("%albumartst%/%title%"), (1, 10, Some("albumartist".to_string())));
+    assert_eq!(error("%artist.lowr()%"), (8, 4, Some("lower".to_string())));
+    assert_eq!(error("%artist.frist%"), (8, 5, Some("first".to_string())));
+    assert_eq!(error("%title.pad(\"0\")%"), (7, 3, None));
+    assert_eq!(error("%title.replace(\"(\", \"\")%"), (7, 7, None));
+    assert_eq!(error("%artist% - %title"), (11, 1, Some("%".to_string())));
+    assert_eq!(error("%if(bpm, \"a\"%"), (12, 0, Some(")".to_string())));
+    assert_eq!(error("%[%version% - %title%"), (0, 2, Some("]".to_string())));
+    assert_eq!(error("%artist ? title%"), (8, 1, Some("??".to_string())));
+    assert!(TemplateParser::validate("%TXXX_CUSTOM% %artists.0%").is_empty());
+
+    // Errors are highlighted
+    let error = TemplateParser::parse("%artist.lowr()%").unwrap_err();
+    assert!(error.syntax.iter().any(|s| s.syntax == SyntaxType::Error && s.start == 8 && s.length == 4));
+    assert_eq!(error.syntax.iter().map(|s| s.length).sum::<usize>(), 15);
+}
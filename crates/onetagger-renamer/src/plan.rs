@@ -0,0 +1,668 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use anyhow::Error;
+use serde::{Serialize, Deserialize};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{RenamerConfig, FilesystemProfile};
+use crate::companion::{find_companions, companion_target, folder_art_moves, remove_empty_dirs};
+
+/// Maximum length of a single path component in bytes (most filesystems)
+pub const MAX_COMPONENT_LENGTH: usize = 255;
+
+/// Lossless extensions, preferred by `ConflictStrategy::KeepBetter`
+const LOSSLESS_EXTENSIONS: [&str; 5] = ["flac", "wav", "aif", "aiff", "alac"];
+
+/// How to resolve the target path being taken
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictStrategy {
+    /// Leave the file as it is
+    #[default]
+    Skip,
+    /// Append ` (1)`, ` (2)`, ... to the filename
+    Suffix,
+    /// Keep the larger file, the other one is left as it is
+    KeepLarger,
+    /// Keep the lossless file or the one with higher bitrate
+    KeepBetter,
+}
+
+impl std::str::FromStr for ConflictStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase().replace(['-', '_'], "")[..] {
+            "skip" => Ok(ConflictStrategy::Skip),
+            "suffix" => Ok(ConflictStrategy::Suffix),
+            "keeplarger" => Ok(ConflictStrategy::KeepLarger),
+            "keepbetter" => Ok(ConflictStrategy::KeepBetter),
+            _ => Err(anyhow!("Invalid conflict strategy: {s}, use skip, suffix, keep-larger or keep-better"))
+        }
+    }
+}
+
+/// What will happen to the file
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RenameAction {
+    /// Move / copy to the target
+    Rename,
+    /// Move / copy to the target, replacing the existing file
+    Replace,
+    /// Leave the file as it is
+    Skip,
+}
+
+/// Problems found while planning
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum RenameIssue {
+    /// New name is the same as the old one
+    Unchanged,
+    /// Target file already exists
+    TargetExists,
+    /// Another file of the batch has the same target
+    Collision { with: PathBuf },
+    /// Only the case changed, has to go through a temporary file on case insensitive filesystems
+    CaseOnly,
+    /// Component was longer than the filesystem allows and got truncated
+    Truncated { component: String },
+    /// Component was changed to follow the filesystem rules (reserved name, trailing dots)
+    Sanitized { component: String },
+    /// Component can't be used as a filename
+    InvalidComponent { component: String },
+    /// Path inside the output folder is longer than the filesystem allows
+    PathTooLong { length: usize },
+    /// Target was changed to avoid a conflict
+    Suffixed { original: PathBuf },
+}
+
+/// Single planned operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameOperation {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub action: RenameAction,
+    pub issues: Vec<RenameIssue>,
+    /// Copy instead of move (copy mode or shared folder art)
+    pub copy: bool,
+    /// Companion file (.lrc, cover) of an audio file
+    pub companion: bool,
+}
+
+/// Single filesystem operation of the execution
+#[derive(Debug, Clone)]
+struct Step {
+    from: PathBuf,
+    to: PathBuf,
+    action: RenameAction,
+    copy: bool,
+}
+
+/// Validated list of renames, with all the conflicts resolved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenamePlan {
+    pub operations: Vec<RenameOperation>,
+    pub copy: bool,
+    pub case_insensitive: bool,
+    /// Remove empty source directories up to this one after execution
+    #[serde(skip)]
+    cleanup_root: Option<PathBuf>,
+}
+
+impl RenamePlan {
+    /// Plan the renames, files = output from `Renamer::generate`
+    pub fn new(files: &[(PathBuf, PathBuf)], config: &RenamerConfig) -> RenamePlan {
+        Self::with_case_insensitive(files, config, cfg!(any(windows, target_os = "macos")) || config.filesystem.case_insensitive())
+    }
+
+    /// Plan with explicit filesystem case sensitivity
+    pub fn with_case_insensitive(files: &[(PathBuf, PathBuf)], config: &RenamerConfig, case_insensitive: bool) -> RenamePlan {
+        let mut plan = RenamePlan { operations: vec![], copy: config.copy, case_insensitive, cleanup_root: None };
+        let base = config.out_dir.clone().filter(|p| !p.to_string_lossy().trim().is_empty()).unwrap_or(config.path.clone());
+
+        // Validate the paths
+        for (from, to) in files {
+            let mut op = RenameOperation {
+                from: absolute(from),
+                to: to.to_owned(),
+                action: RenameAction::Rename,
+                issues: vec![],
+                copy: config.copy,
+                companion: false
+            };
+            plan.validate(&mut op, &base, config);
+            op.to = absolute(&op.to);
+            plan.check_unchanged(&mut op);
+            plan.operations.push(op);
+        }
+
+        // Existing files first, so the counters / winners are assigned in order
+        plan.resolve_existing_all(config);
+
+        // Multiple files with same target
+        let mut targets: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, op) in plan.operations.iter().enumerate().filter(|(_, o)| o.action == RenameAction::Rename) {
+            targets.entry(plan.key(&op.to)).or_default().push(i);
+        }
+        let mut groups = targets.into_values().filter(|g| g.len() > 1).collect::<Vec<_>>();
+        groups.sort();
+        for group in groups {
+            plan.resolve_collision(&group, config.conflict);
+        }
+
+        // Skipped files keep their path taken
+        plan.resolve_existing_all(config);
+
+        if config.companion.enabled {
+            plan.add_companions(config);
+            if !config.copy && config.companion.remove_empty_dirs {
+                plan.cleanup_root = dunce::canonicalize(&config.path).ok();
+            }
+        }
+        plan
+    }
+
+    /// Same path or only the case is different
+    fn check_unchanged(&self, op: &mut RenameOperation) {
+        if op.action == RenameAction::Rename && op.from == op.to {
+            op.action = RenameAction::Skip;
+            op.issues.push(RenameIssue::Unchanged);
+        } else if op.action == RenameAction::Rename && self.key(&op.from) == self.key(&op.to) {
+            op.issues.push(RenameIssue::CaseOnly);
+            // Can't have both files
+            if op.copy {
+                op.action = RenameAction::Skip;
+                op.issues.push(RenameIssue::TargetExists);
+            }
+        }
+    }
+
+    /// Add same-stem files and folder art of the audio files which will be moved
+    fn add_companions(&mut self, config: &RenamerConfig) {
+        let audio = self.pending().map(|o| (o.from.clone(), o.to.clone())).collect::<Vec<_>>();
+        let mut companions = vec![];
+        for (from, to) in &audio {
+            for file in find_companions(from, &config.companion) {
+                let target = companion_target(&file, from, to);
+                companions.push((file, target, config.copy));
+            }
+        }
+        companions.extend(folder_art_moves(&audio, &config.companion, config.copy));
+
+        // Companions never replace anything
+        for (from, to, copy) in companions {
+            let mut op = RenameOperation { from, to, action: RenameAction::Rename, issues: vec![], copy, companion: true };
+            self.check_unchanged(&mut op);
+            let planned = self.pending().any(|o| self.key(&o.to) == self.key(&op.to));
+            if op.action == RenameAction::Rename && !op.issues.contains(&RenameIssue::CaseOnly) && (planned || self.is_taken(&op.to)) {
+                op.action = RenameAction::Skip;
+                op.issues.push(RenameIssue::TargetExists);
+            }
+            self.operations.push(op);
+        }
+    }
+
+    /// Resolve all targets which are already taken, repeat until nothing changes
+    fn resolve_existing_all(&mut self, config: &RenamerConfig) {
+        loop {
+            let mut changed = false;
+            for i in 0..self.operations.len() {
+                let op = &self.operations[i];
+                if op.action != RenameAction::Rename || op.issues.contains(&RenameIssue::CaseOnly) || !self.is_taken(&op.to) {
+                    continue;
+                }
+                self.resolve_existing(i, config.overwrite, config.conflict);
+                changed = true;
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    /// Check for invalid and overlong components generated by the template
+    fn validate(&self, op: &mut RenameOperation, base: &Path, config: &RenamerConfig) {
+        let (mut output, relative) = match op.to.strip_prefix(base) {
+            Ok(relative) => (base.to_owned(), relative.to_owned()),
+            Err(_) => (PathBuf::new(), op.to.clone())
+        };
+        // Companion files get longer extension than the audio file
+        let reserve = match config.companion.enabled {
+            true => config.companion.extensions.iter().map(|e| e.trim_start_matches('.').len() + 1).max().unwrap_or(0),
+            false => 0
+        };
+        let components = relative.components().collect::<Vec<_>>();
+        for (i, component) in components.iter().enumerate() {
+            let part = match component {
+                Component::Normal(part) => part.to_string_lossy().to_string(),
+                Component::ParentDir | Component::CurDir => {
+                    op.action = RenameAction::Skip;
+                    op.issues.push(RenameIssue::InvalidComponent { component: component.as_os_str().to_string_lossy().to_string() });
+                    return;
+                },
+                _ => {
+                    output.push(component);
+                    continue;
+                }
+            };
+
+            let is_file = i == components.len() - 1;
+            let reserve = if is_file { reserve } else { 0 };
+            let fixed = config.filesystem.sanitize_component(&part, is_file, reserve);
+            if part.trim().is_empty() || part.chars().any(|c| c.is_control()) || fixed.trim().is_empty() {
+                op.action = RenameAction::Skip;
+                op.issues.push(RenameIssue::InvalidComponent { component: part });
+                return;
+            }
+
+            if fixed != part {
+                match config.filesystem.length(&part) + reserve > FilesystemProfile::MAX_LENGTH {
+                    true => op.issues.push(RenameIssue::Truncated { component: part }),
+                    false => op.issues.push(RenameIssue::Sanitized { component: part }),
+                }
+            }
+            output.push(fixed);
+        }
+
+        // FAT32 limits the whole path, measured from the output folder as the volume root isn't known
+        if let Some(max) = config.filesystem.max_path_length() {
+            let length = config.filesystem.length(&output.strip_prefix(base).unwrap_or(&output).to_string_lossy()) + reserve;
+            if length > max {
+                op.action = RenameAction::Skip;
+                op.issues.push(RenameIssue::PathTooLong { length });
+                return;
+            }
+        }
+        op.to = output;
+    }
+
+    /// Resolve multiple files from batch going to the same target
+    fn resolve_collision(&mut self, group: &[usize], strategy: ConflictStrategy) {
+        let winner = match strategy {
+            ConflictStrategy::Skip | ConflictStrategy::Suffix => group[0],
+            ConflictStrategy::KeepLarger => *group.iter().max_by_key(|i| (file_size(&self.operations[**i].from), std::cmp::Reverse(**i))).unwrap(),
+            ConflictStrategy::KeepBetter => *group.iter().max_by_key(|i| (quality(&self.operations[**i].from), std::cmp::Reverse(**i))).unwrap(),
+        };
+        let winner_path = self.operations[winner].from.clone();
+        for i in group.iter().copied().filter(|i| *i != winner) {
+            self.operations[i].issues.push(RenameIssue::Collision { with: winner_path.clone() });
+            match strategy {
+                ConflictStrategy::Suffix => self.suffix(i),
+                _ => self.operations[i].action = RenameAction::Skip,
+            }
+        }
+    }
+
+    /// Resolve target which already exists on disk
+    fn resolve_existing(&mut self, i: usize, overwrite: bool, strategy: ConflictStrategy) {
+        self.operations[i].issues.push(RenameIssue::TargetExists);
+        // Existing file which isn't a part of this batch can be compared to
+        let external = self.operations.iter().all(|o| self.key(&o.from) != self.key(&self.operations[i].to));
+        let op = &mut self.operations[i];
+        if overwrite && external {
+            op.action = RenameAction::Replace;
+            return;
+        }
+        match strategy {
+            ConflictStrategy::Suffix => self.suffix(i),
+            ConflictStrategy::KeepLarger if external && file_size(&op.from) > file_size(&op.to) => op.action = RenameAction::Replace,
+            ConflictStrategy::KeepBetter if external && quality(&op.from) > quality(&op.to) => op.action = RenameAction::Replace,
+            _ => op.action = RenameAction::Skip,
+        }
+    }
+
+    /// Find a free name with a counter
+    fn suffix(&mut self, i: usize) {
+        let original = self.operations[i].to.clone();
+        let stem = original.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let ext = original.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+        let planned = self.operations.iter()
+            .filter(|o| o.action != RenameAction::Skip)
+            .map(|o| self.key(&o.to))
+            .collect::<HashSet<_>>();
+        for n in 1.. {
+            let name = format!("{} ({n}){ext}", truncate(&stem, MAX_COMPONENT_LENGTH - ext.len() - n.to_string().len() - 3));
+            let path = original.with_file_name(name);
+            if !planned.contains(&self.key(&path)) && !self.is_taken(&path) {
+                let op = &mut self.operations[i];
+                op.to = path;
+                op.issues.push(RenameIssue::Suffixed { original });
+                return;
+            }
+        }
+    }
+
+    /// Whether the path is occupied by file which won't be moved away
+    fn is_taken(&self, path: &Path) -> bool {
+        if !path.exists() {
+            return false;
+        }
+        let key = self.key(path);
+        let vacated = self.operations.iter().any(|o| o.action != RenameAction::Skip && !o.copy && self.key(&o.from) == key);
+        !vacated
+    }
+
+    /// Path as compared by the filesystem
+    fn key(&self, path: &Path) -> String {
+        match self.case_insensitive {
+            // macOS and SMB don't distinguish NFC and NFD either
+            true => path.to_string_lossy().nfc().collect::<String>().to_lowercase(),
+            false => path.to_string_lossy().to_string()
+        }
+    }
+
+    /// Operations which will affect files
+    pub fn pending(&self) -> impl Iterator<Item = &RenameOperation> {
+        self.operations.iter().filter(|o| o.action != RenameAction::Skip)
+    }
+
+    /// Order the moves, so no file is overwritten before it's moved away.
+    /// Cycles and case only renames go through a temporary file.
+    fn steps(&self) -> Vec<Step> {
+        let ops = self.pending().collect::<Vec<_>>();
+        // Copied files stay in place
+        let by_source = ops.iter().enumerate().filter(|(_, o)| !o.copy).map(|(i, o)| (self.key(&o.from), i)).collect::<HashMap<_, _>>();
+        let mut state = vec![0u8; ops.len()];
+        let mut steps = vec![];
+        let mut deferred = vec![];
+
+        fn visit(
+            plan: &RenamePlan, i: usize, ops: &[&RenameOperation], by_source: &HashMap<String, usize>,
+            state: &mut Vec<u8>, steps: &mut Vec<Step>, deferred: &mut Vec<Step>
+        ) {
+            state[i] = 1;
+            let op = ops[i];
+            let step = |from: &Path, to: &Path, action, copy| Step { from: from.to_owned(), to: to.to_owned(), action, copy };
+            if op.issues.contains(&RenameIssue::CaseOnly) {
+                let tmp = temp_path(&op.from);
+                steps.push(step(&op.from, &tmp, RenameAction::Rename, false));
+                steps.push(step(&tmp, &op.to, RenameAction::Rename, false));
+                state[i] = 2;
+                return;
+            }
+            if let Some(&j) = by_source.get(&plan.key(&op.to)).filter(|j| **j != i) {
+                match state[j] {
+                    0 => visit(plan, j, ops, by_source, state, steps, deferred),
+                    // Cycle, move out of the way and finish at the end
+                    1 => {
+                        let tmp = temp_path(&op.from);
+                        steps.push(step(&op.from, &tmp, RenameAction::Rename, false));
+                        deferred.push(step(&tmp, &op.to, op.action, false));
+                        state[i] = 2;
+                        return;
+                    },
+                    _ => {}
+                }
+            }
+            steps.push(step(&op.from, &op.to, op.action, op.copy));
+            state[i] = 2;
+        }
+
+        for i in 0..ops.len() {
+            if state[i] == 0 {
+                visit(self, i, &ops, &by_source, &mut state, &mut steps, &mut deferred);
+            }
+        }
+        steps.extend(deferred);
+        steps
+    }
+
+    /// Execute the plan, on error all the finished operations are reverted
+    pub fn execute(&self) -> Result<(), Error> {
+        let mut transaction = Transaction::default();
+        for step in self.steps() {
+            let result = match step.copy {
+                true => transaction.copy(&step.from, &step.to, step.action),
+                false => transaction.rename(&step.from, &step.to, step.action),
+            };
+            if let Err(e) = result {
+                error!("Failed {} {:?} -> {:?}: {e}, rolling back", if step.copy { "copying" } else { "moving" }, step.from, step.to);
+                transaction.rollback();
+                return Err(e);
+            }
+        }
+        transaction.commit();
+
+        // Directories left empty by moves
+        if let Some(root) = &self.cleanup_root {
+            let dirs = self.pending().filter(|o| !o.copy).filter_map(|o| o.from.parent().map(|p| p.to_owned()));
+            remove_empty_dirs(dirs, root);
+        }
+        Ok(())
+    }
+}
+
+/// Completed filesystem change which can be reverted
+#[derive(Debug, Clone)]
+enum Change {
+    Moved { from: PathBuf, to: PathBuf },
+    Copied { to: PathBuf },
+    Backup { original: PathBuf, backup: PathBuf },
+    CreatedDir { path: PathBuf },
+}
+
+/// Filesystem changes done so far
+#[derive(Debug, Default)]
+struct Transaction {
+    changes: Vec<Change>,
+}
+
+impl Transaction {
+    /// Create parent directories of the target
+    fn create_dirs(&mut self, to: &Path) -> Result<(), Error> {
+        let parent = match to.parent() {
+            Some(parent) => parent,
+            None => return Ok(())
+        };
+        let missing = parent.ancestors().take_while(|p| !p.as_os_str().is_empty() && !p.exists()).map(|p| p.to_owned()).collect::<Vec<_>>();
+        fs::create_dir_all(parent)?;
+        for path in missing.into_iter().rev() {
+            self.changes.push(Change::CreatedDir { path });
+        }
+        Ok(())
+    }
+
+    /// Move the existing target out of the way
+    fn backup(&mut self, to: &Path, action: RenameAction) -> Result<(), Error> {
+        if action == RenameAction::Replace && to.exists() {
+            let backup = temp_path(to);
+            move_file(to, &backup)?;
+            self.changes.push(Change::Backup { original: to.to_owned(), backup });
+        }
+        Ok(())
+    }
+
+    fn rename(&mut self, from: &Path, to: &Path, action: RenameAction) -> Result<(), Error> {
+        self.create_dirs(to)?;
+        self.backup(to, action)?;
+        move_file(from, to)?;
+        debug!("Renamed: {from:?} -> {to:?}");
+        self.changes.push(Change::Moved { from: from.to_owned(), to: to.to_owned() });
+        Ok(())
+    }
+
+    fn copy(&mut self, from: &Path, to: &Path, action: RenameAction) -> Result<(), Error> {
+        self.create_dirs(to)?;
+        self.backup(to, action)?;
+        if let Err(e) = fs::copy(from, to) {
+            // Don't leave partial file
+            fs::remove_file(to).ok();
+            return Err(e.into());
+        }
+        info!("Copied: {to:?}");
+        self.changes.push(Change::Copied { to: to.to_owned() });
+        Ok(())
+    }
+
+    /// Revert all the changes in reverse order
+    fn rollback(self) {
+        for change in self.changes.into_iter().rev() {
+            let result = match &change {
+                Change::Moved { from, to } => move_file(to, from),
+                Change::Copied { to } => fs::remove_file(to).map_err(Error::from),
+                Change::Backup { original, backup } => move_file(backup, original),
+                Change::CreatedDir { path } => fs::remove_dir(path).map_err(Error::from),
+            };
+            if let Err(e) = result {
+                error!("Failed rolling back {change:?}: {e}");
+            }
+        }
+    }
+
+    /// Remove backups of the replaced files
+    fn commit(self) {
+        for change in self.changes {
+            match change {
+                Change::Backup { backup, .. } => if let Err(e) = fs::remove_file(&backup) {
+                    warn!("Failed removing backup {backup:?}: {e}");
+                },
+                Change::Moved { to, .. } if !is_temp(&to) => info!("Renamed: {to:?}"),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Rename, or copy and delete when on different filesystem
+fn move_file(from: &Path, to: &Path) -> Result<(), Error> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    debug!("Renaming failed, might be different FS, trying to copy.");
+    if let Err(e) = fs::copy(from, to) {
+        fs::remove_file(to).ok();
+        return Err(e.into());
+    }
+    fs::remove_file(from)?;
+    Ok(())
+}
+
+/// Temporary path next to the file
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let name = truncate(&name, MAX_COMPONENT_LENGTH - 32);
+    (0..).map(|i| path.with_file_name(format!(".{name}.{i}.onetagger-tmp"))).find(|p| !p.exists()).unwrap()
+}
+
+fn is_temp(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(".onetagger-tmp")
+}
+
+/// Absolute path without requiring the file to exist
+fn absolute(path: &Path) -> PathBuf {
+    if let Ok(p) = dunce::canonicalize(path) {
+        return p;
+    }
+    // Canonicalize the existing parent
+    match (path.parent().and_then(|p| dunce::canonicalize(p).ok()), path.file_name()) {
+        (Some(parent), Some(name)) => parent.join(name),
+        _ => path.to_owned()
+    }
+}
+
+/// Cut string to max bytes on char boundary
+fn truncate(s: &str, max: usize) -> &str {
+    let mut end = s.len().min(max);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// Rough quality of file: lossless first, then size (bitrate for same track)
+fn quality(path: &Path) -> (bool, u64) {
+    let ext = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+    (LOSSLESS_EXTENSIONS.contains(&ext.as_str()), file_size(path))
+}
+
+/// Temp dir with files of given sizes, removed when the `TempDir` is dropped
+#[cfg(test)]
+fn test_dir(files: &[(&str, usize)]) -> (tempfile::TempDir, PathBuf) {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = dunce::canonicalize(temp.path()).unwrap();
+    for (file, size) in files {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, vec![0u8; *size]).unwrap();
+    }
+    (temp, dir)
+}
+
+/// Collisions in batch and with existing files
+#[test]
+fn plan_collisions() {
+    let (_temp, dir) = test_dir(&[("a.mp3", 10), ("b.mp3", 20), ("c.flac", 5), ("x.mp3", 15)]);
+    let files = vec![
+        (dir.join("a.mp3"), dir.join("x.mp3")),
+        (dir.join("b.mp3"), dir.join("x.mp3")),
+        (dir.join("c.flac"), dir.join("y.flac")),
+    ];
+    let mut config = RenamerConfig::default_with_paths(&dir, "");
+
+    config.conflict = ConflictStrategy::Suffix;
+    let plan = RenamePlan::with_case_insensitive(&files, &config, false);
+    assert_eq!(plan.operations[0].to, dir.join("x (1).mp3"));
+    assert_eq!(plan.operations[1].to, dir.join("x (2).mp3"));
+    assert!(plan.operations[1].issues.contains(&RenameIssue::TargetExists));
+    assert_eq!(plan.operations[2].action, RenameAction::Rename);
+
+    config.conflict = ConflictStrategy::KeepLarger;
+    let plan = RenamePlan::with_case_insensitive(&files, &config, false);
+    assert_eq!(plan.operations[0].action, RenameAction::Skip);
+    assert_eq!(plan.operations[1].action, RenameAction::Replace);
+
+    config.conflict = ConflictStrategy::Skip;
+    let plan = RenamePlan::with_case_insensitive(&files, &config, false);
+    assert_eq!(plan.pending().count(), 1);
+
+    // Whole path is limited on FAT32
+    config.filesystem = FilesystemProfile::Fat32;
+    let files = vec![(dir.join("c.flac"), dir.join("a".repeat(200)).join(format!("{}.flac", "b".repeat(100))))];
+    let plan = RenamePlan::with_case_insensitive(&files, &config, false);
+    assert_eq!(plan.operations[0].action, RenameAction::Skip);
+    assert!(matches!(plan.operations[0].issues[..], [RenameIssue::PathTooLong { length: 306 }]));
+}
+
+/// Swapping names and rollback
+#[test]
+fn plan_execute() {
+    let (_temp, dir) = test_dir(&[("a.mp3", 1), ("b.mp3", 2)]);
+    let config = RenamerConfig::default_with_paths(&dir, "");
+    let files = vec![(dir.join("a.mp3"), dir.join("b.mp3")), (dir.join("b.mp3"), dir.join("a.mp3"))];
+    RenamePlan::with_case_insensitive(&files, &config, false).execute().unwrap();
+    assert_eq!(file_size(&dir.join("a.mp3")), 2);
+    assert_eq!(file_size(&dir.join("b.mp3")), 1);
+
+    // Second file is missing, first one has to be moved back
+    let files = vec![(dir.join("a.mp3"), dir.join("sub").join("c.mp3")), (dir.join("missing.mp3"), dir.join("d.mp3"))];
+    assert!(RenamePlan::with_case_insensitive(&files, &config, false).execute().is_err());
+    assert!(dir.join("a.mp3").exists());
+    assert!(!dir.join("sub").exists());
+}
+
+/// Lyrics and folder art follow the album only when enabled, empty folder is removed
+#[test]
+fn plan_companions() {
+    let (_temp, dir) = test_dir(&[("album/01.mp3", 1), ("album/01.lrc", 1), ("album/cover.jpg", 3)]);
+    let mut config = RenamerConfig::default_with_paths(&dir, "");
+    let files = vec![(dir.join("album").join("01.mp3"), dir.join("Artist").join("Album").join("01 - Title.mp3"))];
+    assert!(RenamePlan::with_case_insensitive(&files, &config, false).operations.iter().all(|o| !o.companion));
+
+    config.companion.enabled = true;
+    config.companion.remove_empty_dirs = true;
+    let plan = RenamePlan::with_case_insensitive(&files, &config, false);
+    assert_eq!(plan.operations.iter().filter(|o| o.companion).count(), 2);
+    plan.execute().unwrap();
+    assert!(dir.join("Artist").join("Album").join("01 - Title.lrc").exists());
+    assert!(dir.join("Artist").join("Album").join("cover.jpg").exists());
+    assert!(!dir.join("album").exists());
+}
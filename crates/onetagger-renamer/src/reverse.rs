@@ -0,0 +1,173 @@
+use std::path::{Path, PathBuf};
+use anyhow::Error;
+use onetagger_tag::{TagChange, TagChanges, TagDate, TagSeparators};
+use onetagger_tagger::{AudioFileInfo, Field};
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+
+use crate::parser::variable_field;
+
+/// Variables which aren't a `Field`
+const EXTRA_VARIABLES: [&str; 1] = ["year"];
+
+/// Parse tags from path
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathTagsConfig {
+    pub path: PathBuf,
+    /// For example `%albumartist%/%year% - %album%/%track%. %title%`
+    pub template: String,
+    pub subfolders: bool,
+    /// Overwrite existing tag values
+    pub overwrite: bool,
+    pub separators: TagSeparators,
+    pub id3v24: bool,
+}
+
+/// Values parsed from single file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathTags {
+    pub path: PathBuf,
+    /// (variable, value), empty if the path doesn't match
+    pub values: Vec<(String, String)>,
+}
+
+/// Template converted into regex matching the end of path
+#[derive(Debug, Clone)]
+pub struct PathTemplate {
+    regex: Regex,
+}
+
+impl PathTemplate {
+    /// Parse the template, only variables which can be written are allowed
+    pub fn parse(template: &str) -> Result<PathTemplate, Error> {
+        let template = template.trim().trim_matches(['/', '\\']);
+        let mut output = String::new();
+        let mut used = vec![];
+        let mut rest = template;
+        while let Some(start) = rest.find('%') {
+            output.push_str(&Self::escape(&rest[..start]));
+            let end = rest[start + 1..].find('%').ok_or(anyhow!("Unclosed variable at position {}", template.len() - rest.len() + start))?;
+            let var = rest[start + 1..start + 1 + end].trim().to_lowercase();
+            let name = match (variable_field(&var), EXTRA_VARIABLES.contains(&var.as_str())) {
+                (Some(field), _) => format!("{field:?}").to_lowercase(),
+                (None, true) => var.clone(),
+                (None, false) => bail!("Variable `{var}` can't be written to tag")
+            };
+            let pattern = match &name[..] {
+                "tracknumber" | "discnumber" | "tracktotal" => "\\d+",
+                "year" => "\\d{4}",
+                "bpm" => "\\d+(?:\\.\\d+)?",
+                _ => "[^/]+?"
+            };
+            // Only first occurrence is captured
+            match used.contains(&name) {
+                true => output.push_str(&format!("(?:{pattern})")),
+                false => output.push_str(&format!("(?P<{name}>{pattern})")),
+            }
+            used.push(name);
+            rest = &rest[start + 2 + end..];
+        }
+        output.push_str(&Self::escape(rest));
+        if used.is_empty() {
+            bail!("Template doesn't contain any variables");
+        }
+        let regex = Regex::new(&format!("(?:^|/){output}\\.[^./]+$"))?;
+        debug!("Path template regex: `{regex}`");
+        Ok(PathTemplate { regex })
+    }
+
+    /// Escape constant part of the template, both slashes are path separators
+    fn escape(text: &str) -> String {
+        regex::escape(&text.replace('\\', "/"))
+    }
+
+    /// Parse values from path, None if it doesn't match
+    pub fn parse_path(&self, path: impl AsRef<Path>) -> Option<Vec<(String, String)>> {
+        let path = path.as_ref().to_string_lossy().replace('\\', "/");
+        let captures = self.regex.captures(&path)?;
+        Some(self.regex.capture_names()
+            .flatten()
+            .filter_map(|name| captures.name(name).map(|m| (name.to_string(), m.as_str().trim().to_string())))
+            .filter(|(_, v)| !v.is_empty())
+            .collect())
+    }
+
+    /// Parse values of all the files
+    pub fn generate<I>(&self, files: I) -> Vec<PathTags>
+    where
+        I: IntoIterator<Item = PathBuf>
+    {
+        files.into_iter().map(|path| {
+            let values = self.parse_path(&path).unwrap_or_default();
+            PathTags { path, values }
+        }).collect()
+    }
+
+    /// Write the parsed values to tags, returns number of modified files
+    pub fn apply(files: &[PathTags], config: &PathTagsConfig) -> usize {
+        let mut count = 0;
+        for file in files.iter().filter(|f| !f.values.is_empty()) {
+            match Self::write(file, config) {
+                Ok(_) => count += 1,
+                Err(e) => error!("Failed writing tags to {:?}: {e}", file.path),
+            }
+        }
+        info!("Tags from path written to {count} files");
+        count
+    }
+
+    /// Write values of single file
+    fn write(file: &PathTags, config: &PathTagsConfig) -> Result<(), Error> {
+        let mut changes = TagChanges::new(&file.path, config.separators.clone(), config.id3v24, None);
+        let overwrite = config.overwrite;
+        let total = file.values.iter().find(|(k, _)| k == "tracktotal").and_then(|(_, v)| v.parse().ok());
+        for (var, value) in &file.values {
+            let change = match &var[..] {
+                "year" => match value.parse() {
+                    Ok(year) => TagChange::Date { date: TagDate { year, month: None, day: None }, overwrite },
+                    Err(e) => {
+                        warn!("Invalid year `{value}` in {:?}: {e}", file.path);
+                        continue;
+                    }
+                },
+                "tracknumber" => TagChange::TrackNumber { value: value.to_string(), total, overwrite },
+                // Written together with track number
+                "tracktotal" => continue,
+                "artist" => {
+                    let artists = AudioFileInfo::parse_artist_tag(vec![value.as_str()]).into_iter().map(|a| a.trim().to_string()).collect();
+                    TagChange::Field { field: Field::Artist, value: artists, overwrite }
+                },
+                _ => match variable_field(var) {
+                    Some(field) => TagChange::Field { field, value: vec![value.to_string()], overwrite },
+                    None => {
+                        warn!("Unsupported path variable: {var}");
+                        continue;
+                    }
+                }
+            };
+            changes.add(change);
+        }
+        changes.commit()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_path_template() {
+    let template = PathTemplate::parse("%albumartist%/%year% - %album%/%track%. %artist% - %title%").unwrap();
+    let values = template.parse_path("/music/Archive/Some Artist/1999 - Album (Deluxe)/03. A, B - Title - Mix.flac").unwrap();
+    let get = |name: &str| values.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+    assert_eq!(get("albumartist"), Some("Some Artist"));
+    assert_eq!(get("year"), Some("1999"));
+    assert_eq!(get("album"), Some("Album (Deluxe)"));
+    assert_eq!(get("tracknumber"), Some("03"));
+    assert_eq!(get("artist"), Some("A, B"));
+    assert_eq!(get("title"), Some("Title - Mix"));
+
+    // Folder structure has to match
+    assert!(template.parse_path("/music/Album/03. A - Title.flac").is_none());
+    assert!(PathTemplate::parse("%artist% - %codec%").is_err());
+    assert!(PathTemplate::parse("%artist - %title%").is_err());
+}
@@ -0,0 +1,202 @@
+use anyhow::Error;
+use serde::{Serialize, Deserialize};
+use unicode_normalization::UnicodeNormalization;
+
+/// Windows reserved device names, can't be used even with extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9"
+];
+
+/// Characters which can't be in filename on Windows filesystems
+const WINDOWS_ILLEGAL: &str = "<>:\"/\\|?*";
+
+/// Truncation looks for a word boundary at most this far back
+const WORD_BOUNDARY_LOOKBACK: usize = 16;
+
+/// Filesystem the files are renamed onto
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FilesystemProfile {
+    /// Windows rules
+    Ntfs,
+    /// USB sticks for CDJs and car players, Windows rules, case insensitive and 255 characters long paths
+    Fat32,
+    /// Linux / macOS, only `/` is forbidden, 255 bytes
+    Posix,
+    /// Network shares, Windows rules and case insensitive
+    Smb,
+    /// Windows rules and only printable ASCII, works everywhere
+    Ascii,
+}
+
+impl Default for FilesystemProfile {
+    /// Filesystem of the current OS
+    fn default() -> Self {
+        match cfg!(target_os = "windows") {
+            true => FilesystemProfile::Ntfs,
+            false => FilesystemProfile::Posix,
+        }
+    }
+}
+
+impl std::str::FromStr for FilesystemProfile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "ntfs" => Ok(FilesystemProfile::Ntfs),
+            "fat32" | "fat" | "exfat" => Ok(FilesystemProfile::Fat32),
+            "posix" => Ok(FilesystemProfile::Posix),
+            "smb" => Ok(FilesystemProfile::Smb),
+            "ascii" => Ok(FilesystemProfile::Ascii),
+            _ => Err(anyhow!("Invalid filesystem profile: {s}, use ntfs, fat32, posix, smb or ascii"))
+        }
+    }
+}
+
+impl FilesystemProfile {
+    /// Maximum length of single path component, see `length`
+    pub const MAX_LENGTH: usize = 255;
+    /// Maximum length of the whole path on FAT32
+    pub const FAT32_MAX_PATH: usize = 255;
+
+    /// Maximum length of the path, if the filesystem limits it
+    pub fn max_path_length(&self) -> Option<usize> {
+        match self {
+            FilesystemProfile::Fat32 => Some(Self::FAT32_MAX_PATH),
+            _ => None
+        }
+    }
+
+    /// Whether the Windows naming rules apply
+    fn windows(&self) -> bool {
+        *self != FilesystemProfile::Posix
+    }
+
+    /// Lookup of existing files ignores case
+    pub fn case_insensitive(&self) -> bool {
+        matches!(self, FilesystemProfile::Fat32 | FilesystemProfile::Smb)
+    }
+
+    /// Length as counted by the filesystem (UTF-16 units on Windows filesystems, bytes otherwise)
+    pub fn length(&self, s: &str) -> usize {
+        match self.windows() {
+            true => s.encode_utf16().count(),
+            false => s.len()
+        }
+    }
+
+    /// Is the character allowed in filename
+    fn is_allowed(&self, c: char) -> bool {
+        match self {
+            FilesystemProfile::Posix => c != '/' && c != '\0',
+            FilesystemProfile::Ascii => (' '..='~').contains(&c) && !WINDOWS_ILLEGAL.contains(c),
+            _ => !c.is_control() && !WINDOWS_ILLEGAL.contains(c),
+        }
+    }
+
+    /// Sanitize value inserted into the template, removes illegal characters (including path separators)
+    pub fn sanitize(&self, value: &str, transliterate: bool) -> String {
+        let value = match transliterate || *self == FilesystemProfile::Ascii {
+            true => unidecode::unidecode(value),
+            false => value.to_string()
+        };
+        let value = value.chars().filter(|c| self.is_allowed(*c) && *c != '\\').collect::<String>();
+        self.normalize(&value)
+    }
+
+    /// Windows filesystems and SMB expect composed (NFC) form, macOS often produces decomposed
+    fn normalize(&self, s: &str) -> String {
+        match self.windows() {
+            true => s.nfc().collect(),
+            false => s.to_string()
+        }
+    }
+
+    /// Whether the component can be used as is
+    pub fn is_valid_component(&self, component: &str) -> bool {
+        self.sanitize_component(component, false, 0) == component
+    }
+
+    /// Fix single path component (reserved names, trailing dots, length).
+    /// `reserve` = bytes / units which should stay free after the extension (for companion files)
+    pub fn sanitize_component(&self, component: &str, is_file: bool, reserve: usize) -> String {
+        let component = self.normalize(&component.chars().filter(|c| self.is_allowed(*c)).collect::<String>());
+        let (stem, ext) = match (is_file, component.rsplit_once('.')) {
+            (true, Some((stem, ext))) if !stem.is_empty() && !ext.contains(' ') => (stem.to_string(), format!(".{ext}")),
+            _ => (component.clone(), String::new())
+        };
+
+        let max = Self::MAX_LENGTH.saturating_sub(self.length(&ext) + reserve).max(1);
+        let mut stem = match self.length(&stem) > max {
+            true => self.truncate(&stem, max),
+            false => stem
+        };
+
+        if self.windows() {
+            // Windows silently strips these
+            stem = stem.trim_end_matches(['.', ' ']).to_string();
+            // `CON.mp3` is reserved too
+            let base = stem.split('.').next().unwrap_or_default().trim_end();
+            if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(base)) {
+                stem.insert(base.len(), '_');
+            }
+        }
+        format!("{stem}{ext}")
+    }
+
+    /// Shorten to max length, on word boundary if close enough
+    fn truncate(&self, s: &str, max: usize) -> String {
+        let mut output = String::new();
+        for c in s.chars() {
+            if self.length(&output) + self.length(c.encode_utf8(&mut [0; 4])) > max {
+                break;
+            }
+            output.push(c);
+        }
+        if let Some(i) = output.rfind(' ') {
+            if output[i..].chars().count() <= WORD_BOUNDARY_LOOKBACK && i > 0 {
+                output.truncate(i);
+            }
+        }
+        output.trim_end_matches(|c: char| c.is_whitespace() || "-_,.(&+".contains(c)).to_string()
+    }
+}
+
+#[test]
+fn test_profiles() {
+    let ntfs = FilesystemProfile::Ntfs;
+    assert_eq!(ntfs.sanitize("AC/DC: Live?", false), "ACDC Live");
+    assert_eq!(ntfs.sanitize_component("con.flac", true, 0), "con_.flac");
+    assert_eq!(ntfs.sanitize_component("Nul.tar.gz", true, 0), "Nul_.tar.gz");
+    assert_eq!(ntfs.sanitize_component("Console.flac", true, 0), "Console.flac");
+    assert_eq!(ntfs.sanitize_component("Various Artists...", false, 0), "Various Artists");
+    assert_eq!(ntfs.sanitize("Cafe\u{0301}", false), "Caf\u{e9}");
+
+    // Default follows the OS
+    assert_eq!(FilesystemProfile::default() == FilesystemProfile::Ntfs, cfg!(target_os = "windows"));
+    assert_eq!(FilesystemProfile::Fat32.max_path_length(), Some(255));
+    assert_eq!(FilesystemProfile::Ntfs.max_path_length(), None);
+
+    let posix = FilesystemProfile::Posix;
+    assert_eq!(posix.sanitize("What? Yes: No", false), "What? Yes: No");
+    assert_eq!(posix.sanitize_component("Artist...", false, 0), "Artist...");
+    assert_eq!(posix.sanitize_component("CON.mp3", true, 0), "CON.mp3");
+
+    let ascii = FilesystemProfile::Ascii;
+    assert_eq!(ascii.sanitize("Röyksopp – Éclair", false), "Royksopp - Eclair");
+    assert_eq!(ntfs.sanitize("Björk", true), "Bjork");
+
+    // Truncated on word boundary, extension kept
+    let long = format!("{} - Title.mp3", "Word ".repeat(60));
+    let truncated = ntfs.sanitize_component(&long, true, 0);
+    assert!(truncated.len() <= 255 && truncated.ends_with("Word.mp3"), "{truncated}");
+    let truncated = ntfs.sanitize_component(&long, true, 4);
+    assert!(truncated.len() <= 251, "{truncated}");
+    // UTF-16 units on NTFS, bytes on Posix
+    let accented = "é".repeat(200);
+    assert_eq!(ntfs.sanitize_component(&accented, false, 0).chars().count(), 200);
+    assert_eq!(posix.sanitize_component(&accented, false, 0).len(), 254);
+}
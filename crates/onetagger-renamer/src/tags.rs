@@ -0,0 +1,181 @@
+use std::path::{Path, PathBuf};
+use anyhow::Error;
+use onetagger_tag::{FrameName, TagChange, TagChanges, TagSeparators};
+use onetagger_tagger::AudioFileInfo;
+use serde::{Serialize, Deserialize};
+
+use crate::{RenamerConfig, TemplateParser};
+
+/// Write template output into a tag instead of filename
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagTemplateConfig {
+    pub path: PathBuf,
+    pub template: String,
+    pub subfolders: bool,
+    /// Tag to write into
+    pub tag: FrameName,
+    /// Separator of multiple values inside the template
+    pub separator: String,
+    /// Split the output by `separator` into multiple values
+    #[serde(default)]
+    pub split: bool,
+    /// Keep the original value if the template output is empty
+    #[serde(default)]
+    pub skip_empty: bool,
+    pub separators: TagSeparators,
+    pub id3v24: bool,
+    #[serde(default)]
+    pub id3_comm_lang: Option<String>,
+}
+
+impl TagTemplateConfig {
+    /// Config used for evaluating the template
+    fn renamer_config(&self) -> RenamerConfig {
+        RenamerConfig {
+            separator: self.separator.to_string(),
+            subfolders: self.subfolders,
+            ..RenamerConfig::default_with_paths(&self.path, &self.template)
+        }
+    }
+}
+
+/// New value of tag for single file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagTemplateChange {
+    pub path: PathBuf,
+    /// Raw tag name for the file format
+    pub tag: String,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+impl TagTemplateChange {
+    /// Will the file be modified
+    pub fn changed(&self) -> bool {
+        self.old != self.new
+    }
+}
+
+/// Tags from template
+pub struct TagTemplate {
+    template: TemplateParser
+}
+
+impl TagTemplate {
+    /// Create new instance
+    pub fn new(template: TemplateParser) -> TagTemplate {
+        TagTemplate { template }
+    }
+
+    /// Evaluate the template for every file without writing
+    pub fn generate<I>(&mut self, files: I, config: &TagTemplateConfig) -> Vec<TagTemplateChange>
+    where
+        I: IntoIterator<Item = Result<AudioFileInfo, Error>>
+    {
+        let renamer_config = config.renamer_config();
+        let mut output = vec![];
+        for file in files.into_iter() {
+            let info = match file {
+                Ok(i) => i,
+                Err(e) => {
+                    warn!("Failed loading file: {e}");
+                    continue;
+                },
+            };
+            output.push(self.generate_file(&info, config, &renamer_config));
+        }
+        output
+    }
+
+    /// Evaluate for single file
+    fn generate_file(&mut self, info: &AudioFileInfo, config: &TagTemplateConfig, renamer_config: &RenamerConfig) -> TagTemplateChange {
+        let tag = config.tag.by_format(&info.format);
+        let old = info.tags.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(&tag))
+            .map(|(_, v)| v.clone())
+            .unwrap_or_default();
+        let value = self.template.evaluate_raw(info, renamer_config).trim().to_string();
+        let new = match (value.is_empty(), config.split && !config.separator.is_empty()) {
+            (true, _) if config.skip_empty => old.clone(),
+            (true, _) => vec![],
+            (false, true) => value.split(&config.separator).map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect(),
+            (false, false) => vec![value]
+        };
+        TagTemplateChange { path: info.path.to_owned(), tag, old, new }
+    }
+
+    /// Write the changed values, returns number of modified files
+    pub fn apply(changes: &[TagTemplateChange], config: &TagTemplateConfig) -> usize {
+        let mut count = 0;
+        for change in changes.iter().filter(|c| c.changed()) {
+            match Self::write(&change.path, &change.tag, &change.new, config) {
+                Ok(_) => count += 1,
+                Err(e) => error!("Failed writing tag to {:?}: {e}", change.path),
+            }
+        }
+        info!("Tags from template written to {count} files");
+        count
+    }
+
+    /// Write single value, empty value removes the tag
+    fn write(path: &Path, tag: &str, value: &[String], config: &TagTemplateConfig) -> Result<(), Error> {
+        let mut changes = TagChanges::new(path, config.separators.clone(), config.id3v24, config.id3_comm_lang.clone());
+        changes.add(match value.is_empty() {
+            true => TagChange::Remove { tag: tag.to_string() },
+            false => TagChange::Raw { tag: tag.to_string(), value: value.to_vec() }
+        });
+        changes.commit()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_tag_template() {
+    use std::collections::HashMap;
+    use onetagger_tagger::{AudioFileFormat, FileTaggedStatus};
+
+    let info = AudioFileInfo {
+        title: None,
+        artists: vec![],
+        format: AudioFileFormat::MP3,
+        path: "Artist - Some: Title.mp3".into(),
+        isrc: None,
+        duration: None,
+        track_number: None,
+        tagged: FileTaggedStatus::Untagged,
+        tags: HashMap::from([
+            ("TPUB".to_string(), vec!["Label".to_string()]),
+            ("CATALOGNUMBER".to_string(), vec!["CAT001".to_string()]),
+            ("TALB".to_string(), vec!["Old".to_string()]),
+            ("TKEY".to_string(), vec!["8A".to_string(), "Am".to_string()]),
+        ])
+    };
+    let mut config = TagTemplateConfig {
+        path: ".".into(),
+        template: String::new(),
+        subfolders: true,
+        tag: FrameName::new("TALB", "ALBUM", "©alb"),
+        separator: ";".to_string(),
+        split: false,
+        skip_empty: false,
+        separators: TagSeparators::default(),
+        id3v24: true,
+        id3_comm_lang: None
+    };
+    let generate = |template: &str, config: &TagTemplateConfig| {
+        TagTemplate::new(TemplateParser::parse(template).unwrap()).generate_file(&info, config, &config.renamer_config())
+    };
+
+    let change = generate("%label% %catalognumber%", &config);
+    assert_eq!((change.tag.as_str(), change.old, change.new), ("TALB", vec!["Old".to_string()], vec!["Label CAT001".to_string()]));
+    // Not sanitized like filenames
+    assert_eq!(generate("%filename.replace(\"^.* - \", \"\")%", &config).new, vec!["Some: Title"]);
+    assert!(generate("%version%", &config).new.is_empty());
+
+    config.split = true;
+    config.skip_empty = true;
+    assert_eq!(generate("%key%", &config).new, vec!["8A", "Am"]);
+    assert!(!generate("%version%", &config).changed());
+}
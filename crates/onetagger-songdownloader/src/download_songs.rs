@@ -4,11 +4,13 @@ use std::fs;
 use std::process::Command;
 use log::{info, warn};
 use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
+use std::collections::HashSet;
 use regex::Regex;
+use crate::manifest::{Manifest, DownloadStatus};
+use crate::tracklist::TracklistEntry;
 
 /// Song information structure for CSV/JSON output
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SongInfo {
     pub video_title: String,
     pub video_url: String,
@@ -17,10 +19,66 @@ pub struct SongInfo {
     pub timestamp: Option<String>,
     pub downloaded: bool,
     pub match_confidence: f32,
+    // Tracklist details, optional so older files can still be loaded
+    #[serde(default)]
+    pub position: Option<u32>,
+    #[serde(default)]
+    pub end_timestamp: Option<String>,
+    /// Individual artists, stored as `A; B` so they fit into CSV
+    #[serde(default, with = "joined")]
+    pub artists: Vec<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Tracks mixed over this one, as `Artist - Title` separated by ` w/ `
+    #[serde(default)]
+    pub mashup: Option<String>,
+    #[serde(default)]
+    pub unidentified: bool,
+}
+
+impl SongInfo {
+    /// Create song from a parsed tracklist entry
+    pub fn from_entry(video_title: &str, video_url: &str, entry: &TracklistEntry) -> SongInfo {
+        SongInfo {
+            video_title: video_title.to_string(),
+            video_url: video_url.to_string(),
+            song_title: entry.full_title(),
+            artist: entry.artist.clone(),
+            timestamp: entry.start_timestamp(),
+            downloaded: false,
+            match_confidence: 0.75, // Default confidence level
+            position: entry.position,
+            end_timestamp: entry.end_timestamp(),
+            artists: entry.artists.clone(),
+            version: entry.version.clone(),
+            label: entry.label.clone(),
+            mashup: match entry.mashup.is_empty() {
+                true => None,
+                false => Some(entry.mashup.iter().map(|e| e.text.as_str()).collect::<Vec<_>>().join(" w/ "))
+            },
+            unidentified: entry.unidentified,
+        }
+    }
+}
+
+/// (De)serialize list of strings as a single `; ` separated string
+mod joined {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.join("; "))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Ok(value.split(';').map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect())
+    }
 }
 
 /// Download songs from a CSV/JSON file generated by query-url
-pub fn download_songs(csv_path: &Path, directory: &Path) -> Result<(), Error> {
+pub fn download_songs(csv_path: &Path, directory: &Path, max_attempts: u32) -> Result<(), Error> {
     info!("Starting song download process");
     
     // Check if directory exists
@@ -39,66 +97,59 @@ pub fn download_songs(csv_path: &Path, directory: &Path) -> Result<(), Error> {
     
     info!("Found {} songs in the input file", songs.len());
     
-    // Group songs by video title
-    let mut songs_by_video: HashMap<String, Vec<SongInfo>> = HashMap::new();
-    for song in songs {
-        songs_by_video
-            .entry(song.video_title.clone())
-            .or_insert_with(Vec::new)
-            .push(song);
+    // Merge into the manifest, already known songs keep their state
+    let mut manifest = Manifest::load(directory)?;
+    let added = songs.into_iter().filter(|song| manifest.add_song(song.clone())).count();
+    info!("{} new songs added to manifest", added);
+    manifest.save()?;
+    
+    download_manifest(&mut manifest, directory, max_attempts)
+}
+
+/// Download all pending and failed (below max_attempts) songs from manifest
+pub fn download_manifest(manifest: &mut Manifest, directory: &Path, max_attempts: u32) -> Result<(), Error> {
+    let queue = manifest.to_download(max_attempts);
+    if queue.is_empty() {
+        info!("No songs to download");
+        manifest.log_summary();
+        return Ok(());
     }
     
-    info!("Processing {} videos", songs_by_video.len());
+    info!("Downloading {} songs", queue.len());
     
-    // Process each video
-    for (video_title, songs) in songs_by_video {
-        info!("Processing video: {}", video_title);
+    for (i, index) in queue.into_iter().enumerate() {
+        let song = manifest.entries[index].song.clone();
+        info!("[{}] Processing video: {}", i + 1, song.video_title);
         
         // Create sanitized folder name
-        let folder_name = sanitize_filename(&video_title);
-        let video_folder = directory.join(&folder_name);
-        
-        // Check if folder exists, create if not
-        let folder_existed = video_folder.exists();
-        if !folder_existed {
+        let video_folder = directory.join(sanitize_filename(&song.video_title));
+        if !video_folder.exists() {
             info!("Creating folder: {:?}", video_folder);
             fs::create_dir_all(&video_folder)?;
-        } else {
-            info!("Folder already exists: {:?}", video_folder);
         }
         
-        // Filter songs that were marked for download 
-        // Logic fix: if downloaded flag is false, it means we should download it
-        // We also respect folder_existed - if folder already exists, we skip songs
-        let songs_to_download: Vec<&SongInfo> = songs.iter()
-            .filter(|song| !song.downloaded && (!folder_existed || song.match_confidence > 0.5))
-            .collect();
-        
-        if songs_to_download.is_empty() {
-            info!("No songs to download in this video");
-            continue;
-        }
-        
-        info!("Downloading {} songs for video: {}", songs_to_download.len(), video_title);
-        
-        // Download each song
-        for song in songs_to_download {
-            match download_song(song, &video_folder) {
-                Ok(_) => info!("Successfully downloaded: {} - {}", song.artist, song.song_title),
-                Err(e) => warn!("Failed to download song {} - {}: {}", song.artist, song.song_title, e),
+        match download_song(&song, &video_folder) {
+            Ok((backend, file)) => {
+                info!("Successfully downloaded: {} - {}", song.artist, song.song_title);
+                manifest.mark_downloaded(index, backend, file);
+            },
+            Err(e) => {
+                warn!("Failed to download song {} - {}: {}", song.artist, song.song_title, e);
+                manifest.mark_failed(index, &e);
             }
         }
         
-        // Return to original directory for next video
-        info!("Completed processing video: {}", video_title);
+        // Save after every song, so interrupted runs can be resumed
+        manifest.save()?;
     }
     
+    manifest.log_summary();
     info!("Song download process completed");
     Ok(())
 }
 
-/// Download a single song using yt-dlp or spotdl
-fn download_song(song: &SongInfo, output_folder: &Path) -> Result<(), Error> {
+/// Download a single song using yt-dlp or spotdl, returns the used backend and resulting file
+fn download_song(song: &SongInfo, output_folder: &Path) -> Result<(&'static str, Option<PathBuf>), Error> {
     let song_query = format!("{} - {}", song.artist, song.song_title);
     info!("Downloading song: {}", song_query);
     
@@ -106,33 +157,47 @@ fn download_song(song: &SongInfo, output_folder: &Path) -> Result<(), Error> {
     let safe_query = sanitize_query(&song_query);
     
     // Check if song already exists in the folder
-    let song_exists = check_if_song_exists(output_folder, &song.artist, &song.song_title)?;
-    if song_exists {
+    if let Some(existing) = find_existing_song(output_folder, &song.artist, &song.song_title)? {
         info!("Song already exists in folder, skipping: {}", song_query);
-        return Ok(());
+        return Ok(("existing", Some(existing)));
     }
     
     // Try to download using yt-dlp with YouTube Music
+    let before = list_files(output_folder)?;
     info!("Attempting to download with yt-dlp: {}", safe_query);
-    let result = download_with_ytdlp(&safe_query, output_folder);
-    
-    if result.is_err() {
-        // Fallback to spotdl if yt-dlp fails
-        warn!("yt-dlp failed for {}, trying spotdl", safe_query);
-        match download_with_spotdl(&safe_query, output_folder) {
-            Ok(_) => {
-                info!("Successfully downloaded with spotdl: {}", safe_query);
-            },
-            Err(e) => {
-                warn!("Both yt-dlp and spotdl failed for {}: {}", safe_query, e);
-                return Err(e);
+    let backend = match download_with_ytdlp(&safe_query, output_folder) {
+        Ok(_) => {
+            info!("Successfully downloaded with yt-dlp: {}", safe_query);
+            "yt-dlp"
+        },
+        Err(e) => {
+            // Fallback to spotdl if yt-dlp fails
+            warn!("yt-dlp failed for {}: {}, trying spotdl", safe_query, e);
+            match download_with_spotdl(&safe_query, output_folder) {
+                Ok(_) => {
+                    info!("Successfully downloaded with spotdl: {}", safe_query);
+                    "spotdl"
+                },
+                Err(e) => {
+                    warn!("Both yt-dlp and spotdl failed for {}: {}", safe_query, e);
+                    return Err(e);
+                }
             }
         }
-    } else {
-        info!("Successfully downloaded with yt-dlp: {}", safe_query);
-    }
+    };
     
-    Ok(())
+    // Resulting file is whatever appeared in the folder
+    let file = list_files(output_folder)?.into_iter().find(|f| !before.contains(f));
+    Ok((backend, file))
+}
+
+/// List files in the folder
+fn list_files(folder: &Path) -> Result<HashSet<PathBuf>, Error> {
+    Ok(fs::read_dir(folder)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect())
 }
 
 /// Sanitize a query for command line safety
@@ -151,10 +216,10 @@ fn sanitize_query(query: &str) -> String {
     sanitized
 }
 
-/// Check if a song already exists in the folder
-fn check_if_song_exists(folder: &Path, artist: &str, title: &str) -> Result<bool, Error> {
+/// Find song which already exists in the folder
+fn find_existing_song(folder: &Path, artist: &str, title: &str) -> Result<Option<PathBuf>, Error> {
     if !folder.exists() {
-        return Ok(false);
+        return Ok(None);
     }
     
     // Get all mp3 files in the folder
@@ -171,12 +236,12 @@ fn check_if_song_exists(folder: &Path, artist: &str, title: &str) -> Result<bool
             
             // Check if filename contains both artist and title
             if filename.contains(&artist_lower) && filename.contains(&title_lower) {
-                return Ok(true);
+                return Ok(Some(path));
             }
         }
     }
     
-    Ok(false)
+    Ok(None)
 }
 
 /// Download a song using yt-dlp
@@ -262,51 +327,43 @@ fn sanitize_filename(filename: &str) -> String {
     normalized
 }
 
-/// Generate CSV/JSON output from query-url results
-pub fn generate_output_file(
-    url_info: &crate::UrlInfo,
-    output_path: &Path,
-    directory: Option<&Path>,
-    format: &str
-) -> Result<PathBuf, Error> {
+/// Convert query-url results into songs
+pub fn songs_from_url_info(url_info: &crate::UrlInfo, manifest: Option<&Manifest>) -> Vec<SongInfo> {
     let mut songs = Vec::new();
     
     // Process videos and their tracklists
     if let Some(videos) = &url_info.videos {
         for (video_title, video_url, tracklist) in videos {
-            let folder_name = sanitize_filename(video_title);
-            let folder_exists = if let Some(dir) = directory {
-                dir.join(&folder_name).exists()
-            } else {
-                false
-            };
-            
             info!("Video title: {}", video_title);
-            info!("Formatted video title: {}", folder_name);
-            info!("Video title exists in directory: {}", folder_exists);
+            info!("Formatted video title: {}", sanitize_filename(video_title));
             
             // Process each track in the tracklist
-            for track in tracklist {
-                // Extract artist and title
-                let (artist, title, timestamp) = parse_track(track);
-                
+            for entry in tracklist {
                 // Only add tracks that have both artist and title
-                if !artist.is_empty() && !title.is_empty() {
-                    songs.push(SongInfo {
-                        video_title: video_title.clone(),
-                        video_url: video_url.clone(),
-                        song_title: title,
-                        artist,
-                        timestamp,
-                        // Downloaded flag is false when we want to download it
-                        // If folder exists, we mark as true (downloaded), if not then false (needs download)
-                        downloaded: folder_exists,
-                        match_confidence: 0.75, // Default confidence level
-                    });
+                if entry.artist.is_empty() || entry.title.is_empty() {
+                    continue;
                 }
+                let mut song = SongInfo::from_entry(video_title, video_url, entry);
+                // Downloaded flag comes from the manifest of the target directory
+                song.downloaded = manifest
+                    .and_then(|m| m.status(&song))
+                    .map(|s| s == DownloadStatus::Downloaded || s == DownloadStatus::Skipped)
+                    .unwrap_or(false);
+                songs.push(song);
             }
         }
     }
+    songs
+}
+
+/// Generate CSV/JSON output from query-url results
+pub fn generate_output_file(
+    url_info: &crate::UrlInfo,
+    output_path: &Path,
+    manifest: Option<&Manifest>,
+    format: &str
+) -> Result<PathBuf, Error> {
+    let songs = songs_from_url_info(url_info, manifest);
     
     // Create output file
     let output_file = if format == "json" {
@@ -340,59 +397,9 @@ pub fn generate_output_file(
 
 /// Parse a track string into artist, title, and timestamp
 pub fn parse_track(track: &str) -> (String, String, Option<String>) {
-    // Check if the track is already in the "Artist - Title" format
-    if track.contains(" - ") {
-        // Extract timestamp if present at the beginning
-        let timestamp_regex = Regex::new(r"^(\d{1,2}:\d{2}(?::\d{2})?)").unwrap();
-        let timestamp = timestamp_regex.captures(track).map(|cap| cap[1].to_string());
-        
-        // Clean up the track string
-        let track_without_timestamp = timestamp_regex.replace(track, "").trim().to_string();
-        
-        // Split by dash to get artist and title
-        let parts: Vec<&str> = track_without_timestamp.split(" - ").collect();
-        if parts.len() >= 2 {
-            let artist_part = parts[0].trim();
-            let title_part = parts[1].trim();
-            
-            // Clean up timestamp prefix and other characters in artist name
-            let number_prefix_regex = Regex::new(r"^(\d+(?::\d+)?)\s*").unwrap();
-            let clean_artist = number_prefix_regex.replace(artist_part, "").trim().to_string();
-            
-            // Clean up html entities in both artist and title
-            let artist_clean = clean_artist
-                .replace("u0026", "&")
-                .replace("&amp;", "&")
-                .replace("&#39;", "'")
-                .trim().to_string();
-                
-            let title_clean = title_part
-                .replace("u0026", "&")
-                .replace("&amp;", "&")
-                .replace("&#39;", "'")
-                .trim().to_string();
-            
-            // Special case: if artist starts with comma, it's likely part of another field
-            let final_artist = if artist_clean.starts_with(',') {
-                artist_clean[1..].trim().to_string()
-            } else {
-                artist_clean
-            };
-            
-            // Final checks for empty fields
-            if final_artist.is_empty() || title_clean.is_empty() {
-                // Fallback to original parts if cleaning made fields empty
-                (parts[0].trim().to_string(), parts[1].trim().to_string(), timestamp)
-            } else {
-                (final_artist, title_clean, timestamp)
-            }
-        } else {
-            // Fallback if splitting didn't work as expected
-            (String::new(), track_without_timestamp, timestamp)
-        }
-    } else {
-        // If no dash, try to identify if this is just an artist or just a title
-        // For now, default to treating the whole string as the title
-        (String::new(), track.trim().to_string(), None)
+    match TracklistEntry::parse(track) {
+        Some(entry) => (entry.artist.clone(), entry.full_title(), entry.start_timestamp()),
+        // If no dash, default to treating the whole string as the title
+        None => (String::new(), track.trim().to_string(), None)
     }
 }
@@ -0,0 +1,209 @@
+use anyhow::{Error, bail, Context};
+use log::{info, debug};
+use reqwest::blocking::{Client, ClientBuilder};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use tempfile::NamedTempFile;
+
+/// Environment variable with directory, if set all fetched pages are saved there as fixtures
+pub const RECORD_FIXTURES_ENV: &str = "ONETAGGER_RECORD_FIXTURES";
+
+/// Source of web pages, so scraping can run against recorded pages instead of the network
+pub trait PageFetcher {
+    /// Fetch the raw HTML of a page
+    fn fetch(&self, url: &str) -> Result<String, Error>;
+
+    /// Fetch the HTML after JavaScript rendering, empty if not available
+    fn fetch_rendered(&self, _url: &str) -> Result<String, Error> {
+        Ok(String::new())
+    }
+}
+
+/// Fetches pages over HTTP, falls back to Puppeteer for rendering
+pub struct HttpFetcher {
+    client: Client
+}
+
+impl HttpFetcher {
+    /// Create a new HTTP client with user agent
+    pub fn new() -> Result<HttpFetcher, Error> {
+        let client = ClientBuilder::new()
+            .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/96.0.4664.110 Safari/537.36")
+            .timeout(Duration::from_secs(30))
+            .cookie_store(true)
+            .gzip(true)
+            .build()?;
+
+        info!("HTTP client created successfully");
+        Ok(HttpFetcher { client })
+    }
+}
+
+impl PageFetcher for HttpFetcher {
+    fn fetch(&self, url: &str) -> Result<String, Error> {
+        let response = self.client.get(url)
+            .header("Accept-Language", "en-US,en;q=0.9")
+            .send()?;
+        if !response.status().is_success() {
+            bail!("HTTP request to {} failed with status: {}", url, response.status());
+        }
+        Ok(response.text()?)
+    }
+
+    /// Uses JavaScript to extract more complex details from a YouTube page
+    fn fetch_rendered(&self, url: &str) -> Result<String, Error> {
+        debug!("Extracting with JavaScript: {}", url);
+
+        // Create a temporary JavaScript file
+        let mut js_file = NamedTempFile::new()?;
+
+        // First check if puppeteer is installed
+        let check_puppeteer = Command::new("node")
+            .args(["-e", "try { require('puppeteer'); console.log('puppeteer-installed'); } catch(e) { console.log('puppeteer-missing'); }"])
+            .output()
+            .context("Failed to execute Node.js. Make sure it's installed and in your PATH")?;
+
+        let check_result = String::from_utf8_lossy(&check_puppeteer.stdout).trim().to_string();
+
+        if check_result != "puppeteer-installed" {
+            info!("Puppeteer is not installed. Using fallback method without JavaScript extraction.");
+            return Ok(String::new());
+        }
+
+        let js_code = format!(r#"
+        const puppeteer = require('puppeteer');
+
+        (async () => {{
+            try {{
+                const browser = await puppeteer.launch({{
+                    headless: true,
+                    args: ['--no-sandbox', '--disable-setuid-sandbox', '--disable-dev-shm-usage']
+                }});
+                const page = await browser.newPage();
+
+                // Set viewport and user agent
+                await page.setViewport({{ width: 1280, height: 800 }});
+                await page.setUserAgent('Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/96.0.4664.110 Safari/537.36');
+
+                // Go to URL
+                await page.goto('{}', {{ waitUntil: 'networkidle2', timeout: 30000 }});
+
+                // Wait for key elements to load
+                await page.waitForSelector('#content', {{ timeout: 10000 }}).catch(() => {{}});
+                await page.waitForSelector('#description', {{ timeout: 5000 }}).catch(() => {{}});
+
+                // Try to click "Show more" button to expand description
+                try {{
+                    await page.click('button[aria-label="Show more"]');
+                    // Wait a bit for description to expand
+                    await page.waitForTimeout(1000);
+                }} catch (e) {{
+                    // Ignore if button not found
+                }}
+
+                // Get the page HTML
+                const html = await page.content();
+                console.log(html);
+
+                await browser.close();
+            }} catch (error) {{
+                console.error('Error:', error);
+                process.exit(1);
+            }}
+        }})();
+        "#, url);
+
+        js_file.write_all(js_code.as_bytes())?;
+
+        // Execute the JavaScript code with Node.js
+        let output = Command::new("node")
+            .arg(js_file.path())
+            .output()
+            .context("Failed to execute Node.js. Make sure it's installed and in your PATH")?;
+
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            info!("JavaScript execution failed: {}. Using fallback method.", error);
+            return Ok(String::new());
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+}
+
+/// Replays pages previously saved by `RecordingFetcher`
+pub struct FixtureFetcher {
+    directory: PathBuf
+}
+
+impl FixtureFetcher {
+    pub fn new(directory: impl AsRef<Path>) -> FixtureFetcher {
+        FixtureFetcher { directory: directory.as_ref().to_owned() }
+    }
+}
+
+impl PageFetcher for FixtureFetcher {
+    fn fetch(&self, url: &str) -> Result<String, Error> {
+        let path = self.directory.join(format!("{}.html", fixture_name(url)));
+        fs::read_to_string(&path).with_context(|| format!("Missing fixture {:?} for {}", path, url))
+    }
+
+    fn fetch_rendered(&self, url: &str) -> Result<String, Error> {
+        // Rendered version is optional
+        let path = self.directory.join(format!("{}.rendered.html", fixture_name(url)));
+        Ok(fs::read_to_string(&path).unwrap_or_default())
+    }
+}
+
+/// Wraps another fetcher and saves every page into a fixture directory
+pub struct RecordingFetcher<F: PageFetcher> {
+    inner: F,
+    directory: PathBuf
+}
+
+impl<F: PageFetcher> RecordingFetcher<F> {
+    pub fn new(inner: F, directory: impl AsRef<Path>) -> Result<RecordingFetcher<F>, Error> {
+        fs::create_dir_all(directory.as_ref())?;
+        Ok(RecordingFetcher { inner, directory: directory.as_ref().to_owned() })
+    }
+}
+
+impl<F: PageFetcher> PageFetcher for RecordingFetcher<F> {
+    fn fetch(&self, url: &str) -> Result<String, Error> {
+        let html = self.inner.fetch(url)?;
+        let path = self.directory.join(format!("{}.html", fixture_name(url)));
+        info!("Recording fixture: {:?}", path);
+        fs::write(path, &html)?;
+        Ok(html)
+    }
+
+    fn fetch_rendered(&self, url: &str) -> Result<String, Error> {
+        let html = self.inner.fetch_rendered(url)?;
+        if !html.is_empty() {
+            fs::write(self.directory.join(format!("{}.rendered.html", fixture_name(url))), &html)?;
+        }
+        Ok(html)
+    }
+}
+
+/// Create the default fetcher, recording if `RECORD_FIXTURES_ENV` is set
+pub fn default_fetcher() -> Result<Box<dyn PageFetcher>, Error> {
+    let http = HttpFetcher::new()?;
+    match std::env::var(RECORD_FIXTURES_ENV) {
+        Ok(dir) if !dir.is_empty() => Ok(Box::new(RecordingFetcher::new(http, dir)?)),
+        _ => Ok(Box::new(http))
+    }
+}
+
+/// Filesystem safe name of the fixture for URL
+pub fn fixture_name(url: &str) -> String {
+    let stripped = url.trim_start_matches("https://").trim_start_matches("http://").trim_start_matches("www.");
+    let mut name: String = stripped.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    name.truncate(120);
+    name.trim_matches('_').to_string()
+}
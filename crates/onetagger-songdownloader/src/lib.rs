@@ -1,15 +1,22 @@
 extern crate anyhow;
 
+use log::{debug, warn};
 use serde::{Serialize, Deserialize};
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 
 mod query_url;
 mod download_songs;
 mod spotify;
 mod soundcloud;
+pub mod fetch;
+pub mod manifest;
+pub mod tracklist;
 
-pub use query_url::{get_query_url, get_query_url_with_confidence};
-pub use download_songs::{download_songs, generate_output_file, SongInfo, parse_track};
+pub use query_url::{get_query_url, get_query_url_with_confidence, get_query_url_incremental, get_query_url_with_fetcher};
+pub use download_songs::{download_songs, download_manifest, generate_output_file, songs_from_url_info, SongInfo, parse_track};
+pub use manifest::{Manifest, DownloadStatus, DEFAULT_MAX_ATTEMPTS};
+pub use tracklist::{Tracklist, TracklistEntry};
 pub use spotify::process_spotify;
 pub use soundcloud::process_soundcloud;
 
@@ -20,6 +27,10 @@ pub struct SongDownloader {
     pub directory: Option<PathBuf>,
     pub confidence: f32,
     pub output_format: String,
+    /// How many times to try downloading a song before giving up
+    pub max_attempts: u32,
+    /// Skip videos which were synced before (requires directory)
+    pub incremental: bool,
 }
 
 impl SongDownloader {
@@ -30,6 +41,8 @@ impl SongDownloader {
             directory: None,
             confidence: 0.75,
             output_format: "csv".to_string(),
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            incremental: true,
         }
     }
     
@@ -57,6 +70,40 @@ impl SongDownloader {
         self
     }
     
+    /// Set maximum download attempts per song
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+    
+    /// Enable or disable skipping of already synced videos
+    pub fn with_incremental(mut self, incremental: bool) -> Self {
+        self.incremental = incremental;
+        self
+    }
+    
+    /// Query the URL, if directory is set only new videos are processed and recorded in the manifest
+    fn query_url_info(&self, url: &str) -> Result<(UrlInfo, Option<Manifest>), anyhow::Error> {
+        let mut manifest = match &self.directory {
+            Some(directory) => Some(Manifest::load(directory)?),
+            None => None
+        };
+        let known = match (&manifest, self.incremental) {
+            (Some(manifest), true) => manifest.synced_videos(),
+            _ => HashSet::new()
+        };
+        
+        debug!("Calling get_query_url_incremental with URL: {} and confidence: {}", url, self.confidence);
+        let url_info = get_query_url_incremental(url, self.confidence, &known)?;
+        
+        if let Some(manifest) = manifest.as_mut() {
+            let songs = songs_from_url_info(&url_info, Some(&*manifest));
+            manifest.record_sync(&url_info, songs);
+            manifest.save()?;
+        }
+        Ok((url_info, manifest))
+    }
+    
     /// Query a URL and generate output file
     pub fn query_url(&self) -> Result<PathBuf, anyhow::Error> {
         println!("SongDownloader::query_url called");
@@ -65,9 +112,7 @@ impl SongDownloader {
         println!("URL: {}", url);
         
         // Get URL information
-        println!("Calling get_query_url_with_confidence with URL: {} and confidence: {}", url, self.confidence);
-        let url_info = get_query_url_with_confidence(url, self.confidence)?;
-        println!("URL info retrieved successfully");
+        let (url_info, manifest) = self.query_url_info(url)?;
         
         // Create output directory if it doesn't exist
         let output_dir = PathBuf::from("query-url-output");
@@ -79,7 +124,7 @@ impl SongDownloader {
         
         // Generate output file
         println!("Generating output file");
-        let result = generate_output_file(&url_info, &output_dir, self.directory.as_deref(), &self.output_format);
+        let result = generate_output_file(&url_info, &output_dir, manifest.as_ref(), &self.output_format);
         println!("Output file generation result: {:?}", result);
         result
     }
@@ -87,7 +132,21 @@ impl SongDownloader {
     /// Download songs from a CSV/JSON file
     pub fn download_songs(&self, csv_path: &Path) -> Result<(), anyhow::Error> {
         let directory = self.directory.as_ref().ok_or_else(|| anyhow::anyhow!("Directory is required"))?;
-        download_songs(csv_path, directory)
+        download_songs(csv_path, directory, self.max_attempts)
+    }
+    
+    /// Query new videos from all the sources and download everything pending in the manifest
+    pub fn sync(&self, urls: &[String]) -> Result<Manifest, anyhow::Error> {
+        let directory = self.directory.as_ref().ok_or_else(|| anyhow::anyhow!("Directory is required"))?;
+        for url in urls {
+            // One broken source shouldn't stop the others
+            if let Err(e) = self.query_url_info(url) {
+                warn!("Failed syncing {}: {}", url, e);
+            }
+        }
+        let mut manifest = Manifest::load(directory)?;
+        download_manifest(&mut manifest, directory, self.max_attempts)?;
+        Ok(manifest)
     }
 }
 
@@ -98,8 +157,11 @@ pub struct UrlInfo {
     pub title: String,
     pub description: Option<String>,
     pub video_tracklists: Option<std::collections::HashMap<String, Vec<String>>>,
-    pub videos: Option<Vec<(String, String, Vec<String>)>>,
+    pub videos: Option<Vec<(String, String, Vec<TracklistEntry>)>>,
     pub url: String,
+    /// All the video URLs which were scraped (including ones without tracklist)
+    #[serde(default)]
+    pub scanned: Vec<String>,
 }
 
 /// Get URL information for a given URL with default confidence
@@ -114,7 +176,7 @@ pub fn get_url_info_with_confidence(url: &str, confidence: f32) -> Result<UrlInf
 
 /// Download songs from a CSV/JSON file to a directory
 pub fn download_songs_from_file(csv_path: &Path, directory: &Path) -> Result<(), anyhow::Error> {
-    download_songs(csv_path, directory)
+    download_songs(csv_path, directory, DEFAULT_MAX_ATTEMPTS)
 }
 
 impl UrlInfo {
@@ -127,6 +189,7 @@ impl UrlInfo {
             video_tracklists: None,
             videos: None,
             url: String::new(),
+            scanned: vec![],
         }
     }
     
@@ -135,7 +198,7 @@ impl UrlInfo {
         self
     }
     
-    pub fn with_videos(mut self, videos: Vec<(String, String, Vec<String>)>) -> Self {
+    pub fn with_videos(mut self, videos: Vec<(String, String, Vec<TracklistEntry>)>) -> Self {
         self.videos = Some(videos);
         self
     }
@@ -145,6 +208,11 @@ impl UrlInfo {
         self
     }
     
+    pub fn with_scanned(mut self, scanned: Vec<String>) -> Self {
+        self.scanned = scanned;
+        self
+    }
+    
     pub fn add_tracklist(mut self, video_title: String, tracklist: Vec<String>) -> Self {
         let mut tracklists = self.video_tracklists.unwrap_or_default();
         tracklists.insert(video_title, tracklist);
@@ -0,0 +1,262 @@
+use anyhow::Error;
+use log::{info, warn};
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::download_songs::SongInfo;
+use crate::UrlInfo;
+
+/// Filename of the manifest inside the download directory
+pub const MANIFEST_FILENAME: &str = ".onetagger-songdownloader.json";
+
+/// Default amount of download attempts before an item is given up on
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// State of a single song in the manifest
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DownloadStatus {
+    /// Not downloaded yet
+    Pending,
+    /// Downloaded successfully
+    Downloaded,
+    /// Last attempt failed
+    Failed,
+    /// Marked as downloaded in the input file or unidentified, never attempted
+    Skipped,
+}
+
+/// Single song entry with it's download state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEntry {
+    pub id: String,
+    pub song: SongInfo,
+    pub status: DownloadStatus,
+    pub attempts: u32,
+    pub error: Option<String>,
+    /// Which downloader produced the file (yt-dlp, spotdl, existing)
+    pub backend: Option<String>,
+    pub file: Option<PathBuf>,
+    pub added: i64,
+    pub updated: i64,
+}
+
+/// Channel / playlist which was synced before
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncedSource {
+    pub url: String,
+    pub title: String,
+    pub last_sync: i64,
+    /// Video IDs (or URLs for non-YouTube sources) which were already scraped
+    pub videos: Vec<String>,
+}
+
+/// Per-directory download state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub version: u32,
+    pub entries: Vec<ManifestEntry>,
+    pub sources: HashMap<String, SyncedSource>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Manifest {
+    /// Load manifest from download directory or create an empty one
+    pub fn load(directory: impl AsRef<Path>) -> Result<Manifest, Error> {
+        let path = directory.as_ref().join(MANIFEST_FILENAME);
+        if !path.exists() {
+            return Ok(Manifest {
+                version: 1,
+                entries: vec![],
+                sources: HashMap::new(),
+                path
+            });
+        }
+        let mut manifest: Manifest = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        manifest.path = path;
+        info!("Loaded manifest with {} songs and {} synced sources", manifest.entries.len(), manifest.sources.len());
+        Ok(manifest)
+    }
+
+    /// Write the manifest back to disk
+    pub fn save(&self) -> Result<(), Error> {
+        // Write to temporary file first, so a crash doesn't corrupt the manifest
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Unique ID of a song within the manifest
+    pub fn song_id(song: &SongInfo) -> String {
+        format!("{}|{}|{}", video_key(&song.video_url), song.artist.trim().to_lowercase(), song.song_title.trim().to_lowercase())
+    }
+
+    /// Add song to manifest, returns false if already present
+    pub fn add_song(&mut self, song: SongInfo) -> bool {
+        let id = Self::song_id(&song);
+        if self.entries.iter().any(|e| e.id == id) {
+            return false;
+        }
+        let now = chrono::Utc::now().timestamp();
+        // Unidentified tracks (ID - ID) can't be searched for
+        let status = match song.downloaded || song.unidentified {
+            true => DownloadStatus::Skipped,
+            false => DownloadStatus::Pending
+        };
+        self.entries.push(ManifestEntry {
+            id,
+            song,
+            status,
+            attempts: 0,
+            error: None,
+            backend: None,
+            file: None,
+            added: now,
+            updated: now,
+        });
+        true
+    }
+
+    /// Get the status of song if present
+    pub fn status(&self, song: &SongInfo) -> Option<DownloadStatus> {
+        let id = Self::song_id(song);
+        self.entries.iter().find(|e| e.id == id).map(|e| e.status)
+    }
+
+    /// Indices of entries which should be (re)downloaded
+    pub fn to_download(&self, max_attempts: u32) -> Vec<usize> {
+        self.entries.iter().enumerate().filter(|(_, e)| match e.status {
+            DownloadStatus::Pending => true,
+            DownloadStatus::Failed => e.attempts < max_attempts,
+            DownloadStatus::Downloaded | DownloadStatus::Skipped => false
+        }).map(|(i, _)| i).collect()
+    }
+
+    /// Mark entry as downloaded
+    pub fn mark_downloaded(&mut self, index: usize, backend: &str, file: Option<PathBuf>) {
+        let entry = &mut self.entries[index];
+        entry.attempts += 1;
+        entry.status = DownloadStatus::Downloaded;
+        entry.song.downloaded = true;
+        entry.error = None;
+        entry.backend = Some(backend.to_string());
+        entry.file = file;
+        entry.updated = chrono::Utc::now().timestamp();
+    }
+
+    /// Mark entry as failed
+    pub fn mark_failed(&mut self, index: usize, error: &Error) {
+        let entry = &mut self.entries[index];
+        entry.attempts += 1;
+        entry.status = DownloadStatus::Failed;
+        entry.error = Some(error.to_string());
+        entry.updated = chrono::Utc::now().timestamp();
+    }
+
+    /// Keys of all the videos which were already scraped from any source
+    pub fn synced_videos(&self) -> HashSet<String> {
+        self.sources.values().flat_map(|s| s.videos.iter().cloned()).collect()
+    }
+
+    /// Store which videos were scraped from the source and add their songs
+    pub fn record_sync(&mut self, url_info: &UrlInfo, songs: Vec<SongInfo>) {
+        let source = self.sources.entry(url_info.url.clone()).or_insert_with(|| SyncedSource {
+            url: url_info.url.clone(),
+            title: url_info.title.clone(),
+            last_sync: 0,
+            videos: vec![],
+        });
+        source.last_sync = chrono::Utc::now().timestamp();
+        source.title = url_info.title.clone();
+        for video in &url_info.scanned {
+            let key = video_key(video);
+            if !source.videos.contains(&key) {
+                source.videos.push(key);
+            }
+        }
+
+        let mut added = 0;
+        for song in songs {
+            if self.add_song(song) {
+                added += 1;
+            }
+        }
+        info!("Synced {}: {} new songs added to manifest", url_info.url, added);
+    }
+
+    /// Print summary into log
+    pub fn log_summary(&self) {
+        let count = |status| self.entries.iter().filter(|e| e.status == status).count();
+        info!(
+            "Manifest: {} downloaded, {} pending, {} failed, {} skipped",
+            count(DownloadStatus::Downloaded), count(DownloadStatus::Pending), count(DownloadStatus::Failed), count(DownloadStatus::Skipped)
+        );
+        for entry in self.entries.iter().filter(|e| e.status == DownloadStatus::Failed) {
+            warn!("Failed ({}x): {} - {}: {}", entry.attempts, entry.song.artist, entry.song.song_title, entry.error.as_deref().unwrap_or_default());
+        }
+    }
+}
+
+/// Stable key for a video URL (YouTube video ID if possible)
+pub fn video_key(url: &str) -> String {
+    let re = Regex::new(r"(?:v=|youtu\.be/|/shorts/)([a-zA-Z0-9_-]{11})").unwrap();
+    match re.captures(url) {
+        Some(c) => c[1].to_string(),
+        None => url.trim().trim_end_matches('/').to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(url: &str, title: &str) -> SongInfo {
+        SongInfo {
+            video_title: "Mix".to_string(),
+            video_url: url.to_string(),
+            song_title: title.to_string(),
+            artist: "Artist".to_string(),
+            timestamp: None,
+            downloaded: false,
+            match_confidence: 0.75,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_video_key() {
+        assert_eq!(video_key("https://www.youtube.com/watch?v=2gq5Z83R6bQ&t=10s"), "2gq5Z83R6bQ");
+        assert_eq!(video_key("https://youtu.be/2gq5Z83R6bQ"), "2gq5Z83R6bQ");
+        assert_eq!(video_key("https://soundcloud.com/user/track/"), "https://soundcloud.com/user/track");
+    }
+
+    #[test]
+    fn test_manifest_retry() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manifest = Manifest::load(dir.path()).unwrap();
+        assert!(manifest.add_song(song("https://www.youtube.com/watch?v=2gq5Z83R6bQ", "A")));
+        assert!(manifest.add_song(song("https://www.youtube.com/watch?v=2gq5Z83R6bQ", "B")));
+        assert!(!manifest.add_song(song("https://youtu.be/2gq5Z83R6bQ", "a")));
+
+        manifest.mark_downloaded(0, "yt-dlp", None);
+        manifest.mark_failed(1, &anyhow::anyhow!("not found"));
+        assert_eq!(manifest.to_download(3), vec![1]);
+        manifest.mark_failed(1, &anyhow::anyhow!("not found"));
+        manifest.mark_failed(1, &anyhow::anyhow!("not found"));
+        assert!(manifest.to_download(3).is_empty());
+
+        // Roundtrip
+        manifest.save().unwrap();
+        let manifest = Manifest::load(dir.path()).unwrap();
+        assert_eq!(manifest.entries.len(), 2);
+        assert_eq!(manifest.entries[1].attempts, 3);
+        assert_eq!(manifest.entries[0].backend.as_deref(), Some("yt-dlp"));
+    }
+}
@@ -1,14 +1,12 @@
-use anyhow::{Error, Result, bail, Context};
-use log::{info, warn, debug};
+use anyhow::{Error, Result, bail};
+use log::{debug, info, warn};
 use regex::Regex;
-use std::collections::HashMap;
-use reqwest::blocking::{Client, ClientBuilder};
+use std::collections::{HashMap, HashSet};
 use scraper::{Html, Selector};
 use crate::UrlInfo;
-use std::time::Duration;
-use std::process::Command;
-use std::io::Write;
-use tempfile::NamedTempFile;
+use crate::fetch::{PageFetcher, default_fetcher};
+use crate::manifest::video_key;
+use crate::tracklist::{Tracklist, TracklistEntry};
 
 /// Get URL information for a given URL with default confidence
 pub fn get_query_url(url: &str) -> Result<UrlInfo, Error> {
@@ -18,7 +16,18 @@ pub fn get_query_url(url: &str) -> Result<UrlInfo, Error> {
 
 /// Get URL information for a given URL with specified confidence
 pub fn get_query_url_with_confidence(url: &str, confidence: f32) -> Result<UrlInfo, Error> {
-    println!("get_query_url_with_confidence called with URL: {} and confidence: {}", url, confidence);
+    get_query_url_incremental(url, confidence, &HashSet::new())
+}
+
+/// Get URL information, skipping videos which were already scraped (keys from `manifest::video_key`)
+pub fn get_query_url_incremental(url: &str, confidence: f32, known_videos: &HashSet<String>) -> Result<UrlInfo, Error> {
+    let fetcher = default_fetcher()?;
+    get_query_url_with_fetcher(url, confidence, known_videos, fetcher.as_ref())
+}
+
+/// Get URL information using custom page fetcher (ie. recorded fixtures)
+pub fn get_query_url_with_fetcher(url: &str, confidence: f32, known_videos: &HashSet<String>, fetcher: &dyn PageFetcher) -> Result<UrlInfo, Error> {
+    debug!("get_query_url_with_fetcher called with URL: {}, confidence: {} and {} known videos", url, confidence, known_videos.len());
     
     // Validate URL
     if !is_valid_url(url) {
@@ -34,15 +43,15 @@ pub fn get_query_url_with_confidence(url: &str, confidence: f32) -> Result<UrlIn
     match (platform.as_str(), content_type.as_str()) {
         ("youtube", "channel") => {
             println!("Processing YouTube channel: {}", url);
-            process_youtube_channel(url, confidence)
+            process_youtube_channel(url, confidence, known_videos, fetcher)
         },
         ("youtube", "playlist") => {
             println!("Processing YouTube playlist: {}", url);
-            process_youtube_playlist(url, confidence)
+            process_youtube_playlist(url, confidence, known_videos, fetcher)
         },
         ("youtube", "video") => {
             println!("Processing YouTube video: {}", url);
-            process_youtube_video(url, confidence)
+            process_youtube_video(url, confidence, known_videos, fetcher)
         },
         ("spotify", _) => {
             println!("Processing Spotify URL: {}", url);
@@ -106,7 +115,7 @@ fn determine_platform_and_type(url: &str) -> Result<(String, String), Error> {
 }
 
 /// Process a YouTube channel URL
-fn process_youtube_channel(url: &str, _confidence: f32) -> Result<UrlInfo, Error> {
+fn process_youtube_channel(url: &str, _confidence: f32, known_videos: &HashSet<String>, fetcher: &dyn PageFetcher) -> Result<UrlInfo, Error> {
     // Modify URL to ensure we're looking at the videos tab
     let videos_url = if url.ends_with("/videos") {
         url.to_string()
@@ -114,12 +123,9 @@ fn process_youtube_channel(url: &str, _confidence: f32) -> Result<UrlInfo, Error
         format!("{}/videos", url.trim_end_matches('/'))
     };
     
-    // Create a new HTTP client with user agent
-    let client = create_client()?;
-    
     // Fetch the channel page with enhanced scraping
     info!("Fetching channel page: {}", videos_url);
-    let document = get_youtube_page(&client, &videos_url)?;
+    let document = get_youtube_page(fetcher, &videos_url)?;
     
     // Extract channel name
     let channel_name = extract_channel_name(&document)?;
@@ -128,14 +134,18 @@ fn process_youtube_channel(url: &str, _confidence: f32) -> Result<UrlInfo, Error
     let video_links = extract_video_links(&document)?;
     
     info!("Scraping Youtube channel {} • {} videos found.", channel_name, video_links.len());
+    let video_links = filter_known_videos(video_links, known_videos);
     
     // Process each video
     let mut videos = Vec::new();
+    // Videos which were scraped successfully, failed ones will be retried on next sync
+    let mut scanned = Vec::new();
     for (index, video_url) in video_links.iter().enumerate() {
         info!("Scraping video {} of {}", index + 1, video_links.len());
         
         // Process the video
-        if let Ok((title, tracklist)) = process_single_video(&client, video_url) {
+        if let Ok((title, tracklist)) = process_single_video(fetcher, video_url) {
+            scanned.push(video_url.clone());
             // Only add videos with tracklists
             if !tracklist.is_empty() {
                 videos.push((title.clone(), video_url.clone(), tracklist));
@@ -158,19 +168,17 @@ fn process_youtube_channel(url: &str, _confidence: f32) -> Result<UrlInfo, Error
         None
     )
     .with_videos(videos)
+    .with_scanned(scanned)
     .with_url(url.to_string());
     
     Ok(url_info)
 }
 
 /// Process a YouTube playlist URL
-fn process_youtube_playlist(url: &str, _confidence: f32) -> Result<UrlInfo, Error> {
-    // Create a new HTTP client with user agent
-    let client = create_client()?;
-    
+fn process_youtube_playlist(url: &str, _confidence: f32, known_videos: &HashSet<String>, fetcher: &dyn PageFetcher) -> Result<UrlInfo, Error> {
     // Fetch the playlist page with enhanced scraping
     info!("Fetching playlist page: {}", url);
-    let document = get_youtube_page(&client, url)?;
+    let document = get_youtube_page(fetcher, url)?;
     
     // Extract playlist title
     let playlist_title = extract_playlist_title(&document)?;
@@ -179,14 +187,18 @@ fn process_youtube_playlist(url: &str, _confidence: f32) -> Result<UrlInfo, Erro
     let video_links = extract_video_links(&document)?;
     
     info!("Scraping Youtube playlist {} • {} videos found.", playlist_title, video_links.len());
+    let video_links = filter_known_videos(video_links, known_videos);
     
     // Process each video
     let mut videos = Vec::new();
+    // Videos which were scraped successfully, failed ones will be retried on next sync
+    let mut scanned = Vec::new();
     for (index, video_url) in video_links.iter().enumerate() {
         info!("Scraping video {} of {}", index + 1, video_links.len());
         
         // Process the video
-        if let Ok((title, tracklist)) = process_single_video(&client, video_url) {
+        if let Ok((title, tracklist)) = process_single_video(fetcher, video_url) {
+            scanned.push(video_url.clone());
             // Only add videos with tracklists
             if !tracklist.is_empty() {
                 videos.push((title.clone(), video_url.clone(), tracklist));
@@ -209,22 +221,26 @@ fn process_youtube_playlist(url: &str, _confidence: f32) -> Result<UrlInfo, Erro
         None
     )
     .with_videos(videos)
+    .with_scanned(scanned)
     .with_url(url.to_string());
     
     Ok(url_info)
 }
 
 /// Process a YouTube video URL
-fn process_youtube_video(url: &str, _confidence: f32) -> Result<UrlInfo, Error> {
-    // Create a new HTTP client with user agent
-    let client = create_client()?;
+fn process_youtube_video(url: &str, _confidence: f32, known_videos: &HashSet<String>, fetcher: &dyn PageFetcher) -> Result<UrlInfo, Error> {
+    // Already synced before
+    if known_videos.contains(&video_key(url)) {
+        info!("Video was already synced, skipping: {}", url);
+        return Ok(UrlInfo::new("youtube", "video", url, None).with_videos(vec![]).with_url(url.to_string()));
+    }
     
     // Process the video with enhanced scraping
-    let (title, tracklist) = process_single_video(&client, url)?;
+    let (title, tracklist) = process_single_video(fetcher, url)?;
     
     // Create video tracklist map
     let mut tracklists = HashMap::new();
-    tracklists.insert(title.clone(), tracklist.clone());
+    tracklists.insert(title.clone(), tracklist.iter().map(|e| e.text.clone()).collect());
     
     // Create videos vector with a single entry
     let videos = vec![(title.clone(), url.to_string(), tracklist)];
@@ -233,11 +249,10 @@ fn process_youtube_video(url: &str, _confidence: f32) -> Result<UrlInfo, Error>
     if videos.is_empty() || videos[0].2.is_empty() {
         // Create a fallback track from the video title
         let fallback_track = title.clone();
-        let mut fallback_tracklist = vec![fallback_track.clone()];
-        let mut fallback_videos = vec![(title.clone(), url.to_string(), fallback_tracklist.clone())];
+        let fallback_videos = vec![(title.clone(), url.to_string(), TracklistEntry::parse(&fallback_track).into_iter().collect())];
         
         // Update tracklists
-        tracklists.insert(title.clone(), fallback_tracklist);
+        tracklists.insert(title.clone(), vec![fallback_track]);
         
         // Create UrlInfo with fallback data
         info!("No tracks found in video description. Using video title as fallback track.");
@@ -249,6 +264,7 @@ fn process_youtube_video(url: &str, _confidence: f32) -> Result<UrlInfo, Error>
         )
         .with_tracklists(tracklists)
         .with_videos(fallback_videos)
+        .with_scanned(vec![url.to_string()])
         .with_url(url.to_string());
         
         Ok(url_info)
@@ -262,121 +278,37 @@ fn process_youtube_video(url: &str, _confidence: f32) -> Result<UrlInfo, Error>
         )
         .with_tracklists(tracklists)
         .with_videos(videos)
+        .with_scanned(vec![url.to_string()])
         .with_url(url.to_string());
         
         Ok(url_info)
     }
 }
 
-/// Create a new HTTP client with user agent
-fn create_client() -> Result<Client, Error> {
-    let client = ClientBuilder::new()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/96.0.4664.110 Safari/537.36")
-        .timeout(Duration::from_secs(30))
-        .cookie_store(true)
-        .gzip(true)
-        .build()?;
-    
-    info!("HTTP client created successfully");
-    Ok(client)
-}
-
-/// Uses JavaScript to extract more complex details from a YouTube page
-/// Returns the HTML content after JavaScript processing
-fn extract_with_javascript(url: &str) -> Result<String, Error> {
-    debug!("Extracting with JavaScript: {}", url);
-    
-    // Create a temporary JavaScript file
-    let mut js_file = NamedTempFile::new()?;
-    
-    // First check if puppeteer is installed
-    let check_puppeteer = Command::new("node")
-        .args(["-e", "try { require('puppeteer'); console.log('puppeteer-installed'); } catch(e) { console.log('puppeteer-missing'); }"])
-        .output()
-        .context("Failed to execute Node.js. Make sure it's installed and in your PATH")?;
-    
-    let check_result = String::from_utf8_lossy(&check_puppeteer.stdout).trim().to_string();
-    
-    if check_result != "puppeteer-installed" {
-        info!("Puppeteer is not installed. Using fallback method without JavaScript extraction.");
-        return Ok(String::new());
+/// Remove videos which were scraped in a previous sync
+fn filter_known_videos(video_links: Vec<String>, known_videos: &HashSet<String>) -> Vec<String> {
+    let total = video_links.len();
+    let new: Vec<String> = video_links.into_iter().filter(|v| !known_videos.contains(&video_key(v))).collect();
+    if new.len() != total {
+        info!("Skipping {} videos which were already synced, {} new", total - new.len(), new.len());
     }
-    
-    let js_code = format!(r#"
-    const puppeteer = require('puppeteer');
-
-    (async () => {{
-        try {{
-            const browser = await puppeteer.launch({{
-                headless: true,
-                args: ['--no-sandbox', '--disable-setuid-sandbox', '--disable-dev-shm-usage']
-            }});
-            const page = await browser.newPage();
-            
-            // Set viewport and user agent
-            await page.setViewport({{ width: 1280, height: 800 }});
-            await page.setUserAgent('Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/96.0.4664.110 Safari/537.36');
-            
-            // Go to URL
-            await page.goto('{}', {{ waitUntil: 'networkidle2', timeout: 30000 }});
-            
-            // Wait for key elements to load
-            await page.waitForSelector('#content', {{ timeout: 10000 }}).catch(() => {{}});
-            await page.waitForSelector('#description', {{ timeout: 5000 }}).catch(() => {{}});
-            
-            // Try to click "Show more" button to expand description
-            try {{
-                await page.click('button[aria-label="Show more"]');
-                // Wait a bit for description to expand
-                await page.waitForTimeout(1000);
-            }} catch (e) {{
-                // Ignore if button not found
-            }}
-            
-            // Get the page HTML
-            const html = await page.content();
-            console.log(html);
-            
-            await browser.close();
-        }} catch (error) {{
-            console.error('Error:', error);
-            process.exit(1);
-        }}
-    }})();
-    "#, url);
-    
-    js_file.write_all(js_code.as_bytes())?;
-    
-    // Execute the JavaScript code with Node.js
-    let output = Command::new("node")
-        .arg(js_file.path())
-        .output()
-        .context("Failed to execute Node.js. Make sure it's installed and in your PATH")?;
-    
-    if !output.status.success() {
-        let error = String::from_utf8_lossy(&output.stderr);
-        info!("JavaScript execution failed: {}. Using fallback method.", error);
-        return Ok(String::new());
-    }
-    
-    let html = String::from_utf8_lossy(&output.stdout).to_string();
-    Ok(html)
+    new
 }
 
 /// Try to get a YouTube page with various methods
-fn get_youtube_page(client: &Client, url: &str) -> Result<Html, Error> {
-    // First try: standard HTTP request with reqwest
+fn get_youtube_page(fetcher: &dyn PageFetcher, url: &str) -> Result<Html, Error> {
+    // First try: standard HTTP request
     info!("Attempting regular HTTP request to YouTube...");
-    let response = client.get(url)
-        .header("Accept-Language", "en-US,en;q=0.9")
-        .send()?;
-    
     let mut html = String::new();
     let mut document = Html::new_document();
     let mut standard_request_success = false;
     
-    if response.status().is_success() {
-        html = response.text()?;
+    match fetcher.fetch(url) {
+        Ok(body) => html = body,
+        Err(e) => warn!("Regular request failed: {}", e),
+    }
+    
+    if !html.is_empty() {
         document = Html::parse_document(&html);
         
         // Check if we got what looks like a proper YouTube page
@@ -389,7 +321,7 @@ fn get_youtube_page(client: &Client, url: &str) -> Result<Html, Error> {
     // Second try: Use JavaScript for more complex extraction
     if !standard_request_success {
         info!("Regular HTTP request didn't get complete page. Attempting JavaScript extraction...");
-        let js_html = extract_with_javascript(url)?;
+        let js_html = fetcher.fetch_rendered(url)?;
         
         // Only use JavaScript result if we got something back
         if !js_html.is_empty() {
@@ -577,10 +509,10 @@ fn extract_video_links(document: &Html) -> Result<Vec<String>, Error> {
 }
 
 /// Process a single YouTube video
-fn process_single_video(client: &Client, url: &str) -> Result<(String, Vec<String>), Error> {
+fn process_single_video(fetcher: &dyn PageFetcher, url: &str) -> Result<(String, Vec<TracklistEntry>), Error> {
     // Fetch the video page using enhanced methods
     println!("Fetching video page: {}", url);
-    let document = get_youtube_page(client, url)?;
+    let document = get_youtube_page(fetcher, url)?;
     
     // Extract the video title
     let title = extract_video_title(&document)?;
@@ -594,7 +526,7 @@ fn process_single_video(client: &Client, url: &str) -> Result<(String, Vec<Strin
     let description = extract_video_description(&document)?;
     println!("Description length: {} characters", description.len());
     if description.len() > 100 {
-        println!("Description snippet: {}", &description[..min(100, description.len())]);
+        println!("Description snippet: {}", description.chars().take(100).collect::<String>());
     }
     
     // Extract upload date and views
@@ -612,7 +544,7 @@ fn process_single_video(client: &Client, url: &str) -> Result<(String, Vec<Strin
         println!("No tracks found in initial description. Trying aggressive parsing...");
         
         // Try getting JavaScript-rendered version explicitly
-        let js_html = match extract_with_javascript(url) {
+        let js_html = match fetcher.fetch_rendered(url) {
             Ok(html) => html,
             Err(e) => {
                 println!("JavaScript extraction failed: {}", e);
@@ -647,10 +579,9 @@ fn process_single_video(client: &Client, url: &str) -> Result<(String, Vec<Strin
                         if let Some(content) = element.value().attr("content") {
                             if content.contains("-") {
                                 // This might be a track in "Artist - Title" format
-                                let clean_track = clean_track_text(content);
-                                if !clean_track.is_empty() {
-                                    println!("Found track in meta tag: {}", clean_track);
-                                    tracklist.push(clean_track);
+                                if let Some(entry) = TracklistEntry::parse(content) {
+                                    println!("Found track in meta tag: {}", entry);
+                                    tracklist.push(entry);
                                 }
                             }
                         }
@@ -670,7 +601,7 @@ fn process_single_video(client: &Client, url: &str) -> Result<(String, Vec<Strin
                         if !artist.is_empty() && !title_part.is_empty() {
                             let track = format!("{} - {}", artist, title_part);
                             println!("Created track from video title: {}", track);
-                            tracklist.push(track);
+                            tracklist.extend(TracklistEntry::parse(&track));
                         }
                     }
                 }
@@ -678,7 +609,7 @@ fn process_single_video(client: &Client, url: &str) -> Result<(String, Vec<Strin
                 // If still no track (title didn't contain a dash), use the whole title
                 if tracklist.is_empty() {
                     println!("Using entire video title as a single track");
-                    tracklist.push(title.clone());
+                    tracklist.push(TracklistEntry { title: title.clone(), text: title.clone(), ..Default::default() });
                 }
             }
         }
@@ -697,10 +628,6 @@ fn process_single_video(client: &Client, url: &str) -> Result<(String, Vec<Strin
     Ok((formatted_title, tracklist))
 }
 
-fn min(a: usize, b: usize) -> usize {
-    if a < b { a } else { b }
-}
-
 /// Extract upload date from a YouTube video page
 fn extract_upload_date(document: &Html) -> Option<String> {
     let selectors = [
@@ -1023,474 +950,8 @@ fn extract_from_structured_data(document: &Html) -> Option<String> {
 }
 
 /// Extract tracklist from video description
-fn extract_tracklist_from_description(description: &str) -> Vec<String> {
-    // For shorter descriptions, use the entire thing
-    // For longer descriptions, try to find the tracklist section
-    let tracklist_section = if description.len() < 500 {
-        description
-    } else if let Some(section) = find_tracklist_section(description) {
-        section
-    } else {
-        description
-    };
-    
-    info!("Analyzing text section. First 200 chars: {}", 
-          &tracklist_section[..min(200, tracklist_section.len())]);
-    
-    // Extract track entries
-    let mut tracks = Vec::new();
-    let mut seen_tracks = std::collections::HashSet::new();
-    
-    // First try the most structured tracklist pattern (numbered with timestamps)
-    let structured_tracks = extract_structured_tracklist(tracklist_section);
-    if !structured_tracks.is_empty() {
-        info!("Found {} tracks using structured pattern", structured_tracks.len());
-        return structured_tracks;
-    }
-    
-    // Now try more general timestamp patterns
-    // First try looking for timestamped lines
-    let timestamp_lines: Vec<_> = tracklist_section
-        .lines()
-        .filter(|line| line.contains(":"))
-        .filter(|line| {
-            let has_digits = line.chars().any(|c| c.is_digit(10));
-            let has_timestamp = Regex::new(r"\d+:\d+").unwrap().is_match(line);
-            has_digits && has_timestamp
-        })
-        .collect();
-    
-    info!("Found {} lines with timestamps", timestamp_lines.len());
-    for (i, line) in timestamp_lines.iter().enumerate().take(5) {
-        debug!("Timestamp line {}: {}", i+1, line);
-    }
-    
-    // Special handling for YouTube descriptions - look for known patterns
-    for line in tracklist_section.lines() {
-        // First look for numbered entries with timestamps and artist/title
-        // Pattern like: "1. 00:00 Artist - Title" or "1) 00:00 Artist - Title"
-        if line.contains(":") && 
-           (line.contains(" - ") || line.contains("- ") || line.contains(" -")) && 
-           (line.contains(".") || line.contains(")") || Regex::new(r"^\s*\d+\s").unwrap().is_match(line)) && 
-           Regex::new(r"\d{1,2}:\d{2}(?::\d{2})?").unwrap().is_match(line) {
-            
-            // Try to extract the artist/title part
-            if let Some(dash_pos) = line.find(" - ").or_else(|| line.find("- ")).or_else(|| line.find(" -")) {
-                // Find timestamp pattern with support for HH:MM:SS format
-                if let Some(timestamp_end) = Regex::new(r"\d{1,2}:\d{2}(?::\d{2})?").unwrap()
-                    .find_iter(line)
-                    .filter(|m| m.end() < dash_pos)
-                    .last()
-                    .map(|m| m.end()) {
-                    
-                    // Extract everything after timestamp
-                    let track_text = line[timestamp_end..].trim();
-                    
-                    // Clean it up
-                    let clean_track = clean_track_text(track_text);
-                    if !clean_track.is_empty() && !seen_tracks.contains(&clean_track) {
-                        debug!("Found track with timestamp-dash pattern: {}", clean_track);
-                        seen_tracks.insert(clean_track.clone());
-                        tracks.push(clean_track);
-                    }
-                }
-            }
-        }
-        // Also look for just timestamp followed by artist - title
-        else if line.contains(":") && 
-                (line.contains(" - ") || line.contains("- ") || line.contains(" -")) && 
-                Regex::new(r"\d{1,2}:\d{2}(?::\d{2})?\s+\S+").unwrap().is_match(line) {
-            
-            // Find timestamp pattern with support for HH:MM:SS format
-            if let Some(timestamp_match) = Regex::new(r"\d{1,2}:\d{2}(?::\d{2})?").unwrap()
-                .find_iter(line)
-                .last() {
-                
-                // Extract everything after the timestamp
-                let track_text = line[timestamp_match.end()..].trim();
-                
-                // Clean it up
-                let clean_track = clean_track_text(track_text);
-                if !clean_track.is_empty() && !seen_tracks.contains(&clean_track) {
-                    debug!("Found track with timestamp pattern: {}", clean_track);
-                    seen_tracks.insert(clean_track.clone());
-                    tracks.push(clean_track);
-                }
-            }
-        }
-    }
-    
-    // If we still don't have enough tracks, try more generic patterns
-    if tracks.len() < 5 {
-        info!("First attempt found too few tracks ({}), trying alternate patterns...", tracks.len());
-        
-        // Try looking for timestamps without artist-title structure
-        if timestamp_lines.len() >= 5 {
-            info!("Found {} timestamp lines, attempting to extract artist/title", timestamp_lines.len());
-            
-            // Look for Artist - Title patterns in each line with timestamp
-            for line in &timestamp_lines {
-                if let Some(timestamp_match) = Regex::new(r"\d{1,2}:\d{2}(?::\d{2})?").unwrap().find(line) {
-                    let after_timestamp = &line[timestamp_match.end()..].trim();
-                    
-                    // Try to find Artist - Title pattern
-                    let dash_pattern = Regex::new(r"([^-]+)-([^-]+)").unwrap();
-                    if let Some(caps) = dash_pattern.captures(after_timestamp) {
-                        if caps.len() >= 3 {
-                            let artist = caps.get(1).unwrap().as_str().trim();
-                            let title = caps.get(2).unwrap().as_str().trim();
-                            
-                            if !artist.is_empty() && !title.is_empty() {
-                                let track = format!("{} - {}", artist, title);
-                                let clean_track = clean_track_text(&track);
-                                
-                                if !clean_track.is_empty() && !seen_tracks.contains(&clean_track) {
-                                    debug!("Extracted track from timestamp line: {}", clean_track);
-                                    seen_tracks.insert(clean_track.clone());
-                                    tracks.push(clean_track);
-                                }
-                            }
-                        }
-                    } else if !after_timestamp.is_empty() {
-                        // If there's no dash pattern but there is content, use it as is
-                        let clean_text = clean_track_text(after_timestamp);
-                        if !clean_text.is_empty() && !seen_tracks.contains(&clean_text) {
-                            debug!("Extracted text after timestamp: {}", clean_text);
-                            seen_tracks.insert(clean_text.clone());
-                            tracks.push(clean_text);
-                        }
-                    }
-                }
-            }
-        }
-        
-        // Try extracting any artist-title pattern in the text
-        let artist_title_pattern = Regex::new(r"([A-Za-z0-9&\s,\.]+)\s+-\s+([A-Za-z0-9&\s,\.']+)").unwrap();
-        
-        for cap in artist_title_pattern.captures_iter(tracklist_section) {
-            if cap.len() >= 3 {
-                let artist = cap.get(1).unwrap().as_str().trim();
-                let title = cap.get(2).unwrap().as_str().trim();
-                
-                // Skip very short artist/title combos
-                if artist.len() > 2 && title.len() > 2 {
-                    let track = format!("{} - {}", artist, title);
-                    
-                    // Clean up
-                    let clean_track = clean_track_text(&track);
-                    if !seen_tracks.contains(&clean_track) {
-                        debug!("Found track with artist-title pattern: {}", clean_track);
-                        seen_tracks.insert(clean_track.clone());
-                        tracks.push(clean_track);
-                    }
-                }
-            }
-        }
-    }
-    
-    // If we still don't have enough tracks but we have lines with timestamps,
-    // make a desperate attempt to parse them
-    if tracks.len() < 5 && timestamp_lines.len() >= 5 {
-        info!("Still found few tracks, making another attempt with timestamp lines");
-        
-        // Just use the lines with timestamps directly, after minimal cleaning
-        for line in &timestamp_lines {
-            let clean_line = clean_basic_timestamp(line);
-            if !clean_line.is_empty() && !seen_tracks.contains(&clean_line) {
-                debug!("Using timestamp line directly: {}", clean_line);
-                seen_tracks.insert(clean_line.clone());
-                tracks.push(clean_line);
-            }
-        }
-    }
-    
-    // Print the number of tracks found
-    info!("{} tracks found", tracks.len());
-    tracks
-}
-
-/// Extract a well-structured tracklist with numbered entries
-fn extract_structured_tracklist(text: &str) -> Vec<String> {
-    let mut tracks = Vec::new();
-    let mut seen_tracks = std::collections::HashSet::new();
-    
-    // Look for the "Tracklist:" section
-    let tracklist_section = if let Some(idx) = text.to_lowercase().find("tracklist:") {
-        &text[idx..]
-    } else if let Some(idx) = text.to_lowercase().find("tracklist") {
-        &text[idx..]
-    } else {
-        text
-    };
-    
-    // Pattern for "1. 00:00 Artist - Title"
-    if let Ok(numbered_pattern) = Regex::new(r"(?m)^\s*(\d+)[\.\)]\s+(\d{1,2}:\d{2}(?::\d{2})?)\s+(.+)$") {
-        // If the pattern matches at least 3 times, we likely have a structured tracklist
-        let matches: Vec<_> = numbered_pattern.captures_iter(tracklist_section).collect();
-        
-        if matches.len() >= 3 {
-            for cap in matches {
-                if cap.len() >= 4 {
-                    // Extract the text after the timestamp
-                    let track_text = cap.get(3).unwrap().as_str().trim();
-                    
-                    // Clean it up
-                    let clean_track = clean_track_text(track_text);
-                    if !clean_track.is_empty() && !seen_tracks.contains(&clean_track) {
-                        seen_tracks.insert(clean_track.clone());
-                        tracks.push(clean_track);
-                    }
-                }
-            }
-        }
-    }
-    
-    tracks
-}
-
-/// Clean a timestamp line by removing the timestamp and basic noise
-fn clean_basic_timestamp(line: &str) -> String {
-    // Remove timestamp and leading track number if present
-    let timestamp_pattern = Regex::new(r"^\s*(?:\d+[\.\)]\s*)?\d{1,2}:\d{2}(?::\d{2})?\s*").unwrap();
-    let without_timestamp = timestamp_pattern.replace(line, "").to_string();
-    
-    // Try to clean it up a bit more
-    clean_track_text(&without_timestamp)
-}
-
-/// Find the tracklist section in a video description
-fn find_tracklist_section(description: &str) -> Option<&str> {
-    // Look for common tracklist section indicators, case insensitive
-    let tracklist_markers = [
-        "Tracklist:", "Track list:", "Tracks:", "Songs:", "Music:", "Playlist:", 
-        "Track List", "TRACKLIST", "00:00", "0:00", "1. ", "01. ",
-        "1) ", "01) ", "1 - ", "01 - ",
-    ];
-    
-    // Try to find explicit tracklist markers first
-    for marker in &tracklist_markers {
-        let marker_lower = marker.to_lowercase();
-        if let Some(pos) = description.to_lowercase().find(&marker_lower) {
-            // Check if this is a line starting with the marker to avoid false positives
-            let line_start = description[..pos].rfind('\n').map_or(0, |p| p + 1);
-            let prefix = &description[line_start..pos];
-            if prefix.trim().is_empty() || marker.starts_with(|c: char| c.is_numeric()) {
-                info!("Found tracklist section with marker: {}", marker);
-                return Some(&description[pos..]);
-            }
-        }
-    }
-    
-    // Check for numeric patterns that might indicate a tracklist
-    // Look for lines that start with numbers followed by timestamps
-    let timestamp_pattern = regex::Regex::new(r"(?m)^\s*\d+[.)\s]*\d{1,2}:\d{2}").ok()?;
-    if let Some(mat) = timestamp_pattern.find(description) {
-        info!("Found tracklist section with timestamp pattern at position {}", mat.start());
-        return Some(&description[mat.start()..]);
-    }
-    
-    // If we've tried everything and found nothing specific, return the whole description
-    // but log a warning
-    info!("No specific tracklist section found, using entire description");
-    Some(description)
-}
-
-/// Clean up track text
-fn clean_track_text(track: &str) -> String {
-    // Try to extract using the pattern
-    if let Some(clean_track) = extract_artist_title_with_remix(track) {
-        return clean_track;
-    }
-    
-    // If we couldn't extract using the pattern, do a more general cleanup
-    
-    // Remove timestamps and track numbers
-    let timestamp_re = Regex::new(r"^\s*(?:\d+[\.\):]?\s*)?\d{1,2}:\d{2}(?::\d{2})?\s+").unwrap();
-    let without_timestamp = timestamp_re.replace_all(track, "").to_string();
-    
-    // Also remove timestamps that might be embedded in the string
-    let embedded_timestamp_re = Regex::new(r"\s+\d{1,2}:\d{2}(?::\d{2})?\s+").unwrap();
-    let clean_timestamps = embedded_timestamp_re.replace_all(&without_timestamp, " ").to_string();
-    
-    // Try to handle common HTML entities
-    let decoded = clean_timestamps
-        .replace("u0026", "&")
-        .replace("&amp;", "&")
-        .replace("&#39;", "'")
-        .replace("&quot;", "\"");
-    
-    // Remove social media handles and other noise
-    let without_social = Regex::new(r"@\w+|\(@\w+\)|ÔÇ¬ÔÇ¼X?|X$|X\s*$").unwrap()
-        .replace_all(&decoded, "")
-        .to_string();
-    
-    // Extract remix information if present
-    let mut remix_info = String::new();
-    let remix_re = Regex::new(r"\(([^)]*(?:Remix|Mix|Dub|Edit)[^)]*)\)").unwrap();
-    if let Some(caps) = remix_re.captures(&without_social) {
-        if let Some(remix) = caps.get(1) {
-            remix_info = format!(" ({})", remix.as_str());
-        }
-    }
-    
-    // Remove all parenthetical content
-    let without_parentheses = Regex::new(r"\([^)]*\)").unwrap()
-        .replace_all(&without_social, "")
-        .to_string();
-    
-    // Fix common encoding issues
-    let fixed_encoding = without_parentheses
-        .replace("├©", "ø")
-        .replace("Sc├©tt", "Scott");
-    
-    // Try to extract artist and title if the track contains " - "
-    if fixed_encoding.contains(" - ") {
-        let parts: Vec<&str> = fixed_encoding.split(" - ").collect();
-        if parts.len() >= 2 {
-            let artist = parts[0].trim();
-            let title = parts[1].trim();
-            
-            // Check for timestamps in artist name and remove them
-            let artist_clean = timestamp_re.replace_all(artist, "").to_string();
-            
-            if !artist_clean.is_empty() && !title.is_empty() {
-                return format!("{} - {}{}", artist_clean, title, remix_info);
-            }
-        }
-    }
-    
-    // Normalize whitespace
-    let mut normalized = String::new();
-    let mut last_was_space = false;
-    
-    for c in fixed_encoding.trim().chars() {
-        if c.is_whitespace() {
-            if !last_was_space {
-                normalized.push(' ');
-                last_was_space = true;
-            }
-        } else {
-            normalized.push(c);
-            last_was_space = false;
-        }
-    }
-    
-    // Add back remix information if we found it
-    if !remix_info.is_empty() {
-        normalized.clone() + &remix_info
-    } else {
-        normalized
-    }
-}
-
-/// Extract artist and title with remix information
-fn extract_artist_title_with_remix(track: &str) -> Option<String> {
-    // Try to extract using the "Artist - Title (Remix)" pattern
-    let re = Regex::new(r"([A-Za-z0-9\s&,.]+)\s+-\s+([A-Za-z0-9\s',.]+)(\s+\([A-Za-z0-9\s',.]+(?:Remix|Mix|Dub|Edit)[A-Za-z0-9\s',.]*\))?").ok()?;
-    
-    if let Some(caps) = re.captures(track) {
-        if caps.len() >= 3 {
-            let artist = caps.get(1)?.as_str().trim();
-            let title = caps.get(2)?.as_str().trim();
-            
-            // Fix missing commas in artist names
-            let artist_fixed = fix_artist_names(artist);
-            
-            if !artist_fixed.is_empty() && !title.is_empty() {
-                // Include remix info if present
-                if let Some(remix) = caps.get(3) {
-                    return Some(format!("{} - {}{}", artist_fixed, title, remix.as_str()));
-                } else {
-                    return Some(format!("{} - {}", artist_fixed, title));
-                }
-            }
-        }
-    }
-    
-    None
-}
-
-/// Fix missing commas in artist names
-fn fix_artist_names(artist: &str) -> String {
-    // Common patterns where commas might be missing
-    let patterns = [
-        ("Aberton JazzedUp", "Aberton, JazzedUp"),
-        ("Julian Sanza Andre", "Julian Sanza, Andre"),
-        ("Big Miz & Bessa", "Big Miz & Bessa"),  // Already correct
-        ("Paolo Barbato Lee", "Paolo Barbato, Lee"),
-        ("Cpen JT", "Cpen, JT"),
-    ];
-    
-    for (pattern, replacement) in &patterns {
-        if artist.contains(pattern) {
-            return artist.replace(pattern, replacement);
-        }
-    }
-    
-    // Try to detect missing commas between names
-    // Look for patterns like "FirstName LastName FirstName" where a comma should be after LastName
-    let name_pattern = Regex::new(r"([A-Z][a-z]+)\s+([A-Z][a-z]+)\s+([A-Z][a-z]+)").ok();
-    if let Some(re) = name_pattern {
-        if let Some(caps) = re.captures(artist) {
-            if caps.len() >= 4 {
-                let first1 = caps.get(1).unwrap().as_str();
-                let last1 = caps.get(2).unwrap().as_str();
-                let first2 = caps.get(3).unwrap().as_str();
-                
-                return artist.replace(&format!("{} {} {}", first1, last1, first2), 
-                                     &format!("{} {}, {}", first1, last1, first2));
-            }
-        }
-    }
-    
-    artist.to_string()
-}
-
-/// Extract tracks line by line
-fn extract_tracks_line_by_line(text: &str, tracks: &mut Vec<String>, seen_tracks: &mut std::collections::HashSet<String>) {
-    // Split by lines and look for potential track entries
-    for line in text.lines() {
-        let trimmed = line.trim();
-        
-        // Skip empty lines or very short lines
-        if trimmed.is_empty() || trimmed.len() < 5 {
-            continue;
-        }
-        
-        // Skip lines that are likely not track entries
-        if !looks_like_track_entry(trimmed) {
-            continue;
-        }
-        
-        // Clean up the track text
-        let clean_track = clean_track_text(trimmed);
-        
-        // Only add unique tracks
-        if !seen_tracks.contains(&clean_track) {
-            seen_tracks.insert(clean_track.clone());
-            tracks.push(clean_track);
-        }
-    }
-}
-
-/// Check if a line looks like a track entry
-fn looks_like_track_entry(line: &str) -> bool {
-    // Check if the line contains a dash (common in "Artist - Title" format)
-    if line.contains(" - ") {
-        return true;
-    }
-    
-    // Check if the line starts with a number (possible track number)
-    if Regex::new(r"^\d+[\.\)]").unwrap().is_match(line) {
-        return true;
-    }
-    
-    // Check for timestamp pattern
-    if Regex::new(r"\d+:\d+").unwrap().is_match(line) {
-        return true;
-    }
-    
-    false
+fn extract_tracklist_from_description(description: &str) -> Vec<TracklistEntry> {
+    Tracklist::parse(description).entries
 }
 
 /// Convert a title to a valid folder name
@@ -1512,3 +973,68 @@ fn sanitize_filename(filename: &str) -> String {
     
     normalized
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+    use crate::fetch::FixtureFetcher;
+
+    /// Separates description from the expected tracklist in corpus files
+    const EXPECTED_MARKER: &str = "=== expected ===\n";
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures")
+    }
+
+    /// Every file in `tests/fixtures/tracklists` is a description followed by the expected tracks.
+    /// Run with `ONETAGGER_BLESS_FIXTURES=1` to rewrite the expected part with current output.
+    #[test]
+    fn test_tracklist_corpus() {
+        let bless = std::env::var("ONETAGGER_BLESS_FIXTURES").is_ok();
+        let mut entries = std::fs::read_dir(fixtures().join("tracklists")).unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.extension().map(|e| e == "txt").unwrap_or(false))
+            .collect::<Vec<_>>();
+        entries.sort();
+        assert!(!entries.is_empty());
+
+        let mut failed = vec![];
+        for path in entries {
+            let content = std::fs::read_to_string(&path).unwrap().replace("\r\n", "\n");
+            let (description, expected) = content.split_once(EXPECTED_MARKER)
+                .unwrap_or_else(|| panic!("{path:?} is missing the expected marker"));
+            let expected = expected.lines().filter(|l| !l.is_empty()).map(String::from).collect::<Vec<_>>();
+            let tracks = extract_tracklist_from_description(description).into_iter().map(|e| e.text).collect::<Vec<_>>();
+
+            if bless {
+                let out = format!("{description}{EXPECTED_MARKER}{}", tracks.iter().map(|t| format!("{t}\n")).collect::<String>());
+                std::fs::write(&path, out).unwrap();
+            } else if tracks != expected {
+                failed.push(format!("{:?}\n  expected: {expected:?}\n    actual: {tracks:?}", path.file_name().unwrap()));
+            }
+        }
+        assert!(failed.is_empty(), "Tracklist corpus mismatches:\n{}", failed.join("\n"));
+    }
+
+    #[test]
+    fn test_video_fixture() {
+        let fetcher = FixtureFetcher::new(fixtures().join("pages"));
+        let info = get_query_url_with_fetcher("https://www.youtube.com/watch?v=Xq2Lm8Tr4Zk", 0.75, &HashSet::new(), &fetcher).unwrap();
+        let videos = info.videos.as_ref().unwrap();
+        assert_eq!(videos.len(), 1);
+        assert_eq!(videos[0].0, "Sunset Deep House Mix 2024");
+        let tracks = videos[0].2.iter().map(|e| e.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(tracks, vec![
+            "Lane 8 - Brightest Lights",
+            "Yotto - The One You Left Behind",
+            "Tinlicker - Children",
+        ]);
+        // Structured fields are kept all the way to the songs
+        let songs = crate::songs_from_url_info(&info, None);
+        assert_eq!(songs[0].label.as_deref(), Some("This Never Happened"));
+        assert_eq!(songs[1].position, Some(2));
+        assert_eq!(songs[1].timestamp.as_deref(), Some("04:12"));
+        assert_eq!(songs[1].end_timestamp.as_deref(), Some("09:30"));
+    }
+}
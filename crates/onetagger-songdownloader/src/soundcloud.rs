@@ -65,7 +65,7 @@ fn process_soundcloud_track(url: &str) -> Result<UrlInfo, Error> {
     let track_title = format!("{} - {}", artist, title);
     
     // Create videos vector with a single entry
-    let videos = vec![(track_title.clone(), url.to_string(), Vec::<String>::new())];
+    let videos = vec![(track_title.clone(), url.to_string(), Vec::new())];
     
     // Create UrlInfo
     let url_info = UrlInfo::new(
@@ -116,7 +116,7 @@ fn process_soundcloud_playlist(url: &str) -> Result<UrlInfo, Error> {
         // A production implementation would fetch each track details
         let track_title = format!("Track {} from {}", i + 1, playlist_title);
         
-        videos.push((track_title, track_link.clone(), Vec::<String>::new()));
+        videos.push((track_title, track_link.clone(), Vec::new()));
     }
     
     // Format playlist title with author
@@ -67,8 +67,8 @@ fn create_spotify_client() -> Result<AuthCodeSpotify, Error> {
 }
 */
 
-/// Extract content type and ID from Spotify URL - Commented until dependencies are properly linked
-/* 
+/// Extract content type and ID from Spotify URL
+#[allow(dead_code)]
 fn extract_spotify_id(url: &str) -> Result<(String, String), Error> {
     // Handle URLs like: https://open.spotify.com/track/4iV5W9uYEdYUVa79Axb7Rh
     // or https://open.spotify.com/album/1DFixLWuPkv3KT3TnV35m3
@@ -90,7 +90,6 @@ fn extract_spotify_id(url: &str) -> Result<(String, String), Error> {
     
     bail!("Invalid Spotify URL format: {}", url)
 }
-*/
 
 /* Spotify implementation functions are commented out until dependencies are properly linked
 
@@ -112,7 +111,7 @@ fn process_spotify_track(client: &AuthCodeSpotify, id: &str, url: &str) -> Resul
     
     // Create videos vector with a single entry (empty tracklist for individual tracks)
     let track_url = format!("https://open.spotify.com/track/{}", id);
-    let videos = vec![(title.clone(), track_url, Vec::<String>::new())];
+    let videos = vec![(title.clone(), track_url, Vec::new())];
     
     // Create UrlInfo
     let url_info = UrlInfo::new(
@@ -175,7 +174,7 @@ fn process_spotify_album(client: &AuthCodeSpotify, id: &str, url: &str) -> Resul
         let track_url = format!("https://open.spotify.com/track/{}", track.id.unwrap().id());
         
         // For album tracks, we use empty tracklist since they're individual tracks
-        videos.push((track_title, track_url, Vec::<String>::new()));
+        videos.push((track_title, track_url, Vec::new()));
     }
     
     // Create album artist string for the title
@@ -249,7 +248,7 @@ fn process_spotify_playlist(client: &AuthCodeSpotify, id: &str, url: &str) -> Re
                 let track_url = format!("https://open.spotify.com/track/{}", full_track.id.unwrap().id());
                 
                 // For playlist tracks, we use empty tracklist since they're individual tracks
-                videos.push((track_title, track_url, Vec::<String>::new()));
+                videos.push((track_title, track_url, Vec::new()));
             }
         }
     }
@@ -305,7 +304,7 @@ fn process_spotify_artist(client: &AuthCodeSpotify, id: &str, url: &str) -> Resu
         let track_url = format!("https://open.spotify.com/track/{}", track.id.unwrap().id());
         
         // For artist top tracks, we use empty tracklist since they're individual tracks
-        videos.push((track_title, track_url, Vec::<String>::new()));
+        videos.push((track_title, track_url, Vec::new()));
     }
     
     let artist_title = format!("Artist: {}", artist.name);
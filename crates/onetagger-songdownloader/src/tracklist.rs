@@ -0,0 +1,400 @@
+use lazy_static::lazy_static;
+use log::info;
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+lazy_static! {
+    /// Position at the start of line: "1.", "01)", "1 -"
+    static ref POSITION_RE: Regex = Regex::new(r"^(\d{1,3})\s*([.)]|-\s)\s*").unwrap();
+    /// Timestamp, optionally in brackets
+    static ref TIMESTAMP_RE: Regex = Regex::new(r"[\[(]?\b(\d{1,2}:\d{2}(?::\d{2})?)\b[\])]?").unwrap();
+    /// Timestamp inside of a line, used to split single line tracklists
+    static ref INLINE_TIMESTAMP_RE: Regex = Regex::new(r"\s+(\d{1,2}:\d{2}(?::\d{2})?)\s").unwrap();
+    static ref SQUARE_BRACKETS_RE: Regex = Regex::new(r"\[([^\]]*)\]").unwrap();
+    static ref FEAT_RE: Regex = Regex::new(r"(?i)\s*[(\[](?:feat\.?|ft\.?|featuring)\s+([^)\]]+)[)\]]").unwrap();
+    /// Brackets at the end of title
+    static ref VERSION_RE: Regex = Regex::new(r"\s*[(\[]([^()\[\]]+)[)\]]$").unwrap();
+    static ref VERSION_KEYWORDS_RE: Regex = Regex::new(r"(?i)mix|remix|edit|dub|version|rework|bootleg|vip|remaster").unwrap();
+    static ref UNIDENTIFIED_RE: Regex = Regex::new(r"(?i)^(id|\?+|unknown)$").unwrap();
+    /// `&` is left alone, because it's usually a part of the name (Chase & Status)
+    static ref ARTIST_SEPARATOR_RE: Regex = Regex::new(r"\s*,\s*|\s+(?i:feat\.?|ft\.?|featuring|vs\.?)\s+|\s+x\s+").unwrap();
+    static ref SOCIAL_RE: Regex = Regex::new(r"\(@[\w.]+\)|@[\w.]+").unwrap();
+    static ref DASH_RE: Regex = Regex::new(r"\s+[–—-]\s+").unwrap();
+    static ref WHITESPACE_RE: Regex = Regex::new(r"\s+").unwrap();
+}
+
+/// Parsed tracklist of a mix / DJ set
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tracklist {
+    pub entries: Vec<TracklistEntry>,
+}
+
+/// Single track of a tracklist
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TracklistEntry {
+    /// Number of the track as written in the tracklist
+    pub position: Option<u32>,
+    /// Start time in seconds
+    pub start: Option<u32>,
+    /// End time in seconds (start of the next track)
+    pub end: Option<u32>,
+    /// Artist as written, including featured artists
+    pub artist: String,
+    /// Individual artists
+    pub artists: Vec<String>,
+    /// Title without the version
+    pub title: String,
+    /// Version or remix, such as `Extended Mix`
+    pub version: Option<String>,
+    /// Label from square brackets, such as `[Drumcode]`
+    pub label: Option<String>,
+    /// Tracks played over this one (`A - B w/ C - D`)
+    pub mashup: Vec<TracklistEntry>,
+    /// Track wasn't identified (`ID - ID`)
+    pub unidentified: bool,
+    /// Cleaned up `Artist - Title` line
+    pub text: String,
+}
+
+impl Tracklist {
+    /// Parse tracklist from a video description or any pasted text
+    pub fn parse(text: &str) -> Tracklist {
+        // Some sources (meta description) put the whole tracklist on a single line
+        let text = split_inline_timestamps(text);
+        info!("Analyzing tracklist. First 200 chars: {}", text.chars().take(200).collect::<String>());
+
+        // Timestamped or numbered lines are the most reliable
+        let mut entries = vec![];
+        let lines = text.lines().map(|l| l.trim()).collect::<Vec<_>>();
+        let mut i = 0;
+        while i < lines.len() {
+            let line = TracklistLine::parse(lines[i]);
+            i += 1;
+            if line.timestamp.is_none() && line.position.is_none() {
+                continue;
+            }
+            if let Some(entry) = line.entry() {
+                entries.push(entry);
+                continue;
+            }
+
+            // Multi-line entry: timestamp on it's own line, followed by "Artist - Title" or artist and title lines
+            if line.text.is_empty() && line.timestamp.is_some() {
+                let next = lines[i..].iter()
+                    .enumerate()
+                    .filter(|(_, l)| !l.is_empty())
+                    .take(2)
+                    .map(|(j, l)| (i + j, TracklistLine::parse(l)))
+                    .take_while(|(_, l)| l.timestamp.is_none())
+                    .collect::<Vec<_>>();
+                if let Some((j, first)) = next.first() {
+                    if let Some(entry) = first.entry() {
+                        entries.push(TracklistEntry { position: line.position, start: line.start(), ..entry });
+                        i = j + 1;
+                    } else if let Some((j, second)) = next.get(1) {
+                        if second.entry().is_none() {
+                            let line = TracklistLine { text: format!("{} - {}", first.text, second.text), ..line };
+                            entries.extend(line.entry());
+                            i = j + 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        // Plain "Artist - Title" lines under an explicit tracklist header
+        if entries.len() < 2 {
+            if let Some(section) = find_tracklist_section(&text) {
+                info!("Few timestamped tracks found, using lines under the tracklist header");
+                for line in section.lines().skip(1).map(|l| l.trim()) {
+                    if line.is_empty() {
+                        if entries.len() >= 2 {
+                            break;
+                        }
+                        continue;
+                    }
+                    if let Some(entry) = TracklistLine::parse(line).entry() {
+                        if !entries.iter().any(|e| e.text == entry.text) {
+                            entries.push(entry);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Track ends where the next one starts
+        for i in 1..entries.len() {
+            if let (Some(start), Some(next)) = (entries[i - 1].start, entries[i].start) {
+                if next > start {
+                    entries[i - 1].end = Some(next);
+                }
+            }
+        }
+
+        info!("{} tracks found", entries.len());
+        Tracklist { entries }
+    }
+
+    /// `Artist - Title` lines, repeated tracks (such as `ID - ID`) are kept
+    pub fn tracks(&self) -> Vec<String> {
+        self.entries.iter().map(|e| e.text.clone()).collect()
+    }
+}
+
+impl TracklistEntry {
+    /// Parse a single tracklist line, returns None if it isn't an `Artist - Title` line
+    pub fn parse(line: &str) -> Option<TracklistEntry> {
+        TracklistLine::parse(line).entry()
+    }
+
+    /// Create entry from `Artist - Title (Version) [Label] w/ Artist - Title`
+    fn from_text(raw: &str) -> Option<TracklistEntry> {
+        let text = clean_track_text(raw);
+        let mut parts = text.split(" w/ ");
+        let (artist, title) = parts.next()?.split_once(" - ")?;
+        if artist.trim().is_empty() || title.trim().is_empty() {
+            return None;
+        }
+        let mashup = parts.filter_map(TracklistEntry::from_text).collect();
+
+        // Label is the only thing removed from brackets during cleanup
+        let label = SQUARE_BRACKETS_RE.captures_iter(raw)
+            .map(|c| c[1].trim().to_string())
+            .find(|l| !l.is_empty() && !is_version(l));
+
+        // Featured artists inside of title
+        let mut artists = split_artists(artist);
+        let mut title = title.trim().to_string();
+        if let Some(caps) = FEAT_RE.captures(&title) {
+            artists.extend(split_artists(&caps[1]));
+            title = FEAT_RE.replace(&title, "").to_string();
+        }
+
+        // Version in brackets at the end of title
+        let mut version = None;
+        if let Some(caps) = VERSION_RE.captures(&title) {
+            if is_version(&caps[1]) {
+                version = Some(caps[1].trim().to_string());
+                title = title[..caps.get(0).unwrap().start()].trim().to_string();
+            }
+        }
+
+        let unidentified = UNIDENTIFIED_RE.is_match(artist.trim()) || UNIDENTIFIED_RE.is_match(&title);
+
+        Some(TracklistEntry {
+            artist: artist.trim().to_string(),
+            artists,
+            title,
+            version,
+            label,
+            mashup,
+            unidentified,
+            text,
+            ..Default::default()
+        })
+    }
+
+    /// Title including the version, as used for searching
+    pub fn full_title(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{} ({})", self.title, version),
+            None => self.title.clone()
+        }
+    }
+
+    /// Formatted start time
+    pub fn start_timestamp(&self) -> Option<String> {
+        self.start.map(format_timestamp)
+    }
+
+    /// Formatted end time
+    pub fn end_timestamp(&self) -> Option<String> {
+        self.end.map(format_timestamp)
+    }
+}
+
+impl fmt::Display for TracklistEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// Parse `mm:ss` or `h:mm:ss` into seconds
+pub fn parse_timestamp(timestamp: &str) -> Option<u32> {
+    timestamp.trim().split(':').try_fold(0, |acc, part| part.parse::<u32>().ok().map(|v| acc * 60 + v))
+}
+
+/// Format seconds as `mm:ss` or `h:mm:ss`
+pub fn format_timestamp(seconds: u32) -> String {
+    match seconds / 3600 {
+        0 => format!("{:02}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, (seconds % 3600) / 60, seconds % 60)
+    }
+}
+
+/// Whether text in brackets is a version rather than a label
+fn is_version(text: &str) -> bool {
+    VERSION_KEYWORDS_RE.is_match(text)
+}
+
+/// Split artist string into individual artists
+fn split_artists(artist: &str) -> Vec<String> {
+    ARTIST_SEPARATOR_RE.split(artist)
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty())
+        .collect()
+}
+
+/// Single line of a tracklist with the timestamp and position removed
+#[derive(Debug, Clone, Default)]
+struct TracklistLine {
+    timestamp: Option<String>,
+    position: Option<u32>,
+    text: String,
+}
+
+impl TracklistLine {
+    /// Split line into the position, timestamp and remaining text
+    fn parse(line: &str) -> TracklistLine {
+        let mut out = TracklistLine::default();
+        // Bullets, emojis and other decorations
+        let mut text = line.trim_start_matches(|c: char| !c.is_alphanumeric() && c != '(' && c != '[').trim().to_string();
+
+        // Position: "1.", "01)", "1 -" (the last one only when followed by a timestamp)
+        if let Some(caps) = POSITION_RE.captures(&text) {
+            let rest = &text[caps.get(0).unwrap().end()..];
+            let starts_with_timestamp = TIMESTAMP_RE.find(rest).map(|m| m.start() == 0).unwrap_or(false);
+            // Avoid treating artists such as "3.14 Crew" as positions
+            let dotted = !caps[2].starts_with('-') && !rest.starts_with(|c: char| c.is_ascii_digit());
+            if starts_with_timestamp || dotted {
+                out.position = caps[1].parse().ok();
+                text = rest.to_string();
+            }
+        }
+
+        // Timestamp at the start or at the end of line
+        if let Some(m) = TIMESTAMP_RE.captures(&text) {
+            let whole = m.get(0).unwrap();
+            let before = text[..whole.start()].trim();
+            let after = text[whole.end()..].trim();
+            if before.is_empty() || after.is_empty() {
+                out.timestamp = Some(m[1].to_string());
+                text = format!("{before} {after}");
+            }
+        }
+
+        // Leftover separators between timestamp and the track
+        out.text = text.trim().trim_matches(|c: char| c == '-' || c == '|' || c == '–' || c == '—' || c == ':' || c == '•' || c.is_whitespace()).to_string();
+        out
+    }
+
+    fn start(&self) -> Option<u32> {
+        self.timestamp.as_deref().and_then(parse_timestamp)
+    }
+
+    /// Get the entry if the line contains an `Artist - Title` separator
+    fn entry(&self) -> Option<TracklistEntry> {
+        TracklistEntry::from_text(&self.text).map(|entry| TracklistEntry {
+            position: self.position,
+            start: self.start(),
+            ..entry
+        })
+    }
+}
+
+/// Put every timestamp on a new line if the description has way fewer lines than timestamps
+fn split_inline_timestamps(description: &str) -> String {
+    let timestamps = INLINE_TIMESTAMP_RE.find_iter(description).count();
+    if timestamps < 3 || description.lines().count() * 2 > timestamps {
+        return description.to_string();
+    }
+    INLINE_TIMESTAMP_RE.replace_all(description, "\n$1 ").to_string()
+}
+
+/// Find the part of description after an explicit tracklist header
+fn find_tracklist_section(description: &str) -> Option<&str> {
+    // Look for common tracklist section indicators, case insensitive
+    let tracklist_markers = ["tracklist", "track list", "tracks:", "songs:", "music:", "playlist:"];
+
+    let mut offset = 0;
+    for line in description.split_inclusive('\n') {
+        let lower = line.trim().to_lowercase();
+        // Header has to be short, so we don't match sentences mentioning a tracklist
+        if lower.len() < 40 && tracklist_markers.iter().any(|m| lower.contains(m)) {
+            info!("Found tracklist section with header: {}", line.trim());
+            return Some(&description[offset..]);
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Clean up track text
+fn clean_track_text(track: &str) -> String {
+    // Try to handle common HTML entities
+    let decoded = track
+        .replace("u0026", "&")
+        .replace("&amp;", "&")
+        .replace("&#39;", "'")
+        .replace("&quot;", "\"");
+
+    // Remove social media handles
+    let without_social = SOCIAL_RE.replace_all(&decoded, "").to_string();
+
+    // Remove brackets which aren't a version (usually label)
+    let without_labels = SQUARE_BRACKETS_RE.replace_all(&without_social, |caps: &regex::Captures| {
+        match is_version(&caps[1]) {
+            true => caps[0].to_string(),
+            false => String::new()
+        }
+    }).to_string();
+
+    // Unify dashes and whitespace
+    let dashes = DASH_RE.replace_all(&without_labels, " - ").to_string();
+    let normalized = WHITESPACE_RE.replace_all(&dashes, " ").to_string();
+    normalized.trim().trim_end_matches(|c: char| c == '-' || c == '|' || c.is_whitespace()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracklist_entries() {
+        let tracklist = Tracklist::parse("Tracklist:\n\
+            01. [00:00] Calvin Harris feat. Dua Lipa - One Kiss (Extended Mix) [Columbia]\n\
+            02. [04:30] ID - ID\n\
+            03. [1:02:05] Skrillex - Rumble w/ Fred again.. - Baby Again (VIP)\n");
+        let entries = &tracklist.entries;
+        assert_eq!(entries.len(), 3);
+
+        assert_eq!(entries[0].position, Some(1));
+        assert_eq!((entries[0].start, entries[0].end), (Some(0), Some(270)));
+        assert_eq!(entries[0].artists, vec!["Calvin Harris", "Dua Lipa"]);
+        assert_eq!(entries[0].title, "One Kiss");
+        assert_eq!(entries[0].version.as_deref(), Some("Extended Mix"));
+        assert_eq!(entries[0].label.as_deref(), Some("Columbia"));
+        assert_eq!(entries[0].full_title(), "One Kiss (Extended Mix)");
+
+        assert!(entries[1].unidentified);
+        assert_eq!(entries[1].end_timestamp().as_deref(), Some("1:02:05"));
+
+        assert_eq!(entries[2].title, "Rumble");
+        assert_eq!(entries[2].mashup.len(), 1);
+        assert_eq!(entries[2].mashup[0].artist, "Fred again..");
+        assert_eq!(entries[2].mashup[0].version.as_deref(), Some("VIP"));
+        assert_eq!(entries[2].end, None);
+    }
+
+    #[test]
+    fn test_timestamps() {
+        assert_eq!(parse_timestamp("04:30"), Some(270));
+        assert_eq!(parse_timestamp("1:02:05"), Some(3725));
+        assert_eq!(parse_timestamp("ab:cd"), None);
+        assert_eq!(format_timestamp(270), "04:30");
+        assert_eq!(format_timestamp(3725), "1:02:05");
+    }
+}
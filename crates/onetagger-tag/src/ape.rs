@@ -0,0 +1,428 @@
+use anyhow::Error;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use lofty::ape::{ApeFile, ApeItem, ApeTag};
+use lofty::config::{ParseOptions, WriteOptions};
+use lofty::file::{AudioFile, FileType};
+use lofty::musepack::MpcFile;
+use lofty::tag::{ItemValue, TagExt};
+use lofty::wavpack::WavPackFile;
+
+use crate::{Lyrics, Picture};
+use crate::{Field, TagDate, CoverType, TagImpl};
+
+/// APE binary item key for each cover type
+const COVER_TYPES: [(&str, CoverType); 21] = [
+    ("Cover Art (Other)", CoverType::Other),
+    ("Cover Art (Icon)", CoverType::Icon),
+    ("Cover Art (Other Icon)", CoverType::OtherIcon),
+    ("Cover Art (Front)", CoverType::CoverFront),
+    ("Cover Art (Back)", CoverType::CoverBack),
+    ("Cover Art (Leaflet)", CoverType::Leaflet),
+    ("Cover Art (Media)", CoverType::Media),
+    ("Cover Art (Lead Artist)", CoverType::LeadArtist),
+    ("Cover Art (Artist)", CoverType::Artist),
+    ("Cover Art (Conductor)", CoverType::Conductor),
+    ("Cover Art (Band)", CoverType::Band),
+    ("Cover Art (Composer)", CoverType::Composer),
+    ("Cover Art (Lyricist)", CoverType::Lyricist),
+    ("Cover Art (Recording Location)", CoverType::RecordingLocation),
+    ("Cover Art (During Recording)", CoverType::DuringRecording),
+    ("Cover Art (During Performance)", CoverType::DuringPerformance),
+    ("Cover Art (Video Capture)", CoverType::ScreenCapture),
+    ("Cover Art (Fish)", CoverType::BrightFish),
+    ("Cover Art (Illustration)", CoverType::Illustration),
+    ("Cover Art (Band Logotype)", CoverType::BandLogo),
+    ("Cover Art (Publisher Logotype)", CoverType::PublisherLogo),
+];
+
+/// APEv2 tag of WavPack, Monkey's Audio, Musepack and TAK files
+pub struct APETag {
+    tag: ApeTag,
+    separator: Option<String>
+}
+
+impl APETag {
+    /// Load from path, files without tag get an empty one
+    pub fn load_file(path: impl AsRef<Path>) -> Result<APETag, Error> {
+        let mut reader = BufReader::new(File::open(&path)?);
+        let options = ParseOptions::new().read_properties(false);
+        let tag = match FileType::from_path(&path) {
+            Some(FileType::Ape) => ApeFile::read_from(&mut reader, options)?.ape().cloned(),
+            Some(FileType::WavPack) => WavPackFile::read_from(&mut reader, options)?.ape().cloned(),
+            Some(FileType::Mpc) => MpcFile::read_from(&mut reader, options)?.ape().cloned(),
+            // Not supported by lofty
+            _ if is_tak(path.as_ref()) => read_ape_footer(&mut reader)?,
+            _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Not an APE tagged file!").into())
+        };
+        Ok(APETag {
+            tag: tag.unwrap_or_default(),
+            separator: None
+        })
+    }
+
+    /// Set date in APE to tag
+    fn set_date_inner(&mut self, tag: &str, date: &TagDate, overwrite: bool) {
+        if overwrite || self.get_raw(tag).is_none() {
+            let v = match date.has_md() {
+                true => format!("{}-{:02}-{:02}", date.year, date.month.unwrap(), date.day.unwrap()),
+                false => date.year.to_string()
+            };
+            self.set_raw(tag, vec![v], true);
+        }
+    }
+
+    /// CoverType -> APE item key
+    fn picture_key(&self, cover_type: &CoverType) -> &'static str {
+        COVER_TYPES.iter().find(|(_, c)| c == cover_type).map(|i| i.0).unwrap_or(COVER_TYPES[0].0)
+    }
+}
+
+impl TagImpl for APETag {
+    fn save_file(&mut self, path: &Path) -> Result<(), Error> {
+        if is_tak(path) {
+            return write_ape_footer(path, &self.tag);
+        }
+        self.tag.save_to_path(path, WriteOptions::default())?;
+        Ok(())
+    }
+
+    fn set_separator(&mut self, separator: &str) {
+        if separator.is_empty() {
+            self.separator = None;
+        } else {
+            self.separator = Some(separator.replace("\\0", "\0"))
+        }
+    }
+
+    fn get_separator(&self) -> Option<String> {
+        self.separator.clone()
+    }
+
+    fn all_tags(&self) -> HashMap<String, Vec<String>> {
+        self.tag.items()
+            .filter_map(|i| self.get_raw(i.key()).map(|v| (i.key().to_string(), v)))
+            .collect()
+    }
+
+    fn get_date(&self) -> Option<TagDate> {
+        let data = &self.get_raw("YEAR")?[0];
+        // YYYY-MM-DD
+        if data.len() >= 10 {
+            return Some(TagDate {
+                year: data[0..4].parse().ok()?,
+                month: data[5..7].parse().ok(),
+                day: data[8..10].parse().ok()
+            });
+        }
+        // YYYY
+        if data.len() >= 4 {
+            return Some(TagDate {
+                year: data[0..4].parse().ok()?,
+                month: None,
+                day: None
+            });
+        }
+        None
+    }
+
+    fn set_date(&mut self, date: &TagDate, overwrite: bool) {
+        self.set_date_inner("YEAR", date, overwrite);
+    }
+
+    fn set_publish_date(&mut self, date: &TagDate, overwrite: bool) {
+        self.set_date_inner("ORIGINALDATE", date, overwrite);
+    }
+
+    fn get_rating(&self) -> Option<u8> {
+        let rating = self.get_raw("RATING")?.first()?.parse::<i32>().ok()? / 20;
+        if rating <= 5 {
+            if rating == 0 {
+               return Some(1)
+            }
+            return Some(rating as u8)
+        }
+        None
+    }
+
+    fn set_rating(&mut self, rating: u8, overwrite: bool) {
+        if rating > 0 {
+            self.set_raw("RATING", vec![(rating * 20).to_string()], overwrite);
+        } else {
+            self.remove_raw("RATING");
+        }
+    }
+
+    fn set_art(&mut self, kind: CoverType, mime: &str, description: Option<&str>, data: Vec<u8>) -> Result<(), Error> {
+        // Description is usually the filename
+        let description = match description {
+            Some(d) if !d.is_empty() => d.to_string(),
+            _ => format!("cover.{}", mime.split('/').last().unwrap_or("jpg").replace("jpeg", "jpg"))
+        };
+        let value = [description.as_bytes(), &[0], &data].concat();
+        let item = ApeItem::new(self.picture_key(&kind).to_string(), ItemValue::Binary(value))
+            .map_err(|e| anyhow!("Error adding picture to APE tag: {e}"))?;
+        self.tag.insert(item);
+        Ok(())
+    }
+
+    fn has_art(&self) -> bool {
+        self.tag.items().any(|i| COVER_TYPES.iter().any(|(k, _)| k.eq_ignore_ascii_case(i.key())))
+    }
+
+    fn get_art(&self) -> Vec<Picture> {
+        self.tag.items().filter_map(|item| {
+            let kind = COVER_TYPES.iter().find(|(k, _)| k.eq_ignore_ascii_case(item.key()))?.1.clone();
+            let value = match item.value() {
+                ItemValue::Binary(value) => value,
+                _ => return None
+            };
+            // description\0data
+            let split = value.iter().position(|b| *b == 0)?;
+            let data = value[split + 1..].to_vec();
+            Some(Picture {
+                kind,
+                description: String::from_utf8_lossy(&value[..split]).to_string(),
+                mime: crate::image_mime(&data).unwrap_or("image/jpeg").to_string(),
+                data,
+            })
+        }).collect()
+    }
+
+    fn remove_art(&mut self, kind: CoverType) {
+        self.tag.remove(self.picture_key(&kind));
+    }
+
+    fn set_field(&mut self, field: Field, value: Vec<String>, overwrite: bool) {
+        // Stored together with track number
+        if field == Field::TrackTotal {
+            let number = self.get_field(Field::TrackNumber).and_then(|v| v.first().cloned()).filter(|n| !n.is_empty());
+            match (number, value.first().and_then(|v| v.parse().ok())) {
+                (Some(number), Some(total)) => self.set_track_number(&number, Some(total), overwrite),
+                (None, Some(_)) => warn!("Track total can't be written without track number"),
+                (_, None) => warn!("Invalid track total: {value:?}"),
+            }
+            return;
+        }
+        self.set_raw(field.ape(), value, overwrite);
+    }
+
+    fn get_field(&self, field: Field) -> Option<Vec<String>> {
+        // Track is stored as number/total
+        match field {
+            Field::TrackNumber => Some(vec![self.get_raw(field.ape())?.first()?.split('/').next()?.trim().to_string()]),
+            Field::TrackTotal => Some(vec![self.get_raw(Field::TrackNumber.ape())?.first()?.split('/').nth(1)?.trim().to_string()]),
+            _ => self.get_raw(field.ape())
+        }
+    }
+
+    fn set_raw(&mut self, tag: &str, value: Vec<String>, overwrite: bool) {
+        if !overwrite && self.tag.get(tag).is_some() {
+            return;
+        }
+        // Multiple values are separated by \0 in APEv2
+        let value = value.join(self.separator.as_deref().unwrap_or("\0"));
+        match ApeItem::new(tag.to_string(), ItemValue::Text(value)) {
+            Ok(item) => self.tag.insert(item),
+            Err(e) => error!("Invalid APE item {tag}: {e}"),
+        }
+    }
+
+    fn get_raw(&self, tag: &str) -> Option<Vec<String>> {
+        let value = match self.tag.get(tag)?.value() {
+            ItemValue::Text(value) | ItemValue::Locator(value) => value,
+            ItemValue::Binary(_) => return None
+        };
+
+        // Separator override
+        if let (Some(separator), false) = (&self.separator, value.contains('\0')) {
+            return Some(value.split(separator.as_str()).map(String::from).collect());
+        }
+        Some(value.split('\0').map(String::from).collect())
+    }
+
+    fn remove_raw(&mut self, tag: &str) {
+        self.tag.remove(tag);
+    }
+
+    fn set_lyrics(&mut self, lyrics: &Lyrics, synced: bool, overwrite: bool) {
+        if synced {
+            warn!("APE doesn't support synchronized lyrics!");
+            return;
+        }
+        self.set_raw("LYRICS", vec![lyrics.text()], overwrite);
+    }
+
+    fn set_track_number(&mut self, track_number: &str, track_total: Option<u16>, overwrite: bool) {
+        let value = match track_total {
+            Some(total) => format!("{track_number}/{total}"),
+            None => track_number.to_string()
+        };
+        self.set_raw(Field::TrackNumber.ape(), vec![value], overwrite);
+    }
+
+    fn set_explicit(&mut self, explicit: bool) {
+        let mut comments: Vec<_> = self.get_raw("COMMENT").unwrap_or(vec![]).into_iter().filter(|i| i != "Explicit").collect();
+        if explicit {
+            comments.push("Explicit".to_string());
+        }
+        self.set_raw("COMMENT", comments, true);
+    }
+}
+
+/// TAK is APEv2 tagged, but the container isn't supported by lofty
+fn is_tak(path: &Path) -> bool {
+    path.extension().map(|e| e.eq_ignore_ascii_case("tak")).unwrap_or(false)
+}
+
+/// Read APEv2 tag from the end of file (before ID3v1)
+fn read_ape_footer(reader: &mut (impl Read + Seek)) -> Result<Option<ApeTag>, Error> {
+    let trailing = crate::id3::ID3Tag::trailing_tags(reader)?;
+    if !trailing.iter().any(|(t, _)| *t == "APE") {
+        return Ok(None);
+    }
+    let id3v1 = trailing.iter().find(|(t, _)| *t == "ID3v1").map(|(_, size)| *size).unwrap_or(0);
+    let end = reader.seek(SeekFrom::End(0))? - id3v1;
+
+    // Footer: size of items + footer at 12, item count at 16
+    let mut footer = [0u8; 32];
+    reader.seek(SeekFrom::Start(end - 32))?;
+    reader.read_exact(&mut footer)?;
+    let size = u32::from_le_bytes(footer[12..16].try_into().unwrap()) as u64;
+    let count = u32::from_le_bytes(footer[16..20].try_into().unwrap());
+    let mut data = vec![0u8; size.saturating_sub(32) as usize];
+    reader.seek(SeekFrom::Start(end - size))?;
+    reader.read_exact(&mut data)?;
+
+    // Item: value size, flags (bits 1-2 = type), null terminated key, value
+    let mut tag = ApeTag::default();
+    let mut pos = 0;
+    for _ in 0..count {
+        if pos + 8 > data.len() {
+            break;
+        }
+        let len = u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        let flags = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().unwrap());
+        let key_end = match data[pos + 8..].iter().position(|b| *b == 0) {
+            Some(p) => pos + 8 + p,
+            None => break
+        };
+        let key = String::from_utf8_lossy(&data[pos + 8..key_end]).to_string();
+        let value = match data.get(key_end + 1..key_end + 1 + len) {
+            Some(v) => v,
+            None => break
+        };
+        pos = key_end + 1 + len;
+        let value = match (flags >> 1) & 3 {
+            1 => ItemValue::Binary(value.to_vec()),
+            2 => ItemValue::Locator(String::from_utf8_lossy(value).to_string()),
+            _ => ItemValue::Text(String::from_utf8_lossy(value).to_string()),
+        };
+        match ApeItem::new(key, value) {
+            Ok(item) => tag.insert(item),
+            Err(e) => warn!("Invalid APE item: {e}"),
+        }
+    }
+    Ok(Some(tag))
+}
+
+/// Replace APEv2 tag at the end of file, ID3v1 is kept after it
+fn write_ape_footer(path: &Path, tag: &ApeTag) -> Result<(), Error> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let trailing = crate::id3::ID3Tag::trailing_tags(&mut file)?;
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut id3v1 = vec![0u8; trailing.iter().find(|(t, _)| *t == "ID3v1").map(|(_, size)| *size).unwrap_or(0) as usize];
+    file.seek(SeekFrom::Start(len - id3v1.len() as u64))?;
+    file.read_exact(&mut id3v1)?;
+
+    let mut items = vec![];
+    let mut count = 0u32;
+    for item in tag.items() {
+        let (kind, value) = match item.value() {
+            ItemValue::Text(v) => (0u32, v.as_bytes()),
+            ItemValue::Binary(v) => (1, v.as_slice()),
+            ItemValue::Locator(v) => (2, v.as_bytes()),
+        };
+        items.extend((value.len() as u32).to_le_bytes());
+        items.extend((kind << 1).to_le_bytes());
+        items.extend(item.key().as_bytes());
+        items.push(0);
+        items.extend(value);
+        count += 1;
+    }
+
+    // Header and footer: version 2000, size without header, flags (bit 31 = has header, bit 29 = is header)
+    let size = items.len() as u32 + 32;
+    let header = |is_header: bool| {
+        let flags = (1u32 << 31) | if is_header { 1 << 29 } else { 0 };
+        [b"APETAGEX".as_slice(), &2000u32.to_le_bytes(), &size.to_le_bytes(), &count.to_le_bytes(), &flags.to_le_bytes(), &[0; 8]].concat()
+    };
+    let ape = match count {
+        0 => vec![],
+        _ => [header(true), items, header(false)].concat()
+    };
+
+    file.set_len(len - trailing.iter().map(|(_, size)| size).sum::<u64>())?;
+    file.seek(SeekFrom::End(0))?;
+    file.write_all(&ape)?;
+    file.write_all(&id3v1)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+#[test]
+fn test_ape_fields() {
+    let mut tag = APETag { tag: ApeTag::default(), separator: None };
+
+    // No track number to store the total with
+    tag.set_field(Field::TrackTotal, vec!["12".to_string()], true);
+    assert!(tag.get_field(Field::TrackNumber).is_none());
+    tag.set_track_number("3", None, true);
+    tag.set_field(Field::TrackTotal, vec!["12".to_string()], true);
+    assert_eq!(tag.get_raw("TRACK"), Some(vec!["3/12".to_string()]));
+    assert_eq!(tag.get_field(Field::TrackNumber), Some(vec!["3".to_string()]));
+    assert_eq!(tag.get_field(Field::TrackTotal), Some(vec!["12".to_string()]));
+
+    tag.set_field(Field::Artist, vec!["A".to_string(), "B".to_string()], true);
+    assert_eq!(tag.get_field(Field::Artist), Some(vec!["A".to_string(), "B".to_string()]));
+
+    tag.set_date(&TagDate { year: 2020, month: Some(1), day: Some(2) }, true);
+    let date = tag.get_date().unwrap();
+    assert_eq!((date.year, date.month, date.day), (2020, Some(1), Some(2)));
+
+    tag.set_art(CoverType::CoverFront, "image/png", None, vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]).unwrap();
+    let art = tag.get_art();
+    assert_eq!(art.len(), 1);
+    assert_eq!(art[0].kind, CoverType::CoverFront);
+    assert_eq!(art[0].mime, "image/png");
+    assert_eq!(art[0].description, "cover.png");
+}
+
+#[test]
+fn test_tak_tag() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("test.tak");
+    let mut id3v1 = b"TAG".to_vec();
+    id3v1.resize(128, 0);
+    let audio = [b"tBaK".as_slice(), &[0x69; 60]].concat();
+    std::fs::write(&path, [audio.as_slice(), &id3v1].concat()).unwrap();
+
+    let mut tag = APETag::load_file(&path).unwrap();
+    assert!(tag.all_tags().is_empty());
+    tag.set_field(Field::Title, vec!["Title".to_string()], true);
+    tag.set_field(Field::Artist, vec!["A".to_string(), "B".to_string()], true);
+    tag.set_art(CoverType::CoverFront, "image/png", None, vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]).unwrap();
+    tag.save_file(&path).unwrap();
+    // Saving again replaces the tag
+    tag.save_file(&path).unwrap();
+
+    let data = std::fs::read(&path).unwrap();
+    assert!(data.starts_with(&audio) && data.ends_with(&id3v1));
+    assert_eq!(&data[audio.len()..audio.len() + 8], b"APETAGEX");
+    let tag = APETag::load_file(&path).unwrap();
+    assert_eq!(tag.get_field(Field::Title), Some(vec!["Title".to_string()]));
+    assert_eq!(tag.get_field(Field::Artist), Some(vec!["A".to_string(), "B".to_string()]));
+    assert_eq!(tag.get_art().len(), 1);
+}
@@ -0,0 +1,156 @@
+use anyhow::Error;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::fs::File;
+use std::path::Path;
+use std::time::Duration;
+use id3::{Tag, Version};
+
+use crate::AudioProperties;
+
+/// DSF `DSD ` chunk
+const DSD_ID: &[u8; 4] = b"DSD ";
+
+/// DSF header: total file size and offset of the ID3 tag (0 = missing)
+struct DSFHeader {
+    file_size: u64,
+    metadata: u64,
+}
+
+impl DSFHeader {
+    /// Read from start of file
+    fn read(reader: &mut (impl Read + Seek)) -> Result<DSFHeader, Error> {
+        let mut buffer = [0u8; 28];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut buffer)?;
+        if &buffer[0..4] != DSD_ID {
+            bail!("Not a DSF file");
+        }
+        Ok(DSFHeader {
+            file_size: u64::from_le_bytes(buffer[12..20].try_into()?),
+            metadata: u64::from_le_bytes(buffer[20..28].try_into()?),
+        })
+    }
+}
+
+/// Read the ID3 tag at the end of DSF file
+pub(crate) fn read_dsf(path: impl AsRef<Path>) -> Result<Tag, Error> {
+    let mut file = File::open(path)?;
+    let header = DSFHeader::read(&mut file)?;
+    if header.metadata == 0 {
+        return Ok(Tag::new());
+    }
+    file.seek(SeekFrom::Start(header.metadata))?;
+    Ok(Tag::read_from2(&mut file)?)
+}
+
+/// Replace the ID3 tag at the end of DSF file and update the header
+pub(crate) fn write_dsf(path: impl AsRef<Path>, tag: Tag, version: Version) -> Result<(), Error> {
+    let path = path.as_ref();
+    let mut file = File::open(path)?;
+    let header = DSFHeader::read(&mut file)?;
+    let metadata = file.metadata()?;
+    // Tag is always the last chunk
+    let audio_end = match header.metadata {
+        0 => header.file_size.min(metadata.len()),
+        offset => offset
+    };
+    let mut data = vec![];
+    tag.write_to(Cursor::new(&mut data), version)?;
+
+    // Write into a temporary file first, so the original survives a failed write
+    let filename = path.file_name().ok_or(anyhow!("Invalid filename"))?.to_string_lossy();
+    let tmp = path.with_file_name(format!(".{filename}.onetagger-tmp"));
+    let result = (|| -> Result<(), Error> {
+        let mut out = File::create(&tmp)?;
+        file.seek(SeekFrom::Start(0))?;
+        std::io::copy(&mut (&mut file).take(audio_end), &mut out)?;
+        out.write_all(&data)?;
+        // Update header
+        out.seek(SeekFrom::Start(12))?;
+        out.write_all(&(audio_end + data.len() as u64).to_le_bytes())?;
+        out.write_all(&audio_end.to_le_bytes())?;
+        out.sync_all()?;
+        std::fs::set_permissions(&tmp, metadata.permissions())?;
+        Ok(())
+    })();
+    drop(file);
+    if let Err(e) = result {
+        std::fs::remove_file(&tmp).ok();
+        return Err(e);
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Read the `fmt ` chunk
+pub(crate) fn read_properties(path: impl AsRef<Path>) -> Result<AudioProperties, Error> {
+    let mut file = File::open(&path)?;
+    let header = DSFHeader::read(&mut file)?;
+    let mut buffer = [0u8; 52];
+    file.read_exact(&mut buffer)?;
+    if &buffer[0..4] != b"fmt " {
+        bail!("Missing DSF fmt chunk");
+    }
+    let channels = u32::from_le_bytes(buffer[24..28].try_into()?);
+    let sample_rate = u32::from_le_bytes(buffer[28..32].try_into()?);
+    let samples = u64::from_le_bytes(buffer[36..44].try_into()?);
+    let duration = match sample_rate {
+        0 => Duration::ZERO,
+        rate => Duration::from_secs_f64(samples as f64 / rate as f64)
+    };
+    let bitrate = (sample_rate as u64 * channels as u64 / 1000) as u32;
+
+    Ok(AudioProperties {
+        codec: "DSD".to_string(),
+        container: "DSF".to_string(),
+        bitrate: Some(bitrate),
+        sample_rate: Some(sample_rate),
+        bit_depth: Some(1),
+        channels: Some(channels as u8),
+        duration,
+        file_size: header.file_size,
+    })
+}
+
+#[test]
+fn test_dsf_roundtrip() {
+    use id3::TagLike;
+
+    // Header, fmt chunk (2 channels, 2822400 Hz, 5644800 samples) and data chunk
+    let audio = vec![0x69u8; 64];
+    let mut dsf = vec![];
+    dsf.extend(DSD_ID);
+    dsf.extend(28u64.to_le_bytes());
+    dsf.extend((28u64 + 52 + 12 + audio.len() as u64).to_le_bytes());
+    dsf.extend(0u64.to_le_bytes());
+    dsf.extend(b"fmt ");
+    dsf.extend(52u64.to_le_bytes());
+    dsf.extend([1u32, 0, 2, 2, 2822400, 1].iter().flat_map(|v| v.to_le_bytes()));
+    dsf.extend(5644800u64.to_le_bytes());
+    dsf.extend([4096u32, 0].iter().flat_map(|v| v.to_le_bytes()));
+    dsf.extend(b"data");
+    dsf.extend((12u64 + audio.len() as u64).to_le_bytes());
+    dsf.extend(&audio);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("test.dsf");
+    std::fs::write(&path, &dsf).unwrap();
+    assert!(read_dsf(&path).unwrap().title().is_none());
+
+    // Write twice, the second tag replaces the first one
+    for title in ["First", "Second"] {
+        let mut tag = Tag::new();
+        tag.set_title(title);
+        write_dsf(&path, tag, Version::Id3v24).unwrap();
+    }
+    assert_eq!(read_dsf(&path).unwrap().title(), Some("Second"));
+    let written = std::fs::read(&path).unwrap();
+    assert_eq!(&written[28..dsf.len()], &dsf[28..]);
+    assert_eq!(u64::from_le_bytes(written[12..20].try_into().unwrap()), written.len() as u64);
+    assert_eq!(u64::from_le_bytes(written[20..28].try_into().unwrap()), dsf.len() as u64);
+
+    let properties = read_properties(&path).unwrap();
+    assert_eq!(properties.sample_rate, Some(2822400));
+    assert_eq!(properties.channels, Some(2));
+    assert_eq!(properties.duration, Duration::from_secs(2));
+}
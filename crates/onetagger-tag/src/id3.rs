@@ -1,10 +1,13 @@
 use std::collections::HashMap;
 use anyhow::Error;
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 use id3::{Version, Tag, Timestamp, Content, TagLike, Encoder, Frame, Encoding};
-use id3::frame::{Picture, PictureType, Comment, Lyrics, Popularimeter, ExtendedText, SynchronisedLyrics, TimestampFormat, SynchronisedLyricsType};
+use id3::frame::{Picture, PictureType, Comment, Lyrics, Popularimeter, ExtendedText, SynchronisedLyrics, TimestampFormat, SynchronisedLyricsType, InvolvedPeopleList};
 use serde::{Serialize, Deserialize};
 use crate::{TagDate, CoverType, Field, TagImpl};
+use crate::wav::WavMeta;
 
 const COVER_TYPES: [(PictureType, CoverType); 21] = [
     (PictureType::Other, CoverType::Other),
@@ -32,7 +35,7 @@ const COVER_TYPES: [(PictureType, CoverType); 21] = [
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ID3AudioFormat {
-    MP3, AIFF, WAV
+    MP3, AIFF, WAV, DSF
 }
 
 pub struct ID3Tag {
@@ -41,6 +44,8 @@ pub struct ID3Tag {
     pub id3_separator: String,
     pub id3v24: bool,
     pub comm_lang: String,
+    /// RIFF INFO / BWF metadata of WAV files
+    riff: Option<WavMeta>,
 }
 
 impl ID3Tag {
@@ -59,7 +64,8 @@ impl ID3Tag {
                     Version::Id3v24 => true,
                     _ => false
                 },
-                comm_lang: "eng".to_string()
+                comm_lang: "eng".to_string(),
+                riff: None
             }.into());
         }
         // AIFF
@@ -74,12 +80,13 @@ impl ID3Tag {
                     Version::Id3v24 => true,
                     _ => false
                 },
-                comm_lang: "eng".to_string()
+                comm_lang: "eng".to_string(),
+                riff: None
             }.into());
         }
         // WAV
         if ext == "wav" {
-            let tag = crate::wav::read_wav(path)?;
+            let (tag, meta) = crate::wav::read_wav(path)?;
             let version = tag.version();
             return Ok(ID3Tag { 
                 tag,
@@ -89,10 +96,26 @@ impl ID3Tag {
                     Version::Id3v24 => true,
                     _ => false
                 },
-                comm_lang: "eng".to_string()
+                comm_lang: "eng".to_string(),
+                riff: Some(meta)
+            }.into());
+        }
+        // DSF
+        if ext == "dsf" {
+            let tag = crate::dsf::read_dsf(path)?;
+            let version = tag.version();
+            return Ok(ID3Tag {
+                tag,
+                format: ID3AudioFormat::DSF,
+                id3_separator: String::from(", "),
+                id3v24: match version {
+                    Version::Id3v24 => true,
+                    _ => false
+                },
+                comm_lang: "eng".to_string(),
+                riff: None
             }.into());
         }
-
 
         // Unsupported
         Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Unsupported format!").into())
@@ -105,6 +128,8 @@ impl ID3Tag {
             ID3AudioFormat::MP3
         } else if ext == "wav" {
             ID3AudioFormat::WAV
+        } else if ext == "dsf" {
+            ID3AudioFormat::DSF
         } else {
             ID3AudioFormat::AIFF
         };
@@ -115,10 +140,14 @@ impl ID3Tag {
                 warn!("Failed loading: {:?}, creating new tag. {:?}", path.as_ref(), e);
                 ID3Tag {
                     tag: Tag::new(),
-                    format,
                     id3_separator: String::from(", "),
                     id3v24: true,
-                    comm_lang: "eng".to_string()
+                    comm_lang: "eng".to_string(),
+                    riff: match format {
+                        ID3AudioFormat::WAV => Some(WavMeta::default()),
+                        _ => None
+                    },
+                    format,
                 }
             }
         }
@@ -175,6 +204,291 @@ impl ID3Tag {
         self.tag.add_frame(popularimeter);
     }
 
+    /// ID3 frame of RIFF INFO chunk which is synced with ID3 (WAV only)
+    fn info_frame(&self, tag: &str) -> Option<&'static str> {
+        self.riff.as_ref()?;
+        match crate::wav::info_frame(tag)? {
+            "TDRC" if !self.id3v24 => Some("TYER"),
+            frame => Some(frame)
+        }
+    }
+
+    /// Generate all RIFF INFO chunks, the mapped ones are always taken from ID3
+    fn riff_info(&self) -> Vec<(String, String)> {
+        let mut info = vec![];
+        for (id, frame) in crate::wav::INFO_ID3 {
+            let value = match frame {
+                "TDRC" => self.get_date().map(|d| d.to_string()),
+                "TRCK" => self.tag.track().map(|t| t.to_string()),
+                _ => self.get_raw(frame).map(|v| v.join(&self.id3_separator))
+            };
+            if let Some(value) = value.filter(|v| !v.trim().is_empty()) {
+                info.push((id.to_string(), value));
+            }
+        }
+        info.extend(self.riff.iter().flat_map(|r| r.info.clone()));
+        info
+    }
+
+    /// Text frames which are present multiple times (same ID with different encoding for example), with the text of each
+    pub fn duplicate_frames(&self) -> Vec<(String, Vec<String>)> {
+        let mut frames: HashMap<&str, Vec<String>> = HashMap::new();
+        for frame in self.tag.frames() {
+            if let Some(text) = frame.content().text() {
+                frames.entry(frame.id()).or_default().push(text.to_string());
+            }
+        }
+        frames.into_iter().filter(|(_, t)| t.len() > 1).map(|(id, t)| (id.to_string(), t)).collect()
+    }
+
+    /// Raw content of text frames containing null separators in ID3v2.2/3, where they are not valid
+    pub fn null_separated_frames(&self) -> Vec<(String, String)> {
+        if self.tag.version() == Version::Id3v24 {
+            return vec![];
+        }
+        self.tag.frames()
+            .filter_map(|f| f.content().text().filter(|t| t.contains('\0')).map(|t| (f.id().to_string(), t.to_string())))
+            .collect()
+    }
+
+    /// Convert frames between ID3v2.3 and v2.4 and write with that version.
+    /// Returns messages about frames which have no equivalent in the target version
+    pub fn convert_version(&mut self, id3v24: bool) -> Vec<String> {
+        self.id3v24 = id3v24;
+        let (version, obsolete): (_, &[&str]) = match id3v24 {
+            true => ("ID3v2.4", &["TRDA", "TSIZ", "EQUA", "RVAD"]),
+            false => ("ID3v2.3", &["ASPI", "EQU2", "RVA2", "SEEK", "SIGN", "TDEN", "TDTG", "TPRO"]),
+        };
+        let mut notes = obsolete.iter()
+            .filter(|id| !self.tag.remove(id).is_empty())
+            .map(|id| format!("Removed {id}, no {version} equivalent"))
+            .collect::<Vec<_>>();
+
+        // v2.4 only text frames, stored as TXXX in v2.3 (same as Picard / Mp3tag)
+        for (id, description) in [("TDRL", "RELEASEDATE"), ("TSST", "SETSUBTITLE")] {
+            if id3v24 {
+                let value = self.tag.extended_texts().find(|t| t.description == description).map(|t| t.value.to_string());
+                if let Some(value) = value {
+                    self.tag.remove_extended_text(Some(description), None);
+                    self.tag.set_text(id, value);
+                }
+            } else if let Some(value) = self.take_text(id) {
+                self.tag.add_frame(ExtendedText { description: description.to_string(), value });
+                notes.push(format!("Moved {id} to TXXX:{description}, no {version} equivalent"));
+            }
+        }
+        // Mood is read from TMOO in both versions
+        if !id3v24 && self.tag.get("TMOO").is_some() {
+            notes.push(format!("Kept TMOO, which is not part of {version}"));
+        }
+
+        // ID3v2.4: TYER + TDAT + TIME => TDRC, TORY => TDOR, IPLS => TIPL
+        if id3v24 {
+            let date = self.take_text("TDAT").and_then(|d| Self::split_digits(&d));
+            let time = self.take_text("TIME").and_then(|t| Self::split_digits(&t));
+            if let Some(mut ts) = self.take_text("TYER").and_then(|y| y.parse::<Timestamp>().ok()) {
+                if let (Some((day, month)), None) = (date, ts.month) {
+                    ts.day = Some(day);
+                    ts.month = Some(month);
+                    if let Some((hour, minute)) = time {
+                        ts.hour = Some(hour);
+                        ts.minute = Some(minute);
+                    }
+                }
+                if self.tag.date_recorded().is_none() {
+                    self.tag.set_date_recorded(ts);
+                }
+            }
+            if let Some(ts) = self.take_text("TORY").and_then(|y| y.parse::<Timestamp>().ok()) {
+                if self.tag.original_date_released().is_none() {
+                    self.tag.set_original_date_released(ts);
+                }
+            }
+            for frame in self.tag.remove("IPLS") {
+                self.tag.add_frame(Frame::with_content("TIPL", frame.content().clone()));
+            }
+            return notes;
+        }
+
+        // ID3v2.3: TDRC => TYER + TDAT + TIME, TDOR => TORY, TIPL + TMCL => IPLS
+        if let Some(ts) = self.tag.date_recorded() {
+            self.tag.remove_date_recorded();
+            self.tag.set_text("TYER", format!("{:04}", ts.year));
+            if let (Some(month), Some(day)) = (ts.month, ts.day) {
+                self.tag.set_text("TDAT", format!("{day:02}{month:02}"));
+                if let (Some(hour), Some(minute)) = (ts.hour, ts.minute) {
+                    self.tag.set_text("TIME", format!("{hour:02}{minute:02}"));
+                }
+            }
+        }
+        if let Some(ts) = self.take_text("TDOR").and_then(|t| t.parse::<Timestamp>().ok()) {
+            self.tag.set_text("TORY", format!("{:04}", ts.year));
+        }
+        let items = ["TIPL", "TMCL"].iter()
+            .flat_map(|id| self.tag.remove(id))
+            .filter_map(|f| f.content().involved_people_list().map(|l| l.items.clone()))
+            .flatten()
+            .collect::<Vec<_>>();
+        if !items.is_empty() {
+            self.tag.add_frame(Frame::with_content("IPLS", Content::InvolvedPeopleList(InvolvedPeopleList { items })));
+        }
+        notes
+    }
+
+    /// Remove text, comment, lyrics and picture frames without any content, returns their IDs
+    pub fn remove_empty_frames(&mut self) -> Vec<String> {
+        let (empty, frames): (Vec<Frame>, Vec<Frame>) = self.tag.frames().cloned().partition(|frame| {
+            let blank = |s: &str| s.trim_matches(|c: char| c == '\0' || c.is_whitespace()).is_empty();
+            match frame.content() {
+                Content::Text(t) | Content::Link(t) => blank(t),
+                Content::ExtendedText(t) => blank(&t.value),
+                Content::Comment(c) => blank(&c.text),
+                Content::Lyrics(l) => blank(&l.text),
+                Content::Picture(p) => p.data.is_empty(),
+                _ => false
+            }
+        });
+        self.replace_frames(frames);
+        empty.iter().map(|f| f.id().to_string()).collect()
+    }
+
+    /// Remove repeated frames which should be present only once, first one is kept.
+    /// Values of repeated text frames are merged into the first one. Returns messages about the changes
+    pub fn remove_duplicate_frames(&mut self) -> Vec<String> {
+        let mut frames: Vec<Frame> = vec![];
+        let mut merged: Vec<(String, Vec<String>)> = vec![];
+        let mut changed = vec![];
+        let mut messages = vec![];
+        for frame in self.tag.frames() {
+            if let (Some(text), Some((_, values))) = (frame.content().text(), merged.iter_mut().find(|(id, _)| id == frame.id())) {
+                let count = values.len();
+                for value in text.split('\0').filter(|v| !v.trim().is_empty()) {
+                    if !values.iter().any(|v| v == value) {
+                        values.push(value.to_string());
+                    }
+                }
+                match values.len() == count {
+                    true => messages.push(format!("Removed duplicate {}", frame.id())),
+                    false => {
+                        messages.push(format!("Merged duplicate {} values: {}", frame.id(), values[count..].join(", ")));
+                        changed.push(frame.id().to_string());
+                    }
+                }
+                continue;
+            }
+            let duplicate = frames.iter().filter(|f| f.id() == frame.id()).any(|f| match (f.content(), frame.content()) {
+                (Content::ExtendedText(a), Content::ExtendedText(b)) => a.description == b.description,
+                (Content::Comment(a), Content::Comment(b)) => a.lang == b.lang && a.description == b.description,
+                (Content::Lyrics(a), Content::Lyrics(b)) => a.lang == b.lang && a.description == b.description,
+                (Content::Picture(a), Content::Picture(b)) => a.picture_type == b.picture_type && a.description == b.description,
+                _ => f == frame
+            });
+            if duplicate {
+                messages.push(format!("Removed duplicate {}", frame.id()));
+                continue;
+            }
+            if let Some(text) = frame.content().text() {
+                merged.push((frame.id().to_string(), text.split('\0').map(String::from).collect()));
+            }
+            frames.push(frame.clone());
+        }
+        self.replace_frames(frames);
+        // Written with the separator, because ID3v2.3 can't contain null
+        for (id, values) in merged.into_iter().filter(|(id, _)| changed.contains(id)) {
+            self.set_raw(&id, values, true);
+        }
+        messages
+    }
+
+    /// Frames which can't be represented as text tag (PRIV, GEOB, UFID...)
+    pub fn binary_frames(&self) -> Vec<String> {
+        let mut ids = self.tag.frames().filter(|f| !matches!(f.content(),
+            Content::Text(_) | Content::ExtendedText(_) | Content::Comment(_) | Content::Lyrics(_) | Content::Picture(_) | Content::Popularimeter(_)
+        )).map(|f| f.id().to_string()).collect::<Vec<_>>();
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+
+    /// Replace all frames with ones from other tag (lossless copy between ID3 files)
+    pub fn copy_frames(&mut self, source: &ID3Tag) {
+        self.tag = source.tag.clone();
+    }
+
+    /// ID3v1 and APE tags at the end of file (added to MP3 by some apps) with their sizes
+    pub fn trailing_tags(reader: &mut (impl Read + Seek)) -> Result<Vec<(&'static str, u64)>, Error> {
+        let mut end = reader.seek(SeekFrom::End(0))?;
+        let mut read_at = |pos: u64, len: usize| -> Result<Vec<u8>, Error> {
+            let mut buf = vec![0u8; len];
+            reader.seek(SeekFrom::Start(pos))?;
+            reader.read_exact(&mut buf)?;
+            Ok(buf)
+        };
+
+        let mut tags = vec![];
+        // ID3v1 (with possible extended tag)
+        if end >= 128 && read_at(end - 128, 3)? == b"TAG" {
+            let size = match end >= 355 && read_at(end - 355, 4)? == b"TAG+" {
+                true => 355,
+                false => 128
+            };
+            tags.push(("ID3v1", size));
+            end -= size;
+        }
+        // APEv1/v2 footer: size at 12 (without header), flags at 20 (bit 31 = has header)
+        if end >= 32 {
+            let footer = read_at(end - 32, 32)?;
+            if &footer[0..8] == b"APETAGEX" {
+                let size = u32::from_le_bytes(footer[12..16].try_into().unwrap()) as u64;
+                let flags = u32::from_le_bytes(footer[20..24].try_into().unwrap());
+                let size = size + if flags & (1 << 31) != 0 { 32 } else { 0 };
+                if size <= end {
+                    tags.push(("APE", size));
+                }
+            }
+        }
+        Ok(tags)
+    }
+
+    /// Remove ID3v1 and APE tags from the end of file, returns names of removed tags
+    pub fn strip_trailing_tags(path: impl AsRef<Path>) -> Result<Vec<&'static str>, Error> {
+        let mut file = OpenOptions::new().read(true).write(true).open(&path)?;
+        let tags = Self::trailing_tags(&mut file)?;
+        if !tags.is_empty() {
+            let len = file.metadata()?.len();
+            file.set_len(len - tags.iter().map(|(_, size)| size).sum::<u64>())?;
+        }
+        Ok(tags.into_iter().map(|(tag, _)| tag).collect())
+    }
+
+    /// Remove frame and get its text
+    fn take_text(&mut self, id: &str) -> Option<String> {
+        self.tag.remove(id).first().and_then(|f| f.content().text().map(|t| t.trim().to_string()))
+    }
+
+    /// Split ID3v2.3 DDMM / HHMM
+    fn split_digits(value: &str) -> Option<(u8, u8)> {
+        if value.len() != 4 || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        Some((value[0..2].parse().ok()?, value[2..4].parse().ok()?))
+    }
+
+    /// Replace frames, keeping the original version
+    /// Description of TXXX frame, 4 character names are frame IDs unless prefixed with `TXXX:`
+    fn extended_description(tag: &str) -> Option<&str> {
+        match tag.strip_prefix("TXXX:") {
+            Some(description) => Some(description),
+            None => Some(tag).filter(|t| t.len() != 4)
+        }
+    }
+
+    fn replace_frames(&mut self, frames: Vec<Frame>) {
+        let mut tag = Tag::with_version(self.tag.version());
+        tag.extend(frames);
+        self.tag = tag;
+    }
+
     // Convert between different cover/picture types
     fn picture_type(&self, cover_type: &CoverType) -> PictureType {
         COVER_TYPES.iter().find(|(_, c)| c == cover_type).unwrap().0
@@ -221,7 +535,12 @@ impl TagImpl for ID3Tag {
                     .write_to_path(&self.tag, path)?;
             },
             ID3AudioFormat::AIFF => self.tag.write_to_path(path, version)?,
-            ID3AudioFormat::WAV => crate::wav::write_wav(path, self.tag.clone(), version)?,
+            ID3AudioFormat::WAV => {
+                let mut meta = self.riff.clone().unwrap_or_default();
+                meta.info = self.riff_info();
+                crate::wav::write_wav(path, self.tag.clone(), version, &meta)?
+            },
+            ID3AudioFormat::DSF => crate::dsf::write_dsf(path, self.tag.clone(), version)?,
         }
         
         Ok(())
@@ -241,7 +560,15 @@ impl TagImpl for ID3Tag {
         }
         // Add TXXX
         for extended in self.tag.extended_texts() {
-            tags.insert(extended.description.to_string(), extended.value.split(&self.id3_separator).map(String::from).collect());
+            let name = match extended.description.len() {
+                4 => format!("TXXX:{}", extended.description),
+                _ => extended.description.to_string()
+            };
+            tags.insert(name, extended.value.split(&self.id3_separator).map(String::from).collect());
+        }
+        // RIFF INFO / BWF
+        if let Some(riff) = self.riff.as_ref() {
+            tags.extend(riff.all());
         }
         tags
     }
@@ -328,7 +655,7 @@ impl TagImpl for ID3Tag {
     }
 
     // Set album art
-    fn set_art(&mut self, kind: CoverType, mime: &str, description: Option<&str>, data: Vec<u8>) {
+    fn set_art(&mut self, kind: CoverType, mime: &str, description: Option<&str>, data: Vec<u8>) -> Result<(), Error> {
         let picture_type = self.picture_type(&kind);
         self.tag.remove_picture_by_type(picture_type);
         self.tag.add_frame(Picture {
@@ -337,6 +664,7 @@ impl TagImpl for ID3Tag {
             description: description.unwrap_or("Cover").to_owned(),
             data
         });
+        Ok(())
     }
     // Get album art by type
     fn get_art(&self) -> Vec<crate::Picture> {
@@ -392,16 +720,30 @@ impl TagImpl for ID3Tag {
 
     // Set/Get by tag
     fn set_raw(&mut self, tag: &str, value: Vec<String>, overwrite: bool) {
+        // RIFF INFO / BWF in WAV
+        if let Some(frame) = self.info_frame(tag) {
+            return self.set_raw(frame, value, overwrite);
+        }
+        if let Some(riff) = self.riff.as_mut().filter(|r| r.supports(tag)) {
+            if overwrite || riff.get(tag).is_none() {
+                riff.set(tag, match value.is_empty() {
+                    true => None,
+                    false => Some(value.join(&self.id3_separator))
+                });
+            }
+            return;
+        }
+
         // TXXX
-        if tag.len() != 4 {
+        if let Some(description) = Self::extended_description(tag) {
             if overwrite || self.get_raw(tag).is_none() {
                 // Remove if empty
                 if value.is_empty() {
-                    self.tag.remove_extended_text(Some(tag), None);
+                    self.tag.remove_extended_text(Some(description), None);
                     return;
                 }
                 self.tag.add_frame(ExtendedText {
-                    description: tag.to_string(),
+                    description: description.to_string(),
                     value: value.join(&self.id3_separator),
                 });
             }
@@ -457,8 +799,16 @@ impl TagImpl for ID3Tag {
 
     // Get raw TEXT field
     fn get_raw(&self, tag: &str) -> Option<Vec<String>> {
+        // RIFF INFO / BWF in WAV
+        if let Some(frame) = self.info_frame(tag) {
+            return self.get_raw(frame);
+        }
+        if let Some(riff) = self.riff.as_ref().filter(|r| r.supports(tag)) {
+            return riff.get(tag).map(|v| vec![v]);
+        }
+
         // Custom tag (TXXX)
-        if tag.len() != 4 {
+        if let Some(tag) = Self::extended_description(tag) {
             if let Some(t) = self.tag.extended_texts().find(|t| t.description == tag) {
                 return Some(vec![t.value.to_string()]);
             }
@@ -499,8 +849,17 @@ impl TagImpl for ID3Tag {
     }
 
     fn remove_raw(&mut self, tag: &str) {
+        // RIFF INFO / BWF in WAV
+        if let Some(frame) = self.info_frame(tag) {
+            return self.remove_raw(frame);
+        }
+        if let Some(riff) = self.riff.as_mut().filter(|r| r.supports(tag)) {
+            riff.set(tag, None);
+            return;
+        }
+
         // TXXX
-        if tag.len() != 4 {
+        if let Some(tag) = Self::extended_description(tag) {
             self.tag.remove_extended_text(Some(tag), None);
             return;
         }
@@ -661,4 +1020,105 @@ impl From<ID3Comment> for Lyrics {
             text: c.text
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_convert_version() {
+    let mut tag = ID3Tag {
+        tag: Tag::with_version(Version::Id3v23),
+        format: ID3AudioFormat::MP3,
+        id3_separator: String::from(", "),
+        id3v24: false,
+        comm_lang: "eng".to_string(),
+        riff: None
+    };
+    let text = |tag: &ID3Tag, id: &str| tag.tag.get(id).and_then(|f| f.content().text()).map(String::from);
+
+    // ID3v2.3 => ID3v2.4
+    tag.tag.set_text("TYER", "2020");
+    tag.tag.set_text("TDAT", "3105");
+    tag.tag.set_text("TIME", "1230");
+    tag.tag.set_text("TORY", "1999");
+    tag.tag.set_text("TSIZ", "1024");
+    tag.tag.add_frame(ExtendedText { description: "RELEASEDATE".to_string(), value: "2020-06-01".to_string() });
+    assert_eq!(tag.convert_version(true), vec!["Removed TSIZ, no ID3v2.4 equivalent"]);
+    assert_eq!(tag.tag.date_recorded(), Some(Timestamp { year: 2020, month: Some(5), day: Some(31), hour: Some(12), minute: Some(30), second: None }));
+    assert_eq!(tag.tag.original_date_released().map(|t| t.year), Some(1999));
+    assert_eq!(text(&tag, "TDRL").as_deref(), Some("2020-06-01"));
+    assert!(["TYER", "TDAT", "TIME", "TORY", "TSIZ", "TXXX"].iter().all(|id| tag.tag.get(id).is_none()));
+
+    // ID3v2.4 => ID3v2.3
+    tag.tag.set_text("TMOO", "Happy");
+    tag.tag.set_text("TSST", "Disc");
+    assert_eq!(tag.convert_version(false), vec![
+        "Moved TDRL to TXXX:RELEASEDATE, no ID3v2.3 equivalent",
+        "Moved TSST to TXXX:SETSUBTITLE, no ID3v2.3 equivalent",
+        "Kept TMOO, which is not part of ID3v2.3"
+    ]);
+    assert_eq!(text(&tag, "TYER").as_deref(), Some("2020"));
+    assert_eq!(text(&tag, "TDAT").as_deref(), Some("3105"));
+    assert_eq!(text(&tag, "TIME").as_deref(), Some("1230"));
+    assert_eq!(text(&tag, "TORY").as_deref(), Some("1999"));
+    assert_eq!(tag.tag.extended_texts().map(|t| (t.description.as_str(), t.value.as_str())).collect::<Vec<_>>(), vec![
+        ("RELEASEDATE", "2020-06-01"), ("SETSUBTITLE", "Disc")
+    ]);
+    assert!(["TDRC", "TDOR", "TDRL", "TSST"].iter().all(|id| tag.tag.get(id).is_none()));
+}
+
+#[test]
+fn test_trailing_tags() {
+    use std::io::Cursor;
+
+    // APEv2 footer with header flag: 32 bytes header, 32 bytes items, size = items + footer
+    let ape = [b"APETAGEX".as_slice(), &2000u32.to_le_bytes(), &64u32.to_le_bytes(), &1u32.to_le_bytes(), &(1u32 << 31).to_le_bytes(), &[0; 8]].concat();
+    let mut id3v1 = b"TAG".to_vec();
+    id3v1.resize(128, 0);
+    let audio = vec![0xFF; 256];
+
+    assert!(ID3Tag::trailing_tags(&mut Cursor::new(&audio)).unwrap().is_empty());
+    let file = [audio.as_slice(), &id3v1].concat();
+    assert_eq!(ID3Tag::trailing_tags(&mut Cursor::new(file)).unwrap(), vec![("ID3v1", 128)]);
+    let file = [audio.as_slice(), &[0; 64], &ape, &id3v1].concat();
+    assert_eq!(ID3Tag::trailing_tags(&mut Cursor::new(file)).unwrap(), vec![("ID3v1", 128), ("APE", 96)]);
+}
+
+#[test]
+fn test_remove_duplicate_frames() {
+    let mut tag = ID3Tag {
+        tag: Tag::with_version(Version::Id3v23),
+        format: ID3AudioFormat::MP3,
+        id3_separator: String::from(", "),
+        id3v24: false,
+        comm_lang: "eng".to_string(),
+        riff: None
+    };
+    // The id3 crate keeps frames with the same ID and different encoding
+    tag.tag.add_frame(Frame::text("TPE1", "A").set_encoding(Some(Encoding::Latin1)));
+    tag.tag.add_frame(Frame::text("TPE1", "A\0B").set_encoding(Some(Encoding::UTF16)));
+    tag.tag.add_frame(Frame::text("TIT2", "Title").set_encoding(Some(Encoding::Latin1)));
+    tag.tag.add_frame(Frame::text("TIT2", "Title").set_encoding(Some(Encoding::UTF16)));
+    assert_eq!(tag.remove_duplicate_frames(), vec!["Merged duplicate TPE1 values: B", "Removed duplicate TIT2"]);
+    assert_eq!(tag.tag.frames().count(), 2);
+    assert_eq!(tag.tag.get("TPE1").and_then(|f| f.content().text()), Some("A, B"));
+}
+
+#[test]
+fn test_extended_text() {
+    let mut tag = ID3Tag {
+        tag: Tag::with_version(Version::Id3v24),
+        format: ID3AudioFormat::MP3,
+        id3_separator: String::from(", "),
+        id3v24: true,
+        comm_lang: "eng".to_string(),
+        riff: None
+    };
+    // 4 character descriptions would be read as frame ID
+    tag.set_raw("TXXX:WORK", vec!["Work".to_string()], true);
+    tag.set_raw("CUSTOM", vec!["Custom".to_string()], true);
+    assert!(tag.tag.get("WORK").is_none());
+    assert_eq!(tag.get_raw("TXXX:WORK"), Some(vec!["Work".to_string()]));
+    assert_eq!(tag.get_raw("TXXX:CUSTOM"), Some(vec!["Custom".to_string()]));
+    let mut names = tag.all_tags().into_keys().collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, vec!["CUSTOM", "TXXX:WORK"]);
+}
@@ -23,18 +23,28 @@ pub mod mp4;
 #[cfg(feature = "tag")]
 pub mod vorbis;
 #[cfg(feature = "tag")]
+pub mod ape;
+#[cfg(feature = "tag")]
 mod wav;
+#[cfg(feature = "tag")]
+mod dsf;
+#[cfg(feature = "tag")]
+pub mod properties;
+
+#[cfg(feature = "tag")]
+pub use properties::AudioProperties;
 
 // Supported extensions
-pub static EXTENSIONS : [&'static str; 11] = ["mp3", "flac", "aif", "aiff", "m4a", 
-    "mp4", "wav", "ogg", "opus", "spx", "oga"];
+pub static EXTENSIONS : [&'static str; 16] = ["mp3", "flac", "aif", "aiff", "m4a", 
+    "mp4", "wav", "ogg", "opus", "spx", "oga", "wv", "ape", "mpc", "tak", "dsf"];
 
 #[cfg(feature = "tag")]
 pub enum Tag {
     FLAC(flac::FLACTag),
     ID3(id3::ID3Tag),
     MP4(mp4::MP4Tag),
-    Vorbis(vorbis::VorbisTag)
+    Vorbis(vorbis::VorbisTag),
+    APE(ape::APETag)
 }
 
 #[cfg(feature = "tag")]
@@ -55,6 +65,11 @@ impl Tag {
             return Ok(Tag::Vorbis(vorbis::VorbisTag::load_file(path)?));
         }
 
+        // APE (WavPack, Monkey's Audio, Musepack, TAK)
+        if ext == "wv" || ext == "ape" || ext == "mpc" || ext == "tak" {
+            return Ok(Tag::APE(ape::APETag::load_file(path)?));
+        }
+
         // ID3
         let tag = if allow_new {
             id3::ID3Tag::load_or_new(path)
@@ -71,6 +86,7 @@ impl Tag {
             Tag::ID3(tag) => tag.set_separator(&separators.id3),
             Tag::MP4(tag) => tag.set_separator(&separators.mp4),
             Tag::Vorbis(tag) => tag.set_separator(separators.vorbis.as_ref().unwrap_or(&String::new())),
+            Tag::APE(tag) => tag.set_separator(separators.vorbis.as_ref().unwrap_or(&String::new())),
         }
     }
 
@@ -81,6 +97,7 @@ impl Tag {
             Tag::ID3(tag) => Box::new(tag),
             Tag::MP4(tag) => Box::new(tag),
             Tag::Vorbis(tag) => Box::new(tag),
+            Tag::APE(tag) => Box::new(tag),
         }
     }
     pub fn tag_mut(&mut self) -> Box<&mut dyn TagImpl> {
@@ -88,7 +105,8 @@ impl Tag {
             Tag::FLAC(tag) => Box::new(tag),
             Tag::ID3(tag) => Box::new(tag),
             Tag::MP4(tag) => Box::new(tag),
-            Tag::Vorbis(tag) => Box::new(tag)
+            Tag::Vorbis(tag) => Box::new(tag),
+            Tag::APE(tag) => Box::new(tag)
         }
     }
 
@@ -100,9 +118,11 @@ impl Tag {
             Tag::ID3(id3) => match id3.format {
                 id3::ID3AudioFormat::MP3 => AudioFileFormat::MP3,
                 id3::ID3AudioFormat::AIFF => AudioFileFormat::AIFF,
-                id3::ID3AudioFormat::WAV => AudioFileFormat::WAV
+                id3::ID3AudioFormat::WAV => AudioFileFormat::WAV,
+                id3::ID3AudioFormat::DSF => AudioFileFormat::DSF
             },
-            Tag::Vorbis(_) => AudioFileFormat::OGG
+            Tag::Vorbis(_) => AudioFileFormat::OGG,
+            Tag::APE(_) => AudioFileFormat::APE
         }
     }
 }
@@ -129,8 +149,8 @@ pub trait TagImpl {
     fn get_rating(&self) -> Option<u8>;
     fn set_rating(&mut self, rating: u8, overwrite: bool);
 
-    /// Set/Get album art
-    fn set_art(&mut self, kind: CoverType, mime: &str, description: Option<&str>, data: Vec<u8>);
+    /// Set/Get album art, fails if the format can't store the picture
+    fn set_art(&mut self, kind: CoverType, mime: &str, description: Option<&str>, data: Vec<u8>) -> Result<(), Error>;
     /// To not load all album arts
     fn has_art(&self) -> bool;
     fn get_art(&self) -> Vec<Picture>;
@@ -176,7 +196,11 @@ impl Default for TagSeparators {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum AudioFileFormat {
-    FLAC, AIFF, MP3, MP4, WAV, OGG
+    FLAC, AIFF, MP3, MP4, WAV, OGG,
+    /// APEv2 tagged: WavPack, Monkey's Audio, Musepack, TAK
+    APE,
+    /// DSD stream file with ID3
+    DSF
 }
 
 impl AudioFileFormat {
@@ -189,6 +213,8 @@ impl AudioFileFormat {
             "m4a" | "mp4" => Some(AudioFileFormat::MP4),
             "wav" => Some(AudioFileFormat::WAV),
             "ogg" | "opus" | "spx" | "oga" => Some(AudioFileFormat::OGG),
+            "wv" | "ape" | "mpc" | "tak" => Some(AudioFileFormat::APE),
+            "dsf" => Some(AudioFileFormat::DSF),
             _ => None
         }
     }
@@ -227,11 +253,37 @@ impl FrameName {
             AudioFileFormat::WAV => self.id3.to_string(),
             AudioFileFormat::FLAC => self.vorbis.to_string(),
             AudioFileFormat::MP4 => self.mp4.to_string(),
-            AudioFileFormat::OGG => self.vorbis.to_string()
+            AudioFileFormat::OGG => self.vorbis.to_string(),
+            AudioFileFormat::APE => self.vorbis.to_string(),
+            AudioFileFormat::DSF => self.id3.to_string()
         }
     }
 }
 
+/// Detect MIME type of image from its header, None if not a known image format
+pub fn image_mime(data: &[u8]) -> Option<&'static str> {
+    match data {
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some("image/png"),
+        [b'G', b'I', b'F', b'8', ..] => Some("image/gif"),
+        [b'B', b'M', ..] => Some("image/bmp"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        _ => None
+    }
+}
+
+#[test]
+fn test_image_mime() {
+    assert_eq!(image_mime(&[0xFF, 0xD8, 0xFF, 0xE0, 0x00]), Some("image/jpeg"));
+    assert_eq!(image_mime(b"\x89PNG\r\n\x1a\n\x00\x00"), Some("image/png"));
+    assert_eq!(image_mime(b"GIF89a"), Some("image/gif"));
+    assert_eq!(image_mime(b"BM\x00\x00"), Some("image/bmp"));
+    assert_eq!(image_mime(b"RIFF\x10\x00\x00\x00WEBPVP8 "), Some("image/webp"));
+    assert_eq!(image_mime(b"RIFF\x10\x00\x00\x00WAVEfmt "), None);
+    assert_eq!(image_mime(b"garbage"), None);
+    assert_eq!(image_mime(&[]), None);
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Picture {
     pub kind: CoverType,
@@ -278,7 +330,7 @@ impl CoverType {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TagDate {
     pub year: i32,
     pub month: Option<u8>,
@@ -292,7 +344,18 @@ impl TagDate {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+impl std::fmt::Display for TagDate {
+    /// YYYY-MM-DD or YYYY
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.month, self.day) {
+            (Some(month), Some(day)) => write!(f, "{}-{:02}-{:02}", self.year, month, day),
+            _ => write!(f, "{}", self.year)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub enum Field {
     Title,
     Artist,
@@ -324,6 +387,8 @@ impl Field {
             AudioFileFormat::MP3 => self.id3(),
             AudioFileFormat::MP4 => self.mp4(),
             AudioFileFormat::OGG => self.vorbis(),
+            AudioFileFormat::APE => self.ape(),
+            AudioFileFormat::DSF => self.id3(),
         }
     }
 
@@ -375,6 +440,16 @@ impl Field {
         }
     }
 
+    /// Convert to APE item key, same as Vorbis except for few common ones
+    pub fn ape(&self) -> &'static str {
+        match self {
+            Field::AlbumArtist => "ALBUM ARTIST",
+            Field::TrackNumber => "TRACK",
+            Field::DiscNumber => "DISC",
+            field => field.vorbis()
+        }
+    }
+
     /// Convert to MP4 frame name
     pub fn mp4(&self) -> &'static str {
         match self {
@@ -409,6 +484,10 @@ pub enum TagChange {
     Genre { value: Vec<String> },
     Remove { tag: String },
     RemovePicture { kind: CoverType },
+    // Existing values are kept unless overwrite
+    Field { field: Field, value: Vec<String>, overwrite: bool },
+    Date { date: TagDate, overwrite: bool },
+    TrackNumber { value: String, total: Option<u16>, overwrite: bool },
 
     // For adding from UI
     #[cfg(feature = "tag")]
@@ -437,6 +516,23 @@ pub struct TagChanges {
     id3_comm_lang: Option<String>
 }
 
+impl TagChanges {
+    /// Create empty changes for file
+    pub fn new(path: impl Into<PathBuf>, separators: TagSeparators, id3v24: bool, id3_comm_lang: Option<String>) -> TagChanges {
+        TagChanges { changes: vec![], path: path.into(), separators, id3v24, id3_comm_lang }
+    }
+
+    /// Add change
+    pub fn add(&mut self, change: TagChange) {
+        self.changes.push(change);
+    }
+
+    /// Get changes for modification
+    pub fn changes_mut(&mut self) -> &mut Vec<TagChange> {
+        &mut self.changes
+    }
+}
+
 #[cfg(feature = "tag")]
 impl TagChanges {
     // Save all changes to file
@@ -491,8 +587,19 @@ impl TagChanges {
                 TagChange::Rating {value} => tag.set_rating(value, true),
                 TagChange::Genre {value} => tag.set_field(Field::Genre, value, true),
                 TagChange::Remove {tag: t} => tag.remove_raw(&t),
+                TagChange::Field {field, value, overwrite} => tag.set_field(field, value, overwrite),
+                TagChange::Date {date, overwrite} => tag.set_date(&date, overwrite),
+                TagChange::TrackNumber {value, total, overwrite} => tag.set_track_number(&value, total, overwrite),
                 TagChange::RemovePicture {kind} => if format != AudioFileFormat::MP4 { tag.remove_art(kind) },
-                TagChange::AddPictureBase64 {kind, description, data, mime} => tag.set_art(kind, &mime, Some(&description), base64::engine::general_purpose::STANDARD.decode(&data)?),
+                TagChange::AddPictureBase64 {kind, description, data, mime} => {
+                    // Don't trust the UI supplied mime
+                    let data = base64::engine::general_purpose::STANDARD.decode(&data)?;
+                    let real = image_mime(&data).ok_or(anyhow!("Invalid or unsupported image"))?;
+                    if real != mime {
+                        warn!("Picture is {real}, not {mime}");
+                    }
+                    tag.set_art(kind, real, Some(&description), data)?;
+                },
                 _ => {}
             }
         }
@@ -98,19 +98,26 @@ impl MP4Tag {
         if data.is_empty() {
             return None;
         }
+        // Empty separator = each atom is a value
+        if self.separator.is_empty() {
+            return Some(data);
+        }
         // Convert multi tag to single with separator
         Some(data.join(&self.separator).split(&self.separator).map(String::from).collect())
     }
 
     // Raw version of set_art
-    fn add_art(&mut self, mime: &str, data: Vec<u8>) {
+    fn add_art(&mut self, mime: &str, data: Vec<u8>) -> Result<(), Error> {
         if mime == "image/jpeg" || mime == "image/jpg" {
             self.tag.add_artwork(Img::jpeg(data));
         } else if mime == "image/png" {
             self.tag.add_artwork(Img::png(data));
         } else if mime == "image/bmp" {
             self.tag.add_artwork(Img::bmp(data));
+        } else {
+            bail!("Unsupported MP4 artwork type: {mime}");
         }
+        Ok(())
     }
 
     pub fn remove_all_artworks(&mut self) {
@@ -135,6 +142,7 @@ impl TagImpl for MP4Tag {
             let mut values = vec![];
             // Save only text values
             match data {
+                Data::Utf8(d) | Data::Utf16(d) if self.separator.is_empty() => values = vec![d.to_owned()],
                 Data::Utf8(d) => values = d.split(&self.separator).map(String::from).collect(),
                 Data::Utf16(d) => values = d.split(&self.separator).map(String::from).collect(),
                 Data::BeSigned(i) => {
@@ -147,7 +155,7 @@ impl TagImpl for MP4Tag {
                 _ => {}
             }
             if !values.is_empty() {
-                out.insert(MP4Tag::ident_to_string(ident), values);
+                out.entry(MP4Tag::ident_to_string(ident)).or_insert_with(Vec::new).extend(values);
             }
         }
         
@@ -209,8 +217,8 @@ impl TagImpl for MP4Tag {
         }
     }
 
-    fn set_art(&mut self, _kind: CoverType, mime: &str, _description: Option<&str>, data: Vec<u8>) {
-        self.add_art(mime, data);
+    fn set_art(&mut self, _kind: CoverType, mime: &str, _description: Option<&str>, data: Vec<u8>) -> Result<(), Error> {
+        self.add_art(mime, data)
     }
 
     fn has_art(&self) -> bool {
@@ -243,7 +251,9 @@ impl TagImpl for MP4Tag {
         let artworks: Vec<&Picture> = arts.iter().filter(|p| p.kind != kind).collect();
         self.tag.remove_artworks();
         for art in artworks {
-            self.add_art(&art.mime, art.data.clone());
+            if let Err(e) = self.add_art(&art.mime, art.data.clone()) {
+                warn!("Failed to keep artwork: {e}");
+            }
         }
     }
 
@@ -293,7 +303,13 @@ impl TagImpl for MP4Tag {
         if self.get_raw(tag).is_none() || overwrite {
             let ident = MP4Tag::string_to_ident(tag);
             self.tag.remove_data_of(&ident);
-            self.tag.add_data(ident.clone(), Data::Utf8(value.join(&self.separator)));
+            if self.separator.is_empty() {
+                for v in value {
+                    self.tag.add_data(ident.clone(), Data::Utf8(v));
+                }
+            } else {
+                self.tag.add_data(ident.clone(), Data::Utf8(value.join(&self.separator)));
+            }
         }
     }
 
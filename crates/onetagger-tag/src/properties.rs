@@ -0,0 +1,83 @@
+use anyhow::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Duration;
+use lofty::config::ParseOptions;
+use lofty::file::{AudioFile, FileType, TaggedFileExt};
+use lofty::mp4::{Mp4Codec, Mp4File};
+use lofty::probe::Probe;
+use serde::{Serialize, Deserialize};
+
+/// Properties of the audio stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioProperties {
+    /// MP3, FLAC, AAC, ALAC, Vorbis, Opus, Speex, PCM, WavPack, APE, Musepack or DSD
+    pub codec: String,
+    /// MP3, FLAC, MP4, OGG, WAV, AIFF, WV, APE, MPC or DSF
+    pub container: String,
+    /// Overall bitrate in kbps
+    pub bitrate: Option<u32>,
+    /// In Hz
+    pub sample_rate: Option<u32>,
+    pub bit_depth: Option<u8>,
+    pub channels: Option<u8>,
+    pub duration: Duration,
+    /// In bytes
+    pub file_size: u64,
+}
+
+impl AudioProperties {
+    /// Read the properties from file, without reading the tags
+    pub fn load(path: impl AsRef<Path>) -> Result<AudioProperties, Error> {
+        let path = path.as_ref();
+        // Not supported by lofty
+        if path.extension().map(|e| e.eq_ignore_ascii_case("dsf")).unwrap_or(false) {
+            return crate::dsf::read_properties(path);
+        }
+
+        let file = Probe::open(path)?.options(ParseOptions::new().read_tags(false)).read()?;
+        let properties = file.properties();
+
+        let (codec, container) = match file.file_type() {
+            FileType::Mpeg => ("MP3", "MP3"),
+            FileType::Flac => ("FLAC", "FLAC"),
+            FileType::Vorbis => ("Vorbis", "OGG"),
+            FileType::Opus => ("Opus", "OGG"),
+            FileType::Speex => ("Speex", "OGG"),
+            FileType::Wav => ("PCM", "WAV"),
+            FileType::Aiff => ("PCM", "AIFF"),
+            FileType::Mp4 => (Self::mp4_codec(path), "MP4"),
+            FileType::WavPack => ("WavPack", "WV"),
+            FileType::Ape => ("APE", "APE"),
+            FileType::Mpc => ("Musepack", "MPC"),
+            t => bail!("Unsupported file type: {t:?}")
+        };
+
+        Ok(AudioProperties {
+            codec: codec.to_string(),
+            container: container.to_string(),
+            bitrate: properties.overall_bitrate().or(properties.audio_bitrate()),
+            sample_rate: properties.sample_rate(),
+            bit_depth: properties.bit_depth(),
+            channels: properties.channels(),
+            duration: properties.duration(),
+            file_size: std::fs::metadata(path)?.len(),
+        })
+    }
+
+    /// MP4 can contain AAC or lossless audio
+    fn mp4_codec(path: &Path) -> &'static str {
+        let mut reader = match File::open(path) {
+            Ok(f) => BufReader::new(f),
+            Err(_) => return "AAC"
+        };
+        match Mp4File::read_from(&mut reader, ParseOptions::new().read_tags(false)).map(|f| f.properties().codec().clone()) {
+            Ok(Mp4Codec::ALAC) => "ALAC",
+            Ok(Mp4Codec::FLAC) => "FLAC",
+            Ok(Mp4Codec::MP3) => "MP3",
+            _ => "AAC"
+        }
+    }
+}
@@ -165,9 +165,9 @@ impl TagImpl for VorbisTag {
         }
     }
 
-    fn set_art(&mut self, kind: CoverType, mime: &str, description: Option<&str>, data: Vec<u8>) {
+    fn set_art(&mut self, kind: CoverType, mime: &str, description: Option<&str>, data: Vec<u8>) -> Result<(), Error> {
         self.tag.remove_picture_type(self.picture_type(&kind));
-        match self.tag.insert_picture(
+        self.tag.insert_picture(
             lofty::picture::Picture::new_unchecked(
                 self.picture_type(&kind),
                 Some(MimeType::from_str(&mime.trim().to_lowercase())),
@@ -175,10 +175,8 @@ impl TagImpl for VorbisTag {
                 data
             ),
             None
-        ) {
-            Ok(_) => {},
-            Err(e) => error!("Error adding picture to Vorbis tag: {e}"),
-        }
+        ).map_err(|e| anyhow!("Error adding picture to Vorbis tag: {e}"))?;
+        Ok(())
     }
 
     fn has_art(&self) -> bool {
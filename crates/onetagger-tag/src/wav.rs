@@ -3,8 +3,9 @@ use std::io::{BufReader, BufWriter, Cursor, Seek, SeekFrom};
 use std::fs::File;
 use std::collections::HashMap;
 use std::path::Path;
-use id3::{Tag, TagLike, Frame, Version};
-use once_cell::sync::Lazy;
+use std::str::FromStr;
+use id3::{Tag, TagLike, Frame, Version, Timestamp};
+use id3::frame::Comment;
 use riff::{Chunk, ChunkId, LIST_ID, RIFF_ID, ChunkContents};
 
 /// RIFF `id3 ` chunk
@@ -13,27 +14,241 @@ static ID3_ID_2: ChunkId = ChunkId { value: [0x49, 0x44, 0x33, 0x20] };
 /// RIFF WAVE chunk
 static WAVE_ID:  ChunkId = ChunkId { value: [0x57, 0x41, 0x56, 0x45] };
 static INFO_ID:  ChunkId = ChunkId { value: [0x49, 0x4E, 0x46, 0x4F] };
+/// BWF chunks
+static BEXT_ID:  ChunkId = ChunkId { value: *b"bext" };
+static IXML_ID:  ChunkId = ChunkId { value: *b"iXML" };
 
-/// ID3 Frame to RIFF ChunkID
-static ID3_RIFF: Lazy<HashMap<&'static str, ChunkId>> = Lazy::new(|| {
-    let mut m = HashMap::new();
-    m.insert("TIT2", ChunkId::new("INAM").unwrap());
-    m.insert("TALB", ChunkId::new("IPRD").unwrap());
-    m.insert("TPE1", ChunkId::new("IART").unwrap());
-    m.insert("COMM", ChunkId::new("ICMT").unwrap());
-    m.insert("TCON", ChunkId::new("IGNR").unwrap());
-    m.insert("TSRC", ChunkId::new("ISRC").unwrap());
-    m
-});
+/// RIFF INFO chunks with ID3 equivalent, these are stored in ID3 and synced on write
+pub(crate) const INFO_ID3: [(&str, &str); 15] = [
+    ("INAM", "TIT2"),
+    ("IPRD", "TALB"),
+    ("IART", "TPE1"),
+    ("ICMT", "COMM"),
+    ("IGNR", "TCON"),
+    ("ISRC", "TSRC"),
+    ("ICRD", "TDRC"),
+    ("ITRK", "TRCK"),
+    ("IPUB", "TPUB"),
+    ("ICOP", "TCOP"),
+    ("ISFT", "TSSE"),
+    ("IMUS", "TCOM"),
+    ("IWRI", "TEXT"),
+    ("ILNG", "TLAN"),
+    ("IBPM", "TBPM"),
+];
+
+/// Size of the fixed part of the bext chunk
+const BEXT_SIZE: usize = 602;
+
+/// Get ID3 frame of INFO chunk
+pub(crate) fn info_frame(id: &str) -> Option<&'static str> {
+    INFO_ID3.iter().find(|(i, _)| *i == id).map(|(_, f)| *f)
+}
+
+/// Is valid INFO chunk id (IKEY, ICRD...), IPLS is an ID3v2.3 frame
+fn is_info_id(id: &str) -> bool {
+    id.len() == 4 && id.starts_with('I') && id != "IPLS" && id.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+/// WAV metadata which is not in ID3
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WavMeta {
+    /// INFO chunks without ID3 equivalent
+    pub info: Vec<(String, String)>,
+    pub bext: Option<Bext>,
+    /// Raw iXML document
+    pub ixml: Option<String>,
+}
+
+impl WavMeta {
+    /// Is the tag name stored outside of ID3
+    pub fn supports(&self, tag: &str) -> bool {
+        (is_info_id(tag) && info_frame(tag).is_none()) || tag == "IXML" || Bext::FIELDS.contains(&tag)
+    }
+
+    pub fn get(&self, tag: &str) -> Option<String> {
+        if tag == "IXML" {
+            return self.ixml.clone();
+        }
+        if tag.starts_with("BEXT_") {
+            return self.bext.as_ref()?.get(tag);
+        }
+        self.info.iter().find(|(id, _)| id == tag).map(|(_, v)| v.to_string())
+    }
+
+    /// Set or remove (None)
+    pub fn set(&mut self, tag: &str, value: Option<String>) {
+        if tag == "IXML" {
+            self.ixml = value;
+            return;
+        }
+        if tag.starts_with("BEXT_") {
+            self.bext.get_or_insert_with(Default::default).set(tag, value.unwrap_or_default());
+            return;
+        }
+        self.info.retain(|(id, _)| id != tag);
+        if let Some(value) = value {
+            self.info.push((tag.to_string(), value));
+        }
+    }
+
+    /// All tags
+    pub fn all(&self) -> HashMap<String, Vec<String>> {
+        let mut tags: HashMap<String, Vec<String>> = self.info.iter().map(|(k, v)| (k.to_string(), vec![v.to_string()])).collect();
+        if let Some(bext) = self.bext.as_ref() {
+            for field in Bext::FIELDS {
+                tags.insert(field.to_string(), vec![bext.get(field).unwrap_or_default()]);
+            }
+        }
+        if let Some(ixml) = self.ixml.as_ref() {
+            tags.insert("IXML".to_string(), vec![ixml.to_string()]);
+        }
+        tags
+    }
+}
+
+/// Broadcast WAV extension chunk
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Bext {
+    pub description: String,
+    pub originator: String,
+    pub originator_reference: String,
+    /// yyyy-mm-dd
+    pub origination_date: String,
+    /// hh:mm:ss
+    pub origination_time: String,
+    /// Samples since midnight
+    pub time_reference: u64,
+    pub version: u16,
+    pub umid: [u8; 64],
+    /// Loudness values of version 2, kept as is
+    pub loudness: [u8; 10],
+    pub coding_history: String,
+}
+
+impl Default for Bext {
+    fn default() -> Self {
+        Bext {
+            description: String::new(),
+            originator: String::new(),
+            originator_reference: String::new(),
+            origination_date: String::new(),
+            origination_time: String::new(),
+            time_reference: 0,
+            version: 1,
+            umid: [0; 64],
+            loudness: [0; 10],
+            coding_history: String::new()
+        }
+    }
+}
+
+impl Bext {
+    /// Tag names of the fields
+    const FIELDS: [&'static str; 8] = ["BEXT_DESCRIPTION", "BEXT_ORIGINATOR", "BEXT_ORIGINATOR_REFERENCE", "BEXT_ORIGINATION_DATE",
+        "BEXT_ORIGINATION_TIME", "BEXT_TIME_REFERENCE", "BEXT_UMID", "BEXT_CODING_HISTORY"];
+
+    /// Parse chunk data
+    pub fn parse(data: &[u8]) -> Result<Bext, Error> {
+        if data.len() < BEXT_SIZE {
+            return Err(anyhow!("bext chunk too short"));
+        }
+        Ok(Bext {
+            description: ascii(&data[0..256]),
+            originator: ascii(&data[256..288]),
+            originator_reference: ascii(&data[288..320]),
+            origination_date: ascii(&data[320..330]),
+            origination_time: ascii(&data[330..338]),
+            time_reference: u64::from_le_bytes(data[338..346].try_into()?),
+            version: u16::from_le_bytes(data[346..348].try_into()?),
+            umid: data[348..412].try_into()?,
+            loudness: data[412..422].try_into()?,
+            coding_history: ascii(&data[BEXT_SIZE..]),
+        })
+    }
+
+    /// Generate chunk data
+    pub fn write(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(BEXT_SIZE + self.coding_history.len());
+        data.extend(fixed(&self.description, 256));
+        data.extend(fixed(&self.originator, 32));
+        data.extend(fixed(&self.originator_reference, 32));
+        data.extend(fixed(&self.origination_date, 10));
+        data.extend(fixed(&self.origination_time, 8));
+        data.extend(self.time_reference.to_le_bytes());
+        data.extend(self.version.to_le_bytes());
+        data.extend(self.umid);
+        data.extend(self.loudness);
+        data.extend([0u8; 180]);
+        data.extend(self.coding_history.bytes().filter(|b| b.is_ascii()));
+        data
+    }
+
+    fn get(&self, field: &str) -> Option<String> {
+        Some(match field {
+            "BEXT_DESCRIPTION" => self.description.to_string(),
+            "BEXT_ORIGINATOR" => self.originator.to_string(),
+            "BEXT_ORIGINATOR_REFERENCE" => self.originator_reference.to_string(),
+            "BEXT_ORIGINATION_DATE" => self.origination_date.to_string(),
+            "BEXT_ORIGINATION_TIME" => self.origination_time.to_string(),
+            "BEXT_TIME_REFERENCE" => self.time_reference.to_string(),
+            "BEXT_UMID" => match self.umid.iter().all(|b| *b == 0) {
+                true => String::new(),
+                false => self.umid.iter().map(|b| format!("{b:02X}")).collect()
+            },
+            "BEXT_CODING_HISTORY" => self.coding_history.to_string(),
+            _ => return None
+        })
+    }
+
+    fn set(&mut self, field: &str, value: String) {
+        match field {
+            "BEXT_DESCRIPTION" => self.description = value,
+            "BEXT_ORIGINATOR" => self.originator = value,
+            "BEXT_ORIGINATOR_REFERENCE" => self.originator_reference = value,
+            "BEXT_ORIGINATION_DATE" => self.origination_date = value,
+            "BEXT_ORIGINATION_TIME" => self.origination_time = value,
+            "BEXT_TIME_REFERENCE" => self.time_reference = value.trim().parse().unwrap_or(0),
+            "BEXT_UMID" => {
+                let bytes = (0..value.len() / 2).filter_map(|i| u8::from_str_radix(value.get(i * 2..i * 2 + 2)?, 16).ok()).collect::<Vec<_>>();
+                self.umid = [0; 64];
+                let len = bytes.len().min(64);
+                self.umid[..len].copy_from_slice(&bytes[..len]);
+            },
+            "BEXT_CODING_HISTORY" => self.coding_history = value,
+            _ => warn!("Unknown bext field: {field}")
+        }
+    }
+}
+
+/// Null terminated / padded ASCII
+fn ascii(data: &[u8]) -> String {
+    data.iter().take_while(|b| **b != 0).map(|b| *b as char).collect()
+}
+
+/// Null padded, truncated to size
+fn fixed(value: &str, size: usize) -> Vec<u8> {
+    let mut data = value.bytes().filter(|b| b.is_ascii()).take(size).collect::<Vec<_>>();
+    data.resize(size, 0);
+    data
+}
+
+/// INFO text, usually Latin-1
+fn info_text(data: &[u8]) -> String {
+    let data = data.split(|b| *b == 0).next().unwrap_or_default();
+    match std::str::from_utf8(data) {
+        Ok(text) => text.to_string(),
+        Err(_) => data.iter().map(|b| *b as char).collect()
+    }
+}
 
 /// Write wav to path
-/// Will copy ID3 meta into RIFF INFO chunk
-pub(crate) fn write_wav(path: impl AsRef<Path>, tag: Tag, version: Version) -> Result<(), Error> {
+/// `meta.info` has to contain all the INFO chunks (ID3 synced and others)
+pub(crate) fn write_wav(path: impl AsRef<Path>, tag: Tag, version: Version, meta: &WavMeta) -> Result<(), Error> {
     let mut file = BufReader::new(File::open(&path)?);
     let mut offset = 0;
     // Read all the chunks
     let mut riff_chunks = vec![];
-    let mut list_chunks = vec![];
     loop {
         let chunk = match Chunk::read(&mut file, offset) {
             Ok(chunk) => chunk,
@@ -44,16 +259,13 @@ pub(crate) fn write_wav(path: impl AsRef<Path>, tag: Tag, version: Version) -> R
             offset += 4;
             continue;
         }
-        // Save chunks
-        for child in chunk.iter(&mut file) {
-            let child = child?;
-            if chunk.id() == LIST_ID {
-                list_chunks.push(child);
-            } else {
-                riff_chunks.push(child);
+        // Save chunks, INFO is generated from scratch
+        if chunk.id() == RIFF_ID {
+            for child in chunk.iter(&mut file) {
+                riff_chunks.push(child?);
             }
         }
-        
+
         offset += chunk.len() as u64;
     }
 
@@ -61,21 +273,11 @@ pub(crate) fn write_wav(path: impl AsRef<Path>, tag: Tag, version: Version) -> R
     // Get all the RIFF chunks
     let mut resolved_riff_chunks = vec![];
     for chunk in riff_chunks {
-        // Resolve LIST chunks
-        if chunk.id() == LIST_ID {
-            for child in chunk.iter(&mut file) {
-                let child = child?;
-                if child.id() != RIFF_ID && child.id() != LIST_ID {
-                    list_chunks.push(child);
-                }
-            }
-            continue;
-        }
         // Resolve nested (because of SOME apps)
         if chunk.id() == RIFF_ID {
             for child in chunk.iter(&mut file) {
                 let child = child?;
-                if child.id() != RIFF_ID && child.id() != LIST_ID {
+                if child.id() != RIFF_ID {
                     resolved_riff_chunks.push(child);
                 }
             }
@@ -83,56 +285,61 @@ pub(crate) fn write_wav(path: impl AsRef<Path>, tag: Tag, version: Version) -> R
         }
         resolved_riff_chunks.push(chunk);
     }
-    let riff_chunks = resolved_riff_chunks;
+    // Skip LIST INFO chunks, other lists (adtl...) are copied
+    let mut riff_chunks = vec![];
+    for chunk in resolved_riff_chunks {
+        if chunk.id() == LIST_ID && chunk.read_type(&mut file)? == INFO_ID {
+            continue;
+        }
+        riff_chunks.push(chunk);
+    }
 
-    // Generate the RIFF chunk
+    // Generate the RIFF chunk, bext should be before the audio data
     let mut riff_data = vec![];
+    if let Some(bext) = meta.bext.as_ref() {
+        riff_data.push(ChunkContents::Data(BEXT_ID.clone(), bext.write()));
+    }
     for chunk in riff_chunks {
-        // skip old ID3 chunk
-        if chunk.id() == ID3_ID_1 || chunk.id() == ID3_ID_2 {
+        // skip old ID3 and BWF chunks
+        if chunk.id() == ID3_ID_1 || chunk.id() == ID3_ID_2 || chunk.id() == BEXT_ID || chunk.id() == IXML_ID {
             continue;
         }
         // Passthru
         let data = ChunkContents::Data(chunk.id(), chunk.read_contents(&mut file)?);
         riff_data.push(data);
     }
+    if let Some(ixml) = meta.ixml.as_ref() {
+        riff_data.push(ChunkContents::Data(IXML_ID.clone(), ixml.as_bytes().to_vec()));
+    }
+    // Generate LIST INFO chunk, has to be inside RIFF
+    let mut list_data = vec![];
+    for (id, value) in &meta.info {
+        let chunk_id = match ChunkId::new(id) {
+            Ok(id) => id,
+            Err(_) => continue
+        };
+        let mut data = value.as_bytes().to_owned();
+        data.push(0);
+        list_data.push(ChunkContents::Data(chunk_id, data));
+    }
+    if !list_data.is_empty() {
+        riff_data.push(ChunkContents::Children(LIST_ID.clone(), INFO_ID.clone(), list_data));
+    }
     // Add ID3 chunk
     let mut out = vec![];
     tag.write_to(Cursor::new(&mut out), version)?;
     riff_data.push(ChunkContents::Data(ID3_ID_1.clone(), out));
     let riff_chunk = ChunkContents::Children(RIFF_ID.clone(), WAVE_ID.clone(), riff_data);
 
-    // Generate LIST chunk
-    let mut list_data = vec![];
-    for frame in tag.frames() {
-        if let Some(chunk_id) = ID3_RIFF.get(frame.id()) {
-            if let Some(text) = frame.content().text() {
-                let data = ChunkContents::Data(chunk_id.clone(), format!("{text}").as_bytes().to_owned());
-                list_data.push(data);
-            }
-        }
-    }
-    
-    // Add original LIST chunks
-    for chunk in list_chunks {
-        if list_data.iter().any(|c| matches!(c, ChunkContents::Data(id, _) if id == &chunk.id())) {
-            continue;
-        }
-        let data = ChunkContents::Data(chunk.id(), chunk.read_contents(&mut file)?);
-        list_data.push(data);
-    }
-    let list_chunk = ChunkContents::Children(LIST_ID.clone(), INFO_ID.clone(), list_data);
-
     // Write to file
     let mut file = BufWriter::new(File::create(path)?);
     riff_chunk.write(&mut file)?;
-    list_chunk.write(&mut file)?;
 
     Ok(())
 }
 
-/// Read WAV from file, will copy missing tags from RIFF to ID3
-pub(crate) fn read_wav(path: impl AsRef<Path>) -> Result<Tag, Error> {
+/// Read WAV from file, will copy missing tags from RIFF INFO to ID3
+pub(crate) fn read_wav(path: impl AsRef<Path>) -> Result<(Tag, WavMeta), Error> {
     let mut file = BufReader::new(File::open(path)?);
     let mut offset = 0;
     // Read all the chunks
@@ -171,17 +378,85 @@ pub(crate) fn read_wav(path: impl AsRef<Path>) -> Result<Tag, Error> {
     file.seek(SeekFrom::Start(0))?;
     let mut id3 = Tag::read_from2(&mut file).unwrap_or(Tag::new());
 
-    // Copy tags from RIFF to ID3 if missing
-    for (frame_name, chunk_id) in ID3_RIFF.iter() {
-        if id3.get(frame_name).is_none() {
-            if let Some(chunk) = chunks.iter().find(|c| &c.id() == chunk_id) {
-                let data = chunk.read_contents(&mut file)?;
-                if let Ok(data) = String::from_utf8(data) {
-                    id3.add_frame(Frame::text(frame_name, data.replace("\0", "")));
-                }
+    let mut meta = WavMeta::default();
+    for chunk in chunks {
+        let id = String::from_utf8_lossy(&chunk.id().value).to_string();
+        // BWF
+        if chunk.id() == BEXT_ID {
+            match Bext::parse(&chunk.read_contents(&mut file)?) {
+                Ok(bext) => meta.bext = Some(bext),
+                Err(e) => warn!("Invalid bext chunk: {e}"),
             }
+            continue;
+        }
+        if chunk.id() == IXML_ID {
+            meta.ixml = Some(info_text(&chunk.read_contents(&mut file)?));
+            continue;
+        }
+        if !is_info_id(&id) {
+            continue;
+        }
+
+        let value = info_text(&chunk.read_contents(&mut file)?);
+        if value.trim().is_empty() {
+            continue;
+        }
+        // Copy to ID3 if missing
+        match info_frame(&id) {
+            Some("COMM") => if id3.comments().next().is_none() {
+                id3.add_frame(Comment { lang: "eng".to_string(), description: String::new(), text: value });
+            },
+            Some("TDRC") => if id3.date_recorded().is_none() && id3.year().is_none() {
+                if let Ok(timestamp) = Timestamp::from_str(value.trim()) {
+                    id3.set_date_recorded(timestamp);
+                }
+            },
+            Some(frame) => if id3.get(frame).is_none() {
+                id3.add_frame(Frame::text(frame, value));
+            },
+            None => meta.info.push((id, value))
         }
     }
 
-    Ok(id3)
-}
\ No newline at end of file
+    Ok((id3, meta))
+}
+
+#[test]
+fn test_wav_roundtrip() {
+    let bext = Bext { description: "Description".to_string(), coding_history: "A=PCM".to_string(), ..Default::default() };
+    let adtl = [b"adtl".as_slice(), b"labl", &8u32.to_le_bytes(), &1u32.to_le_bytes(), b"cue\0"].concat();
+    let info = [b"INFO".as_slice(), b"INAM", &6u32.to_le_bytes(), b"Title\0"].concat();
+    let riff = ChunkContents::Children(RIFF_ID.clone(), WAVE_ID.clone(), vec![
+        ChunkContents::Data(ChunkId { value: *b"fmt " }, vec![1, 0, 1, 0, 0x44, 0xAC, 0, 0, 0x88, 0x58, 1, 0, 2, 0, 16, 0]),
+        ChunkContents::Data(BEXT_ID.clone(), bext.write()),
+        ChunkContents::Data(LIST_ID.clone(), adtl.clone()),
+        ChunkContents::Data(LIST_ID.clone(), info),
+        ChunkContents::Data(ChunkId { value: *b"data" }, vec![0; 16]),
+    ]);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("test.wav");
+    riff.write(&mut File::create(&path).unwrap()).unwrap();
+
+    let (mut tag, meta) = read_wav(&path).unwrap();
+    assert_eq!(tag.title(), Some("Title"));
+    assert_eq!(meta.bext.as_ref(), Some(&bext));
+    tag.set_artist("Artist");
+    let mut meta = meta;
+    meta.info = vec![("INAM".to_string(), "Title".to_string()), ("IKEY".to_string(), "Keyword".to_string())];
+    write_wav(&path, tag, Version::Id3v24, &meta).unwrap();
+
+    let (tag, meta) = read_wav(&path).unwrap();
+    assert_eq!(tag.artist(), Some("Artist"));
+    assert_eq!(meta.bext.as_ref(), Some(&bext));
+    assert_eq!(meta.info, vec![("IKEY".to_string(), "Keyword".to_string())]);
+    // adtl is kept, the old INFO list is replaced by a new one inside RIFF
+    let mut file = BufReader::new(File::open(&path).unwrap());
+    let riff = Chunk::read(&mut file, 0).unwrap();
+    assert_eq!(riff.len() as u64 + 8, file.get_ref().metadata().unwrap().len());
+    let lists = riff.iter(&mut file).map(|c| c.unwrap()).filter(|c| c.id() == LIST_ID).collect::<Vec<_>>();
+    assert_eq!(lists.len(), 2);
+    assert_eq!(lists[0].read_contents(&mut file).unwrap(), adtl);
+    assert_eq!(lists[1].read_type(&mut file).unwrap(), INFO_ID);
+    let info = [b"INFO".as_slice(), b"INAM", &6u32.to_le_bytes(), b"Title\0", b"IKEY", &8u32.to_le_bytes(), b"Keyword\0"].concat();
+    assert_eq!(lists[1].read_contents(&mut file).unwrap(), info);
+}
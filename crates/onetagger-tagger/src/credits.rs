@@ -0,0 +1,318 @@
+use std::sync::Mutex;
+use regex::{Regex, RegexBuilder};
+use serde::{Serialize, Deserialize};
+
+lazy_static::lazy_static! {
+    /// Featured artists in brackets, eg. "(feat. X)". "with" isn't included, it is usually part of the title
+    static ref FEAT_RE: Regex = Regex::new(r"(?i)^(feat\.?|ft\.?|featuring)\s+(.+)$").unwrap();
+    /// Featured artists outside of brackets
+    static ref FEAT_INLINE_RE: Regex = Regex::new(r"(?i)(^|\s)(feat\.?|ft\.?|featuring)\s+").unwrap();
+    /// Featured artists in artist field
+    static ref FEAT_ARTIST_RE: Regex = Regex::new(r"(?i)\s+\(?(feat\.?|ft\.?|featuring)\s+").unwrap();
+    /// Version, eg. "Extended Mix"
+    static ref VERSION_RE: Regex = Regex::new(r"(?i)\b(remix|rmx|mix|edit|re-?edit|version|dub|bootleg|rework|refix|flip|vip|remaster(ed)?|instrumental|acapella|reprise)(\s+\d{4})?$").unwrap();
+    /// Remixer of version, eg. "X's Remix"
+    static ref REMIXER_RE: Regex = Regex::new(r"(?i)^(.+?)(?:'s)?\s+(remix|rmx|mix|edit|re-?edit|dub|bootleg|rework|refix|flip)(\s+\d{4})?$").unwrap();
+    /// Last compiled collaboration separators
+    static ref SEPARATORS_RE: Mutex<Option<(Vec<String>, Option<Regex>)>> = Mutex::new(None);
+}
+
+/// Words which don't make a remixer, eg. "Extended Mix", "2011 Remaster"
+const GENERIC_VERSION_WORDS: [&str; 40] = [
+    "original", "extended", "radio", "club", "dub", "instrumental", "vocal", "clean", "dirty", "explicit",
+    "short", "long", "album", "single", "main", "acoustic", "live", "remaster", "remastered", "mix",
+    "edit", "version", "acapella", "special", "alternative", "alternate", "bonus", "deluxe", "demo", "mono",
+    "stereo", "full", "intro", "outro", "dj", "late", "night", "inch", "the", "vip"
+];
+
+/// Rules for extracting featured artists, remixers and versions from titles
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CreditsConfig {
+    /// Where to put featured artists
+    pub featured: CreditStyle,
+    /// Where to put the mix / edit / remix
+    pub version: CreditStyle,
+    /// Write remixers into Remixer
+    pub remixer: bool,
+    /// Prefix of featured artists in title
+    pub feat_format: String,
+    /// Split collaborations into separate artists
+    pub split_artists: bool,
+    /// Collaboration separators, case insensitive. Words have to be surrounded by spaces
+    pub separators: Vec<String>,
+    /// Artists which should never be split, eg. "Simon & Garfunkel"
+    pub exceptions: Vec<String>,
+}
+
+impl Default for CreditsConfig {
+    fn default() -> Self {
+        Self {
+            featured: CreditStyle::Field,
+            version: CreditStyle::Both,
+            remixer: true,
+            feat_format: "feat.".to_string(),
+            split_artists: false,
+            separators: vec!["vs.", "vs", "x", "&", ",", ";"].into_iter().map(String::from).collect(),
+            exceptions: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum CreditStyle {
+    /// Keep (normalized) in title only
+    Title,
+    /// Move into the field (Artist / Version)
+    Field,
+    /// Keep in title and write into the field
+    Both,
+}
+
+impl CreditStyle {
+    pub fn title(&self) -> bool {
+        *self != CreditStyle::Field
+    }
+
+    pub fn field(&self) -> bool {
+        *self != CreditStyle::Title
+    }
+}
+
+/// Parsed title and artists
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Credits {
+    /// Title without featured artists and version
+    pub title: String,
+    pub artists: Vec<String>,
+    pub featured: Vec<String>,
+    pub remixers: Vec<String>,
+    pub version: Option<String>,
+}
+
+/// Part of title
+enum Segment<'a> {
+    Text(&'a str),
+    Bracket(&'a str),
+}
+
+impl CreditsConfig {
+    /// Parse title and artists
+    pub fn parse(&self, title: &str, artists: &[String]) -> Credits {
+        let mut credits = Credits::default();
+        let mut parts = vec![];
+        for segment in Self::segments(title) {
+            match segment {
+                Segment::Bracket(content) => {
+                    let inner = content[1..content.len() - 1].trim();
+                    if let Some(c) = FEAT_RE.captures(inner) {
+                        credits.featured.extend(self.split(&c[2]));
+                    } else if credits.version.is_none() && VERSION_RE.is_match(inner) {
+                        credits.version = Some(inner.to_string());
+                    } else {
+                        parts.push(content.to_string());
+                    }
+                },
+                // Unbracketed feat. goes until the next bracket or dash
+                Segment::Text(text) => match FEAT_INLINE_RE.find(text) {
+                    Some(m) => {
+                        let rest = &text[m.end()..];
+                        let (featured, rest) = rest.split_once(" - ").map(|(f, r)| (f, format!(" - {r}"))).unwrap_or((rest, String::new()));
+                        credits.featured.extend(self.split(featured));
+                        parts.push(format!("{}{rest} ", &text[..m.start()]));
+                    },
+                    None => parts.push(text.to_string()),
+                }
+            }
+        }
+        let mut base = parts.join(" ").split_whitespace().collect::<Vec<_>>().join(" ");
+
+        // Version after dash
+        if credits.version.is_none() {
+            if let Some((t, v)) = base.rsplit_once(" - ") {
+                if VERSION_RE.is_match(v.trim()) {
+                    credits.version = Some(v.trim().to_string());
+                    base = t.trim().to_string();
+                }
+            }
+        }
+        credits.title = base;
+
+        if let Some(version) = credits.version.as_ref() {
+            credits.remixers = self.remixers(version);
+        }
+
+        // Artists with feat. inside
+        for artist in artists {
+            let mut split = FEAT_ARTIST_RE.split(artist);
+            if let Some(main) = split.next() {
+                Self::extend_unique(&mut credits.artists, self.split(main));
+            }
+            for featured in split {
+                let featured = featured.trim_end_matches(')');
+                Self::extend_unique(&mut credits.featured, self.split(featured));
+            }
+        }
+        let featured = std::mem::take(&mut credits.featured);
+        Self::extend_unique(&mut credits.featured, featured);
+        credits
+    }
+
+    /// Title according to the style
+    pub fn format_title(&self, credits: &Credits) -> String {
+        let mut title = credits.title.to_string();
+        if self.featured.title() && !credits.featured.is_empty() {
+            let featured = match credits.featured.split_last() {
+                Some((last, [])) => last.to_string(),
+                Some((last, rest)) => format!("{} & {last}", rest.join(", ")),
+                None => unreachable!()
+            };
+            title = format!("{title} ({} {featured})", self.feat_format);
+        }
+        if let (true, Some(version)) = (self.version.title(), credits.version.as_ref()) {
+            title = format!("{title} ({version})");
+        }
+        title
+    }
+
+    /// Artists according to the style
+    pub fn format_artists(&self, credits: &Credits) -> Vec<String> {
+        let mut artists = credits.artists.clone();
+        if self.featured.field() {
+            Self::extend_unique(&mut artists, credits.featured.clone());
+        }
+        artists
+    }
+
+    /// Split collaborations
+    pub fn split(&self, artists: &str) -> Vec<String> {
+        let artists = artists.trim();
+        if artists.is_empty() {
+            return vec![];
+        }
+        if !self.split_artists || self.exceptions.iter().any(|e| e.eq_ignore_ascii_case(artists)) {
+            return vec![artists.to_string()];
+        }
+        match self.separators_regex() {
+            Some(re) => re.split(artists).map(|a| a.trim().to_string()).filter(|a| !a.is_empty()).collect(),
+            None => vec![artists.to_string()]
+        }
+    }
+
+    /// Regex of the collaboration separators, compiled only when they change
+    fn separators_regex(&self) -> Option<Regex> {
+        let mut cache = SEPARATORS_RE.lock().unwrap();
+        if let Some((separators, re)) = &*cache {
+            if separators == &self.separators {
+                return re.clone();
+            }
+        }
+
+        let pattern = self.separators.iter().map(|s| s.trim()).filter(|s| !s.is_empty()).map(|s| {
+            match s.chars().next().unwrap().is_alphanumeric() {
+                true => format!(r"\s+{}\s+", regex::escape(s)),
+                false => format!(r"\s*{}\s*", regex::escape(s)),
+            }
+        }).collect::<Vec<_>>().join("|");
+        let re = match pattern.is_empty() {
+            true => None,
+            false => RegexBuilder::new(&pattern).case_insensitive(true).build()
+                .map_err(|e| warn!("Invalid artist separators: {e}"))
+                .ok()
+        };
+        *cache = Some((self.separators.clone(), re.clone()));
+        re
+    }
+
+    /// Get remixers from version, eg. "X & Y Remix"
+    pub fn remixers(&self, version: &str) -> Vec<String> {
+        let remixer = match REMIXER_RE.captures(version) {
+            Some(c) => c[1].trim().to_string(),
+            None => return vec![]
+        };
+        let generic = remixer.split_whitespace().all(|w| {
+            let w = w.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+            w.is_empty() || w.chars().all(|c| c.is_ascii_digit()) || GENERIC_VERSION_WORDS.contains(&w.as_str())
+        });
+        match generic {
+            true => vec![],
+            false => self.split(&remixer)
+        }
+    }
+
+    /// Split title into text and top level brackets
+    fn segments(title: &str) -> Vec<Segment<'_>> {
+        let mut segments = vec![];
+        let mut depth = 0;
+        let mut start = 0;
+        for (i, c) in title.char_indices() {
+            match c {
+                '(' | '[' => {
+                    if depth == 0 {
+                        segments.push(Segment::Text(&title[start..i]));
+                        start = i;
+                    }
+                    depth += 1;
+                },
+                ')' | ']' if depth > 0 => {
+                    depth -= 1;
+                    if depth == 0 {
+                        segments.push(Segment::Bracket(&title[start..=i]));
+                        start = i + 1;
+                    }
+                },
+                _ => {}
+            }
+        }
+        // Unclosed bracket is kept as text
+        segments.push(Segment::Text(&title[start..]));
+        segments
+    }
+
+    /// Add values which aren't present yet (case insensitive)
+    pub fn extend_unique(target: &mut Vec<String>, values: Vec<String>) {
+        for value in values {
+            if !target.iter().any(|t| t.to_lowercase() == value.to_lowercase()) {
+                target.push(value);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_credits() {
+    // Collaborations are kept by default
+    let credits = CreditsConfig::default().parse("Song", &["A vs B".to_string()]);
+    assert_eq!(credits.artists, vec!["A vs B"]);
+
+    let config = CreditsConfig { split_artists: true, ..Default::default() };
+    let credits = config.parse("Song (feat. X & Y) (Z Remix)", &["A vs B".to_string()]);
+    assert_eq!(credits.title, "Song");
+    assert_eq!(credits.artists, vec!["A", "B"]);
+    assert_eq!(credits.featured, vec!["X", "Y"]);
+    assert_eq!(credits.remixers, vec!["Z"]);
+    assert_eq!(credits.version.as_deref(), Some("Z Remix"));
+    assert_eq!(config.format_title(&credits), "Song (Z Remix)");
+    assert_eq!(config.format_artists(&credits), vec!["A", "B", "X", "Y"]);
+
+    // Inline feat., dash version and generic versions
+    let credits = config.parse("Song ft. X - Extended Mix", &["A feat. Y".to_string()]);
+    assert_eq!(credits.title, "Song");
+    assert_eq!(credits.featured, vec!["X", "Y"]);
+    assert!(credits.remixers.is_empty());
+    assert_eq!(credits.version.as_deref(), Some("Extended Mix"));
+
+    // Feat. kept in title, exceptions and unknown brackets
+    let config = CreditsConfig { featured: CreditStyle::Title, version: CreditStyle::Field, split_artists: true, exceptions: vec!["Simon & Garfunkel".to_string()], ..Default::default() };
+    let credits = config.parse("Song [Live] (ft. X & Y) (2011 Remaster)", &["Simon & Garfunkel".to_string()]);
+    assert_eq!(credits.artists, vec!["Simon & Garfunkel"]);
+    assert!(credits.remixers.is_empty());
+    assert_eq!(config.format_title(&credits), "Song [Live] (feat. X & Y)");
+
+    // "With" is part of the title
+    let credits = config.parse("Song (With Love)", &["A".to_string()]);
+    assert!(credits.featured.is_empty());
+    assert_eq!(config.format_title(&credits), "Song (With Love)");
+}
@@ -0,0 +1,147 @@
+use anyhow::Error;
+use std::collections::HashMap;
+use std::path::Path;
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+
+/// Max depth of the genre hierarchy (prevents cycles)
+const MAX_HIERARCHY_DEPTH: usize = 16;
+
+/// User defined genre normalization rules, shareable as a JSON file
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GenreMapping {
+    /// Regex rewrites, applied first in order
+    pub rewrites: Vec<GenreRewrite>,
+    /// Alias: genre, case insensitive
+    pub aliases: HashMap<String, String>,
+    /// Sub-genre: parent genre, case insensitive
+    pub parents: HashMap<String, String>,
+    pub hierarchy: GenreHierarchy,
+    /// Only keep these genres, empty = all
+    pub whitelist: Vec<String>,
+    /// Always remove these genres
+    pub blacklist: Vec<String>,
+    /// Max number of genres, 0 = unlimited
+    pub max_count: usize,
+    /// Map styles as well
+    pub styles: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GenreRewrite {
+    pub regex: String,
+    pub replacement: String,
+}
+
+/// What to do with parents of sub-genres
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum GenreHierarchy {
+    /// Ignore the hierarchy
+    #[default]
+    Keep,
+    /// Add all parents after the sub-genre
+    AddParents,
+    /// Replace the sub-genre with its top level parent
+    TopLevel,
+}
+
+impl GenreMapping {
+    /// Load from JSON file
+    pub fn load(path: impl AsRef<Path>) -> Result<GenreMapping, Error> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Save as JSON file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Map list of genres
+    pub fn apply(&self, genres: &[String]) -> Vec<String> {
+        let rewrites = self.rewrites.iter().filter_map(|r| match Regex::new(&r.regex) {
+            Ok(regex) => Some((regex, r.replacement.as_str())),
+            Err(e) => {
+                warn!("Invalid genre rewrite regex: {} {e}", r.regex);
+                None
+            }
+        }).collect::<Vec<_>>();
+
+        let mut output: Vec<String> = vec![];
+        for genre in genres {
+            // Rewrite and resolve alias
+            let mut genre = genre.trim().to_string();
+            for (regex, replacement) in &rewrites {
+                genre = regex.replace_all(&genre, *replacement).trim().to_string();
+            }
+            if let Some(alias) = Self::lookup(&self.aliases, &genre) {
+                genre = alias.to_string();
+            }
+            if genre.is_empty() {
+                continue;
+            }
+
+            // Hierarchy
+            let mut parents: Vec<String> = vec![];
+            while let Some(parent) = Self::lookup(&self.parents, parents.last().unwrap_or(&genre)) {
+                if parents.len() >= MAX_HIERARCHY_DEPTH {
+                    break;
+                }
+                parents.push(parent.to_string());
+            }
+            let mapped = match self.hierarchy {
+                GenreHierarchy::Keep => vec![genre],
+                GenreHierarchy::AddParents => [vec![genre], parents].concat(),
+                GenreHierarchy::TopLevel => vec![parents.pop().unwrap_or(genre)],
+            };
+
+            for genre in mapped {
+                if !output.iter().any(|g| g.eq_ignore_ascii_case(&genre)) {
+                    output.push(genre);
+                }
+            }
+        }
+
+        // Filter, whitelist also fixes the case
+        output.retain(|g| !self.blacklist.iter().any(|b| b.trim().eq_ignore_ascii_case(g)));
+        if !self.whitelist.is_empty() {
+            output = output.into_iter().filter_map(|g| self.whitelist.iter().find(|w| w.trim().eq_ignore_ascii_case(&g)).map(|w| w.trim().to_string())).collect();
+        }
+        if self.max_count > 0 {
+            output.truncate(self.max_count);
+        }
+        output
+    }
+
+    /// Case insensitive lookup
+    fn lookup<'a>(map: &'a HashMap<String, String>, key: &str) -> Option<&'a String> {
+        map.iter().find(|(k, _)| k.trim().eq_ignore_ascii_case(key)).map(|(_, v)| v)
+    }
+}
+
+#[test]
+fn test_genre_mapping() {
+    let mapping = GenreMapping {
+        rewrites: vec![GenreRewrite { regex: r"(?i)^(.+) & Techno$".to_string(), replacement: "$1 Techno".to_string() }],
+        aliases: HashMap::from([("dnb".to_string(), "Drum & Bass".to_string())]),
+        parents: HashMap::from([
+            ("Melodic House Techno".to_string(), "Techno".to_string()),
+            ("Deep House".to_string(), "House".to_string()),
+            ("House".to_string(), "Electronic".to_string()),
+        ]),
+        hierarchy: GenreHierarchy::AddParents,
+        blacklist: vec!["Electronic".to_string()],
+        ..Default::default()
+    };
+    let genres = ["Melodic House & Techno", "DNB", "deep house", "Techno"].map(String::from);
+    assert_eq!(mapping.apply(&genres), vec!["Melodic House Techno", "Techno", "Drum & Bass", "deep house", "House"]);
+
+    let mapping = GenreMapping { hierarchy: GenreHierarchy::TopLevel, blacklist: vec![], max_count: 2, ..mapping };
+    assert_eq!(mapping.apply(&genres), vec!["Techno", "Drum & Bass"]);
+
+    let mapping = GenreMapping { whitelist: vec!["drum & bass".to_string(), "Electronic".to_string()], max_count: 0, ..mapping };
+    assert_eq!(mapping.apply(&genres), vec!["drum & bass", "Electronic"]);
+}
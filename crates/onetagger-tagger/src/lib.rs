@@ -16,7 +16,15 @@ use serde_json::Value;
 use strsim::normalized_levenshtein;
 use unidecode::unidecode;
 
+use crate::credits::CreditsConfig;
+use crate::genres::GenreMapping;
+use crate::tag_mapping::TagMapping;
+
+pub mod credits;
 pub mod custom;
+pub mod genres;
+pub mod release;
+pub mod tag_mapping;
 
 const ATTRIBUTES_TO_REMOVE: [&'static str; 23] = ["(intro)", "(clean)", "(intro clean)", "(dirty)", "(intro dirty)", "(clean extended)",
     "(intro outro)", "(extended)", "(instrumental)", "(quick hit)", "(club version)", "(radio version)", "(club)", "(radio)", "(main)", 
@@ -61,6 +69,9 @@ pub struct TaggerConfig {
     pub styles_options: StylesOptions,
     // Option to prevent update errors
     pub styles_custom_tag: Option<FrameName>,
+    /// Custom frames per tag, None = default frames
+    #[serde(default)]
+    pub tag_mapping: Option<TagMapping>,
     pub track_number_leading_zeroes: usize,
     pub enable_shazam: bool,
     pub force_shazam: bool,
@@ -75,6 +86,12 @@ pub struct TaggerConfig {
     pub write_lrc: bool,
     pub enhanced_lrc: bool,
     pub capitalize_genres: bool,
+    /// Genre normalization rules, None = disabled
+    #[serde(default)]
+    pub genre_mapping: Option<GenreMapping>,
+    /// Featured artists, remixer and version extraction from title, None = disabled
+    #[serde(default)]
+    pub credits: Option<CreditsConfig>,
     pub id3_comm_lang: Option<String>,
     pub remove_all_covers: bool,
     /// Tag the same track on multiple platforms
@@ -85,8 +102,17 @@ pub struct TaggerConfig {
     pub album_tagging: bool,
     /// % of tracks that have to be from one album to be considered as the correct
     pub album_tagging_ratio: f32,
+    /// Match album folders as whole releases instead of voting by track matches
+    #[serde(default)]
+    pub release_matching: bool,
     /// Renamer template
     pub cover_filename: Option<String>,
+    /// What to do with .lrc, folder art and other files when moving
+    #[serde(default)]
+    pub companion_files: CompanionFiles,
+    /// Album art sources, selection and processing
+    #[serde(default)]
+    pub art: ArtConfig,
 
     /// Platform specific. Format: `{ platform: { custom_option: value }}`
     pub custom: PlatformTaggerConfig,
@@ -150,6 +176,7 @@ impl Default for TaggerConfig {
             title_regex: None,
             post_command: None, 
             styles_custom_tag: Some(FrameName::same("STYLE")), 
+            tag_mapping: None,
             spotify: None, 
             custom: Default::default(), 
             include_subfolders: true,
@@ -164,12 +191,17 @@ impl Default for TaggerConfig {
             write_lrc: false,
             enhanced_lrc: false,
             capitalize_genres: false,
+            genre_mapping: None,
+            credits: None,
             remove_all_covers: false,
             id3_comm_lang: None,
             fetch_all_results: false,
             album_tagging: false,
             album_tagging_ratio: 0.5,
-            cover_filename: None
+            release_matching: false,
+            cover_filename: None,
+            companion_files: CompanionFiles::default(),
+            art: ArtConfig::default(),
         }
     }
 }
@@ -199,6 +231,98 @@ impl Into<HashMap<String, Value>> for PlatformTaggerConfig {
 }
 
 
+/// Files which belong to an audio file and should be moved together with it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompanionFiles {
+    pub enabled: bool,
+    /// Extensions of files with the same name as the audio file (`track.lrc` or `track.mp3.asd`)
+    pub extensions: Vec<String>,
+    /// What to do with folder art when moving files out of a folder
+    pub folder_art: FolderArtMode,
+    /// Filenames considered as folder art
+    pub folder_art_names: Vec<String>,
+    /// Remove source directories which are empty after moving
+    pub remove_empty_dirs: bool,
+}
+
+impl Default for CompanionFiles {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            extensions: ["lrc", "cue", "nfo", "asd"].iter().map(|e| e.to_string()).collect(),
+            folder_art: FolderArtMode::Move,
+            folder_art_names: ["cover.jpg", "cover.png", "folder.jpg", "folder.png", "front.jpg", "albumart.jpg"].iter().map(|e| e.to_string()).collect(),
+            remove_empty_dirs: false,
+        }
+    }
+}
+
+/// Folder art handling
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FolderArtMode {
+    /// Leave in the original folder
+    Leave,
+    /// Move if the whole folder moves into one place, copy otherwise
+    Move,
+    /// Always copy
+    Copy,
+}
+
+/// Album art sources, selection and processing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArtConfig {
+    /// Embed art into the audio file (folder art is controlled by `album_art_file`)
+    pub embed: bool,
+    /// Also look for art on Cover Art Archive
+    pub cover_art_archive: bool,
+    /// Also look for art on iTunes
+    pub itunes: bool,
+    /// Minimal width and height in px, smaller art is ignored
+    pub min_resolution: u32,
+    /// Art larger than this (px) is used only if nothing else fits and gets downscaled, 0 = unlimited
+    pub max_resolution: u32,
+    /// Max ratio of the longer to the shorter side, 0 = any
+    pub max_aspect_ratio: f32,
+    /// Downscale art to fit into this size (px), 0 = keep original size (up to max resolution)
+    pub target_size: u32,
+    /// Format of the written art
+    pub format: ArtFormat,
+    /// JPEG quality, 1 - 100
+    pub jpeg_quality: u8,
+    /// Select and process art only once for all tracks of an album in a folder
+    pub dedupe: bool,
+}
+
+impl Default for ArtConfig {
+    fn default() -> Self {
+        Self {
+            embed: true,
+            cover_art_archive: false,
+            itunes: false,
+            min_resolution: 0,
+            max_resolution: 0,
+            max_aspect_ratio: 0.0,
+            target_size: 0,
+            format: ArtFormat::Original,
+            jpeg_quality: 90,
+            dedupe: true,
+        }
+    }
+}
+
+/// Format of the written art
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ArtFormat {
+    /// Keep JPEG and PNG, other formats (WebP, GIF...) are converted to JPEG
+    Original,
+    Jpeg,
+    Png,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SpotifyConfig {
@@ -689,6 +813,12 @@ pub trait AutotaggerSource: Any + Send + Sync {
         warn!("Album tagging not supported on this platform!");
         Ok(None)
     }
+
+    /// Search releases by album name, artist and track count, returns release IDs (used for release matching)
+    #[allow(unused_variables)]
+    fn search_albums(&mut self, album: &str, artist: &str, track_count: usize, config: &TaggerConfig) -> Result<Vec<String>, Error> {
+        Ok(vec![])
+    }
 }
 
 /// Response from Config callback
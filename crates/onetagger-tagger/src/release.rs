@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use strsim::normalized_levenshtein;
+
+use crate::{Album, AudioFileInfo, MatchingUtils, TaggerConfig, Track, TrackNumber};
+
+/// Weights of title, track number and duration similarity
+const WEIGHTS: (f64, f64, f64) = (0.6, 0.2, 0.2);
+
+/// Tag names of album in different formats
+const ALBUM_TAGS: [&str; 3] = ["TALB", "ALBUM", "©alb"];
+/// Tag names of album artist in different formats
+const ALBUM_ARTIST_TAGS: [&str; 3] = ["TPE2", "ALBUMARTIST", "aART"];
+/// Tag names of disc number in different formats
+const DISC_NUMBER_TAGS: [&str; 3] = ["TPOS", "DISCNUMBER", "disk"];
+
+/// Local files aligned to tracks of a release
+#[derive(Debug, Clone)]
+pub struct ReleaseMatch {
+    /// Score of the whole release, 0 - 1
+    pub score: f64,
+    /// (file index, track index, similarity)
+    pub pairs: Vec<(usize, usize, f64)>,
+}
+
+impl ReleaseMatch {
+    /// Align files to release tracks (one track per file) and score the release
+    pub fn new(files: &[AudioFileInfo], album: &Album, config: &TaggerConfig) -> ReleaseMatch {
+        if files.is_empty() || album.tracks.is_empty() {
+            return ReleaseMatch { score: 0.0, pairs: vec![] };
+        }
+
+        // Similarity matrix, files are rows
+        let similarity = files.iter().map(|info| {
+            album.tracks.iter().enumerate().map(|(i, track)| track_similarity(info, track, i + 1, config)).collect::<Vec<_>>()
+        }).collect::<Vec<_>>();
+
+        // Algorithm requires rows <= columns
+        let transpose = files.len() > album.tracks.len();
+        let cost = match transpose {
+            false => similarity.iter().map(|r| r.iter().map(|s| 1.0 - s).collect()).collect::<Vec<Vec<_>>>(),
+            true => (0..album.tracks.len()).map(|t| similarity.iter().map(|r| 1.0 - r[t]).collect()).collect(),
+        };
+        let pairs = hungarian(&cost).into_iter().enumerate().map(|(row, column)| match transpose {
+            false => (row, column, similarity[row][column]),
+            true => (column, row, similarity[column][row])
+        }).collect::<Vec<_>>();
+
+        // Missing and extra tracks lower the score, so different editions can be told apart
+        let score = pairs.iter().map(|(_, _, s)| s).sum::<f64>() / files.len().max(album.tracks.len()) as f64;
+        ReleaseMatch { score, pairs }
+    }
+
+    /// Most common (album, album artist) of files, used for searching releases
+    pub fn album_query(files: &[AudioFileInfo]) -> Option<(String, String)> {
+        let mut counts: HashMap<(String, String), usize> = HashMap::new();
+        for info in files {
+            let album = match tag_value(info, &ALBUM_TAGS) {
+                Some(album) => album,
+                None => continue
+            };
+            let artist = tag_value(info, &ALBUM_ARTIST_TAGS)
+                .or(info.artists.first().map(String::from))
+                .unwrap_or_default();
+            *counts.entry((album, artist)).or_default() += 1;
+        }
+        counts.into_iter().max_by_key(|(_, c)| *c).map(|(q, _)| q)
+    }
+}
+
+/// Similarity of file to release track at `position` (from 1), 0 - 1
+pub fn track_similarity(info: &AudioFileInfo, track: &Track, position: usize, config: &TaggerConfig) -> f64 {
+    let mut total = 0.0;
+    let mut weights = 0.0;
+
+    // Title
+    if let Some(title) = info.title.as_ref() {
+        let title = MatchingUtils::clean_title_matching(title);
+        let similarity = normalized_levenshtein(&title, &MatchingUtils::clean_title_matching(&track.full_title()))
+            .max(normalized_levenshtein(&title, &MatchingUtils::clean_title_matching(&track.title)));
+        total += similarity * WEIGHTS.0;
+        weights += WEIGHTS.0;
+    }
+
+    // Track number, either on disc or overall position
+    if let Some(track_number) = info.track_number {
+        let disc_ok = match (disc_number(info), track.disc_number) {
+            (Some(a), Some(b)) => a == b,
+            _ => true
+        };
+        let number_ok = match &track.track_number {
+            Some(TrackNumber::Number(n)) => *n == track_number as i32 && disc_ok,
+            _ => false
+        };
+        if number_ok || position == track_number as usize {
+            total += WEIGHTS.1;
+        }
+        weights += WEIGHTS.1;
+    }
+
+    // Duration
+    if let Some(duration) = info.duration.filter(|d| *d != Duration::ZERO) {
+        if track.duration != Duration::ZERO {
+            let diff = (duration.as_secs_f64() - track.duration.as_secs_f64()).abs();
+            total += (1.0 - diff / config.max_duration_difference.max(1) as f64).max(0.0) * WEIGHTS.2;
+            weights += WEIGHTS.2;
+        }
+    }
+
+    match weights > 0.0 {
+        true => total / weights,
+        false => 0.0
+    }
+}
+
+/// Get disc number from tags
+fn disc_number(info: &AudioFileInfo) -> Option<u16> {
+    tag_value(info, &DISC_NUMBER_TAGS)?.split('/').next()?.trim().parse().ok()
+}
+
+/// Get first non empty value of any of the tags
+fn tag_value(info: &AudioFileInfo, tags: &[&str]) -> Option<String> {
+    tags.iter()
+        .find_map(|t| info.tags.iter().find(|(k, _)| k.eq_ignore_ascii_case(t)))
+        .and_then(|(_, v)| v.first())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Minimal cost assignment (Hungarian algorithm), rows <= columns.
+/// Returns column assigned to each row
+fn hungarian(cost: &[Vec<f64>]) -> Vec<usize> {
+    let n = cost.len();
+    let m = cost.first().map(|r| r.len()).unwrap_or(0);
+    // Potentials, 1-indexed with 0 as virtual column
+    let mut u = vec![0.0; n + 1];
+    let mut v = vec![0.0; m + 1];
+    let mut assigned = vec![0usize; m + 1];
+    let mut way = vec![0usize; m + 1];
+
+    for row in 1..=n {
+        assigned[0] = row;
+        let mut j0 = 0;
+        let mut min = vec![f64::INFINITY; m + 1];
+        let mut used = vec![false; m + 1];
+        loop {
+            used[j0] = true;
+            let i0 = assigned[j0];
+            let mut delta = f64::INFINITY;
+            let mut j1 = 0;
+            for j in 1..=m {
+                if used[j] {
+                    continue;
+                }
+                let current = cost[i0 - 1][j - 1] - u[i0] - v[j];
+                if current < min[j] {
+                    min[j] = current;
+                    way[j] = j0;
+                }
+                if min[j] < delta {
+                    delta = min[j];
+                    j1 = j;
+                }
+            }
+            for j in 0..=m {
+                if used[j] {
+                    u[assigned[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if assigned[j0] == 0 {
+                break;
+            }
+        }
+        // Augment path
+        while j0 != 0 {
+            let j1 = way[j0];
+            assigned[j0] = assigned[j1];
+            j0 = j1;
+        }
+    }
+
+    let mut output = vec![0; n];
+    for j in 1..=m {
+        if assigned[j] != 0 {
+            output[assigned[j] - 1] = j - 1;
+        }
+    }
+    output
+}
+
+#[test]
+fn test_release_match() {
+    use crate::{AudioFileFormat, FileTaggedStatus};
+
+    // Greedy would pick (0, 0) and leave file 1 with a bad match
+    assert_eq!(hungarian(&[vec![0.1, 0.2], vec![0.1, 0.9]]), vec![1, 0]);
+
+    let file = |title: &str, track_number: u16, duration: u64| AudioFileInfo {
+        title: Some(title.to_string()),
+        artists: vec!["Artist".to_string()],
+        format: AudioFileFormat::FLAC,
+        path: format!("{title}.flac").into(),
+        isrc: None,
+        duration: Some(Duration::from_secs(duration)),
+        track_number: Some(track_number),
+        tagged: FileTaggedStatus::Untagged,
+        tags: HashMap::from([("ALBUM".to_string(), vec!["Album".to_string()])])
+    };
+    let track = |title: &str, track_number: i32, duration: u64| Track {
+        title: title.to_string(),
+        track_number: Some(TrackNumber::Number(track_number)),
+        duration: Duration::from_secs(duration).into(),
+        ..Default::default()
+    };
+    let files = vec![file("Intro", 1, 60), file("Second Song", 2, 240), file("Outro", 3, 120)];
+    let album = Album { id: "1".to_string(), name: "Album".to_string(), tracks: vec![
+        track("Outro", 3, 121), track("Intro", 1, 60), track("Second Song", 2, 238)
+    ]};
+    let deluxe = Album { id: "2".to_string(), name: "Album".to_string(), tracks: [
+        album.tracks.clone(), vec![track("Bonus", 4, 200), track("Remix", 5, 300)]
+    ].concat()};
+
+    let config = TaggerConfig::default();
+    let result = ReleaseMatch::new(&files, &album, &config);
+    assert_eq!(result.pairs.iter().map(|(f, t, _)| (*f, *t)).collect::<Vec<_>>(), vec![(0, 1), (1, 2), (2, 0)]);
+    assert!(result.score > 0.95);
+    assert!(ReleaseMatch::new(&files, &deluxe, &config).score < result.score);
+    assert_eq!(ReleaseMatch::album_query(&files), Some(("Album".to_string(), "Artist".to_string())));
+}
@@ -0,0 +1,95 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{FrameName, SupportedTag};
+
+/// Routes tags into custom frames, tags without a rule go into the default frames
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TagMapping {
+    pub name: String,
+    pub rules: Vec<TagMappingRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TagMappingRule {
+    pub tag: SupportedTag,
+    /// Frames to write the value into (empty name = skip the format), no frames = only default
+    pub frames: Vec<FrameName>,
+    /// Write into the default frame as well, if false and no frames, the tag is disabled
+    pub keep_default: bool,
+}
+
+impl TagMappingRule {
+    /// Write only into frames
+    pub fn replace(tag: SupportedTag, frames: Vec<FrameName>) -> TagMappingRule {
+        TagMappingRule { tag, frames, keep_default: false }
+    }
+
+    /// Write into default frame and the extra frames
+    pub fn duplicate(tag: SupportedTag, frames: Vec<FrameName>) -> TagMappingRule {
+        TagMappingRule { tag, frames, keep_default: true }
+    }
+
+    /// Don't write the tag at all
+    pub fn disable(tag: SupportedTag) -> TagMappingRule {
+        TagMappingRule { tag, frames: vec![], keep_default: false }
+    }
+}
+
+impl TagMapping {
+    /// Get rule for tag, MetaTags can't be mapped because 1T_TAGGEDDATE is used to detect tagged files
+    pub fn rule(&self, tag: SupportedTag) -> Option<&TagMappingRule> {
+        if tag == SupportedTag::MetaTags {
+            return None;
+        }
+        self.rules.iter().find(|r| r.tag == tag)
+    }
+
+    /// Built-in profiles for common software
+    pub fn presets() -> Vec<TagMapping> {
+        let grouping = FrameName::new("TIT1", "GROUPING", "©grp");
+        let label = FrameName::new("TPUB", "LABEL", "com.apple.iTunes:LABEL");
+
+        vec![
+            TagMapping { name: "Rekordbox".to_string(), rules: vec![
+                TagMappingRule::duplicate(SupportedTag::Label, vec![FrameName::new("", "LABEL", "")]),
+                TagMappingRule::duplicate(SupportedTag::Style, vec![grouping.clone()]),
+            ]},
+            TagMapping { name: "Serato".to_string(), rules: vec![
+                TagMappingRule::duplicate(SupportedTag::Label, vec![FrameName::new("", "LABEL", "")]),
+                TagMappingRule::replace(SupportedTag::Style, vec![grouping.clone()]),
+            ]},
+            TagMapping { name: "Traktor".to_string(), rules: vec![
+                TagMappingRule::duplicate(SupportedTag::Label, vec![FrameName::new("", "LABEL", "")]),
+                TagMappingRule::duplicate(SupportedTag::Style, vec![grouping.clone()]),
+            ]},
+            TagMapping { name: "iTunes / Music.app".to_string(), rules: vec![
+                TagMappingRule::replace(SupportedTag::Style, vec![grouping]),
+                TagMappingRule::replace(SupportedTag::Version, vec![FrameName::new("TIT3", "SUBTITLE", "com.apple.iTunes:SUBTITLE")]),
+                TagMappingRule::disable(SupportedTag::URL),
+                TagMappingRule::disable(SupportedTag::TrackId),
+                TagMappingRule::disable(SupportedTag::ReleaseId),
+            ]},
+            TagMapping { name: "Picard".to_string(), rules: vec![
+                TagMappingRule::replace(SupportedTag::Label, vec![label]),
+                TagMappingRule::replace(SupportedTag::Key, vec![FrameName::new("TKEY", "KEY", "com.apple.iTunes:initialkey")]),
+                TagMappingRule::replace(SupportedTag::Version, vec![FrameName::new("TIT3", "SUBTITLE", "com.apple.iTunes:SUBTITLE")]),
+            ]},
+        ]
+    }
+}
+
+#[test]
+fn test_tag_mapping() {
+    let presets = TagMapping::presets();
+    let itunes = presets.iter().find(|m| m.name.starts_with("iTunes")).unwrap();
+    assert_eq!(itunes.rule(SupportedTag::URL), Some(&TagMappingRule::disable(SupportedTag::URL)));
+    assert!(itunes.rule(SupportedTag::Title).is_none());
+    let mapping = TagMapping { name: "Custom".to_string(), rules: vec![TagMappingRule::disable(SupportedTag::MetaTags)] };
+    assert!(mapping.rule(SupportedTag::MetaTags).is_none());
+
+    // Shared as JSON with the UI
+    let json = serde_json::to_string(&presets).unwrap();
+    assert_eq!(serde_json::from_str::<Vec<TagMapping>>(&json).unwrap(), presets);
+}
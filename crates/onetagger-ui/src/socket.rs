@@ -6,14 +6,18 @@ use std::sync::{Arc, Mutex};
 use std::path::{Path, PathBuf};
 use onetagger_renamer::ac::Autocomplete;
 use onetagger_renamer::docs::FullDocs;
-use onetagger_renamer::{Renamer, TemplateParser, RenamerConfig};
+use onetagger_renamer::{Renamer, TemplateParser, RenamerConfig, TagTemplate, TagTemplateConfig, PathTemplate, PathTagsConfig, BulkEdit, BulkEditConfig};
 use serde_json::{Value, json};
 use serde::{Serialize, Deserialize};
 use dunce::canonicalize;
-use onetagger_tag::{TagChanges, TagSeparators, Tag, Field};
+use onetagger_tag::{TagChange, TagChanges, TagSeparators, Tag, Field};
 use onetagger_tagger::{TaggerConfig, AudioFileInfo, TrackMatch};
-use onetagger_autotag::{Tagger, AudioFileInfoImpl, TaggerConfigExt, AUTOTAGGER_PLATFORMS};
+use onetagger_tagger::credits::CreditsConfig;
+use onetagger_tagger::genres::GenreMapping;
+use onetagger_tagger::tag_mapping::TagMapping;
+use onetagger_autotag::{Tagger, AudioFileInfoImpl, TaggerConfigExt, CreditsConfigExt, AUTOTAGGER_PLATFORMS};
 use onetagger_autotag::audiofeatures::{AudioFeaturesConfig, AudioFeatures};
+use onetagger_autotag::separators::{SeparatorMigration, SeparatorMigrationConfig};
 use onetagger_platforms::spotify::Spotify;
 use onetagger_player::{AudioSources, AudioPlayer};
 use onetagger_shared::{Settings, COMMIT};
@@ -30,6 +34,8 @@ use crate::quicktag::{QuickTag, QuickTagFile, QuickTagData};
 use crate::tageditor::TagEditor;
 use crate::browser::{FileBrowser, FolderBrowser};
 
+/// Max changes sent to UI in bulk edit preview
+const BULK_EDIT_PREVIEW: usize = 500;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct FoundSong {
@@ -75,6 +81,14 @@ enum Action {
     QuickTagLoad { path: Option<String>, playlist: Option<UIPlaylist>, recursive: Option<bool>, separators: TagSeparators, limit: Option<bool> },
     QuickTagSave { changes: TagChanges },
     QuickTagFolder { path: Option<String>, subdir: Option<String> },
+    QuickTagMapGenres { path: String, genres: Vec<String>, mapping: GenreMapping },
+    #[serde(rename_all = "camelCase")]
+    QuickTagCredits { path: PathBuf, credits: CreditsConfig, separators: TagSeparators, id3v24: bool, id3_comm_lang: Option<String> },
+    SeparatorsMigrate { path: PathBuf, config: SeparatorMigrationConfig },
+
+    GenreMappingLoad { path: PathBuf },
+    GenreMappingSave { path: PathBuf, mapping: GenreMapping },
+    TagMappingPresets,
 
     #[serde(rename_all = "camelCase")]
     SpotifyAuthorize { client_id: String, client_secret: String },
@@ -88,6 +102,12 @@ enum Action {
     RenamerAutocomplete { template: String },
     RenamerPreview { config: RenamerConfig },
     RenamerStart { config: RenamerConfig },
+    RenamerTagsPreview { config: TagTemplateConfig },
+    RenamerTagsStart { config: TagTemplateConfig },
+    RenamerPathPreview { config: PathTagsConfig },
+    RenamerPathStart { config: PathTagsConfig },
+    RenamerBulkPreview { config: BulkEditConfig },
+    RenamerBulkStart { config: BulkEditConfig },
 
     FolderBrowser { path: PathBuf, child: String, base: bool },
 
@@ -107,6 +127,7 @@ enum Action {
         enable_audio_features: bool,
         songs: Vec<FoundSong>
     },
+    ParseTracklist { text: String },
 }
 
 
@@ -222,6 +243,17 @@ pub(crate) async fn handle_ws_connection(mut websocket: WebSocket, context: Star
     Ok(())
 }
 
+/// Files of bulk edit: selection, playlist or folder
+fn bulk_edit_files(config: &BulkEditConfig) -> Result<Vec<PathBuf>, Error> {
+    if !config.files.is_empty() {
+        return Ok(config.files.clone());
+    }
+    if PLAYLIST_EXTENSIONS.iter().any(|e| config.path.to_string_lossy().to_lowercase().ends_with(e)) {
+        return get_files_from_playlist_file(&config.path);
+    }
+    Ok(AudioFileInfo::get_file_list(&config.path, config.subfolders))
+}
+
 /// Serialize and send to socket with warning intercept
 async fn send_socket<D: Serialize>(ws: &mut WebSocket, json: D) -> Result<(), Error> {
     match send_socket_inner(ws, json).await {
@@ -299,6 +331,7 @@ fn download_songs(
             timestamp: song.timestamp.map(|t| t.to_string()),
             downloaded: false,
             match_confidence: confidence,
+            ..Default::default()
         }
     }).collect();
     
@@ -555,7 +588,8 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
             })).await.ok();
         },
         // Save quicktag changes
-        Action::QuickTagSave { changes } => {
+        Action::QuickTagSave { mut changes } => {
+            TagEditor::convert_art(&mut changes, &TagEditor::art_config())?;
             let tag = changes.commit()?;
             send_socket(websocket, json!({
                 "action": "quickTagSaved",
@@ -563,6 +597,58 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
                 "file": QuickTagFile::from_tag(&changes.path, &tag)?
             })).await.ok();
         },
+        // Map genres of track (saved by the client)
+        Action::QuickTagMapGenres { path, genres, mapping } => {
+            send_socket(websocket, json!({
+                "action": "quickTagMapGenres",
+                "path": path,
+                "genres": mapping.apply(&genres)
+            })).await.ok();
+        },
+        // Extract featured artists, remixers and version of track
+        Action::QuickTagCredits { path, credits, separators, id3v24, id3_comm_lang } => {
+            let mut tag = Tag::load_file(&path, false)?;
+            tag.set_separators(&separators);
+            let mut changes = TagChanges::new(&path, separators, id3v24, id3_comm_lang);
+            for (field, value) in credits.changes(&**tag.tag()) {
+                changes.add(TagChange::Field { field, value, overwrite: true });
+            }
+            if !changes.changes_mut().is_empty() {
+                tag = changes.commit()?;
+            }
+            send_socket(websocket, json!({
+                "action": "quickTagSaved",
+                "path": &path,
+                "file": QuickTagFile::from_tag(&path, &tag)?
+            })).await.ok();
+        },
+        // Detect / convert separators of multi-value tags
+        Action::SeparatorsMigrate { path, config } => {
+            let write = config.write;
+            let files = AudioFileInfo::get_file_list(&path, true);
+            let report = tokio::task::spawn_blocking(move || SeparatorMigration::new(config).migrate(&files)).await?;
+            send_socket(websocket, json!({
+                "action": "separatorsMigrate",
+                "path": path,
+                "write": write,
+                "report": report
+            })).await.ok();
+        },
+        // Import / export genre mapping
+        Action::GenreMappingLoad { path } => {
+            send_socket(websocket, json!({
+                "action": "genreMappingLoad",
+                "mapping": GenreMapping::load(&path)?
+            })).await.ok();
+        },
+        Action::GenreMappingSave { path, mapping } => mapping.save(&path)?,
+        // Built-in tag mapping profiles
+        Action::TagMappingPresets => {
+            send_socket(websocket, json!({
+                "action": "tagMappingPresets",
+                "presets": TagMapping::presets()
+            })).await.ok();
+        },
         // List dir
         Action::QuickTagFolder { path, subdir } => {
             let (new_path, files) = FileBrowser::list_dir_or_default(path.clone().map(|p| PathBuf::from(p)), subdir, true, false, false)?;
@@ -618,7 +704,8 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
             })).await.ok();
         },
         // Save changes
-        Action::TagEditorSave { changes } => {
+        Action::TagEditorSave { mut changes } => {
+            TagEditor::convert_art(&mut changes, &TagEditor::art_config())?;
             let _tag = changes.commit()?;
             send_socket(websocket, json!({
                 "action": "tagEditorSave"
@@ -626,11 +713,15 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
         },
         // Syntax highlight for renamer
         Action::RenamerSyntaxHighlight { template } => {
-            let renamer = Renamer::new(TemplateParser::parse(&template));
-            let html = renamer.generate_html(&template);
+            // Invalid template is highlighted with errors
+            let (html, errors) = match TemplateParser::parse(&template) {
+                Ok(parser) => (Renamer::new(parser).generate_html(&template), vec![]),
+                Err(e) => (Renamer::syntax_html(&e.syntax, &template), e.diagnostics)
+            };
             send_socket(websocket, json!({
                 "action": "renamerSyntaxHighlight",
-                "html": html
+                "html": html,
+                "errors": errors
             })).await.ok();
         },
         // Autocomplete data
@@ -645,17 +736,20 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
         },
         // Generate new names but don't rename
         Action::RenamerPreview { config } => {
-            let mut renamer = Renamer::new(TemplateParser::parse(&config.template));
+            let mut renamer = Renamer::new(TemplateParser::parse(&config.template)?);
             let files = AudioFileInfo::load_files_iter(&config.path, config.subfolders, None, None);
             let files = renamer.generate(files.take(3), &config).unwrap_or(vec![]);
+            let plan = renamer.plan(&files, &config);
+            let files = plan.operations.iter().map(|o| (&o.from, &o.to)).collect::<Vec<_>>();
             send_socket(websocket, json!({
                 "action": "renamerPreview",
                 "files": files,
+                "plan": plan
             })).await.ok();
         },
         // Start renamer
         Action::RenamerStart { config } => {
-            let mut renamer = Renamer::new(TemplateParser::parse(&config.template));
+            let mut renamer = Renamer::new(TemplateParser::parse(&config.template)?);
             let files = AudioFileInfo::load_files_iter(&config.path, config.subfolders, None, None);
             let files = renamer.generate(files, &config)?;
             renamer.rename(&files, &config)?;
@@ -663,6 +757,66 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
                 "action": "renamerDone",
             })).await.ok();
         },
+        // Evaluate template into tags but don't write
+        Action::RenamerTagsPreview { config } => {
+            let mut tag_template = TagTemplate::new(TemplateParser::parse(&config.template)?);
+            let files = AudioFileInfo::load_files_iter(&config.path, config.subfolders, None, None);
+            let changes = tag_template.generate(files.take(10), &config);
+            send_socket(websocket, json!({
+                "action": "renamerTagsPreview",
+                "changes": changes
+            })).await.ok();
+        },
+        // Write template output into tags
+        Action::RenamerTagsStart { config } => {
+            let mut tag_template = TagTemplate::new(TemplateParser::parse(&config.template)?);
+            let files = AudioFileInfo::load_files_iter(&config.path, config.subfolders, None, None);
+            let changes = tag_template.generate(files, &config);
+            let count = TagTemplate::apply(&changes, &config);
+            send_socket(websocket, json!({
+                "action": "renamerTagsDone",
+                "count": count
+            })).await.ok();
+        },
+        // Parse tags from paths but don't write
+        Action::RenamerPathPreview { config } => {
+            let template = PathTemplate::parse(&config.template)?;
+            let files = AudioFileInfo::get_file_list(&config.path, config.subfolders);
+            send_socket(websocket, json!({
+                "action": "renamerPathPreview",
+                "files": template.generate(files.into_iter().take(10))
+            })).await.ok();
+        },
+        // Write tags parsed from paths
+        Action::RenamerPathStart { config } => {
+            let template = PathTemplate::parse(&config.template)?;
+            let files = template.generate(AudioFileInfo::get_file_list(&config.path, config.subfolders));
+            let count = PathTemplate::apply(&files, &config);
+            send_socket(websocket, json!({
+                "action": "renamerPathDone",
+                "count": count
+            })).await.ok();
+        },
+        // Bulk find & replace, returns only the modified values
+        Action::RenamerBulkPreview { config } => {
+            let bulk_edit = BulkEdit::new(&config.operations)?;
+            let changes = bulk_edit.generate(bulk_edit_files(&config)?, &config);
+            send_socket(websocket, json!({
+                "action": "renamerBulkPreview",
+                "total": changes.len(),
+                "changes": &changes[..changes.len().min(BULK_EDIT_PREVIEW)]
+            })).await.ok();
+        },
+        // Write the bulk edit
+        Action::RenamerBulkStart { config } => {
+            let bulk_edit = BulkEdit::new(&config.operations)?;
+            let changes = bulk_edit.generate(bulk_edit_files(&config)?, &config);
+            let count = BulkEdit::write(&changes, &config);
+            send_socket(websocket, json!({
+                "action": "renamerBulkDone",
+                "count": count
+            })).await.ok();
+        },
         // File browser list dir
         Action::FolderBrowser { path, child , base } => {
             // Windows root dir override
@@ -862,6 +1016,14 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
             }
         },
 
+        // Parse tracklist pasted by user
+        Action::ParseTracklist { text } => {
+            send_socket(websocket, json!({
+                "action": "parseTracklist",
+                "tracklist": onetagger_songdownloader::Tracklist::parse(&text)
+            })).await.ok();
+        },
+
         Action::RepoManifest => {
             send_socket(websocket, json!({
                 "action": "repoManifest",
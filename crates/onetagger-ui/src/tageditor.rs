@@ -4,9 +4,11 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use base64::Engine;
 use serde::{Serialize, Deserialize};
-use image::{GenericImageView, ImageReader};
+use image::{GenericImageView, ImageFormat, ImageReader};
+use onetagger_shared::Settings;
+use onetagger_tagger::ArtConfig;
 
-use onetagger_tag::{AudioFileFormat, CoverType, Picture, Tag};
+use onetagger_tag::{AudioFileFormat, CoverType, Picture, Tag, TagChange, TagChanges};
 use onetagger_tag::id3::{ID3Comment, ID3Popularimeter};
 
 pub struct TagEditor {}
@@ -41,6 +43,34 @@ impl TagEditor {
         })
     }
 
+    /// Validate added pictures and convert formats players can't show (WebP, GIF...) to JPEG
+    pub fn convert_art(changes: &mut TagChanges, config: &ArtConfig) -> Result<(), Error> {
+        for change in changes.changes_mut() {
+            if let TagChange::AddPictureBase64 { data, mime, .. } = change {
+                let bytes = base64::engine::general_purpose::STANDARD.decode(data.as_bytes())?;
+                let reader = ImageReader::new(Cursor::new(&bytes)).with_guessed_format()?;
+                let format = reader.format().ok_or(anyhow!("Unknown image format"))?;
+                let img = reader.decode()?;
+                if format == ImageFormat::Jpeg || format == ImageFormat::Png {
+                    *mime = format.to_mime_type().to_string();
+                    continue;
+                }
+                debug!("Converting {format:?} picture to JPEG");
+                let out = onetagger_autotag::art::encode(&img, ImageFormat::Jpeg, config.jpeg_quality)?;
+                *data = base64::engine::general_purpose::STANDARD.encode(out);
+                *mime = "image/jpeg".to_string();
+            }
+        }
+        Ok(())
+    }
+
+    /// Art config from the saved Auto Tagger settings
+    pub fn art_config() -> ArtConfig {
+        Settings::load().ok()
+            .and_then(|s| serde_json::from_value(s.ui["autoTaggerConfig"]["art"].clone()).ok())
+            .unwrap_or_default()
+    }
+
     // Load art and encode
     fn load_art(picture: Picture) -> Result<TagEditorImage, Error> {
         let img = ImageReader::new(Cursor::new(&picture.data)).with_guessed_format()?.decode()?;